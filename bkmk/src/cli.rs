@@ -5,9 +5,25 @@ pub struct Options {
     #[clap(
         short,
         long,
-        about = "the path to the bookmarks file (default: $BKMN_FILE -> ~/.local/share/bkmk)"
+        about = "the path to the bookmarks file (default: $BKMK_FILE -> bkmk on the data directory, like ~/.local/share/bkmk)"
     )]
     pub path: Option<String>,
+    #[clap(
+        short,
+        long,
+        parse(from_occurrences),
+        about = "show more messages, like the ones about what's being done behind the scenes"
+    )]
+    pub verbose: i32,
+    #[clap(
+        short,
+        long,
+        parse(from_occurrences),
+        about = "show fewer messages - only warnings and errors, or only errors if given twice"
+    )]
+    pub quiet: i32,
+    #[clap(long, about = "prefix the messages with the time they were shown")]
+    pub log_timestamps: bool,
     #[clap(subcommand)]
     pub subcmd: SubCmd,
 }
@@ -18,7 +34,9 @@ pub enum SubCmd {
     Add(AddParameters),
     #[clap(about = "adds the URLs from a newline-delimited bookmarks list file")]
     AddFromFile(FileParameters),
-    #[clap(about = "opens an interactive menu for managing bookmarks using fzagnostic")]
+    #[clap(
+        about = "opens an interactive menu for managing bookmarks using the picker on $PICKER (default: fzagnostic)"
+    )]
     Menu,
 }
 
@@ -1,6 +1,7 @@
 use std::io::{Read, Write};
 use std::path::Path;
 use std::process::Command;
+use std::time::Duration;
 
 use clap::Clap;
 
@@ -14,9 +15,14 @@ mod manager;
 use manager::BookmarkManager;
 
 use utils::aliases::getenv;
-use utils::data::{JsonSerializer, Manager};
-use utils::error::{CliResult, ExitCode};
-use utils::misc::fzagnostic;
+use utils::config;
+use utils::data::{Backups, JsonSerializer, Manager, Retention};
+use utils::dirs;
+use utils::error::{CliError, CliResult, Error, ExitCode};
+use utils::io::FileLock;
+use utils::log::{self, Level};
+use utils::picker::Picker;
+use utils::{debug, error, warn};
 
 fn fallback_string_if_needed<'a>(string: &'a str) -> &'a str {
     for ch in string.chars() {
@@ -29,18 +35,18 @@ fn fallback_string_if_needed<'a>(string: &'a str) -> &'a str {
 }
 
 fn main() -> ExitCode {
-    let home = getenv("HOME").expect("HOME directory is unset - it is needed");
-
-    let _cache_dir: String = std::env::var("XDG_CACHE_DIR")
-        .ok()
-        .unwrap_or_else(|| format!("{}/.cache", home));
-
-    let data_dir: String = std::env::var("XDG_DATA_HOME")
-        .ok()
-        .or_else(|| std::env::var("XDG_DATA_DIR").ok())
-        .unwrap_or_else(|| format!("{}/.local/share", home));
+    if let Err(e) = config::load_into_env("bkmk") {
+        error!("Invalid config file: {}", e);
+        return ExitCode::new(1);
+    }
 
-    let fallback_file = format!("{}/bkmk", data_dir);
+    let fallback_file = match dirs::data_dir() {
+        Some(dir) => dir.join("bkmk").to_string_lossy().into_owned(),
+        None => {
+            error!("Failed to find the data directory (is $HOME set?)");
+            return ExitCode::new(1);
+        }
+    };
 
     let bkmk_file = match std::env::var("BKMK_FILE") {
         Err(_) => fallback_file,
@@ -48,16 +54,46 @@ fn main() -> ExitCode {
         Ok(var) => var,
     };
 
+    // backups are opt-in, like BKMK_BACKUPS=10 or BKMK_BACKUPS=30d
+    let backups = match std::env::var("BKMK_BACKUPS") {
+        Ok(var) if !var.trim().is_empty() => match Retention::parse(&var) {
+            Ok(retention) => Some(retention),
+            Err(why) => {
+                error!("Invalid BKMK_BACKUPS: {}", why);
+                return ExitCode::new(1);
+            }
+        },
+        _ => None,
+    };
+
     let options = cli::Options::parse();
+    log::init(
+        Level::from_verbosity(options.verbose, options.quiet),
+        options.log_timestamps,
+        atty::is(atty::Stream::Stderr),
+    );
 
     // try blocks :))
     (|| -> CliResult {
         let path_string = options.path.unwrap_or(bkmk_file);
         let path = Path::new(&path_string);
 
-        let contents = utils::io::touch_read(&path).or_else(|why| {
-            CliResult::display_err(format!("Failed to load file: {}", why)).into()
-        })?;
+        // the file is only locked while it's loaded and saved, so an open menu doesn't keep other calls from using it
+        let lock = || {
+            FileLock::acquire(path, Duration::from_secs(5)).or_else(|why| {
+                CliResult::display_err(format!("Failed to lock file: {}", why)).into()
+            })
+        };
+        let modified_time = || std::fs::metadata(path).and_then(|m| m.modified()).ok();
+
+        let (contents, loaded_modified) = {
+            let _lock = lock()?;
+            let contents = utils::io::touch_read(&path).or_else(|why| {
+                CliResult::display_err(format!("Failed to load file: {}", why)).into()
+            })?;
+
+            (contents, modified_time())
+        };
 
         let new_contents = fallback_string_if_needed(&contents);
 
@@ -65,8 +101,12 @@ fn main() -> ExitCode {
             CliResult::display_err(format!("Failed to parse file: {}", why)).into()
         })?;
 
-        let mut manager =
-            BookmarkManager::new(data).or_else(|err| CliResult::display_err(err).into())?;
+        let mut manager = BookmarkManager::new(data).map_err(CliError::from)?;
+        debug!(
+            "Loaded {} bookmark(s) from {}",
+            manager.data().len(),
+            path.display()
+        );
 
         match options.subcmd {
             SubCmd::Add(param) => subcmd_add(&mut manager, param),
@@ -74,9 +114,25 @@ fn main() -> ExitCode {
             SubCmd::Menu => subcmd_menu(&mut manager),
         }?;
 
-        manager.save_if_modified(&path).or_else(|why| {
-            CliResult::display_err(format!("Failed to save changes to file: {}", why)).into()
-        })?;
+        if !manager.is_modified() {
+            return CliResult::EMPTY_OK;
+        }
+
+        let backups = backups.map(|retention| Backups::next_to(path, retention));
+        let _lock = lock()?;
+
+        if modified_time() != loaded_modified {
+            return CliResult::display_err(format!(
+                "{} was changed by another call while this one was running, so the changes weren't saved",
+                path.display()
+            ));
+        }
+
+        manager
+            .save_if_modified(&path, backups.as_ref())
+            .or_else(|why| {
+                CliResult::display_err(format!("Failed to save changes to file: {}", why)).into()
+            })?;
 
         CliResult::EMPTY_OK
     })()
@@ -84,11 +140,13 @@ fn main() -> ExitCode {
 }
 
 pub fn subcmd_add(manager: &mut BookmarkManager, param: AddParameters) -> CliResult {
-    CliResult::from_display_result(if let Some(title) = param.title {
+    let result = if let Some(title) = param.title {
         manager.add_bookmark(title, param.url, Vec::new())
     } else {
         manager.add_bookmark_from_url(param.url, true)
-    })
+    };
+
+    result.map_err(CliError::from).into()
 }
 
 pub fn subcmd_add_from_file(manager: &mut BookmarkManager, param: FileParameters) -> CliResult {
@@ -111,8 +169,12 @@ pub fn subcmd_add_from_file(manager: &mut BookmarkManager, param: FileParameters
         .map(str::trim)
         .filter(|line| !line.is_empty())
     {
-        if let Err(e) = manager.add_bookmark_from_url(url.into(), true) {
-            return CliResult::display_err(e);
+        // one bad line shouldn't keep the other bookmarks from being added
+        match manager.add_bookmark_from_url(url.into(), true) {
+            Ok(()) => (),
+            Err(Error::Conflict(why)) => warn!("skipping: {}", why),
+            Err(Error::UserAbort) => warn!("skipping {}", url),
+            Err(error) => return CliResult::from(Err(error.into())),
         }
     }
 
@@ -130,28 +192,17 @@ pub fn subcmd_menu(manager: &mut BookmarkManager) -> CliResult {
         return CliResult::display_err(format!("There are no unarchived bookmarks to select"));
     }
 
-    let chosen_id = {
-        match fzagnostic(
-            &format!("Bookmark ({}):", not_archived.len()),
-            not_archived
-                .iter()
-                .enumerate()
-                .map(|(i, bkmk)| format!("{:>3} {:<95} ({})", i, bkmk.name, bkmk.url)),
-            30,
-        ) {
-            Ok(s) => {
-                not_archived[s
-                    .trim()
-                    .split(" ")
-                    .next()
-                    .unwrap()
-                    .parse::<usize>()
-                    .unwrap()]
-                .id
-            }
-            Err(err) => return CliResult { inner: Err(err) },
-        }
-    };
+    let lines: Vec<String> = not_archived
+        .iter()
+        .map(|bkmk| format!("{:<95} ({})", bkmk.name, bkmk.url))
+        .collect();
+
+    let chosen_id =
+        match Picker::new(format!("Bookmark ({}):", not_archived.len())).pick_one(&lines) {
+            Ok(index) => not_archived[index].id,
+            Err(Error::UserAbort) => return CliResult::silent_err(),
+            Err(error) => return CliResult::new(Err(error.into())),
+        };
 
     type ActionSig = fn(&mut BookmarkManager, u32) -> CliResult;
 
@@ -245,22 +296,11 @@ pub fn subcmd_menu(manager: &mut BookmarkManager) -> CliResult {
         }),
     ];
 
-    let action_id = {
-        match fzagnostic(
-            "Action:",
-            ACTIONS
-                .iter()
-                .enumerate()
-                .map(|(i, (name, _))| format!("{} {}", i, name)),
-            30,
-        ) {
-            Ok(s) => s.split(" ").nth(0).unwrap().parse::<usize>().unwrap(),
-            Err(err) => return CliResult { inner: Err(err) },
-        }
-    };
+    let names: Vec<&str> = ACTIONS.iter().map(|(name, _)| *name).collect();
 
-    match ACTIONS.get(action_id) {
-        Some((_, func)) => func(manager, chosen_id),
-        None => CliResult::display_err(format!("Invalid action ID: {}", action_id)),
+    match Picker::new("Action:").pick_one(&names) {
+        Ok(index) => (ACTIONS[index].1)(manager, chosen_id),
+        Err(Error::UserAbort) => CliResult::silent_err(),
+        Err(error) => CliResult::new(Err(error.into())),
     }
 }
@@ -2,7 +2,9 @@ use std::collections::HashSet;
 use std::path::Path;
 
 use crate::bookmark::Bookmark;
-use utils::data::{data_serialize::SaveToFileError, Id, JsonSerializer, Manager};
+use utils::data::{data_serialize::SaveToFileError, Backups, Id, JsonSerializer, Manager};
+use utils::error::Error;
+use utils::{info, warn};
 
 pub struct BookmarkManager {
     data: Vec<Bookmark>,
@@ -27,15 +29,15 @@ impl Manager for BookmarkManager {
 }
 
 impl BookmarkManager {
-    pub fn new(data: Vec<Bookmark>) -> Result<Self, String> {
+    pub fn new(data: Vec<Bookmark>) -> Result<Self, Error> {
         let mut used_ids: HashSet<Id> = HashSet::new();
 
         for bookmark in data.iter() {
             if used_ids.contains(&bookmark.id) {
-                return Err(format!(
+                return Err(Error::Parse(format!(
                     "repeated ID: {}; it'll have to be removed manually.",
                     bookmark.id
-                ));
+                )));
             } else {
                 used_ids.insert(bookmark.id);
             }
@@ -77,12 +79,16 @@ impl BookmarkManager {
         name: String,
         url: String,
         tags: Vec<String>,
-    ) -> Result<(), String> {
+    ) -> Result<(), Error> {
         if let Some(id) = self.already_has_url(&url) {
-            return Err(format!("Repeated url with bookmark #{}", id));
+            return Err(Error::Conflict(format!(
+                "Repeated url with bookmark #{}",
+                id
+            )));
         }
 
-        let free_id = utils::misc::find_lowest_free_value(&self.used_ids);
+        let free_id = utils::misc::find_lowest_free_value(&self.used_ids)
+            .ok_or_else(|| Error::Conflict("no free bookmark IDs left".into()))?;
 
         self.data_mut().push(Bookmark {
             id: free_id,
@@ -106,30 +112,32 @@ impl BookmarkManager {
     ///
     /// ## Error
     ///
-    /// Returns an error if a bookmark with the same url already exists.
-    pub fn add_bookmark_from_url(&mut self, url: String, read_line: bool) -> Result<(), String> {
+    /// Returns [`Error::Conflict`] if a bookmark with the same url already exists, [`Error::Network`] if the title
+    /// couldn't be fetched and [`Error::UserAbort`] if the user didn't type a new one.
+    pub fn add_bookmark_from_url(&mut self, url: String, read_line: bool) -> Result<(), Error> {
         if let Some(id) = self.already_has_url(&url) {
-            return Err(format!("Repeated url with bookmark #{} ({})", id, url));
+            return Err(Error::Conflict(format!(
+                "Repeated url with bookmark #{} ({})",
+                id, url
+            )));
         }
 
         let title = match crate::bookmark::url_get_title(&url) {
             Ok(title) => title,
             Err(e) => {
                 if read_line {
-                    eprintln!("Failed to get title: {}", e);
-                    eprintln!("  Url: {:?}", url);
+                    warn!("failed to get the title of {:?}: {}", url, e);
 
                     let line =
-                        utils::io::read_line("  Type a new title (type nothing to cancel): ")
-                            .map_err(|e| format!("failed to read line: {}", e))?;
+                        utils::io::read_line("  Type a new title (type nothing to cancel): ")?;
 
                     if line.trim().is_empty() {
-                        return Err(format!("empty title"));
+                        return Err(Error::UserAbort);
                     } else {
                         line
                     }
                 } else {
-                    return Err(format!("failed to get title: {}", e));
+                    return Err(Error::Network(format!("failed to get title: {}", e)));
                 }
             }
         }
@@ -138,9 +146,10 @@ impl BookmarkManager {
         .filter(|c| !matches!(c, '\n' | '\r'))
         .collect::<String>();
 
-        let free_id = utils::misc::find_lowest_free_value(&self.used_ids);
+        let free_id = utils::misc::find_lowest_free_value(&self.used_ids)
+            .ok_or_else(|| Error::Conflict("no free bookmark IDs left".into()))?;
 
-        eprintln!("New bookmark: {:?} ({:?})", title, url);
+        info!("New bookmark: {:?} ({:?})", title, url);
 
         self.data_mut().push(Bookmark {
             id: free_id,
@@ -155,11 +164,21 @@ impl BookmarkManager {
         Ok(())
     }
 
-    pub fn save_if_modified(&self, path: &Path) -> Result<(), SaveToFileError> {
-        if self.modified {
-            self.save_to_file(path, true)
-        } else {
-            Ok(())
+    /// Whether the bookmarks were changed since they were loaded.
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// Saves the bookmarks to `path` if they were changed, backing the file up first with `backups`, if specified.
+    pub fn save_if_modified(
+        &self,
+        path: &Path,
+        backups: Option<&Backups>,
+    ) -> Result<(), SaveToFileError> {
+        match backups {
+            _ if !self.modified => Ok(()),
+            Some(backups) => self.save_to_file_with_backups(path, true, backups),
+            None => self.save_to_file(path, true),
         }
     }
 }
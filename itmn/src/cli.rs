@@ -4,10 +4,17 @@
 //!
 //! [`clap`]: clap
 
-use clap::Clap;
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use clap::{AppSettings, ArgSettings, Clap};
+use std::collections::HashMap;
 
 use crate::item::{Item, ItemState};
+use crate::journal::JournalFormat;
+use crate::manager::IdStrategy;
+use crate::quickadd;
+use crate::report::{DateFilter, Notes, TreeStyle};
 use utils::cowstr::CowStr;
+use utils::data::Retention;
 
 #[derive(Debug, Clap)]
 /// The entry point for the
@@ -15,55 +22,1129 @@ pub struct Options {
     #[clap(
         short,
         long,
-        about = "The path to the entries file (default: $ITMN_FILE => ~/.local/share/itmn)"
+        about = "The path to the entries file, which is encrypted if it ends with .gpg, .asc or .age (default: the profile's file => $ITMN_FILE => itmn on the data directory, like ~/.local/share/itmn)"
     )]
     pub path: Option<String>,
+    #[clap(
+        short,
+        long,
+        parse(from_occurrences),
+        about = "Show more messages, like the ones about what's being done behind the scenes"
+    )]
+    pub verbose: i32,
+    #[clap(
+        short,
+        long,
+        parse(from_occurrences),
+        about = "Show fewer messages - only warnings and errors, or only errors if given twice"
+    )]
+    pub quiet: i32,
+    #[clap(
+        long,
+        about = "Prefix the messages on stderr with the time they were shown (default: $ITMN_LOG_TIMESTAMPS => false)"
+    )]
+    pub log_timestamps: Option<bool>,
+    #[clap(
+        long,
+        about = "Use the entries file of a profile, kept on itmn-profiles/<name> on the data directory (default: $ITMN_PROFILE)"
+    )]
+    pub profile: Option<String>,
+    #[clap(
+        long,
+        about = "When to color the output - auto, always or never (default: $ITMN_COLOR => auto, which respects $NO_COLOR)"
+    )]
+    pub color: Option<String>,
+    #[clap(
+        long,
+        about = "Output the items as JSON - supported by list, next and the list actions of sel"
+    )]
+    pub json: Option<bool>,
+    #[clap(
+        long,
+        about = "Show the changes a command would make to the items, as a diff, without saving them or writing any other file"
+    )]
+    pub dry_run: Option<bool>,
+    #[clap(
+        long,
+        about = "How to sort the items on reports - a comma-separated list of priority, due, name, created, starred and manual (the default), like starred,priority"
+    )]
+    pub sort: Option<String>,
+    #[clap(
+        long,
+        about = "Which items count for the [done/total] progress shown next to parent items - descendants (the default), children or off"
+    )]
+    pub progress: Option<String>,
+    #[clap(
+        long,
+        about = "Which backups of the items file to keep on the backups directory next to it, taken before each save - the last N ones, or the ones of the last N days with 30d; 0 disables them (default: $ITMN_BACKUPS => 10). Backups were kept on ~/.local/share/itmn-backups before, which isn't rotated or used anymore"
+    )]
+    pub backups: Option<String>,
+    #[clap(
+        long,
+        about = "Commit the items file to the git repository it's in after each change (default: $ITMN_GIT_COMMIT => false)"
+    )]
+    pub git_commit: Option<bool>,
+    #[clap(
+        long,
+        about = "Append the items that are done to a journal on itmn-journal on the data directory, which is never pruned - off, text or jsonl (default: $ITMN_JOURNAL => off)"
+    )]
+    pub journal: Option<String>,
+    #[clap(
+        long,
+        about = "Mark open items due within this many days with DUE on reports - 0 only marks the ones due today (default: $ITMN_DUE_SOON => 3)"
+    )]
+    pub due_soon: Option<u32>,
+    #[clap(
+        long,
+        about = "How to draw the trees of items - spaces, ascii or unicode, which falls back to ascii on dumb terminals (default: $ITMN_TREE => spaces)"
+    )]
+    pub tree: Option<String>,
+    #[clap(
+        long,
+        about = "Make items without a context inherit the one of their nearest ancestor on reports and their filters, where it's shown dimmed (default: $ITMN_INHERIT_CONTEXTS => false)"
+    )]
+    pub inherit_contexts: Option<bool>,
+    #[clap(
+        long,
+        about = "Print how many open items are overdue and due today above the reports (default: $ITMN_DUE_BANNER => true)"
+    )]
+    pub due_banner: Option<bool>,
+    #[clap(
+        long,
+        about = "The most pending items each context should have, like work=5,home=3 - add and mod warn when a context goes over its limit (default: $ITMN_WIP_LIMITS)"
+    )]
+    pub wip_limits: Option<String>,
+    #[clap(
+        long,
+        about = "How many columns each level of the trees is indented by (default: $ITMN_INDENT => 2)"
+    )]
+    pub indent: Option<usize>,
+    #[clap(
+        long,
+        about = "Where to show the IDs of the items - after (the default) or before their names (default: $ITMN_ID_POSITION => after)"
+    )]
+    pub id_position: Option<String>,
+    #[clap(
+        long,
+        about = "Show up to this many lines of the description of each item under it, instead of a short excerpt"
+    )]
+    pub show_notes: Option<usize>,
+    #[clap(long, about = "Don't show the descriptions of the items on reports")]
+    pub no_notes: Option<bool>,
+    #[clap(
+        long,
+        about = "How reference IDs are chosen for new items - lowest, which reuses the IDs of deleted items, or random, optionally with the highest ID like random:999 (default: $ITMN_IDS => lowest)"
+    )]
+    pub ids: Option<String>,
     #[clap(subcommand, about = "The command to be ran - defaults to [next]")]
     pub subcmd: Option<SubCmd>,
 }
 
+impl Options {
+    /// Which backups should be kept, falling back to `$ITMN_BACKUPS` and then to the last 10.
+    pub fn backup_retention(&self) -> Result<Retention, String> {
+        if let Some(ref retention) = self.backups {
+            return Retention::parse(retention);
+        }
+
+        match std::env::var("ITMN_BACKUPS") {
+            Ok(retention) if !retention.trim().is_empty() => {
+                Retention::parse(&retention).map_err(|e| format!("{} on $ITMN_BACKUPS", e))
+            }
+            _ => Ok(Retention::Last(10)),
+        }
+    }
+
+    /// How many days ahead items are marked as due soon, falling back to `$ITMN_DUE_SOON` and then to 3.
+    pub fn due_soon_days(&self) -> Result<u32, String> {
+        if let Some(days) = self.due_soon {
+            return Ok(days);
+        }
+
+        match std::env::var("ITMN_DUE_SOON") {
+            Ok(days) if !days.trim().is_empty() => days
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid amount of days on $ITMN_DUE_SOON: {:?}", days)),
+            _ => Ok(3),
+        }
+    }
+
+    /// The style of the trees, falling back to `$ITMN_TREE` and then to spaces. Unicode trees become ASCII ones on dumb
+    /// terminals.
+    pub fn tree_style(&self) -> Result<TreeStyle, String> {
+        let style = match self.tree {
+            Some(ref tree) => TreeStyle::parse(tree)?,
+            None => match std::env::var("ITMN_TREE") {
+                Ok(tree) if !tree.trim().is_empty() => TreeStyle::parse(&tree)?,
+                _ => TreeStyle::Spaces,
+            },
+        };
+
+        Ok(match style {
+            TreeStyle::Unicode if is_dumb_terminal() => TreeStyle::Ascii,
+            style => style,
+        })
+    }
+
+    /// How many columns each level of the trees is indented by, falling back to `$ITMN_INDENT` and then to 2.
+    pub fn indent_width(&self) -> Result<usize, String> {
+        if let Some(width) = self.indent {
+            return Ok(width);
+        }
+
+        match std::env::var("ITMN_INDENT") {
+            Ok(width) if !width.trim().is_empty() => width
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid indent width on $ITMN_INDENT: {:?}", width)),
+            _ => Ok(2),
+        }
+    }
+
+    /// Whether IDs go before the names of the items, falling back to `$ITMN_ID_POSITION` and then to after them.
+    pub fn id_before_name(&self) -> Result<bool, String> {
+        let position = match self.id_position {
+            Some(ref position) => position.clone(),
+            None => std::env::var("ITMN_ID_POSITION").unwrap_or_default(),
+        };
+
+        match position.trim() {
+            "" | "after" => Ok(false),
+            "before" => Ok(true),
+            other => Err(format!(
+                "unknown ID position {:?} (expected before or after)",
+                other
+            )),
+        }
+    }
+
+    /// How the descriptions of the items are shown on reports, which is a short excerpt unless an option says
+    /// otherwise.
+    pub fn notes(&self) -> Result<Notes, String> {
+        match (self.show_notes, self.no_notes) {
+            (Some(_), Some(true)) => {
+                Err("--show-notes and --no-notes can't be used together".into())
+            }
+            (_, Some(true)) => Ok(Notes::Hidden),
+            (Some(count), _) => Ok(Notes::Lines(count)),
+            (None, _) => Ok(Notes::Excerpt),
+        }
+    }
+
+    /// How reference IDs should be chosen, falling back to `$ITMN_IDS` and then to the lowest free ones.
+    pub fn id_strategy(&self) -> Result<IdStrategy, String> {
+        match self.ids {
+            Some(ref ids) => IdStrategy::parse(ids),
+            None => match std::env::var("ITMN_IDS") {
+                Ok(ids) if !ids.trim().is_empty() => IdStrategy::parse(&ids),
+                _ => Ok(IdStrategy::Lowest),
+            },
+        }
+    }
+
+    /// The format of the completion journal, falling back to `$ITMN_JOURNAL`. `None` means there's no journal.
+    pub fn journal(&self) -> Result<Option<JournalFormat>, String> {
+        match self.journal {
+            Some(ref journal) => JournalFormat::parse(journal),
+            None => match std::env::var("ITMN_JOURNAL") {
+                Ok(journal) if !journal.trim().is_empty() => JournalFormat::parse(&journal),
+                _ => Ok(None),
+            },
+        }
+    }
+
+    /// Whether items inherit the contexts of their ancestors on reports, falling back to `$ITMN_INHERIT_CONTEXTS` and
+    /// then to false.
+    pub fn inherit_contexts(&self) -> bool {
+        self.inherit_contexts.unwrap_or_else(|| {
+            matches!(
+                std::env::var("ITMN_INHERIT_CONTEXTS").as_deref(),
+                Ok("1") | Ok("true") | Ok("yes")
+            )
+        })
+    }
+
+    /// Whether the overdue and due today counts are shown above reports, falling back to `$ITMN_DUE_BANNER` and then to
+    /// true.
+    pub fn due_banner(&self) -> bool {
+        self.due_banner.unwrap_or_else(|| {
+            !matches!(
+                std::env::var("ITMN_DUE_BANNER").as_deref(),
+                Ok("0") | Ok("false") | Ok("no")
+            )
+        })
+    }
+
+    /// When to color the output, falling back to `$ITMN_COLOR`.
+    pub fn color(&self) -> Option<String> {
+        self.color.clone().or_else(|| {
+            std::env::var("ITMN_COLOR")
+                .ok()
+                .filter(|color| !color.trim().is_empty())
+        })
+    }
+
+    /// The most pending items each context should have, falling back to `$ITMN_WIP_LIMITS` and then to no limits.
+    pub fn wip_limits(&self) -> Result<HashMap<String, usize>, String> {
+        match self.wip_limits {
+            Some(ref limits) => parse_wip_limits(limits),
+            None => match std::env::var("ITMN_WIP_LIMITS") {
+                Ok(limits) if !limits.trim().is_empty() => parse_wip_limits(&limits),
+                _ => Ok(HashMap::new()),
+            },
+        }
+    }
+
+    /// Whether the messages on stderr should be prefixed by the time, falling back to `$ITMN_LOG_TIMESTAMPS` and then to
+    /// false.
+    pub fn log_timestamps(&self) -> bool {
+        self.log_timestamps.unwrap_or_else(|| {
+            matches!(
+                std::env::var("ITMN_LOG_TIMESTAMPS").as_deref(),
+                Ok("1") | Ok("true") | Ok("yes")
+            )
+        })
+    }
+
+    /// Whether changes should be committed, falling back to `$ITMN_GIT_COMMIT` and then to false.
+    pub fn git_commit(&self) -> bool {
+        self.git_commit.unwrap_or_else(|| {
+            matches!(
+                std::env::var("ITMN_GIT_COMMIT").as_deref(),
+                Ok("1") | Ok("true") | Ok("yes")
+            )
+        })
+    }
+}
+
+#[derive(Debug, Clap)]
+pub enum SubCmd {
+    #[clap(about = "Show a report about the items")]
+    Report(ReportArgs),
+    #[clap(
+        alias = "ls",
+        about = "An alias to the [except-done] report, which is also shown when no subcommand is given - unless $ITMN_DEFAULT_SUBCOMMAND has another one, like agenda or next -c work"
+    )]
+    List(FilterArgs),
+    #[clap(about = "An alias to the [next] report")]
+    Next(NextArgs),
+    #[clap(about = "Add an item")]
+    Add(AddArgs),
+    #[clap(
+        aliases = &["s", "sel", "sri"],
+        about = "Select items by reference ID and do something with them",
+    )]
+    SelRefID(SelectionDetails),
+    #[clap(
+        aliases = &["flatlist", "fl"],
+        about = "List all visible items, prepended by the ID",
+    )]
+    FlatList(FilterArgs),
+    #[clap(about = "List the items that were recently marked as done")]
+    Log(LogArgs),
+    #[clap(
+        about = "Move old done items (and their children) to the archive file, skipping the ones with pending items under them"
+    )]
+    ArchiveDone(ArchiveArgs),
+    #[clap(about = "Delete done items (and their children)")]
+    Prune(PruneArgs),
+    #[clap(
+        about = "Revert the last change made to the items file; run it again to redo the change"
+    )]
+    Undo,
+    #[clap(
+        about = "Merge the second item into the first one, joining their children, descriptions, tags and dependencies"
+    )]
+    Merge(MergeArgs),
+    #[clap(about = "List the items that are blocked by other pending items")]
+    Blocked,
+    #[clap(about = "List the items that are snoozed, with when they'll show up again")]
+    Snoozed,
+    #[clap(about = "List the pending items that are starred")]
+    Starred,
+    #[clap(
+        about = "List the pending items on the someday/maybe list, which are kept out of the other reports"
+    )]
+    Someday,
+    #[clap(about = "List the pending items that weren't touched for a while, oldest first")]
+    Stale(StaleArgs),
+    #[clap(
+        about = "Pick the items to work on today with the picker ($PICKER), replacing the current plan"
+    )]
+    Plan(PlanArgs),
+    #[clap(about = "Show the items planned for today, optionally checking some of them off")]
+    Today(TodayArgs),
+    #[clap(
+        about = "Search the names of the items with a regex, printing refid:depth:name lines for editors and fzf"
+    )]
+    Grep(GrepArgs),
+    #[clap(
+        about = "Print how many items match some filters, exiting with 0 if any does and 1 otherwise, for scripts and prompts"
+    )]
+    Has(HasArgs),
+    #[clap(about = "Show a report again whenever the items file changes, until interrupted")]
+    Watch(WatchArgs),
+    #[clap(
+        about = "Commit the items file to the git repository it's in, then pull the changes from its remote and push the local ones"
+    )]
+    Sync(SyncArgs),
+    #[clap(about = "List the profiles that have an entries file, marking the one in use")]
+    Profiles,
+    #[clap(about = "Show the items being worked on right now")]
+    Active,
+    #[clap(about = "Show how much time was spent on each item and context")]
+    Timesheet(TimesheetArgs),
+    #[clap(about = "Sum the estimated work left on pending items, per context and project")]
+    Remaining,
+    #[clap(about = "Show the pending items with due or scheduled dates, day by day")]
+    Agenda(AgendaArgs),
+    #[clap(about = "Export the items to a format understood by other programs")]
+    Export(ExportArgs),
+    #[clap(about = "Send a notification about overdue items and items due soon")]
+    Remind(RemindArgs),
+    #[clap(about = "List the contexts in use, with how many items are pending and done on each")]
+    Contexts(ContextsArgs),
+    #[clap(about = "Import items from a format used by other programs")]
+    Import(ImportArgs),
+    #[clap(
+        about = "Add the items of another items file (like one written by sel <id> export), with new IDs"
+    )]
+    ImportSubtree(ImportSubtreeArgs),
+    #[clap(about = "Manage the contexts used by the items")]
+    Context(ContextArgs),
+    #[clap(
+        about = "Show how many items there are on each state, context and depth, and how many were done per week"
+    )]
+    Stats(StatsArgs),
+    #[clap(
+        about = "Go through the pending root items (and the items whose review interval is over) one by one, deciding what to do with each of them"
+    )]
+    Review(ReviewArgs),
+    #[clap(about = "Reassign the reference IDs so they're small and follow the order of the tree")]
+    Renumber,
+    #[clap(about = "Save subtrees as templates and create items from them")]
+    Template(TemplateArgs),
+    #[clap(about = "Browse and edit the items on an interactive terminal interface")]
+    Tui,
+    #[clap(about = "List the projects, with how many of their items are done")]
+    Projects(ProjectsArgs),
+    #[clap(about = "Print a completion script for a shell")]
+    Completions(CompletionsArgs),
+    #[clap(about = "Write the man pages of the program and its subcommands")]
+    GenMan(GenManArgs),
+    #[clap(
+        setting = AppSettings::Hidden,
+        about = "List the contexts or reference IDs in use, for the completion scripts"
+    )]
+    Complete(CompleteArgs),
+    #[clap(
+        aliases = &["sel-internal", "sii"],
+        about = "Select items by internal ID and do something with them"
+    )]
+    SelInternalID(SelectionDetails),
+    // TODO: Search,
+    // TODO: RegexMatch,
+}
+
+/// Parses the subcommand to use when none is given from `$ITMN_DEFAULT_SUBCOMMAND`, which holds its arguments like
+/// `next -c work`. They're split on whitespace, without any quoting.
+///
+/// Returns `None` if the variable isn't set. Global options, like `--sort`, go before the subcommand and only the
+/// subcommand is used, so they're refused instead of being ignored.
+pub fn default_subcommand() -> Result<Option<SubCmd>, String> {
+    let line = match std::env::var("ITMN_DEFAULT_SUBCOMMAND") {
+        Ok(line) if !line.trim().is_empty() => line,
+        _ => return Ok(None),
+    };
+
+    if let Some(option) = line
+        .split_whitespace()
+        .next()
+        .filter(|w| w.starts_with('-'))
+    {
+        return Err(format!(
+            "it must start with a subcommand, but starts with {} (global options aren't supported there)",
+            option
+        ));
+    }
+
+    let args = std::iter::once("itmn").chain(line.split_whitespace());
+    Options::try_parse_from(args)
+        .map(|options| options.subcmd)
+        .map_err(|e| {
+            let message = e.to_string();
+            let first_line = message.lines().next().unwrap_or_default();
+            first_line.trim_start_matches("error: ").to_string()
+        })
+}
+
+/// Parses WIP limits like `work=5,home=3` into the limit of each (normalized) context.
+fn parse_wip_limits(string: &str) -> Result<HashMap<String, usize>, String> {
+    let mut limits = HashMap::new();
+
+    for entry in string
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let mut parts = entry.splitn(2, '=');
+        let (context, limit) = match (parts.next(), parts.next()) {
+            (Some(context), Some(limit)) => (context, limit),
+            _ => {
+                return Err(format!(
+                    "invalid WIP limit {:?} (expected context=number)",
+                    entry
+                ))
+            }
+        };
+
+        let context = Item::validate_context(context)
+            .ok_or_else(|| format!("the WIP limit {:?} needs a context", entry))?;
+        let limit = limit
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid number on the WIP limit {:?}", entry))?;
+
+        limits.insert(context, limit);
+    }
+
+    Ok(limits)
+}
+
+impl SubCmd {
+    /// Whether the subcommand adds or modifies items, so the WIP limits are checked after it. The value is whether it
+    /// should refuse to go over them (`--strict`) instead of only warning.
+    pub fn wip_strictness(&self) -> Option<bool> {
+        match self {
+            Self::Add(args) => Some(args.strict.unwrap_or(false)),
+            Self::SelRefID(args) | Self::SelInternalID(args) => match args.action {
+                Some(SelectionAction::Modify(ref args)) => Some(args.strict.unwrap_or(false)),
+                Some(SelectionAction::Add(ref args)) => Some(args.strict.unwrap_or(false)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Whether the subcommand shows a report of the pending items, above which the due banner is shown.
+    pub fn is_report(&self) -> bool {
+        match self {
+            Self::SelRefID(args) | Self::SelInternalID(args) => args.only_lists(),
+            Self::Report(_)
+            | Self::List(_)
+            | Self::Next(_)
+            | Self::FlatList(_)
+            | Self::Blocked
+            | Self::Snoozed
+            | Self::Starred
+            | Self::Someday
+            | Self::Stale(_)
+            | Self::Today(_)
+            | Self::Active
+            | Self::Agenda(_)
+            | Self::Projects(_) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clap)]
+pub struct AddArgs {
+    #[clap(flatten)]
+    pub details: ItemAddDetails,
+    #[clap(
+        long,
+        about = "Read the items from stdin instead, one per line, indenting children under their parents"
+    )]
+    pub stdin: Option<bool>,
+    #[clap(long, about = "The reference ID of the item to add the items under")]
+    pub under: Option<u32>,
+    #[clap(short, long, about = "If the item is a project")]
+    pub project: Option<bool>,
+    #[clap(
+        long,
+        about = "Refuse to add the items if that puts their context over its WIP limit, instead of warning"
+    )]
+    pub strict: Option<bool>,
+}
+
+#[derive(Debug, Clap)]
+pub struct SelAddArgs {
+    #[clap(flatten)]
+    pub details: ItemAddDetails,
+    #[clap(
+        short,
+        long,
+        about = "Add the children without asking for confirmation when more than one item is selected"
+    )]
+    pub yes: Option<bool>,
+    #[clap(
+        long,
+        about = "Refuse to add the children if that puts their context over its WIP limit, instead of warning"
+    )]
+    pub strict: Option<bool>,
+}
+
+#[derive(Debug, Clap)]
+pub struct ItemAddDetails {
+    #[clap(about = "The name of the item")]
+    pub name: Option<String>,
+    #[clap(short, long, about = "The context of the item")]
+    pub context: Option<String>,
+    #[clap(short, long, about = "If the item is a note")]
+    pub note: Option<bool>,
+    #[clap(short, long, about = "The description of the item")]
+    pub description: Option<String>,
+    #[clap(
+        short,
+        long,
+        about = "How long the item is expected to take, like 30min or 2h"
+    )]
+    pub estimate: Option<String>,
+    #[clap(
+        long,
+        about = "When the item should be done by, like 2020-12-31, tomorrow, friday, next week, in 3 weeks or jul 1"
+    )]
+    pub due: Option<String>,
+    #[clap(
+        long,
+        about = "When the item is planned to be worked on, like 2020-12-31, tomorrow, friday, next week, in 3 weeks or jul 1"
+    )]
+    pub scheduled: Option<String>,
+    #[clap(
+        long,
+        about = "A URL of something the item is about, which can be opened with sel <id> open"
+    )]
+    pub url: Option<String>,
+    #[clap(
+        long,
+        about = "How often the item should come up on reviews, like 30d or 2w"
+    )]
+    pub review_every: Option<String>,
+    #[clap(
+        long,
+        about = "Put the item on the someday/maybe list, which keeps it out of the reports but not of reviews"
+    )]
+    pub someday: Option<bool>,
+    #[clap(
+        long,
+        about = "Keep the name as it is, instead of parsing @context, #tag, !priority (h/m/l) and ^due words from it"
+    )]
+    pub raw: Option<bool>,
+}
+
+impl ItemAddDetails {
+    /// Parses the fields that aren't handled by the item constructor.
+    pub fn extras(&self) -> Result<ItemExtras, String> {
+        Ok(ItemExtras {
+            estimate: match self.estimate {
+                Some(ref estimate) => parse_estimate(estimate)?,
+                None => None,
+            },
+            due: match self.due {
+                Some(ref due) => parse_date(due)?,
+                None => None,
+            },
+            scheduled: match self.scheduled {
+                Some(ref scheduled) => parse_date(scheduled)?,
+                None => None,
+            },
+            priority: None,
+            tags: Vec::new(),
+            url: self.url.clone().filter(|url| !url.trim().is_empty()),
+            review_every: match self.review_every {
+                Some(ref interval) => parse_review_interval(interval)?,
+                None => None,
+            },
+            someday: self.someday.unwrap_or(false),
+        })
+    }
+
+    /// Parses the name of the item along with the fields written on it (see [`quickadd`]), returning the name, the
+    /// context and the extras. Fields given as options take precedence over the ones on the name.
+    pub fn parse_name(&self) -> Result<(String, String, ItemExtras), String> {
+        let name = self
+            .name
+            .as_deref()
+            .ok_or("the name of the item is missing")?;
+        let mut extras = self.extras()?;
+
+        if self.raw == Some(true) {
+            return Ok((
+                name.to_string(),
+                self.context.clone().unwrap_or_default(),
+                extras,
+            ));
+        }
+
+        let parsed = quickadd::parse(name)?;
+
+        extras.priority = parsed.priority;
+        extras.tags = parsed.tags;
+        if extras.due.is_none() {
+            extras.due = parsed.due;
+        }
+
+        let context = self.context.clone().or(parsed.context).unwrap_or_default();
+
+        Ok((parsed.name, context, extras))
+    }
+}
+
+/// The already parsed fields of a new item that aren't handled by the item constructor.
+pub struct ItemExtras {
+    pub estimate: Option<u32>,
+    pub due: Option<NaiveDate>,
+    pub scheduled: Option<NaiveDate>,
+    pub priority: Option<char>,
+    pub tags: Vec<String>,
+    pub url: Option<String>,
+    pub review_every: Option<u32>,
+    pub someday: bool,
+}
+
+impl ItemExtras {
+    pub fn apply(&self, item: &mut Item) {
+        item.estimate = self.estimate;
+        item.due = self.due;
+        item.scheduled = self.scheduled;
+        item.priority = self.priority;
+        item.tags = self.tags.clone();
+        item.url = self.url.clone();
+        item.review_every = self.review_every;
+        item.someday = self.someday;
+    }
+}
+
+#[derive(Debug, Clap, Clone)]
+pub struct FilterArgs {
+    #[clap(
+        short,
+        long,
+        setting = ArgSettings::AllowEmptyValues,
+        about = "Only show items on this context - can be repeated; prefix it with ! to hide the context instead, or use .none (or an empty string) for items without one"
+    )]
+    pub context: Vec<String>,
+    #[clap(
+        short,
+        long,
+        about = "Only show the items of this project, given by its name or reference ID"
+    )]
+    pub project: Option<String>,
+    #[clap(
+        long,
+        about = "Only show items modified since a date or since a while ago, like 2020-12-31, last friday or 3d"
+    )]
+    pub modified_since: Option<String>,
+    #[clap(
+        long,
+        about = "Only show items created before a date or before a while ago, like 2020-12-31 or 2w"
+    )]
+    pub created_before: Option<String>,
+}
+
+impl FilterArgs {
+    /// Parses the filters on the creation and modification times of the items.
+    pub fn date_filter(&self) -> Result<DateFilter, String> {
+        Ok(DateFilter {
+            modified_since: match self.modified_since {
+                Some(ref since) => Some(parse_moment(since)?),
+                None => None,
+            },
+            created_before: match self.created_before {
+                Some(ref before) => Some(parse_moment(before)?),
+                None => None,
+            },
+        })
+    }
+}
+
+#[derive(Debug, Clap)]
+pub struct NextArgs {
+    #[clap(
+        short,
+        long,
+        about = "What counts as a next action - first-child (the root items, with their first pending child), leaves (every pending item without pending children) or per-context (the first leaves of each context); default: $ITMN_NEXT_MODE => first-child"
+    )]
+    pub mode: Option<String>,
+    #[clap(
+        short = 'n',
+        long,
+        about = "How many items to show per context on the per-context mode (default: 1)"
+    )]
+    pub count: Option<usize>,
+    #[clap(flatten)]
+    pub filter: FilterArgs,
+}
+
+impl NextArgs {
+    /// Parses the mode, falling back to `$ITMN_NEXT_MODE` and then to [`NextMode::FirstChild`].
+    pub fn mode(&self) -> Result<NextMode, String> {
+        let mode = match self.mode {
+            Some(ref mode) => mode.clone(),
+            None => std::env::var("ITMN_NEXT_MODE").unwrap_or_default(),
+        };
+
+        match mode.trim() {
+            "" | "first-child" => Ok(NextMode::FirstChild),
+            "leaves" => Ok(NextMode::Leaves),
+            "per-context" => match self.count.unwrap_or(1) {
+                0 => Err("the count of items per context must be at least 1".into()),
+                count => Ok(NextMode::PerContext(count)),
+            },
+            other => Err(format!(
+                "unknown next mode {:?} (expected first-child, leaves or per-context)",
+                other
+            )),
+        }
+    }
+}
+
+/// What the `next` report considers to be the next actions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NextMode {
+    /// The root items, each with its first pending child.
+    FirstChild,
+    /// The pending items that don't have pending children.
+    Leaves,
+    /// The first leaves of each context, up to the amount given.
+    PerContext(usize),
+}
+
+#[derive(Debug, Clap)]
+pub struct LogArgs {
+    #[clap(
+        short,
+        long,
+        about = "How far back to look, like 3d, 1w or 2m (default: 1w)"
+    )]
+    pub since: Option<String>,
+}
+
+#[derive(Debug, Clap)]
+pub struct ArchiveArgs {
+    #[clap(
+        short,
+        long,
+        about = "Only archive items done longer than this ago, like 3d, 1w or 2m (default: 30d)"
+    )]
+    pub older_than: Option<String>,
+}
+
 #[derive(Debug, Clap)]
-pub enum SubCmd {
-    // #[clap(subcommand, about = "Shows a report - defaults to [next]")]
-    // TODO: Report(ReportSelection),
-    #[clap(alias = "ls", about = "An alias to the [except-done] report")]
-    List,
-    #[clap(about = "An alias to the [next] report")]
-    Next,
-    #[clap(about = "Add an item")]
-    Add(ItemAddDetails),
+pub struct PruneArgs {
+    #[clap(short, long, about = "Only delete done items without children")]
+    pub leaves_only: Option<bool>,
     #[clap(
-        aliases = &["s", "sel", "sri"],
-        about = "Select items by reference ID and do something with them",
+        short,
+        long,
+        about = "Only delete items done longer than this ago, like 3d, 1w or 2m"
     )]
-    SelRefID(SelectionDetails),
+    pub older_than: Option<String>,
+    #[clap(short, long, about = "Skip warning/confirmation messages (unsafe)")]
+    pub force: Option<bool>,
+}
+
+#[derive(Debug, Clap)]
+pub struct MergeArgs {
+    #[clap(about = "The reference ID of the item that will be kept")]
+    pub first: u32,
+    #[clap(about = "The reference ID of the item that will be merged into the first one")]
+    pub second: u32,
+}
+
+#[derive(Debug, Clap)]
+pub struct ImportArgs {
     #[clap(
-        aliases = &["flatlist", "fl"],
-        about = "List all visible items, prepended by the ID",
+        short,
+        long,
+        about = "The format of the imported data - one of: todotxt, taskwarrior (the output of `task export`), markdown (nested bullet lists)"
     )]
-    FlatList,
-    // #[clap(aliases = &["sel-internal", "sii"], about = "Select items by internal ID and do something with them")]
-    // TODO: SelInternalID(SelectionDetails),
-    // TODO: Search,
-    // TODO: RegexMatch,
+    pub format: String,
+    #[clap(about = "The file to read from (default: stdin)")]
+    pub file: Option<String>,
+    #[clap(
+        short,
+        long,
+        about = "The reference ID of the item to put the imported items under (default: the root)"
+    )]
+    pub under: Option<u32>,
 }
 
 #[derive(Debug, Clap)]
-pub struct ItemAddDetails {
-    #[clap(about = "The name of the item")]
+pub struct ImportSubtreeArgs {
+    #[clap(about = "The items file to read from")]
+    pub file: String,
+    #[clap(
+        short,
+        long,
+        about = "The reference ID of the item to put the imported items under (default: the root)"
+    )]
+    pub under: Option<u32>,
+}
+
+#[derive(Debug, Clap)]
+pub struct ExportArgs {
+    #[clap(
+        short,
+        long,
+        about = "The format of the exported data - one of: ics, markdown, org, todotxt, dot, mermaid"
+    )]
+    pub format: String,
+    #[clap(
+        about = "The reference IDs of the items to export, along with their children (default: all items)"
+    )]
+    pub range: Option<String>,
+    #[clap(short, long, about = "The file to write to (default: stdout)")]
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Clap)]
+pub struct RemindArgs {
+    #[clap(
+        short,
+        long,
+        about = "Keep running, checking the items file periodically"
+    )]
+    pub daemon: Option<bool>,
+    #[clap(
+        short,
+        long,
+        about = "How far ahead to look for due items, like 1d or 1w (default: 1d)"
+    )]
+    pub within: Option<String>,
+    #[clap(
+        short,
+        long,
+        about = "How long to wait between checks on daemon mode, like 15min or 1h (default: 15min)"
+    )]
+    pub interval: Option<String>,
+    #[clap(
+        short,
+        long,
+        about = "Only print the reminders, without using notify-send"
+    )]
+    pub print: Option<bool>,
+}
+
+#[derive(Debug, Clap)]
+pub struct ContextsArgs {
+    #[clap(short, long, about = "Output the list as JSON")]
+    pub json: Option<bool>,
+}
+
+#[derive(Debug, Clap)]
+pub struct ProjectsArgs {
+    #[clap(short, long, about = "Output the list as JSON")]
+    pub json: Option<bool>,
+}
+
+#[derive(Debug, Clap)]
+pub struct StaleArgs {
+    #[clap(
+        about = "How long an item should be untouched to be stale, as a duration (like 2w) or a date",
+        default_value = "30d"
+    )]
+    pub since: String,
+}
+
+#[derive(Debug, Clap)]
+pub struct PlanArgs {
+    #[clap(
+        short,
+        long,
+        about = "Add the picked items to today's plan instead of replacing it"
+    )]
+    pub add: Option<bool>,
+}
+
+#[derive(Debug, Clap)]
+pub struct TodayArgs {
+    #[clap(
+        short,
+        long,
+        about = "Mark the items on these positions of the plan as done, like 1,3"
+    )]
+    pub check: Option<String>,
+}
+
+#[derive(Debug, Clap)]
+pub struct StatsArgs {
+    #[clap(
+        short,
+        long,
+        about = "How many weeks of completions to show, defaults to 8"
+    )]
+    pub weeks: Option<u32>,
+    #[clap(short, long, about = "Output the statistics as JSON")]
+    pub json: Option<bool>,
+}
+
+#[derive(Debug, Clap)]
+pub struct CompletionsArgs {
+    #[clap(about = "The shell - bash, zsh, fish, elvish or powershell")]
+    pub shell: String,
+}
+
+#[derive(Debug, Clap)]
+pub struct GenManArgs {
+    #[clap(about = "The directory to write the pages to", default_value = ".")]
+    pub dir: String,
+}
+
+#[derive(Debug, Clap)]
+pub struct CompleteArgs {
+    #[clap(about = "What to list - contexts or ids")]
+    pub kind: String,
+}
+
+#[derive(Debug, Clap)]
+pub struct ReportArgs {
+    #[clap(subcommand, about = "The report to be shown")]
+    pub kind: ReportKind,
+}
+
+#[derive(Debug, Clap)]
+pub enum ReportKind {
+    #[clap(
+        about = "Chart how many items were created and done per week, to see if the backlog is shrinking"
+    )]
+    Burndown(BurndownArgs),
+    #[clap(
+        about = "List the pending items without a context, so they can be triaged - like list -c .none, but flat and including hidden items"
+    )]
+    NoContext,
+}
+
+#[derive(Debug, Clap)]
+pub struct BurndownArgs {
+    #[clap(short, long, about = "How many weeks to show, defaults to 8")]
+    pub weeks: Option<u32>,
+}
+
+#[derive(Debug, Clap)]
+pub struct ReviewArgs {
+    #[clap(
+        short,
+        long,
+        about = "Review the pending items (on any depth) that weren't touched for this long, like 2w, instead of the root items"
+    )]
+    pub stale: Option<String>,
+}
+
+#[derive(Debug, Clap)]
+pub struct ContextArgs {
+    #[clap(subcommand, about = "What to do with the context")]
+    pub action: ContextAction,
+}
+
+#[derive(Debug, Clap)]
+pub enum ContextAction {
+    #[clap(about = "Change a context on all items that use it")]
+    Rename(ContextRenameArgs),
+}
+
+#[derive(Debug, Clap)]
+pub struct ContextRenameArgs {
+    #[clap(about = "The context to be renamed")]
+    pub old: String,
+    #[clap(about = "The new name of the context; set to .none to remove it from the items")]
+    pub new: String,
+}
+
+#[derive(Debug, Clap)]
+pub struct TemplateArgs {
+    #[clap(subcommand, about = "What to do with the templates")]
+    pub action: TemplateAction,
+}
+
+#[derive(Debug, Clap)]
+pub enum TemplateAction {
+    #[clap(about = "Save the selected items (and their children) as a template")]
+    Save(TemplateSaveArgs),
+    #[clap(about = "Create copies of the items on a template")]
+    Apply(TemplateApplyArgs),
+    #[clap(alias = "ls", about = "List the saved templates")]
+    List,
+    #[clap(alias = "rm", about = "Delete a template")]
+    Delete(TemplateDeleteArgs),
+}
+
+#[derive(Debug, Clap)]
+pub struct TemplateSaveArgs {
+    #[clap(about = "The name of the template")]
     pub name: String,
-    #[clap(short, long, about = "The context of the item")]
-    pub context: Option<String>,
-    #[clap(short, long, about = "If the item is a note")]
-    pub note: Option<bool>,
-    #[clap(short, long, about = "The description of the item")]
-    pub description: Option<String>,
+    #[clap(about = "The items to be saved, as a selection range")]
+    pub range: String,
+}
+
+#[derive(Debug, Clap)]
+pub struct TemplateApplyArgs {
+    #[clap(about = "The name of the template")]
+    pub name: String,
+    #[clap(
+        short,
+        long,
+        about = "The reference ID of the item to put the copies under, instead of the root"
+    )]
+    pub under: Option<u32>,
+}
+
+#[derive(Debug, Clap)]
+pub struct TemplateDeleteArgs {
+    #[clap(about = "The name of the template")]
+    pub name: String,
+}
+
+#[derive(Debug, Clap)]
+pub struct AgendaArgs {
+    #[clap(
+        short,
+        long,
+        about = "How many days to show, starting from today (default: 7)"
+    )]
+    pub days: Option<u32>,
+    #[clap(flatten)]
+    pub filter: FilterArgs,
+}
+
+#[derive(Debug, Clap)]
+pub struct TimesheetArgs {
+    #[clap(short, long, about = "Only count time spent on the current week")]
+    pub week: Option<bool>,
+    #[clap(
+        short,
+        long,
+        about = "Only count time spent since this long ago, like 3d, 1w or 2m"
+    )]
+    pub since: Option<String>,
 }
 
 #[derive(Debug, Clap)]
 pub struct SelectionDetails {
-    #[clap(about = "The selection range")]
-    pub range: String, // TODO: document range syntax
+    #[clap(
+        about = "The selection range - a comma-separated list of IDs, ranges (3..7, 3-7, 5.., ..9), subtrees (12/*), all, state:<state> and ctx:<context>; prefix terms with ! to exclude them, and the whole range with <profile>: to select from another profile"
+    )]
+    pub range: Option<String>,
+    #[clap(
+        short,
+        long,
+        about = "Pick the items with the picker ($PICKER), among the ones on the range (if given) - the default without a range"
+    )]
+    pub pick: Option<bool>,
+    #[clap(
+        short,
+        long,
+        about = "Only select the items on this state (todo, done, note, waiting or cancelled) - without a range, select all of them"
+    )]
+    pub state: Option<String>,
+    #[clap(
+        short,
+        long,
+        about = "Also select the descendants of the selected items, like the 12/* range does"
+    )]
+    pub recursive: Option<bool>,
     #[clap(
         subcommand,
         about = "What to do with the selection, defaults to [list-tree]"
@@ -71,14 +1152,82 @@ pub struct SelectionDetails {
     pub action: Option<SelectionAction>,
 }
 
+impl SelectionDetails {
+    /// Removes the profile prefix of the range (like `work:` on `work:3,5`) and returns it, if there's one.
+    pub fn take_profile(&mut self) -> Option<String> {
+        let range = self.range.as_ref()?;
+        let index = range.find(':')?;
+        let profile = &range[..index];
+
+        // these are selection terms, not profiles
+        let is_term = matches!(profile, "state" | "ctx" | "context")
+            || profile.starts_with('!')
+            || profile.contains(',');
+
+        if is_term {
+            return None;
+        }
+
+        let profile = profile.to_string();
+        self.range = Some(range[index + 1..].to_string());
+
+        Some(profile)
+    }
+
+    /// Whether the action only lists the selection.
+    pub fn only_lists(&self) -> bool {
+        matches!(
+            self.action,
+            None | Some(SelectionAction::ListTree)
+                | Some(SelectionAction::ListBrief)
+                | Some(SelectionAction::ListShallow)
+        )
+    }
+}
+
 #[derive(Debug, Clap)]
 pub enum SelectionAction {
     #[clap(alias = "mod", about = "Modify the matches")]
     Modify(ItemBatchMod),
     #[clap(aliases = &["ac"], about = "Add a child to each one of the matches")]
-    Add(ItemAddDetails),
-    #[clap(about = "Mark the items on the selection as DONE, if their states are TODO")]
-    Done,
+    Add(SelAddArgs),
+    #[clap(
+        about = "Mark the items on the selection as DONE, if their states are TODO or WAITING, warning about the other ones"
+    )]
+    Done(DoneArgs),
+    #[clap(
+        aliases = &["reopen"],
+        about = "Mark the items on the selection as TODO, if their states are DONE or CANCELLED - closed items can be selected with --state or sel-internal-id"
+    )]
+    Undone,
+    #[clap(
+        about = "Mark the items on the selection as DONE if their states are TODO or WAITING, or as TODO if their states are DONE"
+    )]
+    Toggle,
+    #[clap(
+        about = "Mark the items on the selection as WAITING, if their states are TODO or WAITING"
+    )]
+    Wait(WaitArgs),
+    #[clap(
+        about = "Mark the items on the selection as CANCELLED, if their states are TODO or WAITING"
+    )]
+    Cancel,
+    #[clap(
+        about = "Hide the selected item(s) from the reports until a date, like 2020-12-31, friday or next month, or for a while, like 3d or 12h"
+    )]
+    Snooze(SnoozeArgs),
+    #[clap(about = "Make the selected item(s) show up on the reports again")]
+    Unsnooze,
+    #[clap(about = "Star the selected item(s), marking them as ones that must be done today")]
+    Star,
+    #[clap(about = "Remove the star from the selected item(s)")]
+    Unstar,
+    #[clap(about = "Give the selected item a reference ID of your choice, like a memorable one")]
+    SetRef(SetRefArgs),
+    #[clap(
+        about = "Remove the reference IDs of the selected closed item(s), which normally lose them when closed"
+    )]
+    ClearRef,
     #[clap(alias = "tree", about = "List selection in a tree")]
     ListTree,
     #[clap(aliases = &["l", "ls", "list"], about = "List selection, showing only the first child of each, if any")]
@@ -86,15 +1235,92 @@ pub enum SelectionAction {
     #[clap(about = "List selection without showing any children")]
     ListShallow,
     #[clap(aliases = &["del", "rm", "remove"], about = "Delete selected items")]
-    Delete(ForceArgs),
+    Delete(DeleteArgs),
     #[clap(about = "Swap two items")]
     Swap(ForceArgs),
     #[clap(alias = "chown", about = "Change ownership of the selected item(s)")]
     ChangeOwnership(ChownArgs),
+    #[clap(about = "Move the selected item(s) under another item")]
+    MoveTo(MoveToArgs),
+    #[clap(about = "Move the selected item(s) to the root")]
+    MoveToRoot,
+    #[clap(about = "Change the position of an item among its siblings")]
+    Move(MoveArgs),
+    #[clap(
+        aliases = &["cp", "duplicate"],
+        about = "Copy the selected item(s) and their children, with new IDs"
+    )]
+    Copy(CopyArgs),
+    #[clap(
+        about = "Write the selected item(s) and their children to a standalone items file, with new IDs"
+    )]
+    Export(SelExportArgs),
+    #[clap(
+        alias = "outdent",
+        about = "Move the selected item(s) to the level of their parents, right after them"
+    )]
+    Promote,
+    #[clap(
+        alias = "indent",
+        about = "Move the selected item(s) to the end of the children of their previous siblings"
+    )]
+    Demote,
+    #[clap(about = "Block the selected item(s) until another item is done")]
+    BlockOn(BlockArgs),
+    #[clap(
+        about = "Remove a blocker from the selected item(s), or all of them if none is specified"
+    )]
+    Unblock(UnblockArgs),
+    #[clap(about = "Start working on an item, stopping any other running timer")]
+    Start,
+    #[clap(about = "Stop working on the selected item(s)")]
+    Stop,
+    #[clap(about = "Edit the selection and its children as an outline on $EDITOR")]
+    Edit,
     #[clap(aliases = &["ed", "edesc"], about = "Edit the description of an item")]
     EditDescription,
     #[clap(aliases = &["d", "desc"], about = "Print the description of an item")]
     PrintDescription,
+    #[clap(
+        aliases = &["breadcrumbs"],
+        about = "Print the names of the selected item(s) along with the ones of their ancestors, like Project > Phase > Task"
+    )]
+    Path,
+    #[clap(
+        aliases = &["log"],
+        about = "Show when the selected item(s) were created, renamed, moved, deferred and closed"
+    )]
+    History,
+    #[clap(
+        aliases = &["info"],
+        about = "Show every field of the selected item(s), along with their ancestors and children"
+    )]
+    Show,
+    #[clap(about = "Open the URL of the selected item(s) with $OPENER (default: xdg-open)")]
+    Open,
+    #[clap(
+        aliases = &["reviewed"],
+        about = "Record that the selected item(s) were reviewed now, postponing their next review"
+    )]
+    Touch,
+    #[clap(about = "Attach a file to the selected item(s)")]
+    Attach(AttachArgs),
+    #[clap(about = "Remove files attached to the selected item(s)")]
+    Detach(DetachArgs),
+    #[clap(
+        aliases = &["att"],
+        about = "List the files attached to the selected item(s)"
+    )]
+    Attachments,
+    #[clap(
+        aliases = &["open-att"],
+        about = "Open the files attached to the selected item(s) with $OPENER (default: xdg-open)"
+    )]
+    OpenAttachment(OpenAttachmentArgs),
+    #[clap(about = "Link the selected item(s) to an item of another profile")]
+    Link(LinkArgs),
+    #[clap(about = "Remove the links of the selected item(s) to items of other profiles")]
+    Unlink(UnlinkArgs),
 }
 
 #[derive(Debug, Clap, Clone)]
@@ -104,11 +1330,67 @@ pub struct ItemBatchMod {
     #[clap(
         short,
         long,
-        about = "The item's new context; set to an empty string to unset"
+        about = "The item's new context; set to an empty string to unset"
+    )]
+    pub context: Option<String>,
+    #[clap(short, long, about = "The item's new type")]
+    pub note: Option<bool>,
+    #[clap(long, about = "The item's new state - todo, done or note")]
+    pub state: Option<String>,
+    #[clap(
+        short,
+        long,
+        about = "If the item is a project - only root items can be one"
+    )]
+    pub project: Option<bool>,
+    #[clap(
+        long,
+        about = "If the item is on the someday/maybe list, which keeps it out of the reports but not of reviews"
+    )]
+    pub someday: Option<bool>,
+    #[clap(
+        long,
+        about = "The item's new priority, from A (the highest) to Z; set to an empty string to unset"
+    )]
+    pub priority: Option<String>,
+    #[clap(
+        short,
+        long,
+        about = "The item's new estimate, like 30min or 2h; set to an empty string to unset"
+    )]
+    pub estimate: Option<String>,
+    #[clap(
+        long,
+        about = "The item's new due date; set to an empty string to unset"
+    )]
+    pub due: Option<String>,
+    #[clap(
+        long,
+        about = "The item's new scheduled date; set to an empty string to unset"
+    )]
+    pub scheduled: Option<String>,
+    #[clap(long, about = "The item's new URL; set to an empty string to unset")]
+    pub url: Option<String>,
+    #[clap(
+        long,
+        about = "How often the item should come up on reviews, like 30d; set to an empty string to unset"
+    )]
+    pub review_every: Option<String>,
+    #[clap(long, about = "Append a child with this name to each item")]
+    pub add_child: Option<String>,
+    #[clap(
+        short,
+        long,
+        about = "Apply the changes without asking for confirmation"
     )]
-    pub context: Option<String>,
-    #[clap(short, long, about = "The item's new type")]
-    pub note: Option<bool>,
+    pub yes: Option<bool>,
+    #[clap(long, about = "Only show what would change, without changing anything")]
+    pub dry_run: Option<bool>,
+    #[clap(
+        long,
+        about = "Refuse the changes if they put a context over its WIP limit, instead of warning"
+    )]
+    pub strict: Option<bool>,
 }
 
 impl ItemBatchMod {
@@ -136,12 +1418,96 @@ impl ItemBatchMod {
             }
         }
 
+        if let Ok(Some(state)) = self.new_state() {
+            vec.push(format!("Change state to {}", state.name()).into());
+        }
+
+        if let Some(project) = self.project {
+            vec.push(if project {
+                "Mark as a project".into()
+            } else {
+                "Unmark as a project".into()
+            });
+        }
+
+        if let Some(someday) = self.someday {
+            vec.push(if someday {
+                "Move to the someday/maybe list".into()
+            } else {
+                "Remove from the someday/maybe list".into()
+            });
+        }
+
+        if let Some(ref priority) = self.priority {
+            vec.push(match parse_priority(priority) {
+                Ok(Some(priority)) => format!("Change priority to {}", priority).into(),
+                _ => "Remove priority".into(),
+            });
+        }
+
+        if let Some(ref estimate) = self.estimate {
+            vec.push(match parse_estimate(estimate) {
+                Ok(None) => "Remove estimate".into(),
+                _ => format!("Change estimate to {:?}", estimate).into(),
+            });
+        }
+
+        if let Some(ref due) = self.due {
+            vec.push(match parse_date(due) {
+                Ok(Some(date)) => format!("Change due date to {}", date).into(),
+                _ => "Remove due date".into(),
+            });
+        }
+
+        if let Some(ref scheduled) = self.scheduled {
+            vec.push(match parse_date(scheduled) {
+                Ok(Some(date)) => format!("Change scheduled date to {}", date).into(),
+                _ => "Remove scheduled date".into(),
+            });
+        }
+
+        if let Some(ref url) = self.url {
+            vec.push(if url.trim().is_empty() {
+                "Remove URL".into()
+            } else {
+                format!("Change URL to {}", url.trim()).into()
+            });
+        }
+
+        if let Some(ref interval) = self.review_every {
+            vec.push(match parse_review_interval(interval) {
+                Ok(None) => "Remove review interval".into(),
+                _ => format!("Review every {}", interval.trim()).into(),
+            });
+        }
+
+        if let Some(ref child) = self.add_child {
+            vec.push(format!("Add a child named {:?}", child).into());
+        }
+
         vec
     }
 
+    /// Parses the state given with `--state`, if any.
+    pub fn new_state(&self) -> Result<Option<ItemState>, String> {
+        match self.state.as_deref().map(|s| s.trim().to_lowercase()) {
+            None => Ok(None),
+            Some(state) => match state.as_str() {
+                "todo" => Ok(Some(ItemState::Todo)),
+                "done" => Ok(Some(ItemState::Done)),
+                "note" => Ok(Some(ItemState::Note)),
+                _ => Err(format!(
+                    "unknown state {:?} (expected todo, done or note)",
+                    state
+                )),
+            },
+        }
+    }
+
     /// Apply modifications to an item, without consuming self.
     ///
-    /// Might clone some of the contents of self, but not necessarily all.
+    /// Might clone some of the contents of self, but not necessarily all. The reference ID of the item isn't updated
+    /// when its state changes, and the child from `--add-child` isn't added, since both need the manager.
     pub fn mod_item_by_ref(&self, item: &mut Item) {
         if let Some(ref name) = self.name {
             item.name = name.clone();
@@ -151,6 +1517,10 @@ impl ItemBatchMod {
             item.set_context(&context);
         }
 
+        if let Ok(Some(state)) = self.new_state() {
+            item.set_state(state);
+        }
+
         if let Some(note) = self.note {
             if note {
                 item.state = ItemState::Note;
@@ -161,11 +1531,86 @@ impl ItemBatchMod {
                 }
             }
         }
+
+        if let Some(project) = self.project {
+            item.project = project;
+        }
+
+        if let Some(someday) = self.someday {
+            item.someday = someday;
+        }
+
+        if let Some(ref priority) = self.priority {
+            if let Ok(priority) = parse_priority(priority) {
+                item.priority = priority;
+            }
+        }
+
+        if let Some(ref estimate) = self.estimate {
+            if let Ok(minutes) = parse_estimate(estimate) {
+                item.estimate = minutes;
+            }
+        }
+
+        if let Some(ref due) = self.due {
+            if let Ok(date) = parse_date(due) {
+                item.due = date;
+            }
+        }
+
+        if let Some(ref scheduled) = self.scheduled {
+            if let Ok(date) = parse_date(scheduled) {
+                item.scheduled = date;
+            }
+        }
+
+        if let Some(ref url) = self.url {
+            item.url = Some(url.trim().to_string()).filter(|url| !url.is_empty());
+        }
+
+        if let Some(ref interval) = self.review_every {
+            if let Ok(days) = parse_review_interval(interval) {
+                item.review_every = days;
+            }
+        }
+    }
+
+    /// Checks if the values that need parsing are valid.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.note.is_some() && self.state.is_some() {
+            return Err(String::from("--note and --state can't be used together"));
+        }
+
+        self.new_state()?;
+
+        if let Some(ref priority) = self.priority {
+            parse_priority(priority)?;
+        }
+
+        if let Some(ref estimate) = self.estimate {
+            parse_estimate(estimate)?;
+        }
+
+        if let Some(ref due) = self.due {
+            parse_date(due)?;
+        }
+
+        if let Some(ref scheduled) = self.scheduled {
+            parse_date(scheduled)?;
+        }
+
+        if let Some(ref interval) = self.review_every {
+            parse_review_interval(interval)?;
+        }
+
+        Ok(())
     }
 
     /// Apply modifications to an item, consuming self.
     #[allow(unused)]
     pub fn mod_item(self, item: &mut Item) {
+        let new_state = self.new_state();
+
         if let Some(name) = self.name {
             item.name = name;
         }
@@ -184,6 +1629,155 @@ impl ItemBatchMod {
                 }
             }
         }
+
+        if let Ok(Some(state)) = new_state {
+            item.set_state(state);
+        }
+
+        if let Some(project) = self.project {
+            item.project = project;
+        }
+
+        if let Some(someday) = self.someday {
+            item.someday = someday;
+        }
+
+        if let Some(priority) = self.priority {
+            if let Ok(priority) = parse_priority(&priority) {
+                item.priority = priority;
+            }
+        }
+
+        if let Some(estimate) = self.estimate {
+            if let Ok(minutes) = parse_estimate(&estimate) {
+                item.estimate = minutes;
+            }
+        }
+
+        if let Some(due) = self.due {
+            if let Ok(date) = parse_date(&due) {
+                item.due = date;
+            }
+        }
+
+        if let Some(scheduled) = self.scheduled {
+            if let Ok(date) = parse_date(&scheduled) {
+                item.scheduled = date;
+            }
+        }
+
+        if let Some(url) = self.url {
+            item.url = Some(url.trim().to_string()).filter(|url| !url.is_empty());
+        }
+
+        if let Some(interval) = self.review_every {
+            if let Ok(days) = parse_review_interval(&interval) {
+                item.review_every = days;
+            }
+        }
+    }
+}
+
+/// Parses a date given on the command line, like `2020-12-31`, `tomorrow`, `fri` or `in 3 weeks` (see
+/// [`utils::date`]). An empty string means no date.
+pub fn parse_date(string: &str) -> Result<Option<NaiveDate>, String> {
+    if string.trim().is_empty() {
+        return Ok(None);
+    }
+
+    utils::date::parse_date(string, Local::now().date_naive()).map(Some)
+}
+
+/// Returns the moment `date` starts on the local timezone.
+fn start_of(date: NaiveDate) -> Result<DateTime<Utc>, String> {
+    Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| format!("{} doesn't start at midnight", date))
+}
+
+/// Parses a moment in the past: either a date (see [`parse_date`]), at its start, or a duration before now, like `3d`.
+pub fn parse_moment(string: &str) -> Result<DateTime<Utc>, String> {
+    match parse_date(string) {
+        Ok(Some(date)) => start_of(date),
+        _ => utils::time::parse_duration(string)
+            .map(|duration| Utc::now() - duration)
+            .map_err(|_| format!("could not parse {:?} as a date or a duration", string)),
+    }
+}
+
+/// Parses when a snooze should end: either a date (see [`parse_date`]), at its start, or a duration from now, like `3d`
+/// or `12h`.
+pub fn parse_snooze(string: &str) -> Result<DateTime<Utc>, String> {
+    let until = match parse_date(string) {
+        Ok(Some(date)) => start_of(date)?,
+        _ => {
+            let duration = utils::time::parse_duration(string)
+                .map_err(|_| format!("could not parse {:?} as a date or a duration", string))?;
+            Utc::now() + duration
+        }
+    };
+
+    if until <= Utc::now() {
+        Err(format!("{:?} is not in the future", string))
+    } else {
+        Ok(until)
+    }
+}
+
+/// Parses an estimate given on the command line into minutes. An empty string means no estimate.
+pub fn parse_estimate(string: &str) -> Result<Option<u32>, String> {
+    if string.trim().is_empty() {
+        return Ok(None);
+    }
+
+    let duration = utils::time::parse_duration(string)?;
+
+    if duration.num_minutes() <= 0 {
+        Err(format!("estimate {:?} is shorter than a minute", string))
+    } else {
+        Ok(Some(duration.num_minutes() as u32))
+    }
+}
+
+/// Checks if the output goes to a terminal that can't show anything beyond plain text, by `$TERM` being `dumb`.
+pub fn is_dumb_terminal() -> bool {
+    std::env::var("TERM").map_or(false, |term| term == "dumb")
+}
+
+/// Parses a review interval given on the command line into days. An empty string means no interval.
+pub fn parse_review_interval(string: &str) -> Result<Option<u32>, String> {
+    if string.trim().is_empty() {
+        return Ok(None);
+    }
+
+    let duration = utils::time::parse_duration(string)?;
+
+    if duration.num_days() <= 0 {
+        Err(format!(
+            "review interval {:?} is shorter than a day",
+            string
+        ))
+    } else {
+        Ok(Some(duration.num_days() as u32))
+    }
+}
+
+/// Parses a priority given on the command line, a letter from `A` to `Z` in any case. An empty string means no
+/// priority.
+pub fn parse_priority(string: &str) -> Result<Option<char>, String> {
+    let mut chars = string.trim().chars();
+
+    match (chars.next(), chars.next()) {
+        (None, _) => Ok(None),
+        (Some(letter), None) if letter.is_ascii_alphabetic() => {
+            Ok(Some(letter.to_ascii_uppercase()))
+        }
+        _ => Err(format!(
+            "invalid priority {:?} (expected a letter from A to Z)",
+            string
+        )),
     }
 }
 
@@ -200,6 +1794,213 @@ impl Into<bool> for ForceArgs {
     }
 }
 
+#[derive(Debug, Clap)]
+pub struct WaitArgs {
+    #[clap(about = "What the items are waiting on")]
+    pub on: Option<String>,
+}
+
+#[derive(Debug, Clap)]
+pub struct HasArgs {
+    #[clap(
+        short,
+        long,
+        setting = ArgSettings::AllowEmptyValues,
+        about = "Only count items on this context - can be repeated; prefix it with ! to exclude the context instead, or use .none (or an empty string) for items without one"
+    )]
+    pub context: Vec<String>,
+    #[clap(
+        short,
+        long,
+        about = "Only count items on this state - todo, done, note, waiting or cancelled (default: todo and waiting)"
+    )]
+    pub state: Option<String>,
+    #[clap(long, about = "Only count items that are overdue")]
+    pub overdue: Option<bool>,
+    #[clap(long, about = "Only count items that are starred")]
+    pub starred: Option<bool>,
+    #[clap(short, long, about = "Don't print the count, only set the exit status")]
+    pub quiet: Option<bool>,
+}
+
+#[derive(Debug, Clap)]
+pub struct GrepArgs {
+    #[clap(about = "The regex to search for")]
+    pub pattern: String,
+    #[clap(short, long, about = "Ignore case when matching")]
+    pub ignore_case: Option<bool>,
+    #[clap(short, long, about = "Search the descriptions as well as the names")]
+    pub description: Option<bool>,
+    #[clap(
+        short,
+        long,
+        about = "Search closed items too, which are shown with their internal IDs (like i12)"
+    )]
+    pub all: Option<bool>,
+    #[clap(
+        short,
+        long,
+        about = "Print the parent of each match before it, separated with - instead of :, like grep -B"
+    )]
+    pub with_context: Option<bool>,
+}
+
+#[derive(Debug, Clap)]
+pub struct WatchArgs {
+    #[clap(
+        about = "The report to show - list (the default), next, flat-list, agenda, starred, snoozed, someday, blocked or active"
+    )]
+    pub report: Option<String>,
+    #[clap(flatten)]
+    pub filter: FilterArgs,
+}
+
+#[derive(Debug, Clap)]
+pub struct SyncArgs {
+    #[clap(short, long, about = "The message of the commit (default: Sync items)")]
+    pub message: Option<String>,
+    #[clap(short, long, about = "Only commit, without pulling or pushing")]
+    pub local: Option<bool>,
+}
+
+#[derive(Debug, Clap)]
+pub struct DeleteArgs {
+    #[clap(short, long, about = "Skip warning/confirmation messages (unsafe)")]
+    pub force: Option<bool>,
+    #[clap(
+        short,
+        long,
+        about = "Keep the children of the deleted items, moving them to where their parents were"
+    )]
+    pub orphan: Option<bool>,
+}
+
+#[derive(Debug, Clap)]
+pub struct DoneArgs {
+    #[clap(
+        short,
+        long,
+        about = "Also mark the pending descendants of the items as done"
+    )]
+    pub recursive: Option<bool>,
+}
+
+#[derive(Debug, Clap)]
+pub struct SnoozeArgs {
+    #[clap(about = "Until when, or for how long, the items should be hidden")]
+    pub until: String,
+}
+
+#[derive(Debug, Clap)]
+pub struct MoveToArgs {
+    #[clap(about = "The reference ID of the new parent")]
+    pub parent: u32,
+}
+
+#[derive(Debug, Clap)]
+pub struct MoveArgs {
+    #[clap(subcommand, about = "Where to move the item to")]
+    pub position: MovePosition,
+}
+
+#[derive(Debug, Clap)]
+pub enum MovePosition {
+    #[clap(about = "Swap the item with its previous sibling")]
+    Up,
+    #[clap(about = "Swap the item with its next sibling")]
+    Down,
+    #[clap(about = "Move the item before all of its siblings")]
+    Top,
+    #[clap(about = "Move the item after all of its siblings")]
+    Bottom,
+    #[clap(about = "Move the item right before one of its siblings")]
+    Before(SiblingArgs),
+    #[clap(about = "Move the item right after one of its siblings")]
+    After(SiblingArgs),
+}
+
+#[derive(Debug, Clap)]
+pub struct SiblingArgs {
+    #[clap(about = "The reference ID of the sibling")]
+    pub sibling: u32,
+}
+
+#[derive(Debug, Clap)]
+pub struct CopyArgs {
+    #[clap(
+        short,
+        long,
+        about = "Where to put the copies - .ROOT, a reference ID, or an internal ID prefixed by i (default: right after the originals)"
+    )]
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Clap)]
+pub struct SetRefArgs {
+    #[clap(about = "The new reference ID, which shouldn't be used by another item")]
+    pub id: u32,
+}
+
+#[derive(Debug, Clap)]
+pub struct SelExportArgs {
+    #[clap(
+        about = "The file to write to, which shouldn't exist yet - it's encrypted if it ends with .gpg, .asc or .age"
+    )]
+    pub file: String,
+    #[clap(
+        short,
+        long,
+        about = "Remove the items after writing them, splitting them into the new file"
+    )]
+    pub remove: Option<bool>,
+}
+
+#[derive(Debug, Clap)]
+pub struct BlockArgs {
+    #[clap(about = "The reference ID of the blocking item")]
+    pub blocker: u32,
+}
+
+#[derive(Debug, Clap)]
+pub struct AttachArgs {
+    #[clap(about = "The path of the file")]
+    pub path: String,
+}
+
+#[derive(Debug, Clap)]
+pub struct DetachArgs {
+    #[clap(
+        about = "The attachment to remove, as its path or its number on the attachments action - all of them if not specified"
+    )]
+    pub target: Option<String>,
+}
+
+#[derive(Debug, Clap)]
+pub struct OpenAttachmentArgs {
+    #[clap(
+        about = "The number of the attachment to open, as shown by the attachments action - all of them if not specified"
+    )]
+    pub number: Option<usize>,
+}
+
+#[derive(Debug, Clap)]
+pub struct LinkArgs {
+    #[clap(about = "The linked item, as profile:refid (like work:3)")]
+    pub target: String,
+}
+
+#[derive(Debug, Clap)]
+pub struct UnlinkArgs {
+    #[clap(about = "The link to remove, as profile:refid - all of them if not specified")]
+    pub target: Option<String>,
+}
+
+#[derive(Debug, Clap)]
+pub struct UnblockArgs {
+    #[clap(about = "The reference ID of the blocking item")]
+    pub blocker: Option<u32>,
+}
+
 #[derive(Debug, Clap)]
 pub struct ChownArgs {
     #[clap(
@@ -0,0 +1,187 @@
+//! Stores the support for encrypted items files, which are decrypted on load and encrypted again on save by calling
+//! external programs. The encryption is chosen by the extension of the file:
+//!
+//! * `.gpg` and `.asc` use `gpg`, encrypting to `$ITMN_GPG_RECIPIENT` (or to the default key, if it's not set), with
+//!   `.asc` files being ASCII-armored;
+//! * `.age` uses `age`, decrypting with the identity file on `$ITMN_AGE_IDENTITY` and encrypting to the recipient on
+//!   `$ITMN_AGE_RECIPIENT`.
+//!
+//! Other files are read and written as they are.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// The ways a file can be encrypted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encryption {
+    Gpg { armor: bool },
+    Age,
+}
+
+impl Encryption {
+    /// Finds out the encryption of the file on `path` by its extension, if it has one.
+    pub fn detect(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "gpg" => Some(Self::Gpg { armor: false }),
+            "asc" => Some(Self::Gpg { armor: true }),
+            "age" => Some(Self::Age),
+            _ => None,
+        }
+    }
+
+    /// The extension of the files encrypted this way, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Gpg { armor: false } => "gpg",
+            Self::Gpg { armor: true } => "asc",
+            Self::Age => "age",
+        }
+    }
+
+    fn decrypt_command(self, path: &Path) -> Result<Command, String> {
+        let mut command = match self {
+            Self::Gpg { .. } => {
+                let mut command = Command::new("gpg");
+                command.args(&["--quiet", "--decrypt"]);
+                command
+            }
+            Self::Age => {
+                let mut command = Command::new("age");
+                command.args(&["--decrypt", "--identity", &env_var("ITMN_AGE_IDENTITY")?]);
+                command
+            }
+        };
+
+        command.arg(path);
+
+        Ok(command)
+    }
+
+    fn encrypt_command(self) -> Result<Command, String> {
+        match self {
+            Self::Gpg { armor } => {
+                let mut command = Command::new("gpg");
+                command.args(&["--quiet", "--batch", "--yes", "--encrypt"]);
+
+                if armor {
+                    command.arg("--armor");
+                }
+
+                match std::env::var("ITMN_GPG_RECIPIENT") {
+                    Ok(recipient) if !recipient.is_empty() => {
+                        command.args(&["--recipient", &recipient])
+                    }
+                    _ => command.arg("--default-recipient-self"),
+                };
+
+                Ok(command)
+            }
+            Self::Age => {
+                let mut command = Command::new("age");
+                command.args(&["--encrypt", "--recipient", &env_var("ITMN_AGE_RECIPIENT")?]);
+
+                Ok(command)
+            }
+        }
+    }
+
+    /// The name of the program used for this encryption, for error messages.
+    fn program(self) -> &'static str {
+        match self {
+            Self::Gpg { .. } => "gpg",
+            Self::Age => "age",
+        }
+    }
+}
+
+/// Reads the file on `path`, decrypting it according to its extension. Files that don't exist are read as empty.
+pub fn read(path: &Path) -> Result<String, String> {
+    read_with(path, Encryption::detect(path))
+}
+
+/// Reads the file on `path`, decrypting it with `encryption`, if any. Files that don't exist are read as empty.
+pub fn read_with(path: &Path, encryption: Option<Encryption>) -> Result<String, String> {
+    let encryption = match encryption {
+        Some(encryption) => encryption,
+        None => return utils::io::touch_read(path),
+    };
+
+    if !path.exists() {
+        return Ok(String::new());
+    }
+
+    let output = encryption
+        .decrypt_command(path)?
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("failed to start {}: {}", encryption.program(), e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed to decrypt {}",
+            encryption.program(),
+            path.display()
+        ));
+    }
+
+    String::from_utf8(output.stdout).map_err(|_| {
+        format!(
+            "the decrypted contents of {} aren't valid UTF-8",
+            path.display()
+        )
+    })
+}
+
+/// Writes `contents` to the file on `path`, encrypting them according to its extension.
+pub fn write(path: &Path, contents: &str) -> Result<(), String> {
+    let encryption = match Encryption::detect(path) {
+        Some(encryption) => encryption,
+        None => {
+            return utils::io::write_atomic(path, contents.as_bytes()).map_err(|e| e.to_string())
+        }
+    };
+
+    let mut child = encryption
+        .encrypt_command()?
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| format!("failed to start {}: {}", encryption.program(), e))?;
+
+    // the contents are sent from another thread, so the program doesn't get stuck with its output full
+    let mut stdin = child.stdin.take().unwrap();
+    let contents = contents.to_string();
+    let sender = thread::spawn(move || stdin.write_all(contents.as_bytes()));
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("failed to wait for {}: {}", encryption.program(), e))?;
+
+    sender.join().unwrap().map_err(|e| {
+        format!(
+            "failed to send the items to {}: {}",
+            encryption.program(),
+            e
+        )
+    })?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed to encrypt the items",
+            encryption.program()
+        ));
+    }
+
+    // the file is only replaced after the encryption succeeds, so a failure doesn't leave it broken
+    utils::io::write_atomic(path, &output.stdout).map_err(|e| e.to_string())
+}
+
+fn env_var(name: &str) -> Result<String, String> {
+    std::env::var(name)
+        .ok()
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| format!("${} must be set to use age-encrypted files", name))
+}
@@ -0,0 +1,477 @@
+//! Stores the functions that render items into formats understood by other programs.
+
+use chrono::{Local, NaiveDate, Utc};
+use std::collections::HashSet;
+
+use crate::item::{Item, ItemState};
+
+/// Renders the items with due or scheduled dates (searching through all children) into an iCalendar document.
+///
+/// Pending and closed tasks become `VTODO` entries, with their due dates and states. Tasks and notes with a scheduled
+/// date also become all-day `VEVENT` entries, so they show up on the calendar itself. The UIDs are derived from the
+/// internal IDs, with `namespace` telling apart items from different files.
+pub fn to_ics(items: &[&Item], namespace: &str) -> String {
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut out = String::new();
+
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, "PRODID:-//itmn//itmn//EN");
+
+    for root in items {
+        root.traverse(&mut |item| {
+            let uid = format!("i{}-{}@itmn", item.internal_id, namespace);
+
+            if item.state != ItemState::Note && (item.due.is_some() || item.scheduled.is_some()) {
+                push_line(&mut out, "BEGIN:VTODO");
+                push_common(&mut out, item, &uid, &stamp);
+
+                if let Some(scheduled) = item.scheduled {
+                    push_line(
+                        &mut out,
+                        &format!("DTSTART;VALUE=DATE:{}", ics_date(scheduled)),
+                    );
+                }
+
+                if let Some(due) = item.due {
+                    push_line(&mut out, &format!("DUE;VALUE=DATE:{}", ics_date(due)));
+                }
+
+                push_line(
+                    &mut out,
+                    match item.state {
+                        ItemState::Done => "STATUS:COMPLETED",
+                        ItemState::Cancelled => "STATUS:CANCELLED",
+                        _ => "STATUS:NEEDS-ACTION",
+                    },
+                );
+
+                if let Some(done_at) = item.done_at {
+                    push_line(
+                        &mut out,
+                        &format!("COMPLETED:{}", done_at.format("%Y%m%dT%H%M%SZ")),
+                    );
+                }
+
+                push_line(&mut out, "END:VTODO");
+            }
+
+            if let Some(scheduled) = item.scheduled {
+                push_line(&mut out, "BEGIN:VEVENT");
+                push_common(&mut out, item, &format!("event-{}", uid), &stamp);
+                push_line(
+                    &mut out,
+                    &format!("DTSTART;VALUE=DATE:{}", ics_date(scheduled)),
+                );
+                push_line(
+                    &mut out,
+                    &format!(
+                        "DTEND;VALUE=DATE:{}",
+                        ics_date(scheduled.succ_opt().unwrap())
+                    ),
+                );
+
+                if item.state == ItemState::Cancelled {
+                    push_line(&mut out, "STATUS:CANCELLED");
+                }
+
+                push_line(&mut out, "END:VEVENT");
+            }
+        });
+    }
+
+    push_line(&mut out, "END:VCALENDAR");
+
+    out
+}
+
+/// Renders the items and their children into nested markdown checklists.
+///
+/// Tasks become `- [ ]` (or `- [x]` when closed) entries, notes become plain `-` entries and descriptions are kept
+/// as indented paragraphs under the item.
+pub fn to_markdown(items: &[&Item]) -> String {
+    fn write_item(item: &Item, depth: usize, out: &mut String) {
+        let indent = "  ".repeat(depth);
+
+        out.push_str(&indent);
+        out.push_str(match item.state {
+            ItemState::Todo | ItemState::Waiting(_) => "- [ ] ",
+            ItemState::Done | ItemState::Cancelled => "- [x] ",
+            ItemState::Note => "- ",
+        });
+
+        if item.state == ItemState::Cancelled {
+            out.push_str(&format!("~~{}~~", item.name));
+        } else {
+            out.push_str(&item.name);
+        }
+
+        if let Some(ctx) = item.context() {
+            out.push_str(&format!(" `@{}`", ctx));
+        }
+
+        if let ItemState::Waiting(Some(on)) = &item.state {
+            out.push_str(&format!(" (waiting on {})", on));
+        }
+
+        if let Some(due) = item.due {
+            out.push_str(&format!(" (due {})", due));
+        }
+
+        out.push('\n');
+
+        let description = item.description.trim();
+        if !description.is_empty() {
+            for line in description.lines() {
+                if line.trim().is_empty() {
+                    out.push('\n');
+                } else {
+                    out.push_str(&format!("{}  {}\n", indent, line));
+                }
+            }
+        }
+
+        for child in &item.children {
+            write_item(child, depth + 1, out);
+        }
+    }
+
+    let mut out = String::new();
+    for item in items {
+        write_item(item, 0, &mut out);
+    }
+
+    out
+}
+
+/// Renders the items and their children into an org-mode document.
+///
+/// States become keywords (`TODO`, `WAIT`, `DONE` and `CANCELLED`, declared on the header; notes have none), contexts
+/// become tags and dates become `DEADLINE`/`SCHEDULED`/`CLOSED` planning lines.
+pub fn to_org(items: &[&Item]) -> String {
+    fn write_item(item: &Item, depth: usize, out: &mut String) {
+        out.push_str(&"*".repeat(depth + 1));
+
+        match item.state {
+            ItemState::Todo => out.push_str(" TODO"),
+            ItemState::Waiting(_) => out.push_str(" WAIT"),
+            ItemState::Done => out.push_str(" DONE"),
+            ItemState::Cancelled => out.push_str(" CANCELLED"),
+            ItemState::Note => (),
+        }
+
+        out.push(' ');
+        out.push_str(&item.name);
+
+        // tags can't have dashes
+        if let Some(ctx) = item.context() {
+            out.push_str(&format!(" :{}:", ctx.replace('-', "_")));
+        }
+
+        out.push('\n');
+
+        let mut planning = Vec::new();
+        if let Some(done_at) = item.done_at {
+            planning.push(format!(
+                "CLOSED: [{}]",
+                done_at.with_timezone(&Local).format("%Y-%m-%d %a %H:%M")
+            ));
+        }
+
+        if let Some(due) = item.due {
+            planning.push(format!("DEADLINE: <{}>", due.format("%Y-%m-%d %a")));
+        }
+
+        if let Some(scheduled) = item.scheduled {
+            planning.push(format!("SCHEDULED: <{}>", scheduled.format("%Y-%m-%d %a")));
+        }
+
+        if !planning.is_empty() {
+            out.push_str(&planning.join(" "));
+            out.push('\n');
+        }
+
+        if let ItemState::Waiting(Some(on)) = &item.state {
+            out.push_str(&format!("Waiting on {}.\n", on));
+        }
+
+        let description = item.description.trim();
+        if !description.is_empty() {
+            for line in description.lines() {
+                // a line starting with a star would be read as a heading
+                if line.starts_with('*') {
+                    out.push(' ');
+                }
+
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+
+        for child in &item.children {
+            write_item(child, depth + 1, out);
+        }
+    }
+
+    let mut out = String::from("#+TODO: TODO WAIT | DONE CANCELLED\n");
+    for item in items {
+        write_item(item, 0, &mut out);
+    }
+
+    out
+}
+
+/// Renders the tasks (searching through all children) into a todo.txt document.
+///
+/// Since todo.txt has no hierarchy, items inside a top-level item get its name as their `+project`. Cancelled items
+/// are exported as completed and notes are skipped.
+pub fn to_todotxt(items: &[&Item]) -> String {
+    fn write_task(item: &Item, project: Option<&str>, out: &mut String) {
+        if item.state == ItemState::Note {
+            return;
+        }
+
+        let mut words: Vec<String> = Vec::new();
+
+        if item.state.is_closed() {
+            words.push("x".into());
+
+            if let Some(done_at) = item.done_at {
+                words.push(done_at.with_timezone(&Local).format("%Y-%m-%d").to_string());
+            }
+        } else if let Some(priority) = item.priority {
+            words.push(format!("({})", priority));
+        }
+
+        words.push(item.name.clone());
+
+        if let Some(ctx) = item.context() {
+            words.push(format!("@{}", ctx));
+        }
+
+        if let Some(project) = project {
+            words.push(format!("+{}", project));
+        }
+
+        if let Some(due) = item.due {
+            words.push(format!("due:{}", due));
+        }
+
+        // completed tasks can't have the priority at the start
+        if let (true, Some(priority)) = (item.state.is_closed(), item.priority) {
+            words.push(format!("pri:{}", priority));
+        }
+
+        out.push_str(&words.join(" "));
+        out.push('\n');
+    }
+
+    let mut out = String::new();
+    for root in items {
+        write_task(root, None, &mut out);
+
+        let project = root.name.split_whitespace().collect::<Vec<_>>().join("-");
+        for child in &root.children {
+            child.traverse(&mut |item| write_task(item, Some(&project), &mut out));
+        }
+    }
+
+    out
+}
+
+/// Renders the items and their children into a Graphviz (DOT) graph.
+///
+/// Every item becomes a node, with solid edges from parents to their children and dashed edges from blockers to the
+/// items they block (as long as both are exported). Closed items are grayed out, notes are drawn as notes and projects
+/// are bold.
+pub fn to_dot(items: &[&Item]) -> String {
+    let (nodes, edges) = graph(items);
+    let mut out = String::from("digraph itmn {\n    rankdir=LR;\n    node [shape=box];\n\n");
+
+    for item in nodes {
+        let mut attributes = vec![format!("label=\"{}\"", dot_escape(&graph_label(item)))];
+
+        if item.state == ItemState::Note {
+            attributes.push("shape=note".into());
+        }
+        if item.project {
+            attributes.push("style=bold".into());
+        }
+        if item.state.is_closed() {
+            attributes.push("color=gray fontcolor=gray".into());
+        }
+
+        out.push_str(&format!(
+            "    i{} [{}];\n",
+            item.internal_id,
+            attributes.join(" ")
+        ));
+    }
+
+    out.push('\n');
+    for edge in edges {
+        out.push_str(&match edge {
+            GraphEdge::Child(parent, child) => format!("    i{} -> i{};\n", parent, child),
+            GraphEdge::Blocks(blocker, blocked) => {
+                format!(
+                    "    i{} -> i{} [style=dashed label=\"blocks\"];\n",
+                    blocker, blocked
+                )
+            }
+        });
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+/// Renders the items and their children into a mermaid flowchart, with the same nodes and edges as [`to_dot`].
+pub fn to_mermaid(items: &[&Item]) -> String {
+    let (nodes, edges) = graph(items);
+    let mut out = String::from("flowchart LR\n");
+    let mut closed = Vec::new();
+
+    for item in nodes {
+        let label = mermaid_escape(&graph_label(item));
+
+        out.push_str(&match item.state {
+            ItemState::Note => format!("    i{}[/\"{}\"/]\n", item.internal_id, label),
+            _ if item.project => format!("    i{}[[\"{}\"]]\n", item.internal_id, label),
+            _ => format!("    i{}[\"{}\"]\n", item.internal_id, label),
+        });
+
+        if item.state.is_closed() {
+            closed.push(format!("i{}", item.internal_id));
+        }
+    }
+
+    for edge in edges {
+        out.push_str(&match edge {
+            GraphEdge::Child(parent, child) => format!("    i{} --> i{}\n", parent, child),
+            GraphEdge::Blocks(blocker, blocked) => {
+                format!("    i{} -.->|blocks| i{}\n", blocker, blocked)
+            }
+        });
+    }
+
+    if !closed.is_empty() {
+        out.push_str("    classDef closed color:gray,stroke:gray\n");
+        out.push_str(&format!("    class {} closed\n", closed.join(",")));
+    }
+
+    out
+}
+
+/// An edge of the dependency graph, between the internal IDs of two items.
+enum GraphEdge {
+    /// From a parent to one of its children.
+    Child(u32, u32),
+    /// From a blocker to the item it blocks.
+    Blocks(u32, u32),
+}
+
+/// Returns the items and all of their children, along with the edges between them. Blockers that aren't among the
+/// items are left out.
+fn graph<'a>(items: &[&'a Item]) -> (Vec<&'a Item>, Vec<GraphEdge>) {
+    let mut nodes: Vec<&Item> = Vec::new();
+    for root in items {
+        root.traverse(&mut |item| nodes.push(item));
+    }
+
+    let exported: HashSet<u32> = nodes.iter().map(|item| item.internal_id).collect();
+    let mut edges = Vec::new();
+
+    for item in &nodes {
+        for child in &item.children {
+            edges.push(GraphEdge::Child(item.internal_id, child.internal_id));
+        }
+
+        for &blocker in item.blocked_by.iter().filter(|id| exported.contains(id)) {
+            edges.push(GraphEdge::Blocks(blocker, item.internal_id));
+        }
+    }
+
+    (nodes, edges)
+}
+
+fn graph_label(item: &Item) -> String {
+    match item.ref_id {
+        Some(ref_id) => format!("{} (#{})", item.name, ref_id),
+        None => item.name.clone(),
+    }
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Escapes the quotes that would end a label, using mermaid's entity codes.
+fn mermaid_escape(text: &str) -> String {
+    text.replace('"', "#quot;")
+}
+
+/// Writes the properties shared by `VTODO` and `VEVENT` entries.
+fn push_common(out: &mut String, item: &Item, uid: &str, stamp: &str) {
+    push_line(out, &format!("UID:{}", uid));
+    push_line(out, &format!("DTSTAMP:{}", stamp));
+    push_line(out, &format!("SUMMARY:{}", ics_escape(&item.name)));
+
+    let mut description = item.description.trim().to_string();
+    if let ItemState::Waiting(Some(on)) = &item.state {
+        if !description.is_empty() {
+            description.push_str("\n\n");
+        }
+
+        description.push_str(&format!("Waiting on {}", on));
+    }
+
+    if !description.is_empty() {
+        push_line(out, &format!("DESCRIPTION:{}", ics_escape(&description)));
+    }
+
+    if let Some(ctx) = item.context() {
+        push_line(out, &format!("CATEGORIES:{}", ics_escape(ctx)));
+    }
+}
+
+fn ics_date(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+/// Escapes the characters that have special meanings on property values.
+fn ics_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => (),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Writes a content line, folding it so that no line is longer than 75 bytes, as required by the format.
+fn push_line(out: &mut String, line: &str) {
+    const MAX_LENGTH: usize = 75;
+
+    let mut length = 0;
+    for c in line.chars() {
+        // folded lines start with a space, which counts towards the limit
+        if length + c.len_utf8() > MAX_LENGTH {
+            out.push_str("\r\n ");
+            length = 1;
+        }
+
+        out.push(c);
+        length += c.len_utf8();
+    }
+
+    out.push_str("\r\n");
+}
@@ -0,0 +1,477 @@
+//! Stores the parsers for the formats that items can be imported from.
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde::Deserialize;
+
+use crate::item::ItemState;
+
+/// A task parsed from another program's format, ready to be turned into an item.
+#[derive(Debug)]
+pub struct ImportedTask {
+    pub name: String,
+    /// The context of the task, or an empty string if there's none.
+    pub context: String,
+    pub state: ItemState,
+    pub description: String,
+    pub done_at: Option<DateTime<Utc>>,
+    pub priority: Option<char>,
+    pub due: Option<NaiveDate>,
+    pub scheduled: Option<NaiveDate>,
+    /// The names of the items the task should be under, starting from the top-level one.
+    pub project: Vec<String>,
+}
+
+impl ImportedTask {
+    fn new(name: String, state: ItemState) -> Self {
+        Self {
+            name,
+            context: String::new(),
+            state,
+            description: String::new(),
+            done_at: None,
+            priority: None,
+            due: None,
+            scheduled: None,
+            project: Vec::new(),
+        }
+    }
+}
+
+/// Parses the tasks of a todo.txt document, skipping empty lines.
+///
+/// Only the first context and project of each task are extracted; the other ones are kept on the name. Key/value tags
+/// other than `due:` and `pri:` are kept on the name as well.
+pub fn parse_todotxt(text: &str) -> Result<Vec<ImportedTask>, String> {
+    let mut tasks = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut words = line.split_whitespace().peekable();
+        let mut task = ImportedTask::new(String::new(), ItemState::Todo);
+
+        if words.peek() == Some(&"x") {
+            words.next();
+            task.state = ItemState::Done;
+
+            // the completion date comes before the creation date, which is discarded
+            if let Some(date) = words.peek().and_then(|w| parse_date(w)) {
+                words.next();
+                task.done_at = Local
+                    .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+                    .earliest()
+                    .map(|time| time.with_timezone(&Utc));
+
+                if words.peek().and_then(|w| parse_date(w)).is_some() {
+                    words.next();
+                }
+            }
+        } else {
+            if let Some(priority) = words.peek().and_then(|w| parse_priority(w)) {
+                words.next();
+                task.priority = Some(priority);
+            }
+
+            // creation date
+            if words.peek().and_then(|w| parse_date(w)).is_some() {
+                words.next();
+            }
+        }
+
+        let mut name_words: Vec<&str> = Vec::new();
+        for word in words {
+            if word.len() > 1 && word.starts_with('@') && task.context.is_empty() {
+                task.context = word[1..].to_string();
+            } else if word.len() > 1 && word.starts_with('+') && task.project.is_empty() {
+                task.project.push(word[1..].to_string());
+            } else if let Some(due) = word.strip_prefix("due:") {
+                task.due = Some(
+                    parse_date(due)
+                        .ok_or_else(|| format!("line {}: invalid due date {:?}", i + 1, due))?,
+                );
+            } else if let Some(priority) = word.strip_prefix("pri:") {
+                task.priority = priority.chars().next().filter(|c| c.is_ascii_uppercase());
+            } else {
+                name_words.push(word);
+            }
+        }
+
+        if name_words.is_empty() {
+            return Err(format!("line {}: the task has no description", i + 1));
+        }
+
+        task.name = name_words.join(" ");
+        tasks.push(task);
+    }
+
+    Ok(tasks)
+}
+
+/// A task as exported by `task export`. Only the used fields are declared.
+#[derive(Deserialize)]
+struct TaskwarriorTask {
+    description: String,
+    status: String,
+    project: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    priority: Option<String>,
+    due: Option<String>,
+    scheduled: Option<String>,
+    end: Option<String>,
+    #[serde(default)]
+    annotations: Vec<TaskwarriorAnnotation>,
+}
+
+#[derive(Deserialize)]
+struct TaskwarriorAnnotation {
+    description: String,
+}
+
+/// Parses the JSON output of Taskwarrior's `task export`.
+///
+/// Dotted projects (`home.kitchen`) become nested parents, the first tag becomes the context (the other ones are
+/// listed on the description, along with the annotations) and the `H`/`M`/`L` priorities become `A`/`B`/`C`.
+/// Recurring task templates are skipped, since their instances are exported separately.
+pub fn parse_taskwarrior(text: &str) -> Result<Vec<ImportedTask>, String> {
+    let exported: Vec<TaskwarriorTask> = serde_json::from_str(text)
+        .map_err(|e| format!("failed to parse Taskwarrior export: {}", e))?;
+
+    let mut tasks = Vec::new();
+
+    for tw in exported {
+        let state = match tw.status.as_str() {
+            "pending" => ItemState::Todo,
+            "waiting" => ItemState::Waiting(None),
+            "completed" => ItemState::Done,
+            "deleted" => ItemState::Cancelled,
+            "recurring" => continue,
+            other => {
+                return Err(format!(
+                    "task {:?} has an unknown status: {:?}",
+                    tw.description, other
+                ))
+            }
+        };
+
+        let mut task = ImportedTask::new(tw.description, state);
+
+        let mut tags = tw.tags.into_iter();
+        if let Some(tag) = tags.next() {
+            task.context = tag;
+        }
+
+        let mut description: Vec<String> = Vec::new();
+
+        let other_tags: Vec<String> = tags.map(|tag| format!("+{}", tag)).collect();
+        if !other_tags.is_empty() {
+            description.push(format!("Tags: {}", other_tags.join(" ")));
+        }
+
+        description.extend(tw.annotations.into_iter().map(|a| a.description));
+        task.description = description.join("\n");
+
+        if let Some(project) = tw.project {
+            task.project = project
+                .split('.')
+                .filter(|part| !part.is_empty())
+                .map(String::from)
+                .collect();
+        }
+
+        task.priority = match tw.priority.as_deref() {
+            Some("H") => Some('A'),
+            Some("M") => Some('B'),
+            Some("L") => Some('C'),
+            _ => None,
+        };
+
+        task.due = match tw.due {
+            Some(ref due) => Some(
+                parse_taskwarrior_date(due)?
+                    .with_timezone(&Local)
+                    .date_naive(),
+            ),
+            None => None,
+        };
+
+        task.scheduled = match tw.scheduled {
+            Some(ref scheduled) => Some(
+                parse_taskwarrior_date(scheduled)?
+                    .with_timezone(&Local)
+                    .date_naive(),
+            ),
+            None => None,
+        };
+
+        if task.state.is_closed() {
+            task.done_at = match tw.end {
+                Some(ref end) => Some(parse_taskwarrior_date(end)?),
+                None => None,
+            };
+        }
+
+        tasks.push(task);
+    }
+
+    Ok(tasks)
+}
+
+/// Parses a date on Taskwarrior's format, like `20201017T153000Z`.
+fn parse_taskwarrior_date(date: &str) -> Result<DateTime<Utc>, String> {
+    NaiveDateTime::parse_from_str(date, "%Y%m%dT%H%M%SZ")
+        .map(|naive| Utc.from_utc_datetime(&naive))
+        .map_err(|_| format!("invalid Taskwarrior date: {:?}", date))
+}
+
+/// A task parsed from a format that has a tree of tasks, along with the ones under it.
+#[derive(Debug)]
+pub struct ImportedNode {
+    pub task: ImportedTask,
+    pub children: Vec<ImportedNode>,
+}
+
+/// Parses the nested bullet lists (`-`, `*` or `+`) of a markdown document into a tree of tasks, like the ones written
+/// by the markdown export.
+///
+/// `[ ]` checkboxes and plain bullets become todo items, `[x]` ones become done (or cancelled, if the name is struck
+/// through with `~~`). A trailing `` `@context` `` and `(due YYYY-MM-DD)` are extracted from the names. Lines that
+/// aren't bullets are added to the description of the item above them if they're indented further than it, and
+/// ignored otherwise (like headings and paragraphs); empty lines are always ignored. An item is a child of the closest item above it that is less
+/// indented, so any indentation width works; tabs count as four spaces.
+pub fn parse_markdown(text: &str) -> Result<Vec<ImportedNode>, String> {
+    let mut stack: Vec<Vec<ImportedNode>> = vec![Vec::new()];
+    // The indentation width of the last item on each depth.
+    let mut widths: Vec<usize> = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        let indent_width: usize = line[..line.len() - line.trim_start().len()]
+            .chars()
+            .map(|c| if c == '\t' { 4 } else { 1 })
+            .sum();
+
+        let bullet = if matches!(trimmed, "-" | "*" | "+") {
+            Some("")
+        } else {
+            ["- ", "* ", "+ "]
+                .iter()
+                .find_map(|bullet| trimmed.strip_prefix(bullet))
+        };
+
+        let content = match bullet {
+            Some(content) => content.trim(),
+            None => {
+                // the last item is the last one of the deepest level
+                let last_item = stack.iter_mut().rev().find_map(|level| level.last_mut());
+
+                match (last_item, widths.last()) {
+                    (Some(item), Some(&width)) if indent_width > width => {
+                        item.task.description.push_str(trimmed);
+                        item.task.description.push('\n');
+                    }
+                    _ => (),
+                }
+
+                continue;
+            }
+        };
+
+        while widths.last().map_or(false, |&w| indent_width < w) {
+            widths.pop();
+        }
+
+        if widths.last() != Some(&indent_width) {
+            widths.push(indent_width);
+        }
+
+        let depth = widths.len() - 1;
+
+        let task = parse_markdown_item(content)
+            .ok_or_else(|| format!("line {}: item has an empty name", i + 1))?;
+
+        while stack.len() > depth + 1 {
+            let children = stack.pop().unwrap();
+            stack.last_mut().unwrap().last_mut().unwrap().children = children;
+        }
+
+        stack.last_mut().unwrap().push(ImportedNode {
+            task,
+            children: Vec::new(),
+        });
+        stack.push(Vec::new());
+    }
+
+    while stack.len() > 1 {
+        let children = stack.pop().unwrap();
+        stack.last_mut().unwrap().last_mut().unwrap().children = children;
+    }
+
+    let mut nodes = stack.pop().unwrap();
+    for node in nodes.iter_mut() {
+        trim_descriptions(node);
+    }
+
+    Ok(nodes)
+}
+
+/// Parses the content of a markdown bullet, after the bullet itself, returning `None` if it has no name.
+fn parse_markdown_item(content: &str) -> Option<ImportedTask> {
+    let (state, mut name) = if let Some(rest) = content.strip_prefix("[ ]") {
+        (ItemState::Todo, rest.trim())
+    } else if let Some(rest) = content
+        .strip_prefix("[x]")
+        .or_else(|| content.strip_prefix("[X]"))
+    {
+        (ItemState::Done, rest.trim())
+    } else {
+        (ItemState::Todo, content)
+    };
+
+    let mut task = ImportedTask::new(String::new(), state);
+
+    if let Some(start) = name.rfind(" (due ") {
+        if let Some(due) = name[start + 6..].strip_suffix(')').and_then(parse_date) {
+            task.due = Some(due);
+            name = name[..start].trim_end();
+        }
+    }
+
+    if let Some(start) = name.rfind(" `@") {
+        if name.ends_with('`') && !name[start + 3..].contains(' ') {
+            task.context = name[start + 3..name.len() - 1].to_string();
+            name = name[..start].trim_end();
+        }
+    }
+
+    if task.state == ItemState::Done
+        && name.len() > 4
+        && name.starts_with("~~")
+        && name.ends_with("~~")
+    {
+        task.state = ItemState::Cancelled;
+        name = &name[2..name.len() - 2];
+    }
+
+    if name.is_empty() {
+        return None;
+    }
+
+    task.name = name.to_string();
+
+    Some(task)
+}
+
+fn trim_descriptions(node: &mut ImportedNode) {
+    node.task.description = node.task.description.trim_end().to_string();
+
+    for child in node.children.iter_mut() {
+        trim_descriptions(child);
+    }
+}
+
+fn parse_date(word: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(word, "%Y-%m-%d").ok()
+}
+
+/// Parses a priority in the `(A)` format.
+fn parse_priority(word: &str) -> Option<char> {
+    let mut chars = word.chars();
+
+    match (chars.next(), chars.next(), chars.next(), chars.next()) {
+        (Some('('), Some(p), Some(')'), None) if p.is_ascii_uppercase() => Some(p),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn todotxt() {
+        let text = "(A) 2020-01-01 Call mom @phone +family due:2020-12-31 +later\n\n\
+                    x 2020-02-02 2020-01-01 Pay rent pri:B\n\
+                    x Water the plants\n";
+        let tasks = parse_todotxt(text).unwrap();
+
+        assert_eq!(tasks.len(), 3);
+        assert_eq!(tasks[0].name, "Call mom +later");
+        assert_eq!(tasks[0].state, ItemState::Todo);
+        assert_eq!(tasks[0].priority, Some('A'));
+        assert_eq!(tasks[0].context, "phone");
+        assert_eq!(tasks[0].project, ["family"]);
+        assert_eq!(tasks[0].due, NaiveDate::from_ymd_opt(2020, 12, 31));
+
+        assert_eq!(tasks[1].name, "Pay rent");
+        assert_eq!(tasks[1].state, ItemState::Done);
+        assert_eq!(tasks[1].priority, Some('B'));
+        assert_eq!(
+            tasks[1]
+                .done_at
+                .map(|t| t.with_timezone(&Local).date_naive()),
+            NaiveDate::from_ymd_opt(2020, 2, 2)
+        );
+
+        assert_eq!(tasks[2].state, ItemState::Done);
+        assert_eq!(tasks[2].done_at, None);
+
+        assert!(parse_todotxt("a\nb due:tomorrow\n")
+            .unwrap_err()
+            .contains("line 2: invalid due date"));
+        assert!(parse_todotxt("(B) 2020-01-01 @home\n")
+            .unwrap_err()
+            .contains("line 1: the task has no description"));
+    }
+
+    #[test]
+    fn markdown() {
+        let text = concat!(
+            "# Chores\n",
+            "\n",
+            "- [ ] Clean the house `@home` (due 2020-12-31)\n",
+            "  The kitchen first\n",
+            "  * [x] Vacuum\n",
+            "  * [X] ~~Wash the windows~~\n",
+            "  * [x] ~~~~\n",
+            "\t\t+ Buy soap (due someday)\n",
+            "A paragraph\n",
+            "* Plain bullet\n",
+        );
+        let nodes = parse_markdown(text).unwrap();
+
+        assert_eq!(nodes.len(), 2);
+        let house = &nodes[0].task;
+        assert_eq!(house.name, "Clean the house");
+        assert_eq!(house.state, ItemState::Todo);
+        assert_eq!(house.context, "home");
+        assert_eq!(house.due, NaiveDate::from_ymd_opt(2020, 12, 31));
+        assert_eq!(house.description, "The kitchen first");
+
+        let children = &nodes[0].children;
+        assert_eq!(children.len(), 3);
+        assert_eq!(children[0].task.state, ItemState::Done);
+        // only done items are cancelled by being struck through
+        assert_eq!(children[1].task.name, "Wash the windows");
+        assert_eq!(children[1].task.state, ItemState::Cancelled);
+        // a strikethrough without anything inside is kept as the name
+        assert_eq!(children[2].task.name, "~~~~");
+        assert_eq!(children[2].task.state, ItemState::Done);
+        assert_eq!(children[2].children[0].task.name, "Buy soap (due someday)");
+        assert_eq!(children[2].children[0].task.due, None);
+
+        assert_eq!(nodes[1].task.name, "Plain bullet");
+        assert_eq!(nodes[1].task.description, "");
+
+        assert!(parse_markdown("- a\n  - [ ]\n")
+            .unwrap_err()
+            .contains("line 2: item has an empty name"));
+    }
+}
@@ -0,0 +1,564 @@
+//! Stores the interactive terminal interface used by the `tui` subcommand.
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use std::collections::HashSet;
+use std::io::{self, Stdout};
+use tui::backend::{Backend, CrosstermBackend};
+use tui::layout::{Constraint, Direction, Layout};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use tui::{Frame, Terminal};
+
+use crate::item::{InternalId, Item, ItemState};
+use crate::manager::{ItemManager, Searchable};
+
+const HELP: &str =
+    "j/k: up/down  h/l: fold  d: done  a: add child  D: delete  J/K: move  /: search  q: quit";
+
+/// A visible line of the tree.
+struct Row {
+    id: u32,
+    depth: usize,
+}
+
+/// What the keys are being used for.
+enum Mode {
+    Normal,
+    /// Typing the name of a new child for the selected item.
+    AddChild(String),
+    /// Typing a search query, with the cursor following the first match.
+    Search(String),
+    /// Waiting for the deletion of the selected item to be confirmed.
+    ConfirmDelete,
+}
+
+struct App<'a> {
+    manager: &'a mut ItemManager,
+    /// The internal IDs of the items whose children are shown.
+    expanded: HashSet<u32>,
+    rows: Vec<Row>,
+    list_state: ListState,
+    mode: Mode,
+    /// The last search query, used by `n` and `N`.
+    query: String,
+    message: String,
+    modified: bool,
+}
+
+/// Runs the interface until the user quits.
+///
+/// Returns whether the items should be saved, which is the case if they were modified and the user didn't abort with
+/// Ctrl-C.
+pub fn run(manager: &mut ItemManager) -> Result<bool, String> {
+    enable_raw_mode().map_err(|e| format!("failed to enable raw mode: {}", e))?;
+
+    let mut stdout = io::stdout();
+    if let Err(e) = execute!(stdout, EnterAlternateScreen) {
+        disable_raw_mode().ok();
+        return Err(format!("failed to enter the alternate screen: {}", e));
+    }
+
+    let result = Terminal::new(CrosstermBackend::new(stdout))
+        .map_err(|e| format!("failed to set up the terminal: {}", e))
+        .and_then(|mut terminal| {
+            let result = App::new(manager).event_loop(&mut terminal);
+            terminal.show_cursor().ok();
+            result
+        });
+
+    // the terminal should be restored even if something failed
+    disable_raw_mode().ok();
+    execute!(io::stdout(), LeaveAlternateScreen).ok();
+
+    result
+}
+
+impl<'a> App<'a> {
+    fn new(manager: &'a mut ItemManager) -> Self {
+        let mut expanded = HashSet::new();
+        for root in &manager.data {
+            root.traverse(&mut |item| {
+                if !item.state.is_closed() {
+                    expanded.insert(item.internal_id);
+                }
+            });
+        }
+
+        let mut app = Self {
+            manager,
+            expanded,
+            rows: Vec::new(),
+            list_state: ListState::default(),
+            mode: Mode::Normal,
+            query: String::new(),
+            message: String::new(),
+            modified: false,
+        };
+
+        app.rebuild();
+        app.list_state.select(Some(0));
+
+        app
+    }
+
+    /// Handles keys until the user quits, returning whether the items should be saved.
+    fn event_loop(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    ) -> Result<bool, String> {
+        loop {
+            terminal
+                .draw(|f| self.draw(f))
+                .map_err(|e| format!("failed to draw: {}", e))?;
+
+            let key = match event::read().map_err(|e| format!("failed to read event: {}", e))? {
+                Event::Key(key) => key,
+                _ => continue,
+            };
+
+            if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                return Ok(false);
+            }
+
+            self.message.clear();
+
+            let mode = std::mem::replace(&mut self.mode, Mode::Normal);
+            self.mode = match mode {
+                Mode::Normal => match self.handle_normal(key) {
+                    Some(mode) => mode,
+                    None => return Ok(self.modified),
+                },
+                Mode::AddChild(name) => self.handle_add_child(key, name),
+                Mode::Search(query) => self.handle_search(key, query),
+                Mode::ConfirmDelete => {
+                    if key.code == KeyCode::Char('y') {
+                        self.delete_selected();
+                    }
+
+                    Mode::Normal
+                }
+            };
+        }
+    }
+
+    /// Handles a key on the normal mode, returning the next mode or `None` if the user wants to quit.
+    fn handle_normal(&mut self, key: KeyEvent) -> Option<Mode> {
+        let cursor = self.cursor();
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return None,
+            KeyCode::Char('j') | KeyCode::Down => self.select_row(cursor + 1),
+            KeyCode::Char('k') | KeyCode::Up => self.select_row(cursor.saturating_sub(1)),
+            KeyCode::Char('g') | KeyCode::Home => self.select_row(0),
+            KeyCode::Char('G') | KeyCode::End => self.select_row(self.rows.len().saturating_sub(1)),
+            KeyCode::Char('l') | KeyCode::Right => {
+                if let Some(item) = self.selected() {
+                    let id = item.internal_id;
+
+                    if !item.children.is_empty() && !self.expanded.insert(id) {
+                        // already expanded, so go to the first child
+                        self.select_row(cursor + 1);
+                    }
+
+                    self.rebuild();
+                }
+            }
+            KeyCode::Char('h') | KeyCode::Left => {
+                if let Some(item) = self.selected() {
+                    let id = item.internal_id;
+
+                    if item.children.is_empty() || !self.expanded.remove(&id) {
+                        // already collapsed, so go to the parent
+                        let depth = self.rows[cursor].depth;
+                        if let Some(parent) =
+                            (0..cursor).rev().find(|&i| self.rows[i].depth < depth)
+                        {
+                            self.select_row(parent);
+                        }
+                    }
+
+                    self.rebuild();
+                }
+            }
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                if let Some(item) = self.selected() {
+                    let id = item.internal_id;
+
+                    if !self.expanded.remove(&id) {
+                        self.expanded.insert(id);
+                    }
+
+                    self.rebuild();
+                }
+            }
+            KeyCode::Char('d') => self.toggle_done(),
+            KeyCode::Char('a') => return Some(Mode::AddChild(String::new())),
+            KeyCode::Char('D') if self.selected().is_some() => return Some(Mode::ConfirmDelete),
+            KeyCode::Char('J') => self.move_selected(true),
+            KeyCode::Char('K') => self.move_selected(false),
+            KeyCode::Char('/') => return Some(Mode::Search(String::new())),
+            KeyCode::Char('n') => self.search_next(self.cursor() + 1, true),
+            KeyCode::Char('N') => self.search_next(self.cursor(), false),
+            _ => (),
+        }
+
+        Some(Mode::Normal)
+    }
+
+    fn handle_add_child(&mut self, key: KeyEvent, mut name: String) -> Mode {
+        match key.code {
+            KeyCode::Esc => Mode::Normal,
+            KeyCode::Enter => {
+                let name = name.trim();
+                if !name.is_empty() {
+                    self.add_child(name);
+                }
+
+                Mode::Normal
+            }
+            KeyCode::Backspace => {
+                name.pop();
+                Mode::AddChild(name)
+            }
+            KeyCode::Char(c) => {
+                name.push(c);
+                Mode::AddChild(name)
+            }
+            _ => Mode::AddChild(name),
+        }
+    }
+
+    fn handle_search(&mut self, key: KeyEvent, mut query: String) -> Mode {
+        match key.code {
+            KeyCode::Esc | KeyCode::Enter => return Mode::Normal,
+            KeyCode::Backspace => {
+                query.pop();
+            }
+            KeyCode::Char(c) => query.push(c),
+            _ => return Mode::Search(query),
+        }
+
+        // search again from the current item, so it stays selected while it still matches
+        self.query = query.clone();
+        self.search_next(self.cursor(), true);
+
+        Mode::Search(query)
+    }
+
+    fn cursor(&self) -> usize {
+        self.list_state.selected().unwrap_or(0)
+    }
+
+    fn selected(&self) -> Option<&Item> {
+        let row = self.rows.get(self.cursor())?;
+        self.manager.find(InternalId(row.id))
+    }
+
+    fn select_row(&mut self, index: usize) {
+        self.list_state
+            .select(Some(index.min(self.rows.len().saturating_sub(1))));
+    }
+
+    fn select_id(&mut self, id: u32) {
+        if let Some(index) = self.rows.iter().position(|row| row.id == id) {
+            self.select_row(index);
+        }
+    }
+
+    /// Recalculates the visible rows, keeping the cursor on the same item if it's still visible.
+    fn rebuild(&mut self) {
+        fn push_rows(items: &[Item], depth: usize, expanded: &HashSet<u32>, rows: &mut Vec<Row>) {
+            for item in items {
+                rows.push(Row {
+                    id: item.internal_id,
+                    depth,
+                });
+
+                if expanded.contains(&item.internal_id) {
+                    push_rows(&item.children, depth + 1, expanded, rows);
+                }
+            }
+        }
+
+        let selected = self.rows.get(self.cursor()).map(|row| row.id);
+
+        self.rows.clear();
+        push_rows(&self.manager.data, 0, &self.expanded, &mut self.rows);
+
+        match selected {
+            Some(id) if self.rows.iter().any(|row| row.id == id) => self.select_id(id),
+            _ => self.select_row(self.cursor()),
+        }
+    }
+
+    /// Marks the selected item as done, or as todo again if it's closed.
+    fn toggle_done(&mut self) {
+        let item = match self.selected() {
+            Some(item) => item,
+            None => return,
+        };
+
+        let id = InternalId(item.internal_id);
+
+        if item.state == ItemState::Note {
+            self.message = "notes can't be marked as done".into();
+            return;
+        }
+
+        let changed = self
+            .manager
+            .change_item_state(id, |previous| match previous {
+                ItemState::Done | ItemState::Cancelled => ItemState::Todo,
+                _ => ItemState::Done,
+            });
+
+        match changed {
+            Ok(()) => self.modified = true,
+            Err(why) => self.message = why.to_string(),
+        }
+    }
+
+    fn add_child(&mut self, name: &str) {
+        let added = match self.selected().map(|item| item.internal_id) {
+            Some(parent) => {
+                self.expanded.insert(parent);
+                self.manager.add_child(
+                    InternalId(parent),
+                    name,
+                    "",
+                    ItemState::Todo,
+                    String::new(),
+                    Vec::new(),
+                )
+            }
+            // with no items at all, the new one goes to the root
+            None => {
+                self.manager
+                    .add_item_on_root(name, "", ItemState::Todo, String::new(), Vec::new())
+            }
+        };
+
+        let ref_id = match added {
+            Ok(ref_id) => ref_id,
+            Err(why) => return self.message = why.to_string(),
+        };
+
+        let id = self.manager.find(ref_id).unwrap().internal_id;
+        self.expanded.insert(id);
+        self.modified = true;
+
+        self.rebuild();
+        self.select_id(id);
+    }
+
+    fn delete_selected(&mut self) {
+        let id = match self.selected() {
+            Some(item) => item.internal_id,
+            None => return,
+        };
+
+        let removed = {
+            let (siblings, index) = self.manager.locate_mut(|i| i.internal_id == id).unwrap();
+            siblings.remove(index)
+        };
+
+        self.manager.release_ref_ids(std::slice::from_ref(&removed));
+        self.message = format!("Deleted {:?}", removed.name);
+        self.modified = true;
+
+        self.rebuild();
+    }
+
+    /// Swaps the selected item with its next (or previous) sibling.
+    fn move_selected(&mut self, down: bool) {
+        let id = match self.selected() {
+            Some(item) => item.internal_id,
+            None => return,
+        };
+
+        let (siblings, index) = self.manager.locate_mut(|i| i.internal_id == id).unwrap();
+        let other = if down {
+            index + 1
+        } else {
+            match index.checked_sub(1) {
+                Some(other) => other,
+                None => return,
+            }
+        };
+
+        if other < siblings.len() {
+            siblings.swap(index, other);
+            self.modified = true;
+            self.rebuild();
+        }
+    }
+
+    /// Selects the next item (in the order of the tree, including hidden items) whose name contains the last query,
+    /// starting from the row `from` and expanding its parents if needed.
+    fn search_next(&mut self, from: usize, forward: bool) {
+        if self.query.is_empty() {
+            return;
+        }
+
+        let query = self.query.to_lowercase();
+
+        // (internal ID, IDs of the parents, whether it matches)
+        let mut all: Vec<(u32, Vec<u32>, bool)> = Vec::new();
+        fn collect(
+            items: &[Item],
+            parents: &mut Vec<u32>,
+            query: &str,
+            all: &mut Vec<(u32, Vec<u32>, bool)>,
+        ) {
+            for item in items {
+                let matches = item.name.to_lowercase().contains(query);
+                all.push((item.internal_id, parents.clone(), matches));
+
+                parents.push(item.internal_id);
+                collect(&item.children, parents, query, all);
+                parents.pop();
+            }
+        }
+        collect(&self.manager.data, &mut Vec::new(), &query, &mut all);
+
+        if all.is_empty() {
+            return;
+        }
+
+        // where to start on the full list, based on the row
+        let start = match self.rows.get(from) {
+            Some(row) => all.iter().position(|(id, _, _)| *id == row.id).unwrap_or(0),
+            None => 0,
+        };
+
+        let len = all.len();
+        let found = (0..len)
+            .map(|offset| {
+                if forward {
+                    (start + offset) % len
+                } else {
+                    (start + len - offset - 1) % len
+                }
+            })
+            .find(|&i| all[i].2);
+
+        match found {
+            Some(i) => {
+                let (id, parents, _) = &all[i];
+                self.expanded.extend(parents.iter().copied());
+                let id = *id;
+
+                self.rebuild();
+                self.select_id(id);
+            }
+            None => self.message = format!("No matches for {:?}", self.query),
+        }
+    }
+
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
+            .split(f.size());
+
+        let (manager, expanded) = (&self.manager, &self.expanded);
+        let items: Vec<ListItem> = self
+            .rows
+            .iter()
+            .filter_map(|row| {
+                let item = manager.find(InternalId(row.id))?;
+                Some(ListItem::new(row_spans(item, row.depth, expanded)))
+            })
+            .collect();
+
+        let title = if self.modified {
+            " itmn [+] "
+        } else {
+            " itmn "
+        };
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+        f.render_stateful_widget(list, chunks[0], &mut self.list_state);
+
+        let status = match &self.mode {
+            Mode::Normal if self.manager.data.is_empty() => {
+                "No items yet - press a to add one".to_string()
+            }
+            Mode::Normal if !self.message.is_empty() => self.message.clone(),
+            Mode::Normal => HELP.to_string(),
+            Mode::AddChild(name) => format!("New child: {}", name),
+            Mode::Search(query) => format!("/{}", query),
+            Mode::ConfirmDelete => format!(
+                "Delete {:?} and its children? (y/n)",
+                self.selected().map_or("", |item| item.name.as_str())
+            ),
+        };
+
+        f.render_widget(Paragraph::new(status), chunks[1]);
+    }
+}
+
+/// Renders a row of the tree.
+fn row_spans<'a>(item: &'a Item, depth: usize, expanded: &HashSet<u32>) -> Spans<'a> {
+    let fold = match (
+        item.children.is_empty(),
+        expanded.contains(&item.internal_id),
+    ) {
+        (true, _) => "  ",
+        (false, true) => "- ",
+        (false, false) => "+ ",
+    };
+
+    let symbol_color = match item.state {
+        ItemState::Todo => Color::Yellow,
+        ItemState::Done => Color::Green,
+        ItemState::Note => Color::Blue,
+        ItemState::Waiting(_) => Color::Magenta,
+        ItemState::Cancelled => Color::Red,
+    };
+
+    let name_style = if item.state.is_closed() {
+        Style::default().fg(Color::DarkGray)
+    } else {
+        Style::default()
+    };
+
+    let mut spans = vec![
+        Span::raw(format!("{}{}", "  ".repeat(depth), fold)),
+        Span::styled(
+            format!("{} ", item.state.symbol()),
+            Style::default().fg(symbol_color),
+        ),
+        Span::styled(item.name.as_str(), name_style),
+    ];
+
+    if let Some(ctx) = item.context() {
+        spans.push(Span::styled(
+            format!(" @{}", ctx),
+            Style::default().fg(Color::Cyan),
+        ));
+    }
+
+    if let Some(ref_id) = item.ref_id {
+        spans.push(Span::styled(
+            format!(" #{:02}", ref_id),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+
+    if !item.children.is_empty() && !expanded.contains(&item.internal_id) {
+        spans.push(Span::styled(
+            format!(" [{}]", item.children.len()),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+
+    Spans::from(spans)
+}
@@ -1,10 +1,12 @@
 //! Stores data structures related to the database's storage unit.
 
+use chrono::{DateTime, Duration, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
-/// An item state describes whether said item is actionable (to do / done) or a note. More possible states might be
-/// added on the future.
-#[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Copy, Clone)]
+/// An item state describes whether said item is actionable (to do / waiting / done / cancelled) or a note. More
+/// possible states might be added on the future.
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Clone)]
 pub enum ItemState {
     /// The item is actionable, and is not yet marked as done.
     Todo,
@@ -12,10 +14,118 @@ pub enum ItemState {
     Done,
     /// The item is not actionable, so it can't be marked as done.
     Note,
+    /// The item is pending, but can't be acted upon until something else happens - optionally described here.
+    Waiting(Option<String>),
+    /// The item was actionable, but was dropped before being done.
+    Cancelled,
+}
+
+impl ItemState {
+    /// Returns the symbol used to represent this state on reports.
+    pub fn symbol(&self) -> char {
+        match self {
+            Self::Todo => 'o',
+            Self::Done => 'x',
+            Self::Note => '-',
+            Self::Waiting(_) => 'w',
+            Self::Cancelled => 'c',
+        }
+    }
+
+    /// The name of the state, as used on selections and reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Todo => "todo",
+            Self::Done => "done",
+            Self::Note => "note",
+            Self::Waiting(_) => "waiting",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    /// Whether the item is closed, either by being done or cancelled. Closed items don't have reference IDs.
+    pub fn is_closed(&self) -> bool {
+        matches!(self, Self::Done | Self::Cancelled)
+    }
+}
+
+/// A period of time in which an item was worked on.
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Clone)]
+pub struct WorkInterval {
+    pub start: DateTime<Utc>,
+    /// When the work stopped, or `None` if it's still going on.
+    pub end: Option<DateTime<Utc>>,
+}
+
+/// A change that was made to an item, as recorded on its history.
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Clone)]
+pub enum ItemEvent {
+    /// The item was added to the database.
+    Created,
+    /// The item's name changed from the first one to the second one.
+    Renamed(String, String),
+    /// The item's state changed from the first one to the second one.
+    StateChanged(ItemState, ItemState),
+    /// The item was moved to another parent (or to the root).
+    Moved,
+    /// The item was snoozed until some moment, or unsnoozed.
+    Snoozed(Option<DateTime<Utc>>),
+    /// The item's scheduled date changed.
+    Rescheduled(Option<NaiveDate>),
+    /// The item's due date changed.
+    DueChanged(Option<NaiveDate>),
+}
+
+/// An entry of an item's history.
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Clone)]
+pub struct HistoryEntry {
+    pub at: DateTime<Utc>,
+    pub event: ItemEvent,
+}
+
+/// A reference to an item of another items file, written as `profile:refid`.
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Clone)]
+pub struct ItemLink {
+    /// The profile that has the items file.
+    pub profile: String,
+    /// The reference ID of the item on that file.
+    pub ref_id: u32,
+}
+
+impl ItemLink {
+    /// Parses a link written as `profile:refid`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "invalid link {:?} (expected profile:refid, like work:3)",
+                text
+            )
+        };
+
+        let (profile, ref_id) = match text.find(':') {
+            Some(index) => (&text[..index], &text[index + 1..]),
+            None => return Err(invalid()),
+        };
+
+        if profile.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            profile: profile.to_string(),
+            ref_id: ref_id.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl std::fmt::Display for ItemLink {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "{}:{}", self.profile, self.ref_id)
+    }
 }
 
 /// Used for reference ID search operations
-#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct RefId(pub u32);
 
 impl From<u32> for RefId {
@@ -31,7 +141,7 @@ impl Into<u32> for RefId {
 }
 
 /// Used for internal ID search operations
-#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct InternalId(pub u32);
 
 impl Into<u32> for InternalId {
@@ -47,7 +157,7 @@ impl From<u32> for InternalId {
 }
 
 /// The main data unit used to store information on this program's database.
-#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Clone)]
 pub struct Item {
     /// The public name of the item. It usually appears on most reports.
     pub name: String,
@@ -77,6 +187,68 @@ pub struct Item {
     /// [`Vec::with_capacity(0)`]: std::vec::Vec::with_capacity
     /// [`shrink_to_fit`]: Vec::shrink_to_fit
     pub children: Vec<Item>,
+    /// When the item was marked as done, if it currently is.
+    #[serde(default)]
+    pub done_at: Option<DateTime<Utc>>,
+    /// The internal IDs of the items that need to be closed before this one can be acted upon.
+    #[serde(default)]
+    pub blocked_by: Vec<u32>,
+    /// The periods of time in which the item was worked on, in chronological order.
+    #[serde(default)]
+    pub work_log: Vec<WorkInterval>,
+    /// How many minutes the item is expected to take.
+    #[serde(default)]
+    pub estimate: Option<u32>,
+    /// The date the item should be done by.
+    #[serde(default)]
+    pub due: Option<NaiveDate>,
+    /// The date the item is planned to be worked on.
+    #[serde(default)]
+    pub scheduled: Option<NaiveDate>,
+    /// The priority of the item, from `A` (the highest) to `Z`, like on todo.txt.
+    #[serde(default)]
+    pub priority: Option<char>,
+    /// Until when the item is hidden from the reports.
+    #[serde(default)]
+    pub snoozed_until: Option<DateTime<Utc>>,
+    /// Whether the item is starred, as something that must be done today.
+    #[serde(default)]
+    pub starred: bool,
+    /// Whether the item is a project. Only root items are considered to be projects.
+    #[serde(default)]
+    pub project: bool,
+    /// Whether the item is on the someday/maybe list, of things that might be done one day, which keeps it out of the
+    /// reports (but not out of searches and reviews).
+    #[serde(default)]
+    pub someday: bool,
+    /// Free-form labels for the item, without the leading `#`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Items of other items files this one refers to.
+    #[serde(default)]
+    pub links: Vec<ItemLink>,
+    /// A URL of something the item is about, like a ticket, a document or a pull request.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Files related to the item, as absolute paths.
+    #[serde(default)]
+    pub attachments: Vec<PathBuf>,
+    /// When the item was created. Items created before this was tracked don't have it.
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    /// When the item itself (not counting its children) was last changed. Items that weren't changed since this was
+    /// tracked don't have it.
+    #[serde(default)]
+    pub modified_at: Option<DateTime<Utc>>,
+    /// When the item was last looked at on a review.
+    #[serde(default)]
+    pub reviewed_at: Option<DateTime<Utc>>,
+    /// How many days to wait between reviews of the item. Items without it are reviewed along with the others.
+    #[serde(default)]
+    pub review_every: Option<u32>,
+    /// The changes made to the item, in chronological order. Changes made before this was tracked aren't there.
+    #[serde(default)]
+    pub history: Vec<HistoryEntry>,
     // pub creation_date: Option<String>,
     // TODO: defer_date: Option</* idk */>,
     // TODO: deprecate context (possibly)
@@ -106,11 +278,36 @@ impl Item {
         description: String,
         children: Vec<Item>,
     ) -> Self {
+        let now = Utc::now();
+
         Self {
             ref_id,
             internal_id,
             name: Self::validate_name(name),
             context: Self::validate_context(context),
+            done_at: match state {
+                ItemState::Done => Some(now),
+                _ => None,
+            },
+            blocked_by: Vec::new(),
+            work_log: Vec::new(),
+            estimate: None,
+            due: None,
+            scheduled: None,
+            priority: None,
+            snoozed_until: None,
+            starred: false,
+            project: false,
+            someday: false,
+            tags: Vec::new(),
+            links: Vec::new(),
+            url: None,
+            attachments: Vec::new(),
+            created_at: Some(now),
+            modified_at: Some(now),
+            reviewed_at: None,
+            review_every: None,
+            history: Vec::new(),
             state,
             description,
             children,
@@ -123,11 +320,33 @@ impl Item {
     }
 
     /// Processes a context string, returning whatever should be stored on the `context` field of the item.
-    fn validate_context(context: &str) -> Option<String> {
+    ///
+    /// Contexts are normalized so that the same context isn't spelled in different ways: they're lowercased, a leading
+    /// `@` is removed and each run of whitespace, underscores and dashes becomes a single dash (`Side Project` becomes
+    /// `side-project`).
+    pub fn validate_context(context: &str) -> Option<String> {
         if Self::context_translates_to_null(&context) {
+            return None;
+        }
+
+        let normalized = context
+            .trim()
+            .trim_start_matches('@')
+            .to_lowercase()
+            .split(|c: char| c.is_whitespace() || c == '_' || c == '-')
+            .filter(|word| !word.is_empty())
+            .map(|word| {
+                word.chars()
+                    .filter(|&c| validate_char(c))
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("-");
+
+        if normalized.is_empty() {
             None
         } else {
-            Some(context.chars().filter(|&c| validate_char(c)).collect())
+            Some(normalized)
         }
     }
 
@@ -162,6 +381,244 @@ impl Item {
         self.context = Self::validate_context(new_context);
     }
 
+    /// Calls `f` on the item and then on each one of its descendants, parents first.
+    pub fn traverse<'a, F>(&'a self, f: &mut F)
+    where
+        F: FnMut(&'a Item),
+    {
+        f(self);
+
+        for child in &self.children {
+            child.traverse(f);
+        }
+    }
+
+    /// Joins the fields of `other` into the item, when it's merged into this one. The item keeps its own name, state and
+    /// IDs, while the lists (like the children, tags and work log) are joined and the fields it doesn't have (like the
+    /// context and the due date) are taken from `other`. The references to `other` aren't changed.
+    pub fn absorb(&mut self, other: Item) {
+        fn join<T: PartialEq>(list: &mut Vec<T>, other: Vec<T>) {
+            for value in other {
+                if !list.contains(&value) {
+                    list.push(value);
+                }
+            }
+        }
+
+        if self.context.is_none() {
+            self.context = other.context;
+        }
+
+        if self.description.trim().is_empty() {
+            self.description = other.description;
+        } else if !other.description.trim().is_empty() {
+            self.description = format!("{}\n\n{}", self.description.trim_end(), other.description);
+        }
+
+        self.children.extend(other.children);
+        join(&mut self.blocked_by, other.blocked_by);
+        join(&mut self.tags, other.tags);
+        join(&mut self.links, other.links);
+        join(&mut self.attachments, other.attachments);
+
+        self.work_log.extend(other.work_log);
+        self.work_log.sort_by_key(|interval| interval.start);
+        self.history.extend(other.history);
+        self.history.sort_by_key(|entry| entry.at);
+
+        self.estimate = self.estimate.or(other.estimate);
+        self.due = self.due.or(other.due);
+        self.scheduled = self.scheduled.or(other.scheduled);
+        self.priority = self.priority.or(other.priority);
+        self.snoozed_until = self.snoozed_until.or(other.snoozed_until);
+        self.url = self.url.take().or(other.url);
+        self.review_every = self.review_every.or(other.review_every);
+        self.reviewed_at = self.reviewed_at.max(other.reviewed_at);
+
+        self.created_at = match (self.created_at, other.created_at) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        self.starred |= other.starred;
+        self.project |= other.project;
+        // the merged item is only kept out of the reports if both of them were
+        self.someday &= other.someday;
+    }
+
+    /// Like `traverse`, but skips the items kept out of the reports (see `is_hidden`) along with their descendants.
+    pub fn traverse_shown<'a, F>(&'a self, f: &mut F)
+    where
+        F: FnMut(&'a Item),
+    {
+        if self.is_hidden() {
+            return;
+        }
+
+        f(self);
+
+        for child in &self.children {
+            child.traverse_shown(f);
+        }
+    }
+
+    /// Sets the state of the item, keeping track of when it was marked as done.
+    pub fn set_state(&mut self, new_state: ItemState) {
+        match (&self.state, &new_state) {
+            (ItemState::Done, ItemState::Done) => (),
+            (_, ItemState::Done) => self.done_at = Some(Utc::now()),
+            (_, _) => self.done_at = None,
+        }
+
+        if new_state.is_closed() {
+            self.stop_work();
+        }
+
+        self.state = new_state;
+    }
+
+    /// Calls `f` on the item and then on each one of its descendants, parents first, allowing them to be mutated.
+    pub fn traverse_mut<F>(&mut self, f: &mut F)
+    where
+        F: FnMut(&mut Item),
+    {
+        f(self);
+
+        for child in self.children.iter_mut() {
+            child.traverse_mut(f);
+        }
+    }
+
+    /// Whether the review interval of the item is over, counting from its last review (or from its creation, if it was
+    /// never reviewed). Items without a review interval never need a review of their own.
+    pub fn needs_review(&self, now: DateTime<Utc>) -> bool {
+        match self.review_every {
+            Some(days) => self
+                .reviewed_at
+                .or(self.created_at)
+                .map_or(true, |last| last + Duration::days(days as i64) <= now),
+            None => false,
+        }
+    }
+
+    /// Returns the last moment the item is known to have been touched, by being created, modified, reviewed, marked as
+    /// done or worked on.
+    pub fn last_touched(&self) -> Option<DateTime<Utc>> {
+        let worked_on = self
+            .work_log
+            .last()
+            .map(|interval| interval.end.unwrap_or_else(Utc::now));
+
+        self.reviewed_at
+            .max(self.done_at)
+            .max(worked_on)
+            .max(self.created_at)
+            .max(self.modified_at)
+    }
+
+    /// Whether the item is snoozed right now.
+    pub fn is_snoozed(&self) -> bool {
+        self.snoozed_until.map_or(false, |until| until > Utc::now())
+    }
+
+    /// Whether the item is kept out of the reports right now, by being snoozed or on the someday/maybe list.
+    pub fn is_hidden(&self) -> bool {
+        self.someday || self.is_snoozed()
+    }
+
+    /// Counts the actionable items under this one, returning how many of them are done and how many there are in total.
+    /// Notes and cancelled items aren't counted.
+    ///
+    /// If `recursive` is false, only the direct children are looked at.
+    pub fn progress(&self, recursive: bool) -> (usize, usize) {
+        let mut done = 0;
+        let mut total = 0;
+
+        let mut count = |item: &Item| match item.state {
+            ItemState::Todo | ItemState::Waiting(_) => total += 1,
+            ItemState::Done => {
+                done += 1;
+                total += 1;
+            }
+            ItemState::Note | ItemState::Cancelled => (),
+        };
+
+        for child in &self.children {
+            if recursive {
+                child.traverse(&mut count);
+            } else {
+                count(child);
+            }
+        }
+
+        (done, total)
+    }
+
+    /// Returns the moment the item started being worked on, if it's being worked on right now.
+    pub fn working_since(&self) -> Option<DateTime<Utc>> {
+        self.work_log
+            .last()
+            .filter(|interval| interval.end.is_none())
+            .map(|interval| interval.start)
+    }
+
+    /// Starts a work interval, if one isn't running already.
+    pub fn start_work(&mut self) {
+        if self.working_since().is_none() {
+            self.work_log.push(WorkInterval {
+                start: Utc::now(),
+                end: None,
+            });
+        }
+    }
+
+    /// Stops the running work interval, returning whether there was one.
+    pub fn stop_work(&mut self) -> bool {
+        match self.work_log.last_mut() {
+            Some(interval) if interval.end.is_none() => {
+                interval.end = Some(Utc::now());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns how much time was spent working on the item after `since`, if specified. The running interval counts
+    /// until now.
+    pub fn time_spent(&self, since: Option<DateTime<Utc>>) -> Duration {
+        let now = Utc::now();
+
+        self.work_log
+            .iter()
+            .map(|interval| {
+                let start = match since {
+                    Some(since) => interval.start.max(since),
+                    None => interval.start,
+                };
+                let end = interval.end.unwrap_or(now);
+
+                if end > start {
+                    end - start
+                } else {
+                    Duration::zero()
+                }
+            })
+            .fold(Duration::zero(), |total, duration| total + duration)
+    }
+
+    /// Returns how much of the estimate is left after subtracting the time already spent, if there's an estimate.
+    pub fn remaining_estimate(&self) -> Option<Duration> {
+        self.estimate.map(|minutes| {
+            let remaining = Duration::minutes(minutes as i64) - self.time_spent(None);
+
+            if remaining > Duration::zero() {
+                remaining
+            } else {
+                Duration::zero()
+            }
+        })
+    }
+
     pub fn has_child(&self, child: &Item) -> bool {
         for item in &self.children {
             if item.internal_id == child.internal_id || item.has_child(child) {
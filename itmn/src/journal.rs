@@ -0,0 +1,104 @@
+//! Stores the completion journal, a permanent record of the items that were done, which is only ever appended to - so,
+//! unlike the items file and its archive, it's not affected by pruning or deletions.
+//!
+//! The journal is kept on `itmn-journal` on the data directory, named after the items file (like `itmn-journal.txt`),
+//! and can be written as plain text (one item per line) or as JSON lines.
+
+use chrono::{DateTime, Local, Utc};
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The formats the journal can be written in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JournalFormat {
+    /// Lines like `2020-12-31 23:59  Project > Task @context`.
+    Text,
+    /// One JSON object per line, with the fields of [`Entry`].
+    Jsonl,
+}
+
+impl JournalFormat {
+    /// Parses a format, `text` or `jsonl`. `off` means there's no journal.
+    pub fn parse(string: &str) -> Result<Option<Self>, String> {
+        match string.trim() {
+            "off" => Ok(None),
+            "text" | "txt" => Ok(Some(Self::Text)),
+            "jsonl" => Ok(Some(Self::Jsonl)),
+            other => Err(format!(
+                "unknown journal format {:?} (expected off, text or jsonl)",
+                other
+            )),
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Text => "txt",
+            Self::Jsonl => "jsonl",
+        }
+    }
+}
+
+/// A done item, as recorded on the journal.
+#[derive(Debug, Serialize)]
+pub struct Entry {
+    pub done_at: DateTime<Utc>,
+    pub name: String,
+    pub context: Option<String>,
+    /// The names of the ancestors of the item, from the root down.
+    pub path: Vec<String>,
+}
+
+/// Returns the path of the journal that goes along with the items file on `path`, on the data directory.
+pub fn journal_path(path: &Path, format: JournalFormat) -> Result<PathBuf, String> {
+    let dir = utils::dirs::data_dir()
+        .map(|dir| dir.join("itmn-journal"))
+        .ok_or_else(|| "failed to find the data directory (is $HOME set?)".to_string())?;
+
+    let name = path
+        .file_stem()
+        .map_or("itmn".into(), |n| n.to_string_lossy());
+
+    Ok(dir.join(format!("{}-journal.{}", name, format.extension())))
+}
+
+/// Appends `entries` to the journal on `path`, creating it (and its directory) if needed.
+pub fn append(path: &Path, format: JournalFormat, entries: &[Entry]) -> Result<(), String> {
+    let mut lines = String::new();
+
+    for entry in entries {
+        match format {
+            JournalFormat::Text => {
+                let mut names = entry.path.clone();
+                names.push(entry.name.clone());
+
+                lines.push_str(&format!(
+                    "{}  {}",
+                    entry.done_at.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                    names.join(" > ")
+                ));
+                if let Some(ref context) = entry.context {
+                    lines.push_str(&format!(" @{}", context));
+                }
+            }
+            JournalFormat::Jsonl => {
+                lines.push_str(&serde_json::to_string(entry).map_err(|e| e.to_string())?)
+            }
+        }
+
+        lines.push('\n');
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(lines.as_bytes()))
+        .map_err(|e| e.to_string())
+}
@@ -1,125 +1,732 @@
 #![feature(termination_trait_lib)]
 
-use clap::Clap;
+use chrono::{DateTime, Datelike, Local, TimeZone, Utc};
+use clap::{Clap, IntoApp};
 
-use std::collections::HashSet;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use terminal_size::Width;
 
 mod cli;
 use cli::*;
 
 mod item;
-use item::{InternalId, Item, ItemState, RefId};
+use item::{InternalId, Item, ItemEvent, ItemLink, ItemState, RefId};
 
 mod manager;
+use manager::{Change, ItemManager, ManagerError, ProgramResult};
 use manager::{Interactable, Searchable};
-use manager::{ItemManager, ManagerError, ProgramResult};
+
+mod crypt;
+use crypt::Encryption;
+
+mod export;
+
+mod import;
+
+mod interactive;
+
+mod journal;
+use journal::JournalFormat;
+
+mod manpage;
+
+mod outline;
+
+mod pending;
+mod plan;
+use plan::Plan;
+
+mod quickadd;
 
 mod report;
-use report::{FlatReport, Report, ReportConfig, ReportDepth, ReportInfo};
 
-use utils::data::data_serialize;
-use utils::error::ExitCode;
+mod selection;
+
+mod sync;
+use sync::GitSync;
+
+mod template;
+use report::{
+    ContextFilter, FlatReport, JsonReport, Progress, Report, ReportConfig, ReportDepth, ReportInfo,
+    SortKey,
+};
+use selection::{IdKind, Selection};
+use template::Template;
+
+use utils::config;
+use utils::data::{data_serialize, Backups};
+use utils::dirs;
+use utils::error::{Error, ExitCode};
+use utils::io::{write_atomic, FileLock};
+use utils::log::{self, Level};
 use utils::misc::confirm_with_default;
+use utils::picker::Picker;
 use utils::tmp;
+use utils::{debug, error, info, warn};
 
 fn main() -> ExitCode {
-    let itmn_file = std::env::var("ITMN_FILE")
-        .unwrap_or_else(|_| format!("{}/.local/share/itmn", std::env::var("HOME").unwrap()));
+    if let Err(e) = config::load_into_env("itmn") {
+        error!("Invalid config file: {}", e);
+        return ExitCode::new(1);
+    }
+
+    let itmn_file = match (std::env::var("ITMN_FILE"), dirs::data_dir()) {
+        (Ok(file), _) => file,
+        (Err(_), Some(dir)) => dir.join("itmn").to_string_lossy().into_owned(),
+        (Err(_), None) => {
+            error!("Failed to find the data directory (is $HOME set?)");
+            return ExitCode::new(1);
+        }
+    };
+
+    let mut options = cli::Options::parse();
+    if options.subcmd.is_none() {
+        match cli::default_subcommand() {
+            Ok(subcmd) => options.subcmd = subcmd,
+            Err(e) => {
+                error!("Invalid default subcommand: {}", e);
+                return ExitCode::new(1);
+            }
+        }
+    }
+    let backup_retention = match options.backup_retention() {
+        Ok(retention) => retention,
+        Err(e) => {
+            error!("Invalid backups option: {}", e);
+            return ExitCode::new(1);
+        }
+    };
+    let due_soon = match options.due_soon_days() {
+        Ok(days) => days,
+        Err(e) => {
+            error!("Invalid due-soon option: {}", e);
+            return ExitCode::new(1);
+        }
+    };
+    let id_strategy = match options.id_strategy() {
+        Ok(strategy) => strategy,
+        Err(e) => {
+            error!("Invalid ids option: {}", e);
+            return ExitCode::new(1);
+        }
+    };
+    // the completion scripts don't depend on the items, so they don't need to be loaded
+    if let Some(SubCmd::Completions(ref args)) = options.subcmd {
+        return match subcmd_completions(args) {
+            Ok(()) => ExitCode::new(0),
+            Err(e) => {
+                error!("{}", e);
+                ExitCode::new(1)
+            }
+        };
+    }
+    if let Some(SubCmd::GenMan(ref args)) = options.subcmd {
+        return match subcmd_gen_man(args) {
+            Ok(()) => ExitCode::new(0),
+            Err(e) => {
+                error!("{}", e);
+                ExitCode::new(1)
+            }
+        };
+    }
+
+    let tree = match options.tree_style() {
+        Ok(tree) => tree,
+        Err(e) => {
+            error!("Invalid tree option: {}", e);
+            return ExitCode::new(1);
+        }
+    };
+    let spaces_per_indent = match options.indent_width() {
+        Ok(width) => width,
+        Err(e) => {
+            error!("Invalid indent option: {}", e);
+            return ExitCode::new(1);
+        }
+    };
+    let id_before_name = match options.id_before_name() {
+        Ok(before) => before,
+        Err(e) => {
+            error!("Invalid id-position option: {}", e);
+            return ExitCode::new(1);
+        }
+    };
+    let notes = match options.notes() {
+        Ok(notes) => notes,
+        Err(e) => {
+            error!("Invalid notes option: {}", e);
+            return ExitCode::new(1);
+        }
+    };
+    let journal = match options.journal() {
+        Ok(journal) => journal,
+        Err(e) => {
+            error!("Invalid journal option: {}", e);
+            return ExitCode::new(1);
+        }
+    };
+    let inherit_contexts = options.inherit_contexts();
+    let due_banner = options.due_banner();
+    let wip_limits = match options.wip_limits() {
+        Ok(limits) => limits,
+        Err(e) => {
+            error!("Invalid wip-limits option: {}", e);
+            return ExitCode::new(1);
+        }
+    };
+    let git_commit = options.git_commit();
+    let color_option = options.color();
+    let log_level = Level::from_verbosity(options.verbose, options.quiet);
+    let log_timestamps = options.log_timestamps();
+    let mut subcmd = options.subcmd;
+    let json = options.json.unwrap_or(false);
+    let dry_run = options.dry_run.unwrap_or(false);
+
+    // a selection like work:3 refers to the items of the work profile, no matter which file would be used otherwise
+    let linked_profile = match subcmd {
+        Some(SubCmd::SelRefID(ref mut args)) | Some(SubCmd::SelInternalID(ref mut args)) => {
+            args.take_profile()
+        }
+        _ => None,
+    };
+
+    if let Some(ref profile) = linked_profile {
+        if !profile_path(profile).map_or(false, |path| path.exists()) {
+            error!("There's no profile named {:?}", profile);
+            return ExitCode::new(1);
+        }
+    }
 
-    let options = cli::Options::parse();
-    let subcmd = options.subcmd;
-    let path_string = options.path.unwrap_or(itmn_file);
+    // --path takes precedence over the other profiles, since it's more specific
+    let profile = match (linked_profile, &options.path) {
+        (Some(profile), _) => Some(profile),
+        (None, Some(_)) => None,
+        (None, None) => options
+            .profile
+            .or_else(|| std::env::var("ITMN_PROFILE").ok())
+            .filter(|profile| !profile.is_empty()),
+    };
+    let path_string = match (&profile, options.path) {
+        (Some(profile), _) => match profile_path(profile) {
+            Ok(path) => path.to_string_lossy().into_owned(),
+            Err(e) => {
+                error!("Invalid profile: {}", e);
+                return ExitCode::new(1);
+            }
+        },
+        (None, Some(path)) => path,
+        (None, None) => itmn_file,
+    };
     let path = Path::new(&path_string);
 
-    let contents = match utils::io::touch_read(&path) {
-        Ok(string) => string,
-        Err(why) => {
-            eprintln!("Failed to load file: {}", why);
+    let color = match color_option.as_deref() {
+        None | Some("auto") => {
+            std::env::var_os("NO_COLOR").map_or(true, |v| v.is_empty())
+                && atty::is(atty::Stream::Stdout)
+                && !cli::is_dumb_terminal()
+        }
+        Some("always") => true,
+        Some("never") => false,
+        Some(other) => {
+            error!(
+                "Invalid color option: {:?} (expected auto, always or never)",
+                other
+            );
+            return ExitCode::new(1);
+        }
+    };
+
+    // the messages go to stderr, so they're only colored if it's a terminal too
+    let stderr_color = match color_option.as_deref() {
+        None | Some("auto") => color && atty::is(atty::Stream::Stderr),
+        _ => color,
+    };
+    log::init(log_level, log_timestamps, stderr_color);
+
+    let progress = match options.progress.as_deref() {
+        None | Some("descendants") => Some(Progress::Descendants),
+        Some("children") => Some(Progress::Children),
+        Some("off") => None,
+        Some(other) => {
+            error!(
+                "Invalid progress option: {:?} (expected descendants, children or off)",
+                other
+            );
             return ExitCode::new(1);
         }
     };
 
-    let data: Vec<Item> = match data_serialize::import(validate_parsed_string(&contents)) {
-        Ok(data) => data,
-        Err(why) => {
-            eprintln!("Failed to parse file: {}", why);
+    let sort = match options.sort.as_deref().map(SortKey::parse_list) {
+        None => Vec::new(),
+        Some(Ok(keys)) => keys,
+        Some(Err(e)) => {
+            error!("Invalid sort option: {}", e);
             return ExitCode::new(1);
         }
     };
 
-    let mut manager = match ItemManager::new(data) {
+    let archive = archive_path(&path);
+    let templates = templates_path(&path);
+    let undo = undo_path(&path);
+
+    // `next` only shows pending items, so it can load them from the pending index (see `pending`)
+    let pending_only =
+        matches!(subcmd, Some(SubCmd::Next(_))) && Encryption::detect(path).is_none();
+
+    // the file is only locked while it's loaded and saved, so long-running subcommands like `watch` and
+    // `remind --daemon` don't keep other instances from using it
+    let (data, source, loaded_modified) = {
+        let _lock = match FileLock::acquire(path, manager::LOCK_TIMEOUT) {
+            Ok(lock) => lock,
+            Err(why) => {
+                error!("Failed to lock file: {}", why);
+                return ExitCode::new(1);
+            }
+        };
+
+        let loaded = if pending_only {
+            pending::load(path, &pending_path(path), || load_items(path)).map(|data| (data, None))
+        } else {
+            load_items_with_source(path).map(|(data, source)| (data, Some(source)))
+        };
+
+        match loaded {
+            Ok((data, source)) => (data, source, manager::modified_time(path)),
+            Err(why) => {
+                error!("{}", why);
+                return ExitCode::new(1);
+            }
+        }
+    };
+    debug!(
+        "Loaded {} top-level item(s) from {}",
+        data.len(),
+        path.display()
+    );
+
+    let manager = match source {
+        Some(source) => ItemManager::with_source(data, source),
+        None => ItemManager::partial(data),
+    };
+    let mut manager = match manager {
         Ok(manager) => manager,
         Err(ManagerError::RepeatedRefID(RefId(id))) => {
-            eprintln!(
+            error!(
                 "Repeated reference ID in file: {}; it'll have to be removed manually.",
                 id
             );
             return ExitCode::new(1);
         }
         Err(ManagerError::RepeatedInternalID(InternalId(id))) => {
-            eprintln!(
+            error!(
                 "Repeated internal ID in file: {}; it'll have to be removed manually.",
                 id
             );
             return ExitCode::new(1);
         }
+        Err(ManagerError::NoFreeRefID) => {
+            error!("There are no free reference IDs left for the pending items in the file.");
+            return ExitCode::new(1);
+        }
     };
 
-    let code = manager.start_program_with_file(&path, |manager| {
-        type UsedReport = report::BasicReport;
-        const DEFAULT_SUBCOMMAND: SubCmd = SubCmd::List;
-        const DEFAULT_SPACES_PER_INDENT: usize = 2;
+    manager.set_id_strategy(id_strategy);
+    manager.set_loaded_modified(loaded_modified);
 
-        let report_cfg = ReportConfig {
-            spaces_per_indent: DEFAULT_SPACES_PER_INDENT,
-        };
+    let backups = Backups::next_to(path, backup_retention);
+    let git = GitSync::new(vec![path.to_path_buf(), archive.clone(), templates.clone()]);
+    let auto_commit = if git_commit { Some(&git) } else { None };
 
-        let result = match subcmd.unwrap_or(DEFAULT_SUBCOMMAND) {
-            SubCmd::SelRefID(args) => subcmd_selection::<UsedReport>(manager, args, &report_cfg),
-            SubCmd::Add(args) => subcmd_add(manager, args),
-            SubCmd::List => subcmd_list::<UsedReport>(manager, &report_cfg),
-            SubCmd::Next => subcmd_next::<UsedReport>(manager, &report_cfg),
-            SubCmd::FlatList => subcmd_flatlist(manager, &report_cfg),
-        };
+    // the items taken out of the items file by `archive-done`, which are written to the archive after it's saved
+    let to_archive: RefCell<Vec<Item>> = RefCell::new(Vec::new());
 
-        match result {
-            Ok(pr) => pr,
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                ProgramResult {
+    let code = manager.start_program_with_file(
+        &path,
+        Some(&undo),
+        Some(&backups),
+        auto_commit,
+        |manager| {
+            type UsedReport = report::BasicReport;
+            const DEFAULT_SUBCOMMAND: SubCmd = SubCmd::List(FilterArgs {
+                context: Vec::new(),
+                project: None,
+                modified_since: None,
+                created_before: None,
+            });
+
+            let report_cfg = ReportConfig {
+                spaces_per_indent,
+                color,
+                width: terminal_size::terminal_size().map(|(Width(width), _)| width as usize),
+                sort,
+                progress,
+                due_soon,
+                tree,
+                id_before_name,
+                notes,
+                inherited_contexts: if inherit_contexts {
+                    report::inherited_contexts(&manager.data)
+                } else {
+                    HashMap::new()
+                },
+            };
+
+            let subcmd = subcmd.unwrap_or(DEFAULT_SUBCOMMAND);
+            let wip_strictness = subcmd.wip_strictness();
+            if due_banner && !json && subcmd.is_report() {
+                print_due_banner(&manager.data, &report_cfg);
+            }
+
+            let result = match subcmd {
+                SubCmd::SelRefID(args) if json && args.only_lists() => {
+                    subcmd_selection::<JsonReport>(manager, args, IdKind::Ref, dry_run, &report_cfg)
+                }
+                SubCmd::SelInternalID(args) if json && args.only_lists() => {
+                    subcmd_selection::<JsonReport>(
+                        manager,
+                        args,
+                        IdKind::Internal,
+                        dry_run,
+                        &report_cfg,
+                    )
+                }
+                SubCmd::SelRefID(_) | SubCmd::SelInternalID(_) if json => {
+                    Err("--json is only supported by the list actions of sel".into())
+                }
+                SubCmd::List(args) if json => subcmd_list::<JsonReport>(manager, args, &report_cfg),
+                SubCmd::Next(args) if json => subcmd_next::<JsonReport>(manager, args, &report_cfg),
+                _ if json => {
+                    Err("--json is only supported by list, next and the list actions of sel".into())
+                }
+                SubCmd::SelRefID(args) => {
+                    subcmd_selection::<UsedReport>(manager, args, IdKind::Ref, dry_run, &report_cfg)
+                }
+                SubCmd::SelInternalID(args) => subcmd_selection::<UsedReport>(
+                    manager,
+                    args,
+                    IdKind::Internal,
+                    dry_run,
+                    &report_cfg,
+                ),
+                SubCmd::Add(args) => subcmd_add(manager, args),
+                SubCmd::List(args) => subcmd_list::<UsedReport>(manager, args, &report_cfg),
+                SubCmd::Next(args) => subcmd_next::<UsedReport>(manager, args, &report_cfg),
+                SubCmd::FlatList(args) => subcmd_flatlist(manager, args, &report_cfg),
+                SubCmd::Log(args) => subcmd_log::<UsedReport>(manager, args, &archive, &report_cfg),
+                SubCmd::ArchiveDone(args) => {
+                    subcmd_archive_done(manager, args, &archive, &to_archive, dry_run)
+                }
+                SubCmd::Prune(args) => subcmd_prune::<UsedReport>(manager, args, &report_cfg),
+                SubCmd::Undo => subcmd_undo(&path, &undo, dry_run),
+                SubCmd::Merge(args) => subcmd_merge::<UsedReport>(manager, args, &report_cfg),
+                SubCmd::Blocked => subcmd_blocked::<UsedReport>(manager, &report_cfg),
+                SubCmd::Snoozed => subcmd_snoozed::<UsedReport>(manager, &report_cfg),
+                SubCmd::Starred => subcmd_starred::<UsedReport>(manager, &report_cfg),
+                SubCmd::Someday => subcmd_someday::<UsedReport>(manager, &report_cfg),
+                SubCmd::Stale(args) => subcmd_stale::<UsedReport>(manager, &args, &report_cfg),
+                SubCmd::Plan(args) => subcmd_plan(manager, args, &plan_path(path), dry_run),
+                SubCmd::Today(args) => subcmd_today(manager, args, &plan_path(path), &report_cfg),
+                SubCmd::Grep(args) => subcmd_grep(manager, args),
+                SubCmd::Has(args) => subcmd_has(manager, args, &report_cfg),
+                SubCmd::Watch(args) => subcmd_watch::<UsedReport>(args, &path, &report_cfg),
+                SubCmd::Sync(args) => subcmd_sync(&git, path, args),
+                SubCmd::Profiles => subcmd_profiles(profile.as_deref()),
+                SubCmd::Active => subcmd_active::<UsedReport>(manager, &report_cfg),
+                SubCmd::Timesheet(args) => subcmd_timesheet(manager, args, &report_cfg),
+                SubCmd::Remaining => subcmd_remaining(manager, &report_cfg),
+                SubCmd::Agenda(args) => subcmd_agenda::<UsedReport>(manager, args, &report_cfg),
+                SubCmd::Export(args) => subcmd_export(manager, args, &path),
+                SubCmd::Import(args) => subcmd_import(manager, args),
+                SubCmd::ImportSubtree(args) => subcmd_import_subtree(manager, args),
+                SubCmd::Remind(args) => subcmd_remind(manager, args, &path),
+                SubCmd::Contexts(args) => subcmd_contexts(manager, args, &report_cfg),
+                SubCmd::Context(args) => subcmd_context(manager, args),
+                SubCmd::Stats(args) => subcmd_stats(manager, args, &archive, &report_cfg),
+                SubCmd::Report(args) => subcmd_report(manager, args, &archive, &report_cfg),
+                SubCmd::Review(args) => subcmd_review::<UsedReport>(manager, args, &report_cfg),
+                SubCmd::Renumber => subcmd_renumber(manager),
+                SubCmd::Template(args) => subcmd_template(manager, args, &templates, dry_run),
+                SubCmd::Tui => subcmd_tui(manager),
+                SubCmd::Projects(args) => subcmd_projects(manager, args, &report_cfg),
+                SubCmd::Completions(_) | SubCmd::GenMan(_) => unreachable!(),
+                SubCmd::Complete(args) => subcmd_complete(manager, args),
+            };
+
+            let result = match result {
+                Ok(pr) => pr,
+                Err(e) => {
+                    error!("{}", e);
+                    ProgramResult {
+                        should_save: false,
+                        exit_status: 1,
+                    }
+                }
+            };
+
+            if let Some(strict) =
+                wip_strictness.filter(|_| result.should_save && !wip_limits.is_empty())
+            {
+                let over = contexts_over_wip_limit(manager, &wip_limits);
+
+                for (context, count, limit) in &over {
+                    let message = format!(
+                        "@{} has {} pending items, over its WIP limit of {}",
+                        context, count, limit
+                    );
+
+                    if strict {
+                        error!("{}", message);
+                    } else {
+                        warn!("{}", message);
+                    }
+                }
+
+                if strict && !over.is_empty() {
+                    return ProgramResult {
+                        should_save: false,
+                        exit_status: 1,
+                    };
+                }
+            }
+
+            if dry_run && result.should_save {
+                print_changes(&manager.changes(), &report_cfg);
+                info!("Dry run: no changes were saved");
+
+                return ProgramResult {
                     should_save: false,
-                    exit_status: 1,
+                    exit_status: result.exit_status,
+                };
+            }
+
+            result
+        },
+        |manager| {
+            if let Some(format) = journal {
+                if let Err(e) = record_completions(manager, path, format) {
+                    warn!("failed to update the completion journal: {}", e);
                 }
             }
-        }
-    });
+
+            // the archived items are only added to the archive once they're out of the items file, so they don't end
+            // up on both if the save fails
+            let moved = to_archive.take();
+            if !moved.is_empty() {
+                let count = moved.len();
+                append_to_archive(&archive, moved).map_err(|e| {
+                    format!(
+                        "failed to save archive: {}; the archived items can be brought back with `itmn undo`",
+                        e
+                    )
+                })?;
+
+                info!("Archived {} item(s) to {}", count, archive.display());
+            }
+
+            Ok(())
+        },
+    );
 
     ExitCode::new(code)
 }
 
-fn subcmd_add(
+/// Returns the contexts that got more pending items with the changes made to `manager` and ended up over their WIP
+/// limits on `limits`, along with how many pending items they have and their limits. Items on the someday/maybe list
+/// don't count.
+fn contexts_over_wip_limit(
     manager: &mut ItemManager,
-    ItemAddDetails {
-        name,
-        context,
-        note,
-        description,
-    }: ItemAddDetails,
-) -> Result<ProgramResult, String> {
-    let RefId(ref_id) = manager.add_item_on_root(
-        &name,
-        &context.unwrap_or(String::new()),
-        match note {
-            Some(false) | None => ItemState::Todo,
-            Some(true) => ItemState::Note,
-        },
-        description.unwrap_or_else(String::new), // description
-        Vec::new(),                              // children
-    );
+    limits: &HashMap<String, usize>,
+) -> Vec<(String, usize, usize)> {
+    fn pending_context(item: &Item) -> Option<String> {
+        if matches!(item.state, ItemState::Todo | ItemState::Waiting(_)) && !item.someday {
+            item.context().and_then(Item::validate_context)
+        } else {
+            None
+        }
+    }
+
+    let mut growth: HashMap<String, i64> = HashMap::new();
+    for change in manager.changes() {
+        let (old, new) = match change {
+            Change::Added(item) => (None, Some(item)),
+            Change::Removed(item) => (Some(item), None),
+            Change::Modified { old, new, .. } => (Some(*old), Some(*new)),
+        };
+
+        if let Some(context) = old.as_ref().and_then(pending_context) {
+            *growth.entry(context).or_insert(0) -= 1;
+        }
+        if let Some(context) = new.as_ref().and_then(pending_context) {
+            *growth.entry(context).or_insert(0) += 1;
+        }
+    }
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for root in &manager.data {
+        root.traverse(&mut |item| {
+            if let Some(context) = pending_context(item) {
+                *counts.entry(context).or_insert(0) += 1;
+            }
+        });
+    }
+
+    let mut over: Vec<(String, usize, usize)> = growth
+        .into_iter()
+        .filter(|&(_, grown)| grown > 0)
+        .filter_map(|(context, _)| {
+            let limit = *limits.get(&context)?;
+            let count = counts.get(&context).copied().unwrap_or(0);
+
+            if count > limit {
+                Some((context, count, limit))
+            } else {
+                None
+            }
+        })
+        .collect();
+    over.sort();
+
+    over
+}
+
+fn subcmd_add(manager: &mut ItemManager, args: AddArgs) -> Result<ProgramResult, String> {
+    let AddArgs {
+        details,
+        stdin,
+        under,
+        project,
+        strict: _,
+    } = args;
+
+    if project == Some(true) && under.is_some() {
+        return Err(
+            "only root items can be projects, so --project can't be used along with --under".into(),
+        );
+    }
+
+    let state = match details.note {
+        Some(false) | None => ItemState::Todo,
+        Some(true) => ItemState::Note,
+    };
+
+    if let Some(under) = under {
+        if manager.find(RefId(under)).is_none() {
+            return Err(format!("could not find item with RefId = {}", under));
+        }
+    }
+
+    if stdin == Some(true) {
+        if details.name.is_some() {
+            return Err("the name of the item can't be given along with --stdin".into());
+        }
+
+        if details.description.is_some() {
+            return Err("--description can't be used along with --stdin".into());
+        }
+
+        let extras = details.extras()?;
+        let context = details.context.unwrap_or(String::new());
+
+        let mut text = String::new();
+        io::Read::read_to_string(&mut io::stdin(), &mut text)
+            .map_err(|e| format!("failed to read from stdin: {}", e))?;
+
+        let nodes = outline::parse_plain(&text)?;
+        if nodes.is_empty() {
+            return Err("no items were given on stdin".into());
+        }
+
+        /// Creates the items of `nodes`, giving the parents their IDs before the children.
+        fn build(
+            manager: &mut ItemManager,
+            nodes: Vec<outline::OutlineNode>,
+            context: &str,
+            state: &ItemState,
+            extras: &ItemExtras,
+        ) -> Result<Vec<Item>, String> {
+            nodes
+                .into_iter()
+                .map(|node| {
+                    let node_context = if node.context.is_empty() {
+                        context
+                    } else {
+                        &node.context
+                    };
+
+                    let mut item = manager.create_item(
+                        &node.name,
+                        node_context,
+                        state.clone(),
+                        String::new(), // description
+                        Vec::new(),    // children
+                    )?;
+                    extras.apply(&mut item);
+                    item.children = build(manager, node.children, context, state, extras)?;
 
-    eprintln!("Item Added! | RefID: {}", ref_id);
+                    Ok(item)
+                })
+                .collect()
+        }
+
+        let mut items = build(manager, nodes, &context, &state, &extras)?;
+        for item in items.iter_mut() {
+            item.project = project == Some(true);
+        }
+
+        let mut count = 0;
+        for item in &items {
+            item.traverse(&mut |_| count += 1);
+        }
+
+        info!("Adding items:");
+
+        for item in items {
+            if let Some(ref_id) = item.ref_id {
+                info!("* RefID: {}", ref_id);
+            }
+
+            match under {
+                Some(under) => manager.find_mut(RefId(under)).unwrap().children.push(item),
+                None => manager.data.push(item),
+            }
+        }
+
+        info!("{} item(s) added", count);
+
+        return Ok(ProgramResult {
+            should_save: true,
+            exit_status: 0,
+        });
+    }
+
+    if details.name.is_none() {
+        return Err("the name of the item is missing (or use --stdin)".into());
+    }
+
+    let (name, context, extras) = details.parse_name()?;
+    let description = details.description.unwrap_or_else(String::new);
+
+    let RefId(ref_id) = match under {
+        Some(under) => manager.add_child(
+            RefId(under),
+            &name,
+            &context,
+            state,
+            description,
+            Vec::new(),
+        )?,
+        None => manager.add_item_on_root(&name, &context, state, description, Vec::new())?,
+    };
+
+    manager.interact_mut(RefId(ref_id), |item| {
+        extras.apply(item);
+        item.project = project == Some(true);
+    });
+
+    info!("Item Added! | RefID: {}", ref_id);
 
     Ok(ProgramResult {
         should_save: true,
@@ -132,13 +739,18 @@ fn subcmd_add(
 /// Type argument `R` is the type of report that should be shown.
 fn subcmd_list<R: Report>(
     manager: &ItemManager,
+    args: FilterArgs,
     report_cfg: &ReportConfig,
 ) -> Result<ProgramResult, String> {
-    let items: Vec<&Item> = manager
-        .surface_ref_ids()
-        .iter()
-        .map(|&i| manager.find(i).unwrap())
-        .collect();
+    let context_filter = ContextFilter::new(&args.context, &report_cfg.inherited_contexts);
+    let date_filter = args.date_filter()?;
+    let items = report_roots(manager, &args)?;
+
+    report_overdue::<R>(
+        &items,
+        &|i| context_filter.matches(i) && date_filter.matches(i),
+        report_cfg,
+    );
 
     R::report(
         "All items (surface)",
@@ -146,7 +758,12 @@ fn subcmd_list<R: Report>(
         &ReportInfo {
             config: report_cfg,
             indent: 0,
-            filter: Some(&|i: &Item| i.state != ItemState::Done),
+            filter: Some(&|i: &Item| {
+                !i.state.is_closed()
+                    && !i.is_hidden()
+                    && context_filter.allows(i)
+                    && date_filter.allows(i)
+            }),
             depth: ReportDepth::Tree,
         },
         &mut io::stdout(),
@@ -162,13 +779,12 @@ fn subcmd_list<R: Report>(
 /// A function for the `flat-list` subcommand.
 fn subcmd_flatlist(
     manager: &ItemManager,
+    args: FilterArgs,
     report_cfg: &ReportConfig,
 ) -> Result<ProgramResult, String> {
-    let items: Vec<&Item> = manager
-        .surface_ref_ids()
-        .iter()
-        .map(|&i| manager.find(i).unwrap())
-        .collect();
+    let context_filter = ContextFilter::new(&args.context, &report_cfg.inherited_contexts);
+    let date_filter = args.date_filter()?;
+    let items = report_roots(manager, &args)?;
 
     FlatReport::report(
         "All items (flat report)",
@@ -176,7 +792,12 @@ fn subcmd_flatlist(
         &ReportInfo {
             config: report_cfg,
             indent: 0,
-            filter: Some(&|i: &Item| i.state != ItemState::Done),
+            filter: Some(&|i: &Item| {
+                !i.state.is_closed()
+                    && !i.is_hidden()
+                    && context_filter.allows(i)
+                    && date_filter.allows(i)
+            }),
             depth: ReportDepth::Tree,
         },
         &mut io::stdout(),
@@ -189,31 +810,78 @@ fn subcmd_flatlist(
     })
 }
 
-/// A function for the `next` subcommand.
+/// A function for the `log` subcommand.
 ///
 /// Type argument `R` is the type of report that should be shown.
-fn subcmd_next<R: Report>(
+fn subcmd_log<R: Report>(
     manager: &ItemManager,
+    args: LogArgs,
+    archive: &Path,
     report_cfg: &ReportConfig,
 ) -> Result<ProgramResult, String> {
-    let items: Vec<&Item> = manager
-        .surface_ref_ids()
-        .iter()
-        .map(|&i| manager.find(i).unwrap())
-        .collect();
+    let since = utils::time::parse_duration(args.since.as_deref().unwrap_or("1w"))
+        .map_err(|e| format!("failed to parse --since: {}", e))?;
+    let cutoff = Utc::now() - since;
 
-    R::report(
-        "Next",
-        &mut items.into_iter(),
-        &ReportInfo {
-            config: report_cfg,
-            indent: 0,
-            filter: Some(&|i: &Item| i.state != ItemState::Done),
-            depth: ReportDepth::Brief,
-        },
-        &mut io::stdout(),
-    )
-    .unwrap();
+    let archived = if archive.exists() {
+        load_items(archive)?
+    } else {
+        Vec::new()
+    };
+
+    let mut done: Vec<(&Item, DateTime<Local>)> = Vec::new();
+    for root in manager.data.iter().chain(archived.iter()) {
+        root.traverse(&mut |item| {
+            if let (ItemState::Done, Some(done_at)) = (&item.state, item.done_at) {
+                if done_at >= cutoff {
+                    done.push((item, done_at.with_timezone(&Local)));
+                }
+            }
+        });
+    }
+    done.sort_by_key(|&(_, done_at)| done_at);
+
+    println!(
+        "Done since {} | {} item(s)",
+        cutoff.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+        done.len()
+    );
+
+    let info = ReportInfo {
+        config: report_cfg,
+        indent: 1,
+        filter: None,
+        depth: ReportDepth::Shallow,
+    };
+
+    let mut last_day = None;
+    let mut per_week: BTreeMap<(i32, u32), usize> = BTreeMap::new();
+    for (item, done_at) in &done {
+        let day = done_at.date_naive();
+        if last_day != Some(day) {
+            println!("{}", day.format("%Y-%m-%d %a"));
+            last_day = Some(day);
+        }
+
+        R::display(item, &info, &mut io::stdout()).unwrap();
+
+        let week = done_at.iso_week();
+        *per_week.entry((week.year(), week.week())).or_insert(0) += 1;
+    }
+
+    if !per_week.is_empty() {
+        println!();
+        println!("Per week:");
+        for ((year, week), count) in per_week {
+            println!(
+                "{}{}-W{:02}  {}",
+                report_cfg.get_indent_spaces(1),
+                year,
+                week,
+                count
+            );
+        }
+    }
 
     Ok(ProgramResult {
         should_save: false,
@@ -221,547 +889,4812 @@ fn subcmd_next<R: Report>(
     })
 }
 
-/// A function for the `sel-ref-id` subcommand.
-///
-/// Type argument `R` is the type of report that should be shown.
-fn subcmd_selection<R: Report>(
+/// A function for the `archive-done` subcommand.
+fn subcmd_archive_done(
     manager: &mut ItemManager,
-    args: SelectionDetails,
-    report_cfg: &ReportConfig,
+    args: ArchiveArgs,
+    archive: &Path,
+    to_archive: &RefCell<Vec<Item>>,
+    dry_run: bool,
 ) -> Result<ProgramResult, String> {
-    type SelAct = SelectionAction;
+    /// Whether any of the descendants of `item` is still to be done.
+    fn has_pending_descendants(item: &Item) -> bool {
+        item.children.iter().any(|child| {
+            matches!(child.state, ItemState::Todo | ItemState::Waiting(_))
+                || has_pending_descendants(child)
+        })
+    }
 
-    let range = match utils::misc::parse_range_str(&args.range) {
-        Ok(vec) => {
-            // check if empty
-            if vec.is_empty() {
-                return Err("no selection was specified".into());
-            }
+    let older_than = utils::time::parse_duration(args.older_than.as_deref().unwrap_or("30d"))
+        .map_err(|e| format!("failed to parse --older-than: {}", e))?;
+    let cutoff = Utc::now() - older_than;
 
-            // abort if there's an invalid ID
-            if let Some(RefId(missing)) = manager.first_invalid_ref_id(vec.iter()) {
-                return Err(format!(
-                    "there's at least one invalid ID (#{}) on the selection",
-                    missing,
-                ));
-            }
+    // Items done before completion timestamps were recorded are considered old enough. The ones with pending items
+    // under them are kept, since the whole subtree would go to the archive.
+    let kept = Cell::new(0);
+    let moved = manager.extract_where(|item| {
+        let old_enough =
+            item.state == ItemState::Done && item.done_at.map_or(true, |done_at| done_at < cutoff);
 
-            vec
-        }
-        Err(e) => {
-            return Err(format!("failed to parse range: {}", e));
+        if old_enough && has_pending_descendants(item) {
+            kept.set(kept.get() + 1);
+            return false;
         }
-    };
-
-    match args.action.unwrap_or(SelAct::ListBrief) {
-        SelAct::Modify(sargs) => {
-            let proceed = |manager: &mut ItemManager| {
-                for &id in &range {
-                    manager.interact_mut(RefId(id), |item| sargs.mod_item_by_ref(item));
-                }
 
-                Ok(ProgramResult {
-                    should_save: true,
-                    exit_status: 0,
-                })
-            };
+        old_enough
+    });
 
-            let selected: Vec<&Item> = range
-                .iter()
-                .map(|&id| manager.find(RefId(id)).unwrap())
-                .collect();
+    if kept.get() > 0 {
+        warn!(
+            "{} done item(s) weren't archived because they still have pending items under them",
+            kept.get()
+        );
+    }
 
-            R::report(
-                "Items to be modified",
-                &mut selected.into_iter(),
-                &ReportInfo {
-                    config: report_cfg,
-                    indent: 0,
-                    filter: None,
-                    depth: ReportDepth::Shallow,
-                },
-                &mut io::stdout(),
-            )
-            .unwrap();
+    if moved.is_empty() {
+        info!("No items to be archived");
 
-            eprintln!();
+        return Ok(ProgramResult {
+            should_save: false,
+            exit_status: 0,
+        });
+    }
 
-            let modifications = sargs.modifications_description();
+    let count = moved.len();
 
-            if modifications.is_empty() {
-                eprintln!("No changes were specified");
+    if dry_run {
+        info!(
+            "Dry run: {} item(s) would be archived to {}",
+            count,
+            archive.display()
+        );
 
-                // Exit sucessfully though, I don't think this is necessarily a problem.
-                Ok(ProgramResult {
-                    should_save: false,
-                    exit_status: 0,
-                })
-            } else {
-                eprintln!("Changes to be made:");
-                for modification in sargs.modifications_description() {
-                    eprintln!(" * {}", modification);
-                }
+        return Ok(ProgramResult {
+            should_save: true,
+            exit_status: 0,
+        });
+    }
 
-                if confirm_with_default(true) {
-                    proceed(manager)
-                } else {
-                    Ok(ProgramResult {
-                        should_save: false,
-                        exit_status: 1,
-                    })
-                }
-            }
-        }
-        SelAct::Add(sargs) => {
-            let mut proceed = || {
-                eprintln!("Adding items:");
+    manager.release_ref_ids(&moved);
+    to_archive.borrow_mut().extend(moved);
+    debug!(
+        "{} item(s) will be archived once the items are saved",
+        count
+    );
 
-                for &id in &range {
-                    let RefId(ref_id) = manager
-                        .add_child(
-                            RefId(id),
-                            &sargs.name,
-                            sargs.context.as_ref().map_or("", |s| s.as_str()),
-                            match sargs.note {
-                                Some(false) | None => ItemState::Todo,
-                                Some(true) => ItemState::Note,
-                            },
-                            sargs.description.clone().unwrap_or_else(String::new),
-                            Vec::new(), // children
-                        )
-                        .unwrap();
+    Ok(ProgramResult {
+        should_save: true,
+        exit_status: 0,
+    })
+}
 
-                    eprintln!("* RefID: {}", ref_id);
-                }
+/// Adds `items` to the end of the archive file on `archive`.
+fn append_to_archive(archive: &Path, items: Vec<Item>) -> Result<(), String> {
+    let mut archived = load_items(archive)?;
+    archived.extend(items);
 
-                Ok(ProgramResult {
-                    should_save: true,
-                    exit_status: 0,
-                })
-            };
+    data_serialize::export(&archived, true)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| crypt::write(archive, &contents))
+}
 
-            if range.len() > 1 {
-                eprintln!("More than one item was selected. All of them will receive new identical children copies.");
+/// A function for the `prune` subcommand.
+///
+/// Type argument `R` is the type of report that should be shown.
+fn subcmd_prune<R: Report>(
+    manager: &mut ItemManager,
+    args: PruneArgs,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    let cutoff = match args.older_than {
+        Some(ref duration) => Some(
+            Utc::now()
+                - utils::time::parse_duration(duration)
+                    .map_err(|e| format!("failed to parse --older-than: {}", e))?,
+        ),
+        None => None,
+    };
+    let leaves_only = args.leaves_only.unwrap_or(false);
 
-                if confirm_with_default(false) {
-                    proceed()
-                } else {
-                    Ok(ProgramResult {
-                        should_save: false,
-                        exit_status: 1,
-                    })
-                }
-            } else {
-                proceed()
-            }
-        }
-        SelAct::PrintDescription => {
-            if range.len() != 1 {
-                return Err("The selection should have exactly one item.".into());
+    let predicate = |item: &Item| {
+        item.state == ItemState::Done
+            && (!leaves_only || item.children.is_empty())
+            && match (cutoff, item.done_at) {
+                (Some(cutoff), Some(done_at)) => done_at < cutoff,
+                _ => true,
             }
+    };
 
-            manager
-                .interact(RefId(range[0]), |i| {
-                    // Check which char is the last one
-                    match i.description.chars().rev().nth(0).unwrap_or('\n') {
-                        '\n' => eprint!("{}", i.description),
-                        _ => eprintln!("{}", i.description),
-                    }
+    let selection = manager.find_where(predicate);
+    if selection.is_empty() {
+        info!("No items to be pruned");
 
-                    Ok(ProgramResult {
-                        should_save: false,
-                        exit_status: 0,
-                    })
-                })
-                .unwrap()
-        }
-        SelAct::EditDescription => {
-            if range.len() != 1 {
-                return Err("The selection should have exactly one item.".into());
-            }
+        return Ok(ProgramResult {
+            should_save: false,
+            exit_status: 0,
+        });
+    }
 
-            manager
-                .interact_mut(RefId(range[0]), |i| {
-                    match tmp::edit_text(&i.description, Some("txt")) {
-                        Ok((new_description, 0)) => {
-                            i.description = new_description;
+    if !args.force.unwrap_or(false) {
+        R::report(
+            "Items to be pruned",
+            &mut selection.into_iter(),
+            &ReportInfo {
+                config: report_cfg,
+                indent: 0,
+                filter: None,
+                depth: ReportDepth::Tree,
+            },
+            &mut io::stdout(),
+        )
+        .unwrap();
 
-                            Ok(ProgramResult {
-                                should_save: true,
-                                exit_status: 0,
-                            })
-                        }
-                        Ok((_, code)) => Err(format!("non-zero exit code: {}", code)),
-                        Err(e) => Err(format!("failed to edit text: {}", e)),
-                    }
-                })
-                .unwrap()
+        if !confirm_with_default(true) {
+            return Ok(ProgramResult {
+                should_save: false,
+                exit_status: 1,
+            });
         }
-        SelAct::Done => {
-            for &id in &range {
-                manager
-                    .change_item_state(RefId(id), |previous| match previous {
-                        ItemState::Todo => ItemState::Done,
-                        other => other,
-                    })
-                    .unwrap(); // safe because we already made sure all IDs in the range exist.
-            }
+    }
 
-            Ok(ProgramResult {
-                should_save: true,
-                exit_status: 0,
-            })
-        }
-        SelAct::ListTree => {
-            let selected: Vec<&Item> = range
-                .iter()
-                .map(|&id| manager.find(RefId(id)).unwrap())
-                .collect();
+    let removed = manager.extract_where(predicate);
+    manager.release_ref_ids(&removed);
 
-            R::report(
-                "Tree listing",
-                &mut selected.into_iter(),
-                &ReportInfo {
-                    config: report_cfg,
-                    indent: 0,
-                    filter: None,
-                    depth: ReportDepth::Tree,
-                },
-                &mut io::stdout(),
-            )
-            .expect("Failed to show report");
+    info!("Pruned {} item(s)", removed.len());
 
-            Ok(ProgramResult {
-                should_save: false,
-                exit_status: 0,
-            })
-        }
-        SelAct::ListBrief => {
-            let selected: Vec<&Item> = range
-                .iter()
-                .map(|&id| manager.find(RefId(id)).unwrap())
-                .collect();
+    Ok(ProgramResult {
+        should_save: true,
+        exit_status: 0,
+    })
+}
 
-            R::report(
-                "Brief listing",
-                &mut selected.into_iter(),
-                &ReportInfo {
-                    config: report_cfg,
-                    indent: 0,
-                    filter: None,
-                    depth: ReportDepth::Brief,
-                },
-                &mut io::stdout(),
-            )
-            .expect("Failed to show report");
+/// A function for the `undo` subcommand.
+///
+/// Swaps the items file with the undo snapshot, so running it twice redoes the change.
+fn subcmd_undo(path: &Path, undo: &Path, dry_run: bool) -> Result<ProgramResult, String> {
+    if !undo.exists() {
+        return Err("there's nothing to undo".into());
+    }
 
-            Ok(ProgramResult {
-                should_save: false,
-                exit_status: 0,
-            })
-        }
-        SelAct::ListShallow => {
-            let selected: Vec<&Item> = range
-                .iter()
-                .map(|&id| manager.find(RefId(id)).unwrap())
-                .collect();
+    let _lock = FileLock::acquire(path, manager::LOCK_TIMEOUT)
+        .map_err(|e| format!("failed to lock file: {}", e))?;
 
-            R::report(
-                "Shallow listing",
-                &mut selected.into_iter(),
-                &ReportInfo {
-                    config: report_cfg,
-                    indent: 0,
-                    filter: None,
-                    depth: ReportDepth::Shallow,
-                },
-                &mut io::stdout(),
-            )
-            .expect("Failed to show report");
+    let snapshot =
+        std::fs::read(undo).map_err(|e| format!("failed to read undo snapshot: {}", e))?;
 
-            Ok(ProgramResult {
-                should_save: false,
-                exit_status: 0,
-            })
-        }
-        SelAct::Delete(sargs) => {
-            /// Iterates recursively of a vector of items and their children, removing any items that are on the
-            /// selection. IDs on the selection that aren't found will be ignored. This is probably not a problem
-            /// because we already made sure the selection passed here has only valid IDs, so any missing IDs are from
-            /// children of items that were already deleted on this run.
-            fn thing(data: &mut Vec<Item>, selection: &HashSet<RefId>) {
-                data.retain(|item| {
-                    if let Some(id) = item.ref_id {
-                        if selection.contains(&RefId(id)) {
-                            return false;
-                        }
-                    }
+    // Make sure a broken snapshot doesn't replace a good file. The snapshot is a copy of the items file, so it's
+    // encrypted the same way.
+    let contents = crypt::read_with(undo, Encryption::detect(path))
+        .map_err(|e| format!("failed to read undo snapshot: {}", e))?;
+    data_serialize::import::<Item>(validate_parsed_string(&contents))
+        .map_err(|e| format!("failed to parse undo snapshot: {}", e))?;
 
-                    true
-                });
+    if dry_run {
+        info!("Dry run: the last change would be reverted");
 
-                for item in data.iter_mut() {
-                    thing(&mut item.children, selection);
-                }
-            }
+        return Ok(ProgramResult {
+            should_save: false,
+            exit_status: 0,
+        });
+    }
 
-            let proceed = |manager: &mut ItemManager| {
-                thing(
-                    &mut manager.data,
-                    &range.iter().map(|&id| RefId(id)).collect(),
-                );
+    std::fs::copy(path, undo).map_err(|e| format!("failed to save redo snapshot: {}", e))?;
+    write_atomic(path, &snapshot).map_err(|e| format!("failed to restore snapshot: {}", e))?;
 
-                // I don't think IDs need to be freed since the application closes soon after this, but that might be a
-                // thing to worry on the future.
+    info!("Reverted the last change (run undo again to redo it)");
 
-                Ok(ProgramResult {
-                    should_save: true,
-                    exit_status: 0,
-                })
-            };
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
 
-            if !sargs.force.unwrap_or(false) {
-                let selection: Vec<&Item> = range
-                    .iter()
-                    .map(|&id| manager.find(RefId(id)).unwrap())
-                    .collect();
+/// A function for the `merge` subcommand.
+///
+/// Type argument `R` is the type of report that should be shown.
+fn subcmd_merge<R: Report>(
+    manager: &mut ItemManager,
+    MergeArgs { first, second }: MergeArgs,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    if first == second {
+        return Err("an item can't be merged with itself".into());
+    }
 
-                R::report(
-                    "Items to be deleted",
-                    &mut selection.into_iter(),
-                    &ReportInfo {
-                        config: report_cfg,
-                        indent: 0,
-                        filter: None,
-                        depth: ReportDepth::Tree,
-                    },
-                    &mut io::stdout(),
-                )
-                .unwrap();
+    if let Some(RefId(missing)) = manager.first_invalid_ref_id([first, second].iter()) {
+        return Err(format!("could not find item with RefId = {}", missing));
+    }
 
-                if confirm_with_default(true) {
-                    proceed(manager)
-                } else {
-                    Ok(ProgramResult {
+    let (first_item, second_item) = (
+        manager.find(RefId(first)).unwrap(),
+        manager.find(RefId(second)).unwrap(),
+    );
+
+    if second_item.has_child(first_item) {
+        return Err(format!(
+            "#{} is inside of #{}; try swapping the arguments",
+            first, second
+        ));
+    }
+
+    R::report(
+        "Items to be merged",
+        &mut vec![first_item, second_item].into_iter(),
+        &ReportInfo {
+            config: report_cfg,
+            indent: 0,
+            filter: None,
+            depth: ReportDepth::Shallow,
+        },
+        &mut io::stdout(),
+    )
+    .unwrap();
+
+    eprintln!();
+    eprintln!("Which name should be kept? (the other item is removed, and its children, tags, links, attachments");
+    eprintln!("and work log are added to the kept one, along with the fields it doesn't have, like the due date)");
+    eprintln!("  1: {:?}", first_item.name);
+    eprintln!("  2: {:?}", second_item.name);
+
+    let keep_second_name = loop {
+        match utils::io::read_line("Choice [1/2, empty to cancel]: ")
+            .map_err(|e| format!("failed to read line: {}", e))?
+            .as_str()
+        {
+            "" => {
+                return Ok(ProgramResult {
+                    should_save: false,
+                    exit_status: 1,
+                })
+            }
+            "1" => break false,
+            "2" => break true,
+            _ => (),
+        }
+    };
+
+    manager
+        .merge(RefId(first), RefId(second), keep_second_name)
+        .map_err(|e| e.to_string())?;
+
+    info!("Merged #{} into #{}", second, first);
+
+    Ok(ProgramResult {
+        should_save: true,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `snoozed` subcommand.
+///
+/// Type argument `R` is the type of report that should be shown.
+fn subcmd_snoozed<R: Report>(
+    manager: &ItemManager,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    let mut snoozed: Vec<&Item> = Vec::new();
+    for root in &manager.data {
+        root.traverse(&mut |item| {
+            if !item.state.is_closed() && item.is_snoozed() {
+                snoozed.push(item);
+            }
+        });
+    }
+    snoozed.sort_by_key(|item| item.snoozed_until);
+
+    println!("Snoozed items | {} item(s)", snoozed.len());
+
+    R::display_all(
+        &mut snoozed.into_iter(),
+        &ReportInfo {
+            config: report_cfg,
+            indent: 0,
+            filter: None,
+            depth: ReportDepth::Shallow,
+        },
+        &mut io::stdout(),
+    )
+    .unwrap();
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `someday` subcommand.
+///
+/// Type argument `R` is the type of report that should be shown.
+fn subcmd_someday<R: Report>(
+    manager: &ItemManager,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    /// Collects the pending items on the someday/maybe list, skipping the ones inside of others (which are shown
+    /// along with them).
+    fn collect<'a>(items: &'a [Item], out: &mut Vec<&'a Item>) {
+        for item in items {
+            if item.state.is_closed() {
+                continue;
+            }
+
+            if item.someday {
+                out.push(item);
+            } else {
+                collect(&item.children, out);
+            }
+        }
+    }
+
+    let mut someday: Vec<&Item> = Vec::new();
+    collect(&manager.data, &mut someday);
+
+    R::report(
+        "Someday/maybe",
+        &mut someday.into_iter(),
+        &ReportInfo {
+            config: report_cfg,
+            indent: 0,
+            filter: Some(&|i: &Item| !i.state.is_closed()),
+            depth: ReportDepth::Tree,
+        },
+        &mut io::stdout(),
+    )
+    .unwrap();
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `stale` subcommand.
+///
+/// Type argument `R` is the type of report that should be shown.
+fn subcmd_stale<R: Report>(
+    manager: &ItemManager,
+    args: &StaleArgs,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    let cutoff = parse_moment(&args.since)?;
+
+    let mut stale: Vec<&Item> = Vec::new();
+    for root in &manager.data {
+        root.traverse_shown(&mut |item| {
+            let untouched = item.last_touched().map_or(true, |touched| touched < cutoff);
+
+            if !item.state.is_closed() && item.state != ItemState::Note && untouched {
+                stale.push(item);
+            }
+        });
+    }
+    stale.sort_by_key(|item| item.last_touched());
+
+    println!(
+        "Stale items | {} item(s) untouched since {}",
+        stale.len(),
+        cutoff.with_timezone(&Local).format("%Y-%m-%d")
+    );
+
+    R::display_all(
+        &mut stale.into_iter(),
+        &ReportInfo {
+            config: report_cfg,
+            indent: 0,
+            filter: None,
+            depth: ReportDepth::Shallow,
+        },
+        &mut io::stdout(),
+    )
+    .unwrap();
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `plan` subcommand.
+fn subcmd_plan(
+    manager: &ItemManager,
+    args: PlanArgs,
+    plan_path: &Path,
+    dry_run: bool,
+) -> Result<ProgramResult, String> {
+    let mut plan = match Plan::load(plan_path)? {
+        Some(plan) if plan.is_current() && args.add.unwrap_or(false) => plan,
+        _ => Plan::today(),
+    };
+
+    let mut candidates: Vec<InternalId> = Vec::new();
+    for root in &manager.data {
+        root.traverse(&mut |item| {
+            let pending = matches!(item.state, ItemState::Todo | ItemState::Waiting(_));
+
+            if pending
+                && item.ref_id.is_some()
+                && !item.is_hidden()
+                && !plan.items.contains(&item.internal_id)
+            {
+                candidates.push(InternalId(item.internal_id));
+            }
+        });
+    }
+
+    if candidates.is_empty() {
+        return Err("there are no pending items to plan".into());
+    }
+
+    let picked = pick_items(manager, &candidates)?;
+    plan.items.extend(picked.iter().map(|id| id.0));
+
+    if dry_run {
+        info!(
+            "Dry run: {} item(s) would be planned for today",
+            plan.items.len()
+        );
+    } else {
+        plan.save(plan_path)?;
+        info!("{} item(s) planned for today", plan.items.len());
+    }
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `today` subcommand.
+fn subcmd_today(
+    manager: &mut ItemManager,
+    args: TodayArgs,
+    plan_path: &Path,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    let plan = match Plan::load(plan_path)? {
+        Some(plan) if plan.is_current() => plan,
+        _ => return Err("there's no plan for today (run the plan subcommand to make one)".into()),
+    };
+
+    // items that were deleted since the plan was made are just left out
+    let planned: Vec<InternalId> = plan
+        .items
+        .iter()
+        .map(|&id| InternalId(id))
+        .filter(|&id| manager.find(id).is_some())
+        .collect();
+
+    let should_save = match args.check {
+        Some(positions) => {
+            let mut targets = Vec::new();
+
+            for position in positions.split(',').map(str::trim) {
+                let index = position
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&index| index >= 1 && index <= planned.len())
+                    .ok_or_else(|| format!("invalid position on the plan: {:?}", position))?;
+
+                targets.push(planned[index - 1]);
+            }
+
+            done_selection(manager, &targets, false)?.should_save
+        }
+        None => false,
+    };
+
+    let done = planned
+        .iter()
+        .filter(|&&id| manager.find(id).unwrap().state.is_closed())
+        .count();
+
+    println!("Today | {} of {} item(s) done", done, planned.len());
+
+    for (i, &id) in planned.iter().enumerate() {
+        let item = manager.find(id).unwrap();
+        let line = format!(
+            "{:>3}. [{}] {} ({})",
+            i + 1,
+            if item.state.is_closed() { 'x' } else { ' ' },
+            item.name,
+            id_label(item)
+        );
+
+        if item.state.is_closed() {
+            println!("{}", report_cfg.paint(report::style::DIM, &line));
+        } else {
+            println!("{}", line);
+        }
+    }
+
+    Ok(ProgramResult {
+        should_save,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `has` subcommand.
+fn subcmd_has(
+    manager: &ItemManager,
+    args: HasArgs,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    let state = args
+        .state
+        .as_deref()
+        .map(selection::parse_state)
+        .transpose()?;
+    let context_filter = ContextFilter::new(&args.context, &report_cfg.inherited_contexts);
+    let (overdue, starred) = (args.overdue.unwrap_or(false), args.starred.unwrap_or(false));
+    let today = Local::now().date_naive();
+
+    let mut count = 0;
+    for root in &manager.data {
+        root.traverse(&mut |item| {
+            let state_matches = match state {
+                Some(ref state) => item.state.name() == state,
+                None => matches!(item.state, ItemState::Todo | ItemState::Waiting(_)),
+            };
+            let is_overdue =
+                !item.state.is_closed() && matches!(item.due, Some(due) if due < today);
+
+            if state_matches
+                && context_filter.matches(item)
+                && (!overdue || is_overdue)
+                && (!starred || item.starred)
+            {
+                count += 1;
+            }
+        });
+    }
+
+    if !args.quiet.unwrap_or(false) {
+        println!("{}", count);
+    }
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: if count > 0 { 0 } else { 1 },
+    })
+}
+
+/// A function for the `starred` subcommand.
+///
+/// Type argument `R` is the type of report that should be shown.
+fn subcmd_starred<R: Report>(
+    manager: &ItemManager,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    let mut starred: Vec<&Item> = Vec::new();
+    for root in &manager.data {
+        root.traverse_shown(&mut |item| {
+            if !item.state.is_closed() && item.starred {
+                starred.push(item);
+            }
+        });
+    }
+
+    println!("Starred items | {} item(s)", starred.len());
+
+    R::display_all(
+        &mut starred.into_iter(),
+        &ReportInfo {
+            config: report_cfg,
+            indent: 0,
+            filter: None,
+            depth: ReportDepth::Shallow,
+        },
+        &mut io::stdout(),
+    )
+    .unwrap();
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `grep` subcommand.
+fn subcmd_grep(manager: &ItemManager, args: GrepArgs) -> Result<ProgramResult, String> {
+    struct Search<'a> {
+        regex: regex::Regex,
+        args: &'a GrepArgs,
+        /// The internal IDs of the last match and of its parent, so parents aren't printed again right after them.
+        last_printed: Option<u32>,
+        last_parent: Option<u32>,
+        matches: usize,
+    }
+
+    impl Search<'_> {
+        fn line(item: &Item, depth: usize, separator: char) -> String {
+            let id = match item.ref_id {
+                Some(id) => id.to_string(),
+                None => format!("i{}", item.internal_id),
+            };
+
+            format!("{}{}{}{}{}", id, separator, depth, separator, item.name)
+        }
+
+        fn walk(&mut self, items: &[Item], parent: Option<&Item>, depth: usize) {
+            for item in items {
+                if item.state.is_closed() && !self.args.all.unwrap_or(false) {
+                    continue;
+                }
+
+                let matched = self.regex.is_match(&item.name)
+                    || (self.args.description.unwrap_or(false)
+                        && self.regex.is_match(&item.description));
+
+                if matched {
+                    if let Some(parent) = parent.filter(|_| self.args.with_context.unwrap_or(false))
+                    {
+                        let id = Some(parent.internal_id);
+                        if self.last_printed != id && self.last_parent != id {
+                            println!("{}", Self::line(parent, depth - 1, '-'));
+                        }
+                    }
+
+                    println!("{}", Self::line(item, depth, ':'));
+                    self.last_printed = Some(item.internal_id);
+                    self.last_parent = parent.map(|parent| parent.internal_id);
+                    self.matches += 1;
+                }
+
+                self.walk(&item.children, Some(item), depth + 1);
+            }
+        }
+    }
+
+    let regex = regex::RegexBuilder::new(&args.pattern)
+        .case_insensitive(args.ignore_case.unwrap_or(false))
+        .build()
+        .map_err(|e| format!("invalid pattern: {}", e))?;
+
+    let mut search = Search {
+        regex,
+        args: &args,
+        last_printed: None,
+        last_parent: None,
+        matches: 0,
+    };
+    search.walk(&manager.data, None, 0);
+
+    // like grep, fail when nothing matches
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: if search.matches == 0 { 1 } else { 0 },
+    })
+}
+
+/// A function for the `profiles` subcommand. `active` is the name of the profile in use, if any.
+fn subcmd_profiles(active: Option<&str>) -> Result<ProgramResult, String> {
+    let dir = profiles_dir()?;
+
+    let mut profiles: Vec<String> = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| profile_path(name).map_or(false, |path| path.exists()))
+            .collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(format!("failed to read {}: {}", dir.display(), e)),
+    };
+    profiles.sort();
+
+    if profiles.is_empty() {
+        println!("No profiles were created yet (use --profile <name> to create one)");
+    } else {
+        println!("Profiles | {} in total", profiles.len());
+
+        for profile in &profiles {
+            let marker = if Some(profile.as_str()) == active {
+                '*'
+            } else {
+                ' '
+            };
+
+            println!("{} {}", marker, profile);
+        }
+    }
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `sync` subcommand.
+fn subcmd_sync(git: &GitSync, path: &Path, args: SyncArgs) -> Result<ProgramResult, String> {
+    // the pull might change the items file, which shouldn't happen while another instance is saving it
+    let _lock = FileLock::acquire(path, manager::LOCK_TIMEOUT)
+        .map_err(|e| format!("failed to lock file: {}", e))?;
+    let message = args.message.unwrap_or_else(|| String::from("Sync items"));
+
+    if git.commit(&message)? {
+        info!("Committed the local changes");
+    } else {
+        info!("There were no local changes to commit");
+    }
+
+    if args.local != Some(true) {
+        match git.remote()? {
+            Some(remote) => {
+                git.pull_and_push(&remote)?;
+                info!("Synced with the remote");
+            }
+            None => info!(
+                "The repository on {} has no remote, so nothing was pulled or pushed",
+                git.dir().display()
+            ),
+        }
+    }
+
+    // the file might have changed with the pull, so the loaded items are outdated
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `watch` subcommand. The items are loaded from `path` again on every change, so the manager of
+/// the program isn't used.
+///
+/// Type argument `R` is the type of report that should be shown.
+fn subcmd_watch<R: Report>(
+    args: WatchArgs,
+    path: &Path,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    use notify::{DebouncedEvent, RecursiveMode, Watcher};
+    use std::sync::mpsc;
+
+    const REPORTS: [&str; 9] = [
+        "list",
+        "next",
+        "flat-list",
+        "agenda",
+        "starred",
+        "snoozed",
+        "someday",
+        "blocked",
+        "active",
+    ];
+
+    let report = args.report.as_deref().unwrap_or("list");
+    if !REPORTS.contains(&report) {
+        return Err(format!(
+            "unknown report {:?} (expected one of: {})",
+            report,
+            REPORTS.join(", ")
+        ));
+    }
+
+    let filter = || args.filter.clone();
+
+    let render = || -> Result<(), String> {
+        let manager = ItemManager::new(load_items(path)?)
+            .map_err(|_| "the items file has repeated IDs".to_string())?;
+
+        match report {
+            "list" => subcmd_list::<R>(&manager, filter(), report_cfg),
+            "next" => subcmd_next::<R>(
+                &manager,
+                NextArgs {
+                    mode: None,
+                    count: None,
+                    filter: filter(),
+                },
+                report_cfg,
+            ),
+            "flat-list" => subcmd_flatlist(&manager, filter(), report_cfg),
+            "agenda" => subcmd_agenda::<R>(
+                &manager,
+                AgendaArgs {
+                    days: None,
+                    filter: filter(),
+                },
+                report_cfg,
+            ),
+            "starred" => subcmd_starred::<R>(&manager, report_cfg),
+            "snoozed" => subcmd_snoozed::<R>(&manager, report_cfg),
+            "someday" => subcmd_someday::<R>(&manager, report_cfg),
+            "blocked" => subcmd_blocked::<R>(&manager, report_cfg),
+            _ => subcmd_active::<R>(&manager, report_cfg),
+        }
+        .map(|_| ())
+    };
+
+    let redraw = || {
+        // clear the screen and go to its top
+        print!("\x1b[2J\x1b[H");
+        println!(
+            "Watching {} | updated at {} (Ctrl-C to stop)\n",
+            path.display(),
+            Local::now().format("%H:%M:%S")
+        );
+
+        if let Err(e) = render() {
+            println!("Error: {}", e);
+        }
+    };
+
+    // the directory is watched instead of the file, since it might be replaced instead of written to
+    let directory = match path.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::watcher(sender, std::time::Duration::from_millis(200))
+        .map_err(|e| format!("failed to start watching files: {}", e))?;
+    watcher
+        .watch(directory, RecursiveMode::NonRecursive)
+        .map_err(|e| format!("failed to watch {}: {}", directory.display(), e))?;
+
+    redraw();
+
+    loop {
+        let changed = match receiver.recv() {
+            Ok(DebouncedEvent::Create(changed))
+            | Ok(DebouncedEvent::Write(changed))
+            | Ok(DebouncedEvent::Rename(_, changed)) => changed,
+            Ok(DebouncedEvent::Error(e, _)) => return Err(format!("failed to watch files: {}", e)),
+            Ok(_) => continue,
+            Err(_) => return Err("stopped receiving file changes".into()),
+        };
+
+        if changed.file_name() == path.file_name() {
+            redraw();
+        }
+    }
+}
+
+/// A function for the `blocked` subcommand.
+///
+/// Type argument `R` is the type of report that should be shown.
+fn subcmd_blocked<R: Report>(
+    manager: &ItemManager,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    let mut blocked: Vec<&Item> = Vec::new();
+    for root in &manager.data {
+        root.traverse(&mut |item| {
+            if !item.state.is_closed() && manager.is_blocked(item) {
+                blocked.push(item);
+            }
+        });
+    }
+
+    println!("Blocked items | {} item(s)", blocked.len());
+
+    let info = ReportInfo {
+        config: report_cfg,
+        indent: 0,
+        filter: None,
+        depth: ReportDepth::Shallow,
+    };
+
+    for item in blocked {
+        R::display(item, &info, &mut io::stdout()).unwrap();
+
+        for &id in &item.blocked_by {
+            if let Some(blocker) = manager.find(InternalId(id)) {
+                if !blocker.state.is_closed() {
+                    println!(
+                        "{}  <- {} {}",
+                        report_cfg.get_indent_spaces(1),
+                        match blocker.ref_id {
+                            Some(id) => format!("#{:>02}", id),
+                            None => format!("i{:>02}", blocker.internal_id),
+                        },
+                        blocker.name
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `active` subcommand.
+///
+/// Type argument `R` is the type of report that should be shown.
+fn subcmd_active<R: Report>(
+    manager: &ItemManager,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    let mut active: Vec<(&Item, DateTime<Utc>)> = Vec::new();
+    for root in &manager.data {
+        root.traverse(&mut |item| {
+            if let Some(since) = item.working_since() {
+                active.push((item, since));
+            }
+        });
+    }
+
+    if active.is_empty() {
+        println!("No items are being worked on");
+    }
+
+    let info = ReportInfo {
+        config: report_cfg,
+        indent: 0,
+        filter: None,
+        depth: ReportDepth::Shallow,
+    };
+
+    for (item, since) in active {
+        R::display(item, &info, &mut io::stdout()).unwrap();
+        println!(
+            "{}  :: running for {} (since {})",
+            report_cfg.get_indent_spaces(0),
+            utils::time::format_duration(Utc::now() - since),
+            since.with_timezone(&Local).format("%H:%M")
+        );
+    }
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `timesheet` subcommand.
+fn subcmd_timesheet(
+    manager: &ItemManager,
+    args: TimesheetArgs,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    let since = if args.week.unwrap_or(false) {
+        let today = Local::now().date_naive();
+        let monday = today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64);
+
+        Some(
+            Local
+                .from_local_datetime(&monday.and_hms_opt(0, 0, 0).unwrap())
+                .unwrap()
+                .with_timezone(&Utc),
+        )
+    } else if let Some(ref duration) = args.since {
+        Some(
+            Utc::now()
+                - utils::time::parse_duration(duration)
+                    .map_err(|e| format!("failed to parse --since: {}", e))?,
+        )
+    } else {
+        None
+    };
+
+    let mut per_item: Vec<(&Item, chrono::Duration)> = Vec::new();
+    let mut per_context: BTreeMap<&str, chrono::Duration> = BTreeMap::new();
+    for root in &manager.data {
+        root.traverse(&mut |item| {
+            let spent = item.time_spent(since);
+
+            if spent > chrono::Duration::zero() {
+                per_item.push((item, spent));
+
+                let total = per_context
+                    .entry(item.context().unwrap_or(""))
+                    .or_insert_with(chrono::Duration::zero);
+                *total = *total + spent;
+            }
+        });
+    }
+    per_item.sort_by_key(|&(_, spent)| std::cmp::Reverse(spent));
+
+    let total = per_item
+        .iter()
+        .fold(chrono::Duration::zero(), |total, &(_, spent)| total + spent);
+
+    println!(
+        "Timesheet{} | {} total",
+        match since {
+            Some(since) => format!(
+                " since {}",
+                since.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+            ),
+            None => String::new(),
+        },
+        utils::time::format_duration(total)
+    );
+
+    let indent = report_cfg.get_indent_spaces(1);
+
+    println!("Per item:");
+    for (item, spent) in per_item {
+        println!(
+            "{}{:>8}  {} {}",
+            indent,
+            utils::time::format_duration(spent),
+            match item.ref_id {
+                Some(id) => format!("#{:>02}", id),
+                None => format!("i{:>02}", item.internal_id),
+            },
+            item.name
+        );
+    }
+
+    println!("Per context:");
+    for (context, spent) in per_context {
+        println!(
+            "{}{:>8}  {}",
+            indent,
+            utils::time::format_duration(spent),
+            if context.is_empty() {
+                "(no context)".to_string()
+            } else {
+                format!("@{}", context)
+            }
+        );
+    }
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `export` subcommand.
+fn subcmd_export(
+    manager: &ItemManager,
+    args: ExportArgs,
+    path: &Path,
+) -> Result<ProgramResult, String> {
+    let items: Vec<&Item> = match args.range {
+        Some(ref range) => parse_selection(manager, range, IdKind::Ref, None, false)?
+            .into_iter()
+            .map(|id| manager.find(id).unwrap())
+            .collect(),
+        None => manager.data.iter().collect(),
+    };
+
+    let exported = match args.format.as_str() {
+        "ics" | "ical" | "icalendar" => {
+            let namespace = path
+                .file_stem()
+                .map_or("itmn".into(), |n| n.to_string_lossy());
+
+            export::to_ics(&items, &namespace)
+        }
+        "markdown" | "md" => export::to_markdown(&items),
+        "org" => export::to_org(&items),
+        "todotxt" | "todo.txt" => export::to_todotxt(&items),
+        "dot" | "graphviz" => export::to_dot(&items),
+        "mermaid" => export::to_mermaid(&items),
+        other => return Err(format!("unknown export format: {:?}", other)),
+    };
+
+    match args.output {
+        Some(output) => std::fs::write(&output, exported)
+            .map_err(|e| format!("failed to write to {:?}: {}", output, e))?,
+        None => print!("{}", exported),
+    }
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `import-subtree` subcommand.
+fn subcmd_import_subtree(
+    manager: &mut ItemManager,
+    args: ImportSubtreeArgs,
+) -> Result<ProgramResult, String> {
+    if let Some(under) = args.under {
+        if manager.find(RefId(under)).is_none() {
+            return Err(format!("could not find item with RefId = {}", under));
+        }
+    }
+
+    // loading creates missing files, which would turn a mistyped path into an empty import
+    let file = Path::new(&args.file);
+    if !file.exists() {
+        return Err(format!("could not find {}", args.file));
+    }
+
+    let mut items = load_items(file)?;
+    manager.adopt(&mut items)?;
+
+    info!("Importing items:");
+    for item in &items {
+        match item.ref_id {
+            Some(id) => info!("* {} => RefID: {}", item.name, id),
+            None => info!("* {} => (no RefID)", item.name),
+        }
+    }
+
+    match args.under {
+        Some(under) => manager
+            .find_mut(RefId(under))
+            .unwrap()
+            .children
+            .extend(items),
+        None => manager.data.extend(items),
+    }
+
+    Ok(ProgramResult {
+        should_save: true,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `import` subcommand.
+fn subcmd_import(manager: &mut ItemManager, args: ImportArgs) -> Result<ProgramResult, String> {
+    let text = match args.file {
+        Some(ref file) => std::fs::read_to_string(file)
+            .map_err(|e| format!("failed to read {:?}: {}", file, e))?,
+        None => {
+            let mut text = String::new();
+            io::Read::read_to_string(&mut io::stdin(), &mut text)
+                .map_err(|e| format!("failed to read stdin: {}", e))?;
+            text
+        }
+    };
+
+    if let Some(under) = args.under {
+        if manager.find(RefId(under)).is_none() {
+            return Err(format!("could not find item with RefId = {}", under));
+        }
+    }
+
+    /// Returns the list the imported items go to: the children of `under`, or the root.
+    fn destination(manager: &mut ItemManager, under: Option<u32>) -> &mut Vec<Item> {
+        match under {
+            Some(under) => &mut manager.find_mut(RefId(under)).unwrap().children,
+            None => &mut manager.data,
+        }
+    }
+
+    /// Turns a task into an item with freshly allocated IDs.
+    fn create(manager: &mut ItemManager, task: import::ImportedTask) -> Result<Item, String> {
+        let mut item = manager.create_item(
+            &task.name,
+            &task.context,
+            task.state,
+            task.description,
+            Vec::new(), // children
+        )?;
+        item.priority = task.priority;
+        item.due = task.due;
+        item.scheduled = task.scheduled;
+        if task.done_at.is_some() {
+            item.done_at = task.done_at;
+        }
+
+        Ok(item)
+    }
+
+    // formats with trees of tasks are added as they are
+    if let "markdown" | "md" = args.format.as_str() {
+        fn build(
+            manager: &mut ItemManager,
+            node: import::ImportedNode,
+            count: &mut usize,
+        ) -> Result<Item, String> {
+            let mut item = create(manager, node.task)?;
+            *count += 1;
+
+            item.children = node
+                .children
+                .into_iter()
+                .map(|child| build(manager, child, count))
+                .collect::<Result<Vec<Item>, String>>()?;
+
+            Ok(item)
+        }
+
+        let mut count = 0;
+        for node in import::parse_markdown(&text)? {
+            let item = build(manager, node, &mut count)?;
+            destination(manager, args.under).push(item);
+        }
+
+        info!("Imported {} item(s)", count);
+
+        return Ok(ProgramResult {
+            should_save: true,
+            exit_status: 0,
+        });
+    }
+
+    let tasks = match args.format.as_str() {
+        "todotxt" | "todo.txt" => import::parse_todotxt(&text)?,
+        "taskwarrior" | "tw" => import::parse_taskwarrior(&text)?,
+        other => return Err(format!("unknown import format: {:?}", other)),
+    };
+    let count = tasks.len();
+
+    /// Returns the children of the item on `path` - a list of indexes, one for each level of the tree.
+    fn children_at<'a>(data: &'a mut Vec<Item>, path: &[usize]) -> &'a mut Vec<Item> {
+        let mut siblings = data;
+        for &index in path {
+            siblings = &mut siblings[index].children;
+        }
+
+        siblings
+    }
+
+    /// Makes project names comparable with the names of the existing items.
+    fn project_key(name: &str) -> String {
+        name.split(|c: char| c.is_whitespace() || c == '-')
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>()
+            .join("-")
+            .to_lowercase()
+    }
+
+    for mut task in tasks {
+        let project = std::mem::take(&mut task.project);
+        let item = create(manager, task)?;
+
+        // find (or create) each one of the parents, starting from the top-level one
+        let mut path: Vec<usize> = Vec::new();
+        for project in &project {
+            let key = project_key(project);
+            let existing = children_at(destination(manager, args.under), &path)
+                .iter()
+                .position(|sibling| project_key(&sibling.name) == key);
+
+            let index = match existing {
+                Some(index) => index,
+                None => {
+                    let parent = manager.create_item(
+                        project,
+                        "",
+                        ItemState::Todo,
+                        String::new(),
+                        Vec::new(),
+                    )?;
+
+                    let siblings = children_at(destination(manager, args.under), &path);
+                    siblings.push(parent);
+                    siblings.len() - 1
+                }
+            };
+
+            path.push(index);
+        }
+
+        children_at(destination(manager, args.under), &path).push(item);
+    }
+
+    info!("Imported {} item(s)", count);
+
+    Ok(ProgramResult {
+        should_save: true,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `contexts` subcommand.
+fn subcmd_contexts(
+    manager: &ItemManager,
+    args: ContextsArgs,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    #[derive(serde::Serialize)]
+    struct ContextUsage<'a> {
+        context: &'a str,
+        pending: usize,
+        done: usize,
+    }
+
+    let mut usage: BTreeMap<&str, ContextUsage> = BTreeMap::new();
+    for root in &manager.data {
+        root.traverse(&mut |item| {
+            if let Some(context) = item.context() {
+                let entry = usage.entry(context).or_insert(ContextUsage {
+                    context,
+                    pending: 0,
+                    done: 0,
+                });
+
+                match item.state {
+                    ItemState::Todo | ItemState::Waiting(_) => entry.pending += 1,
+                    ItemState::Done => entry.done += 1,
+                    _ => (),
+                }
+            }
+        });
+    }
+
+    let usage: Vec<ContextUsage> = usage.into_iter().map(|(_, u)| u).collect();
+
+    if args.json.unwrap_or(false) {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&usage)
+                .map_err(|e| format!("failed to serialize contexts: {}", e))?
+        );
+    } else if usage.is_empty() {
+        println!("No contexts are in use");
+    } else {
+        println!("Contexts | {} in use", usage.len());
+
+        let indent = report_cfg.get_indent_spaces(1);
+        for u in usage {
+            println!(
+                "{}@{} - {} pending, {} done",
+                indent, u.context, u.pending, u.done
+            );
+        }
+    }
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `projects` subcommand.
+fn subcmd_projects(
+    manager: &ItemManager,
+    args: ProjectsArgs,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    #[derive(serde::Serialize)]
+    struct ProjectProgress<'a> {
+        name: &'a str,
+        ref_id: Option<u32>,
+        internal_id: u32,
+        /// How many of the actionable descendants are done; cancelled items and notes aren't counted.
+        done: usize,
+        total: usize,
+    }
+
+    let progress: Vec<ProjectProgress> = manager
+        .projects()
+        .map(|project| {
+            let (done, total) = project.progress(true);
+
+            ProjectProgress {
+                name: &project.name,
+                ref_id: project.ref_id,
+                internal_id: project.internal_id,
+                done,
+                total,
+            }
+        })
+        .collect();
+
+    if args.json.unwrap_or(false) {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&progress)
+                .map_err(|e| format!("failed to serialize projects: {}", e))?
+        );
+    } else if progress.is_empty() {
+        println!("No items are marked as projects");
+    } else {
+        println!("Projects | {} in total", progress.len());
+
+        let indent = report_cfg.get_indent_spaces(1);
+        for p in progress {
+            let id = match p.ref_id {
+                Some(ref_id) => format!("#{:02}", ref_id),
+                None => format!("i{}", p.internal_id),
+            };
+            let percentage = match (p.done * 100).checked_div(p.total) {
+                Some(percentage) => format!(" ({}%)", percentage),
+                None => String::new(),
+            };
+
+            println!(
+                "{}{} {} - {}/{} done{}",
+                indent, p.name, id, p.done, p.total, percentage
+            );
+        }
+    }
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `stats` subcommand.
+fn subcmd_stats(
+    manager: &ItemManager,
+    args: StatsArgs,
+    archive: &Path,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    #[derive(serde::Serialize)]
+    struct ContextStats<'a> {
+        /// `None` for items without a context.
+        context: Option<&'a str>,
+        pending: usize,
+        done: usize,
+        total: usize,
+    }
+
+    #[derive(serde::Serialize)]
+    struct WeekStats {
+        week: String,
+        done: usize,
+    }
+
+    #[derive(serde::Serialize)]
+    struct Stats<'a> {
+        total: usize,
+        states: BTreeMap<&'static str, usize>,
+        contexts: Vec<ContextStats<'a>>,
+        /// How many items there are on each depth, starting from the root.
+        depths: Vec<usize>,
+        weeks: Vec<WeekStats>,
+    }
+
+    fn count_depths(items: &[Item], depth: usize, depths: &mut Vec<usize>) {
+        for item in items {
+            if depths.len() <= depth {
+                depths.push(0);
+            }
+
+            depths[depth] += 1;
+            count_depths(&item.children, depth + 1, depths);
+        }
+    }
+
+    let mut total = 0;
+    let mut states: BTreeMap<&'static str, usize> = BTreeMap::new();
+    let mut contexts: BTreeMap<Option<&str>, ContextStats> = BTreeMap::new();
+    for root in &manager.data {
+        root.traverse(&mut |item| {
+            total += 1;
+            *states.entry(item.state.name()).or_insert(0) += 1;
+
+            let context = item.context();
+            let entry = contexts.entry(context).or_insert(ContextStats {
+                context,
+                pending: 0,
+                done: 0,
+                total: 0,
+            });
+
+            entry.total += 1;
+            match item.state {
+                ItemState::Todo | ItemState::Waiting(_) => entry.pending += 1,
+                ItemState::Done => entry.done += 1,
+                _ => (),
+            }
+        });
+    }
+
+    let mut depths = Vec::new();
+    count_depths(&manager.data, 0, &mut depths);
+
+    // archived items count towards the completions as well
+    let archived = if archive.exists() {
+        load_items(archive)?
+    } else {
+        Vec::new()
+    };
+
+    let mut per_week: BTreeMap<(i32, u32), usize> = BTreeMap::new();
+    for root in manager.data.iter().chain(archived.iter()) {
+        root.traverse(&mut |item| {
+            if let (ItemState::Done, Some(done_at)) = (&item.state, item.done_at) {
+                let week = done_at.with_timezone(&Local).iso_week();
+                *per_week.entry((week.year(), week.week())).or_insert(0) += 1;
+            }
+        });
+    }
+
+    let today = Local::now().date_naive();
+    let weeks: Vec<WeekStats> = (0..args.weeks.unwrap_or(8))
+        .rev()
+        .map(|i| {
+            let week = (today - chrono::Duration::weeks(i as i64)).iso_week();
+
+            WeekStats {
+                week: format!("{}-W{:02}", week.year(), week.week()),
+                done: per_week
+                    .get(&(week.year(), week.week()))
+                    .copied()
+                    .unwrap_or(0),
+            }
+        })
+        .collect();
+
+    let stats = Stats {
+        total,
+        states,
+        contexts: contexts.into_iter().map(|(_, c)| c).collect(),
+        depths,
+        weeks,
+    };
+
+    if args.json.unwrap_or(false) {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&stats)
+                .map_err(|e| format!("failed to serialize statistics: {}", e))?
+        );
+
+        return Ok(ProgramResult {
+            should_save: false,
+            exit_status: 0,
+        });
+    }
+
+    let indent = report_cfg.get_indent_spaces(1);
+
+    println!("Statistics | {} item(s)", stats.total);
+
+    println!("States:");
+    for (state, count) in &stats.states {
+        println!("{}{}: {}", indent, state, count);
+    }
+
+    println!("Contexts:");
+    for c in &stats.contexts {
+        println!(
+            "{}{} - {} pending, {} done, {} in total",
+            indent,
+            c.context.map_or("(none)".into(), |ctx| format!("@{}", ctx)),
+            c.pending,
+            c.done,
+            c.total
+        );
+    }
+
+    println!("Depths:");
+    for (depth, count) in stats.depths.iter().enumerate() {
+        println!("{}{}: {}", indent, depth, count);
+    }
+
+    println!("Done per week:");
+    let most = stats.weeks.iter().map(|w| w.done).max().unwrap_or(0).max(1);
+    for w in &stats.weeks {
+        // the bars are scaled so that the busiest week has 30 characters
+        println!(
+            "{}{}  {:>3} {}",
+            indent,
+            w.week,
+            w.done,
+            "#".repeat(w.done * 30 / most)
+        );
+    }
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `completions` subcommand.
+///
+/// The scripts for bash and fish also complete contexts and the reference IDs of `sel` with the ones in use, which are
+/// listed by the hidden `complete` subcommand.
+fn subcmd_completions(args: &CompletionsArgs) -> Result<(), String> {
+    use clap_generate::generators::{Bash, Elvish, Fish, PowerShell, Zsh};
+
+    const BASH_DYNAMIC: &str = r#"
+_itmn_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+
+    case "$prev" in
+        -c|--context|--contexts)
+            COMPREPLY=($(compgen -W "$(itmn complete contexts 2>/dev/null)" -- "$cur"))
+            return 0
+            ;;
+        s|sel|sri|sel-ref-id)
+            COMPREPLY=($(compgen -W "$(itmn complete ids 2>/dev/null | cut -f1)" -- "$cur"))
+            return 0
+            ;;
+    esac
+
+    _itmn "$@"
+}
+
+complete -F _itmn_dynamic -o bashdefault -o default itmn
+"#;
+
+    const FISH_DYNAMIC: &str = r#"
+complete -c itmn -s c -l context -xa '(itmn complete contexts 2>/dev/null)'
+complete -c itmn -n '__fish_seen_subcommand_from s sel sri sel-ref-id' -xa '(itmn complete ids 2>/dev/null)'
+"#;
+
+    let mut app = cli::Options::into_app();
+    let mut out = io::stdout();
+
+    match args.shell.as_str() {
+        "bash" => {
+            clap_generate::generate::<Bash, _>(&mut app, "itmn", &mut out);
+            print!("{}", BASH_DYNAMIC);
+        }
+        "zsh" => clap_generate::generate::<Zsh, _>(&mut app, "itmn", &mut out),
+        "fish" => {
+            clap_generate::generate::<Fish, _>(&mut app, "itmn", &mut out);
+            print!("{}", FISH_DYNAMIC);
+        }
+        "elvish" => clap_generate::generate::<Elvish, _>(&mut app, "itmn", &mut out),
+        "powershell" => clap_generate::generate::<PowerShell, _>(&mut app, "itmn", &mut out),
+        other => {
+            return Err(format!(
+                "unknown shell {:?} (expected bash, zsh, fish, elvish or powershell)",
+                other
+            ))
+        }
+    }
+
+    Ok(())
+}
+
+/// A function for the `gen-man` subcommand.
+fn subcmd_gen_man(args: &GenManArgs) -> Result<(), String> {
+    let dir = Path::new(&args.dir);
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("failed to create directory {:?}: {}", args.dir, e))?;
+
+    let app = cli::Options::into_app();
+    let pages = manpage::render_all(&app, "manage a tree of tasks and notes");
+
+    for page in &pages {
+        let path = dir.join(&page.file_name);
+        std::fs::write(&path, &page.contents)
+            .map_err(|e| format!("failed to write {:?}: {}", path, e))?;
+    }
+
+    info!("Wrote {} man page(s) to {}", pages.len(), dir.display());
+    Ok(())
+}
+
+/// A function for the hidden `complete` subcommand, which lists the contexts or the reference IDs (along with the
+/// names of their items, separated by a tab) in use, one per line.
+fn subcmd_complete(manager: &ItemManager, args: CompleteArgs) -> Result<ProgramResult, String> {
+    match args.kind.as_str() {
+        "contexts" => {
+            let mut contexts: Vec<&str> = Vec::new();
+            for root in &manager.data {
+                root.traverse(&mut |item| {
+                    if let Some(context) = item.context() {
+                        contexts.push(context);
+                    }
+                });
+            }
+            contexts.sort_unstable();
+            contexts.dedup();
+
+            for context in contexts {
+                println!("{}", context);
+            }
+        }
+        "ids" => {
+            for root in &manager.data {
+                root.traverse(&mut |item| {
+                    if let Some(id) = item.ref_id {
+                        println!("{}\t{}", id, item.name);
+                    }
+                });
+            }
+        }
+        other => {
+            return Err(format!(
+                "unknown kind {:?} (expected contexts or ids)",
+                other
+            ))
+        }
+    }
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `report` subcommand.
+fn subcmd_report(
+    manager: &ItemManager,
+    args: ReportArgs,
+    archive: &Path,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    match args.kind {
+        ReportKind::Burndown(args) => report_burndown(manager, args, archive, report_cfg),
+        ReportKind::NoContext => report_no_context(manager, report_cfg),
+    }
+}
+
+/// Shows the `no-context` report, with the pending items that don't have a context along with the path to them.
+fn report_no_context(
+    manager: &ItemManager,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    let mut items: Vec<&Item> = Vec::new();
+    for root in &manager.data {
+        root.traverse(&mut |item| {
+            let pending = !item.state.is_closed() && item.state != ItemState::Note;
+
+            if pending && item.context().is_none() {
+                items.push(item);
+            }
+        });
+    }
+
+    println!("Items without a context | {} item(s)", items.len());
+
+    for item in items {
+        let path = item_path(manager, InternalId(item.internal_id));
+
+        println!(
+            "{} {}",
+            report_cfg.paint(report::style::GRAY, &id_label(item)),
+            path
+        );
+    }
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// Shows the `burndown` report, with bars for how many items were created and done on each of the last weeks.
+fn report_burndown(
+    manager: &ItemManager,
+    args: BurndownArgs,
+    archive: &Path,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    #[derive(Default)]
+    struct Week {
+        created: usize,
+        done: usize,
+    }
+
+    // archived items count as well, since they were created and done like any other
+    let archived = if archive.exists() {
+        load_items(archive)?
+    } else {
+        Vec::new()
+    };
+
+    let key = |time: DateTime<Utc>| {
+        let week = time.with_timezone(&Local).iso_week();
+        (week.year(), week.week())
+    };
+
+    let mut per_week: BTreeMap<(i32, u32), Week> = BTreeMap::new();
+    let mut open = 0;
+    for root in manager.data.iter().chain(archived.iter()) {
+        root.traverse(&mut |item| {
+            if item.state == ItemState::Note {
+                return;
+            }
+
+            if let Some(created_at) = item.created_at {
+                per_week.entry(key(created_at)).or_default().created += 1;
+            }
+
+            if let (ItemState::Done, Some(done_at)) = (&item.state, item.done_at) {
+                per_week.entry(key(done_at)).or_default().done += 1;
+            }
+
+            if !item.state.is_closed() {
+                open += 1;
+            }
+        });
+    }
+
+    let today = Local::now().date_naive();
+    let weeks: Vec<(String, &Week)> = (0..args.weeks.unwrap_or(8).max(1))
+        .rev()
+        .map(|i| {
+            let week = (today - chrono::Duration::weeks(i as i64)).iso_week();
+
+            (
+                format!("{}-W{:02}", week.year(), week.week()),
+                per_week.get(&(week.year(), week.week())).unwrap_or(&Week {
+                    created: 0,
+                    done: 0,
+                }),
+            )
+        })
+        .collect();
+
+    let created: usize = weeks.iter().map(|(_, w)| w.created).sum();
+    let done: usize = weeks.iter().map(|(_, w)| w.done).sum();
+
+    println!("Burndown | last {} week(s)", weeks.len());
+
+    // the bars are scaled so that the busiest week has 30 characters
+    let indent = report_cfg.get_indent_spaces(1);
+    let most = weeks
+        .iter()
+        .map(|(_, w)| w.created.max(w.done))
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    for (name, w) in &weeks {
+        println!(
+            "{}{}  created {:>3} {}",
+            indent,
+            name,
+            w.created,
+            report_cfg.paint(report::style::YELLOW, &"+".repeat(w.created * 30 / most))
+        );
+        println!(
+            "{}{}  done    {:>3} {}",
+            indent,
+            " ".repeat(name.len()),
+            w.done,
+            report_cfg.paint(report::style::GREEN, &"-".repeat(w.done * 30 / most))
+        );
+    }
+
+    // cancelled items aren't taken into account, so this is only an estimate
+    let open_before = (open + done).saturating_sub(created);
+    println!(
+        "{} created, {} done - the backlog {} ({} open item(s) before, {} now)",
+        created,
+        done,
+        match open.cmp(&open_before) {
+            std::cmp::Ordering::Less => "shrank",
+            std::cmp::Ordering::Equal => "kept its size",
+            std::cmp::Ordering::Greater => "grew",
+        },
+        open_before,
+        open
+    );
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `review` subcommand.
+///
+/// Type argument `R` is the type of report that should be shown.
+fn subcmd_review<R: Report>(
+    manager: &mut ItemManager,
+    args: ReviewArgs,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    const ACTIONS: &str = "[k]eep (default), [d]one, de[f]er, [x] delete, [a]dd child, [q]uit: ";
+
+    // internal IDs are used since items might be deleted along the way
+    let now = Utc::now();
+    let cutoff = match args.stale {
+        Some(ref stale) => Some(
+            now - utils::time::parse_duration(stale)
+                .map_err(|e| format!("failed to parse --stale: {}", e))?,
+        ),
+        None => None,
+    };
+
+    let mut queue: Vec<InternalId> = Vec::new();
+    for root in &manager.data {
+        root.traverse(&mut |item| {
+            let pending = !item.state.is_closed() && item.state != ItemState::Note;
+
+            // items with a review interval of their own only come up when it's over
+            let wanted = if item.review_every.is_some() {
+                pending && item.needs_review(now)
+            } else {
+                match cutoff {
+                    Some(cutoff) => {
+                        pending && item.last_touched().map_or(true, |time| time < cutoff)
+                    }
+                    // the someday/maybe list is kept out of the reports, so it's only looked at here
+                    None => {
+                        (item.internal_id == root.internal_id || item.someday)
+                            && !item.state.is_closed()
+                    }
+                }
+            };
+
+            if wanted {
+                queue.push(InternalId(item.internal_id));
+            }
+        });
+    }
+
+    if queue.is_empty() {
+        println!("Nothing to review");
+
+        return Ok(ProgramResult {
+            should_save: false,
+            exit_status: 0,
+        });
+    }
+
+    let info = ReportInfo {
+        config: report_cfg,
+        indent: 0,
+        filter: None,
+        depth: ReportDepth::Brief,
+    };
+
+    let read = |prompt: &str| {
+        utils::io::read_line(prompt).map_err(|e| format!("failed to read input: {}", e))
+    };
+
+    let mut modified = false;
+
+    for (i, &id) in queue.iter().enumerate() {
+        // the item might have been deleted along with a parent
+        let item = match manager.find(id) {
+            Some(item) => item,
+            None => continue,
+        };
+
+        println!();
+        println!("Review | {} of {}", i + 1, queue.len());
+        R::display(item, &info, &mut io::stdout()).unwrap();
+
+        loop {
+            match read(ACTIONS)?.to_lowercase().as_str() {
+                "" | "k" | "keep" => break,
+                "d" | "done" => {
+                    manager
+                        .change_item_state(id, |previous| match previous {
+                            ItemState::Todo | ItemState::Waiting(_) => ItemState::Done,
+                            other => other,
+                        })
+                        .unwrap();
+
+                    break;
+                }
+                "f" | "defer" => {
+                    let until = read(
+                        "Defer until (a date like 2020-12-31, tomorrow, or a duration like 3d): ",
+                    )?;
+
+                    let date = match cli::parse_date(&until) {
+                        Ok(date) => date,
+                        Err(_) => utils::time::parse_duration(&until)
+                            .ok()
+                            .map(|duration| (Local::now() + duration).date_naive()),
+                    };
+
+                    match date {
+                        Some(date) => {
+                            manager.interact_mut(id, |item| item.scheduled = Some(date));
+                            break;
+                        }
+                        None => eprintln!("Could not understand {:?}", until),
+                    }
+                }
+                "x" | "delete" => {
+                    if confirm_with_default(true) {
+                        let removed = {
+                            let (siblings, index) =
+                                manager.locate_mut(|i| i.internal_id == id.0).unwrap();
+                            siblings.remove(index)
+                        };
+
+                        manager.release_ref_ids(std::slice::from_ref(&removed));
+                        break;
+                    }
+                }
+                "a" | "add" => {
+                    let name = read("Name of the child: ")?;
+
+                    if !name.is_empty() {
+                        let RefId(ref_id) = manager.add_child(
+                            id,
+                            &name,
+                            "",
+                            ItemState::Todo,
+                            String::new(),
+                            Vec::new(),
+                        )?;
+                        info!("* RefID: {}", ref_id);
+
+                        modified = true;
+                    }
+                }
+                "q" | "quit" => {
+                    return Ok(ProgramResult {
+                        should_save: modified,
+                        exit_status: 0,
+                    })
+                }
+                other => eprintln!("Unknown action {:?}", other),
+            }
+        }
+
+        // deleted items aren't found anymore
+        manager.interact_mut(id, |item| item.reviewed_at = Some(Utc::now()));
+        modified = true;
+    }
+
+    println!();
+    println!("Review finished");
+
+    Ok(ProgramResult {
+        should_save: modified,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `renumber` subcommand.
+fn subcmd_renumber(manager: &mut ItemManager) -> Result<ProgramResult, String> {
+    // the changes are only kept if they're confirmed, since the file isn't saved otherwise
+    let changes = manager.renumber_ref_ids();
+
+    if changes.is_empty() {
+        println!("The reference IDs are already in order");
+
+        return Ok(ProgramResult {
+            should_save: false,
+            exit_status: 0,
+        });
+    }
+
+    println!("Reference IDs to be changed | {} item(s)", changes.len());
+    for (old, new) in &changes {
+        println!(
+            "  #{:02} -> #{:02} {}",
+            old,
+            new,
+            manager.find(RefId(*new)).unwrap().name
+        );
+    }
+
+    if confirm_with_default(true) {
+        Ok(ProgramResult {
+            should_save: true,
+            exit_status: 0,
+        })
+    } else {
+        Ok(ProgramResult {
+            should_save: false,
+            exit_status: 1,
+        })
+    }
+}
+
+/// A function for the `template` subcommand.
+fn subcmd_template(
+    manager: &mut ItemManager,
+    args: TemplateArgs,
+    path: &Path,
+    dry_run: bool,
+) -> Result<ProgramResult, String> {
+    let contents = crypt::read(path).map_err(|why| format!("failed to load templates: {}", why))?;
+    let mut templates: Vec<Template> = data_serialize::import(validate_parsed_string(&contents))
+        .map_err(|why| format!("failed to parse templates: {}", why))?;
+
+    let save = |templates: &[Template]| {
+        if dry_run {
+            info!(
+                "Dry run: {} template(s) would be saved to {}",
+                templates.len(),
+                path.display()
+            );
+            return Ok(());
+        }
+
+        data_serialize::export(templates, true)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| crypt::write(path, &contents))
+            .map_err(|e| format!("failed to save templates: {}", e))
+    };
+
+    match args.action {
+        TemplateAction::Save(TemplateSaveArgs { name, range }) => {
+            let items: Vec<&Item> = parse_selection(manager, &range, IdKind::Ref, None, false)?
+                .into_iter()
+                .map(|id| manager.find(id).unwrap())
+                .collect();
+
+            // An item inside of another selected item would be saved twice.
+            for item in &items {
+                for item2 in &items {
+                    if item.has_child(item2) {
+                        return Err(format!(
+                            "{:?} is a child of {:?}, but both are on the selection",
+                            item2.name, item.name
+                        ));
+                    }
+                }
+            }
+
+            let template = Template::new(&name, &items);
+
+            match templates.iter().position(|t| t.name == name) {
+                Some(index) => {
+                    eprintln!(
+                        "The template {:?} already exists and will be replaced.",
+                        name
+                    );
+
+                    if !confirm_with_default(true) {
+                        return Ok(ProgramResult {
+                            should_save: false,
+                            exit_status: 1,
+                        });
+                    }
+
+                    templates[index] = template;
+                }
+                None => templates.push(template),
+            }
+
+            save(&templates)?;
+            if !dry_run {
+                info!("Saved the template {:?}", name);
+            }
+        }
+        TemplateAction::Apply(TemplateApplyArgs { name, under }) => {
+            let template = templates
+                .iter()
+                .find(|t| t.name == name)
+                .ok_or_else(|| format!("there's no template named {:?}", name))?;
+
+            if let Some(under) = under {
+                if manager.find(RefId(under)).is_none() {
+                    return Err(format!("could not find item with RefId = {}", under));
+                }
+            }
+
+            info!("Adding items:");
+
+            for item in &template.items {
+                let mut copy = item.clone();
+                manager.refresh_ids(&mut copy)?;
+
+                if let Some(ref_id) = copy.ref_id {
+                    info!("* RefID: {}", ref_id);
+                }
+
+                match under {
+                    Some(under) => manager.find_mut(RefId(under)).unwrap().children.push(copy),
+                    None => manager.data.push(copy),
+                }
+            }
+
+            return Ok(ProgramResult {
+                should_save: true,
+                exit_status: 0,
+            });
+        }
+        TemplateAction::List => {
+            if templates.is_empty() {
+                println!("No templates were saved");
+            } else {
+                println!("Templates | {} saved", templates.len());
+
+                for template in &templates {
+                    println!("  {} ({} item(s))", template.name, template.item_count());
+                }
+            }
+        }
+        TemplateAction::Delete(TemplateDeleteArgs { name }) => {
+            let index = templates
+                .iter()
+                .position(|t| t.name == name)
+                .ok_or_else(|| format!("there's no template named {:?}", name))?;
+
+            templates.remove(index);
+            save(&templates)?;
+            if !dry_run {
+                info!("Deleted the template {:?}", name);
+            }
+        }
+    }
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `tui` subcommand.
+fn subcmd_tui(manager: &mut ItemManager) -> Result<ProgramResult, String> {
+    if !atty::is(atty::Stream::Stdout) {
+        return Err("the interface needs a terminal".into());
+    }
+
+    let should_save = interactive::run(manager)?;
+
+    Ok(ProgramResult {
+        should_save,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `context` subcommand.
+fn subcmd_context(manager: &mut ItemManager, args: ContextArgs) -> Result<ProgramResult, String> {
+    match args.action {
+        ContextAction::Rename(ContextRenameArgs { old, new }) => {
+            let old_normalized = match Item::validate_context(&old) {
+                Some(context) => context,
+                None => return Err("the context to be renamed can't be empty".into()),
+            };
+
+            let mut affected = 0;
+            for root in &manager.data {
+                root.traverse(&mut |item| {
+                    if item.context().and_then(Item::validate_context).as_ref()
+                        == Some(&old_normalized)
+                    {
+                        affected += 1;
+                    }
+                });
+            }
+
+            if affected == 0 {
+                return Err(format!("no items have the context @{}", old_normalized));
+            }
+
+            match Item::validate_context(&new) {
+                Some(new_normalized) => eprintln!(
+                    "{} item(s) will have their context changed from @{} to @{}",
+                    affected, old_normalized, new_normalized
+                ),
+                None => eprintln!(
+                    "{} item(s) will have their context (@{}) removed",
+                    affected, old_normalized
+                ),
+            }
+
+            if !confirm_with_default(true) {
+                return Ok(ProgramResult {
+                    should_save: false,
+                    exit_status: 1,
+                });
+            }
+
+            let changed = manager.rename_context(&old, &new);
+            info!("Changed the context of {} item(s)", changed);
+
+            Ok(ProgramResult {
+                should_save: true,
+                exit_status: 0,
+            })
+        }
+    }
+}
+
+/// A function for the `remind` subcommand.
+fn subcmd_remind(
+    manager: &ItemManager,
+    args: RemindArgs,
+    path: &Path,
+) -> Result<ProgramResult, String> {
+    let within = match args.within {
+        Some(ref within) => utils::time::parse_duration(within)
+            .map_err(|e| format!("failed to parse --within: {}", e))?,
+        None => chrono::Duration::days(1),
+    };
+    let interval = match args.interval {
+        Some(ref interval) => utils::time::parse_duration(interval)
+            .map_err(|e| format!("failed to parse --interval: {}", e))?
+            .to_std()
+            .map_err(|_| "the interval should be positive".to_string())?,
+        None => std::time::Duration::from_secs(15 * 60),
+    };
+    let print_only = args.print.unwrap_or(false);
+
+    /// Sends a notification with the reminders, or prints them if that isn't possible (or if `print_only` is set).
+    fn remind(overdue: &[&Item], upcoming: &[&Item], print_only: bool) {
+        let summary = match (overdue.len(), upcoming.len()) {
+            (0, u) => format!("{} item(s) due soon", u),
+            (o, 0) => format!("{} overdue item(s)", o),
+            (o, u) => format!("{} overdue item(s), {} due soon", o, u),
+        };
+
+        let body = overdue
+            .iter()
+            .map(|item| format!("{} (overdue since {})", item.name, item.due.unwrap()))
+            .chain(
+                upcoming
+                    .iter()
+                    .map(|item| format!("{} (due {})", item.name, item.due.unwrap())),
+            )
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        if !print_only {
+            match std::process::Command::new("notify-send")
+                .args(&["--app-name=itmn", &summary, &body])
+                .status()
+            {
+                Ok(status) if status.success() => return,
+                Ok(_) => warn!("notify-send failed; printing the reminders instead"),
+                Err(why) => warn!(
+                    "failed to start notify-send ({}); printing the reminders instead",
+                    why
+                ),
+            }
+        }
+
+        println!("{}", summary);
+        for line in body.lines() {
+            println!(" * {}", line);
+        }
+    }
+
+    if !args.daemon.unwrap_or(false) {
+        let (overdue, upcoming) = due_items(&manager.data, within);
+
+        if overdue.is_empty() && upcoming.is_empty() {
+            println!("Nothing is overdue or due soon");
+        } else {
+            remind(&overdue, &upcoming, print_only);
+        }
+
+        return Ok(ProgramResult {
+            should_save: false,
+            exit_status: 0,
+        });
+    }
+
+    // On daemon mode, each item is only reminded of once a day, so the reminders don't pile up.
+    let mut reminded: HashSet<u32> = HashSet::new();
+    let mut reminded_on = Local::now().date_naive();
+
+    loop {
+        if Local::now().date_naive() != reminded_on {
+            reminded.clear();
+            reminded_on = Local::now().date_naive();
+        }
+
+        match load_items(path) {
+            Ok(data) => {
+                let (overdue, upcoming) = due_items(&data, within);
+                let overdue: Vec<&Item> = overdue
+                    .into_iter()
+                    .filter(|item| !reminded.contains(&item.internal_id))
+                    .collect();
+                let upcoming: Vec<&Item> = upcoming
+                    .into_iter()
+                    .filter(|item| !reminded.contains(&item.internal_id))
+                    .collect();
+
+                if !overdue.is_empty() || !upcoming.is_empty() {
+                    remind(&overdue, &upcoming, print_only);
+                    reminded.extend(overdue.iter().chain(&upcoming).map(|item| item.internal_id));
+                }
+            }
+            Err(why) => error!("{}", why),
+        }
+
+        std::thread::sleep(interval);
+    }
+}
+
+/// A function for the `agenda` subcommand.
+///
+/// Type argument `R` is the type of report that should be shown.
+fn subcmd_agenda<R: Report>(
+    manager: &ItemManager,
+    args: AgendaArgs,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    let days = args.days.unwrap_or(7).max(1) as i64;
+    let context_filter = ContextFilter::new(&args.filter.context, &report_cfg.inherited_contexts);
+    let date_filter = args.filter.date_filter()?;
+    let today = Local::now().date_naive();
+    let last_day = today + chrono::Duration::days(days - 1);
+
+    let mut dated: Vec<(chrono::NaiveDate, &Item)> = Vec::new();
+    for root in report_roots(manager, &args.filter)? {
+        root.traverse_shown(&mut |item| {
+            if item.state.is_closed() || !context_filter.matches(item) || !date_filter.matches(item)
+            {
+                return;
+            }
+
+            let date = match (item.due, item.scheduled) {
+                (Some(due), Some(scheduled)) => Some(due.min(scheduled)),
+                (due, scheduled) => due.or(scheduled),
+            };
+
+            if let Some(date) = date {
+                dated.push((date, item));
+            }
+        });
+    }
+    dated.sort_by_key(|&(date, _)| date);
+
+    println!("Agenda | {} to {}", today, last_day);
+
+    let info = ReportInfo {
+        config: report_cfg,
+        indent: 1,
+        filter: None,
+        depth: ReportDepth::Shallow,
+    };
+
+    let overdue: Vec<&Item> = dated
+        .iter()
+        .filter(|(_, item)| item.due.map_or(false, |due| due < today))
+        .map(|&(_, item)| item)
+        .collect();
+
+    if !overdue.is_empty() {
+        println!("Overdue:");
+        R::display_all(&mut overdue.into_iter(), &info, &mut io::stdout()).unwrap();
+    }
+
+    for offset in 0..days {
+        let day = today + chrono::Duration::days(offset);
+
+        println!(
+            "{}{}:",
+            day.format("%a %Y-%m-%d"),
+            if day == today { " (today)" } else { "" }
+        );
+
+        // Items scheduled to a past day that still aren't done (nor overdue) are carried over to today.
+        let items: Vec<&Item> = dated
+            .iter()
+            .filter(|(_, item)| {
+                item.due == Some(day)
+                    || item.scheduled == Some(day)
+                    || (day == today
+                        && item.scheduled.map_or(false, |scheduled| scheduled < today)
+                        && item.due.map_or(true, |due| due >= today))
+            })
+            .map(|&(_, item)| item)
+            .collect();
+
+        R::display_all(&mut items.into_iter(), &info, &mut io::stdout()).unwrap();
+    }
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `remaining` subcommand.
+fn subcmd_remaining(
+    manager: &ItemManager,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    let mut per_context: BTreeMap<&str, chrono::Duration> = BTreeMap::new();
+    let mut per_project: Vec<(&Item, chrono::Duration)> = Vec::new();
+    let mut total = chrono::Duration::zero();
+    let mut unestimated = 0;
+
+    for root in &manager.data {
+        let mut project_total = chrono::Duration::zero();
+
+        root.traverse(&mut |item| {
+            if item.state.is_closed() || item.state == ItemState::Note {
+                return;
+            }
+
+            match item.remaining_estimate() {
+                Some(remaining) => {
+                    let context_total = per_context
+                        .entry(item.context().unwrap_or(""))
+                        .or_insert_with(chrono::Duration::zero);
+                    *context_total = *context_total + remaining;
+
+                    project_total = project_total + remaining;
+                    total = total + remaining;
+                }
+                None => unestimated += 1,
+            }
+        });
+
+        if project_total > chrono::Duration::zero() {
+            per_project.push((root, project_total));
+        }
+    }
+
+    println!(
+        "Remaining work | {} estimated, {} pending item(s) without an estimate",
+        utils::time::format_duration(total),
+        unestimated
+    );
+
+    let indent = report_cfg.get_indent_spaces(1);
+
+    println!("Per context:");
+    for (context, remaining) in per_context {
+        println!(
+            "{}{:>8}  {}",
+            indent,
+            utils::time::format_duration(remaining),
+            if context.is_empty() {
+                "(no context)".to_string()
+            } else {
+                format!("@{}", context)
+            }
+        );
+    }
+
+    println!("Per project:");
+    for (root, remaining) in per_project {
+        println!(
+            "{}{:>8}  {} {}",
+            indent,
+            utils::time::format_duration(remaining),
+            match root.ref_id {
+                Some(id) => format!("#{:>02}", id),
+                None => format!("i{:>02}", root.internal_id),
+            },
+            root.name
+        );
+    }
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `next` subcommand.
+///
+/// Type argument `R` is the type of report that should be shown.
+fn subcmd_next<R: Report>(
+    manager: &ItemManager,
+    args: NextArgs,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    let mode = args.mode()?;
+    let context_filter = ContextFilter::new(&args.filter.context, &report_cfg.inherited_contexts);
+    let date_filter = args.filter.date_filter()?;
+
+    let actionable = |i: &Item| {
+        !i.state.is_closed()
+            && !matches!(i.state, ItemState::Waiting(_))
+            && !i.is_hidden()
+            && !manager.is_blocked(i)
+            && context_filter.allows(i)
+            && date_filter.allows(i)
+    };
+
+    let roots = report_roots(manager, &args.filter)?;
+
+    report_overdue::<R>(
+        &roots,
+        &|i| context_filter.matches(i) && date_filter.matches(i),
+        report_cfg,
+    );
+
+    if mode == NextMode::FirstChild {
+        R::report(
+            "Next",
+            &mut roots.into_iter(),
+            &ReportInfo {
+                config: report_cfg,
+                indent: 0,
+                filter: Some(&actionable),
+                depth: ReportDepth::Brief,
+            },
+            &mut io::stdout(),
+        )
+        .unwrap();
+
+        return Ok(ProgramResult {
+            should_save: false,
+            exit_status: 0,
+        });
+    }
+
+    /// Collects the todo items under `items` (including them) that don't have any pending children, skipping the
+    /// children of closed and hidden items.
+    fn leaves<'a>(items: &'a [Item], out: &mut Vec<&'a Item>) {
+        for item in items {
+            if item.state.is_closed() || item.is_hidden() {
+                continue;
+            }
+
+            let has_pending_children = item
+                .children
+                .iter()
+                .any(|c| matches!(c.state, ItemState::Todo | ItemState::Waiting(_)));
+
+            if item.state == ItemState::Todo && !has_pending_children {
+                out.push(item);
+            }
+
+            leaves(&item.children, out);
+        }
+    }
+
+    let mut items: Vec<&Item> = Vec::new();
+    for root in roots {
+        leaves(std::slice::from_ref(root), &mut items);
+    }
+    items.retain(|i| actionable(i));
+
+    let label = match mode {
+        NextMode::PerContext(count) => {
+            // items without a context go first, then the contexts in alphabetical order
+            let mut by_context: BTreeMap<Option<&str>, Vec<&Item>> = BTreeMap::new();
+            for item in items {
+                let group = by_context.entry(item.context()).or_default();
+                if group.len() < count {
+                    group.push(item);
+                }
+            }
+
+            items = by_context.values().flatten().copied().collect();
+            "Next (per context)"
+        }
+        _ => "Next (leaves)",
+    };
+
+    R::report(
+        label,
+        &mut items.into_iter(),
+        &ReportInfo {
+            config: report_cfg,
+            indent: 0,
+            filter: Some(&actionable),
+            depth: ReportDepth::Shallow,
+        },
+        &mut io::stdout(),
+    )
+    .unwrap();
+
+    Ok(ProgramResult {
+        should_save: false,
+        exit_status: 0,
+    })
+}
+
+/// A function for the `sel-ref-id` and `sel-internal-id` subcommands, with `ids` telling which kind of ID the range
+/// uses.
+///
+/// Type argument `R` is the type of report that should be shown.
+fn subcmd_selection<R: Report>(
+    manager: &mut ItemManager,
+    args: SelectionDetails,
+    ids: IdKind,
+    dry_run: bool,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    type SelAct = SelectionAction;
+
+    // without a range, the items on --state are selected directly, while the other items are picked
+    let (range, pick) = match (args.range, args.state.as_deref()) {
+        (Some(range), _) => (range, args.pick.unwrap_or(false)),
+        (None, Some(state)) => (format!("state:{}", state), args.pick.unwrap_or(false)),
+        (None, None) => ("all".into(), true),
+    };
+
+    let range = parse_selection(
+        manager,
+        &range,
+        ids,
+        args.state.as_deref(),
+        args.recursive == Some(true),
+    )?;
+    let range = if pick {
+        pick_items(manager, &range)?
+    } else {
+        range
+    };
+
+    match args.action.unwrap_or(SelAct::ListBrief) {
+        SelAct::Modify(sargs) => {
+            sargs.validate()?;
+
+            if sargs.project == Some(true) {
+                if let Some(&id) = range
+                    .iter()
+                    .find(|id| !manager.data.iter().any(|root| root.internal_id == id.0))
+                {
+                    return Err(format!(
+                        "only root items can be projects, but {} isn't one",
+                        id_label(manager.find(id).unwrap())
+                    ));
+                }
+            }
+
+            let modifications = sargs.modifications_description();
+
+            if modifications.is_empty() {
+                info!("No changes were specified");
+
+                // Exit sucessfully though, I don't think this is necessarily a problem.
+                return Ok(ProgramResult {
+                    should_save: false,
+                    exit_status: 0,
+                });
+            }
+
+            // only the items that would actually end up different are shown and modified
+            let changing: Vec<(&Item, Item)> = range
+                .iter()
+                .map(|&id| {
+                    let item = manager.find(id).unwrap();
+                    let mut modified = item.clone();
+                    sargs.mod_item_by_ref(&mut modified);
+
+                    (item, modified)
+                })
+                .filter(|(item, modified)| modified != *item || sargs.add_child.is_some())
+                .collect();
+            let unchanged = range.len() - changing.len();
+
+            if changing.is_empty() {
+                info!("The selected item(s) already have these changes");
+
+                return Ok(ProgramResult {
+                    should_save: false,
+                    exit_status: 0,
+                });
+            }
+
+            let changing_ids: Vec<InternalId> = changing
+                .iter()
+                .map(|(item, _)| InternalId(item.internal_id))
+                .collect();
+
+            println!("Items to be modified | {} item(s)", changing.len());
+
+            for (item, modified) in &changing {
+                println!(
+                    "{} {} ({})",
+                    report_cfg.paint(report::style::YELLOW, "~"),
+                    modified.name,
+                    id_label(item)
+                );
+                print_item_diff(item, modified, report_cfg);
+
+                if let Some(ref child) = sargs.add_child {
+                    println!(
+                        "    {}",
+                        report_cfg.paint(report::style::GREEN, &format!("+ child: {:?}", child))
+                    );
+                }
+            }
+            println!();
+
+            if unchanged != 0 {
+                info!(
+                    "{} other selected item(s) already have these changes and will be left as they are",
+                    unchanged
+                );
+            }
+
+            if sargs.dry_run == Some(true) {
+                info!("Dry run: no changes were made");
+
+                return Ok(ProgramResult {
+                    should_save: false,
+                    exit_status: 0,
+                });
+            }
+
+            if sargs.yes == Some(true) || confirm_with_default(true) {
+                let new_state = sargs.new_state()?;
+
+                for &id in &changing_ids {
+                    if let Some(ref state) = new_state {
+                        manager.change_item_state(id, |_| state.clone())?;
+                    }
+
+                    manager.interact_mut(id, |item| sargs.mod_item_by_ref(item));
+
+                    if let Some(ref child) = sargs.add_child {
+                        manager.add_child(
+                            id,
+                            child,
+                            "",
+                            ItemState::Todo,
+                            String::new(),
+                            Vec::new(),
+                        )?;
+                    }
+                }
+
+                info!("{} item(s) modified", changing_ids.len());
+
+                Ok(ProgramResult {
+                    should_save: true,
+                    exit_status: 0,
+                })
+            } else {
+                Ok(ProgramResult {
+                    should_save: false,
+                    exit_status: 1,
+                })
+            }
+        }
+        SelAct::Add(SelAddArgs {
+            details: sargs,
+            yes,
+            strict: _,
+        }) => {
+            let (name, context, extras) = sargs.parse_name()?;
+
+            let mut proceed = || {
+                info!("Adding items:");
+
+                for &id in &range {
+                    let RefId(ref_id) = manager.add_child(
+                        id,
+                        &name,
+                        &context,
+                        match sargs.note {
+                            Some(false) | None => ItemState::Todo,
+                            Some(true) => ItemState::Note,
+                        },
+                        sargs.description.clone().unwrap_or_else(String::new),
+                        Vec::new(), // children
+                    )?;
+                    manager.interact_mut(RefId(ref_id), |item| extras.apply(item));
+
+                    info!("* RefID: {}", ref_id);
+                }
+
+                info!("{} child(ren) added", range.len());
+
+                Ok(ProgramResult {
+                    should_save: true,
+                    exit_status: 0,
+                })
+            };
+
+            if range.len() > 1 && yes != Some(true) {
+                eprintln!(
+                    "{} items were selected. All of them will receive new identical children copies.",
+                    range.len()
+                );
+
+                if confirm_with_default(false) {
+                    proceed()
+                } else {
+                    Ok(ProgramResult {
+                        should_save: false,
+                        exit_status: 1,
+                    })
+                }
+            } else {
+                proceed()
+            }
+        }
+        SelAct::PrintDescription => {
+            if range.len() != 1 {
+                return Err("The selection should have exactly one item.".into());
+            }
+
+            manager
+                .interact(range[0], |i| {
+                    // Check which char is the last one
+                    match i.description.chars().rev().nth(0).unwrap_or('\n') {
+                        '\n' => eprint!("{}", i.description),
+                        _ => eprintln!("{}", i.description),
+                    }
+
+                    Ok(ProgramResult {
+                        should_save: false,
+                        exit_status: 0,
+                    })
+                })
+                .unwrap()
+        }
+        SelAct::Path => {
+            for &id in &range {
+                println!(
+                    "{} ({})",
+                    item_path(manager, id),
+                    id_label(manager.find(id).unwrap())
+                );
+            }
+
+            Ok(ProgramResult {
+                should_save: false,
+                exit_status: 0,
+            })
+        }
+        SelAct::Show => {
+            for (i, &id) in range.iter().enumerate() {
+                if i != 0 {
+                    println!();
+                }
+                print_item_details(manager, id, report_cfg);
+            }
+
+            Ok(ProgramResult {
+                should_save: false,
+                exit_status: 0,
+            })
+        }
+        SelAct::History => {
+            for (i, &id) in range.iter().enumerate() {
+                let item = manager.find(id).unwrap();
+
+                if i != 0 {
+                    println!();
+                }
+                println!("History of {} ({})", item.name, id_label(item));
+
+                if item.history.is_empty() {
+                    println!("  (no changes were recorded)");
+                }
+
+                for entry in &item.history {
+                    println!(
+                        "  {}  {}",
+                        entry.at.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                        describe_event(&entry.event)
+                    );
+                }
+            }
+
+            Ok(ProgramResult {
+                should_save: false,
+                exit_status: 0,
+            })
+        }
+        SelAct::BlockOn(sargs) => {
+            let blocker = match manager.find(RefId(sargs.blocker)) {
+                Some(item) => InternalId(item.internal_id),
+                None => {
+                    return Err(format!(
+                        "could not find item with RefId = {}",
+                        sargs.blocker
+                    ))
+                }
+            };
+
+            for &item in &range {
+                if item == blocker || manager.depends_on(blocker, item) {
+                    return Err(format!(
+                        "#{} can't block {}, since that would make them block each other",
+                        sargs.blocker,
+                        id_label(manager.find(item).unwrap())
+                    ));
+                }
+
+                manager.interact_mut(item, |i| {
+                    if !i.blocked_by.contains(&blocker.0) {
+                        i.blocked_by.push(blocker.0);
+                    }
+                });
+            }
+
+            Ok(ProgramResult {
+                should_save: true,
+                exit_status: 0,
+            })
+        }
+        SelAct::Touch => {
+            let now = Utc::now();
+
+            for &id in &range {
+                manager.interact_mut(id, |item| item.reviewed_at = Some(now));
+            }
+
+            info!("{} item(s) marked as reviewed", range.len());
+
+            Ok(ProgramResult {
+                should_save: true,
+                exit_status: 0,
+            })
+        }
+        SelAct::Open => {
+            let mut exit_status = 0;
+
+            for &id in &range {
+                let item = manager.find(id).unwrap();
+
+                let url = match item.url {
+                    Some(ref url) => url,
+                    None => {
+                        warn!("{} has no URL", id_label(item));
+                        exit_status = 1;
+                        continue;
+                    }
+                };
+
+                if !open_with_opener(url.as_ref())? {
+                    exit_status = 1;
+                }
+            }
+
+            Ok(ProgramResult {
+                should_save: false,
+                exit_status,
+            })
+        }
+        SelAct::Attach(sargs) => {
+            let path = Path::new(&sargs.path)
+                .canonicalize()
+                .map_err(|e| format!("could not find {}: {}", sargs.path, e))?;
+
+            for &id in &range {
+                manager.interact_mut(id, |item| {
+                    if !item.attachments.contains(&path) {
+                        item.attachments.push(path.clone());
+                    }
+                });
+            }
+
+            info!("Attached {} to {} item(s)", path.display(), range.len());
+
+            Ok(ProgramResult {
+                should_save: true,
+                exit_status: 0,
+            })
+        }
+        SelAct::Detach(sargs) => {
+            let mut exit_status = 0;
+
+            for &id in &range {
+                let item = manager.find(id).unwrap();
+                let label = id_label(item);
+
+                let attachment = match sargs.target {
+                    None => None,
+                    Some(ref target) => match find_attachment(item, target) {
+                        Some(i) => Some(i),
+                        None => {
+                            warn!("{} has no attachment {}", label, target);
+                            exit_status = 1;
+                            continue;
+                        }
+                    },
+                };
+
+                manager.interact_mut(id, |item| match attachment {
+                    Some(i) => {
+                        item.attachments.remove(i);
+                    }
+                    None => item.attachments.clear(),
+                });
+            }
+
+            Ok(ProgramResult {
+                should_save: true,
+                exit_status,
+            })
+        }
+        SelAct::Attachments => {
+            for (i, &id) in range.iter().enumerate() {
+                let item = manager.find(id).unwrap();
+
+                if i != 0 {
+                    println!();
+                }
+                println!("Attachments of {} ({})", item.name, id_label(item));
+
+                if item.attachments.is_empty() {
+                    println!("  (no files are attached)");
+                }
+
+                for (number, path) in item.attachments.iter().enumerate() {
+                    println!(
+                        "  {}. {}{}",
+                        number + 1,
+                        path.display(),
+                        if path.exists() { "" } else { " (missing)" }
+                    );
+                }
+            }
+
+            Ok(ProgramResult {
+                should_save: false,
+                exit_status: 0,
+            })
+        }
+        SelAct::OpenAttachment(sargs) => {
+            let mut exit_status = 0;
+
+            for &id in &range {
+                let item = manager.find(id).unwrap();
+
+                let paths: &[PathBuf] = match sargs.number {
+                    None if item.attachments.is_empty() => {
+                        warn!("{} has no attachments", id_label(item));
+                        exit_status = 1;
+                        continue;
+                    }
+                    None => &item.attachments,
+                    Some(number) => {
+                        match number.checked_sub(1).and_then(|i| item.attachments.get(i)) {
+                            Some(path) => std::slice::from_ref(path),
+                            None => {
+                                warn!("{} has no attachment {}", id_label(item), number);
+                                exit_status = 1;
+                                continue;
+                            }
+                        }
+                    }
+                };
+
+                for path in paths {
+                    if !path.exists() {
+                        warn!("{} doesn't exist anymore", path.display());
+                        exit_status = 1;
+                    } else if !open_with_opener(path.as_os_str())? {
+                        exit_status = 1;
+                    }
+                }
+            }
+
+            Ok(ProgramResult {
+                should_save: false,
+                exit_status,
+            })
+        }
+        SelAct::Link(sargs) => {
+            let link = ItemLink::parse(&sargs.target)?;
+
+            // loading creates missing files, which would turn a mistyped profile into an empty one
+            let linked_path = profile_path(&link.profile)?;
+            if !linked_path.exists() {
+                return Err(format!("there's no profile named {:?}", link.profile));
+            }
+
+            let linked = load_items(&linked_path)
+                .ok()
+                .and_then(|items| ItemManager::new(items).ok())
+                .and_then(|other| other.find(RefId(link.ref_id)).map(|item| item.name.clone()))
+                .ok_or_else(|| format!("could not find the item {}", link))?;
+
+            for &id in &range {
+                manager.interact_mut(id, |item| {
+                    if !item.links.contains(&link) {
+                        item.links.push(link.clone());
+                    }
+                });
+            }
+
+            info!("Linked {} item(s) to {:?} ({})", range.len(), linked, link);
+
+            Ok(ProgramResult {
+                should_save: true,
+                exit_status: 0,
+            })
+        }
+        SelAct::Unlink(sargs) => {
+            let link = match sargs.target {
+                Some(ref target) => Some(ItemLink::parse(target)?),
+                None => None,
+            };
+
+            for &id in &range {
+                manager.interact_mut(id, |item| match link {
+                    Some(ref link) => item.links.retain(|l| l != link),
+                    None => item.links.clear(),
+                });
+            }
+
+            Ok(ProgramResult {
+                should_save: true,
+                exit_status: 0,
+            })
+        }
+        SelAct::Unblock(sargs) => {
+            let blocker = match sargs.blocker {
+                Some(ref_id) => match manager.find(RefId(ref_id)) {
+                    Some(item) => Some(item.internal_id),
+                    None => return Err(format!("could not find item with RefId = {}", ref_id)),
+                },
+                None => None,
+            };
+
+            for &id in &range {
+                manager.interact_mut(id, |i| match blocker {
+                    Some(blocker) => i.blocked_by.retain(|&b| b != blocker),
+                    None => i.blocked_by.clear(),
+                });
+            }
+
+            Ok(ProgramResult {
+                should_save: true,
+                exit_status: 0,
+            })
+        }
+        SelAct::Start => {
+            if range.len() != 1 {
+                return Err("The selection should have exactly one item.".into());
+            }
+
+            let id = range[0];
+
+            for root in manager.data.iter_mut() {
+                root.traverse_mut(&mut |item| {
+                    if item.internal_id != id.0 && item.stop_work() {
+                        info!("Stopped working on {:?}", item.name);
+                    }
+                });
+            }
+
+            manager.interact_mut(id, |item| {
+                if item.working_since().is_some() {
+                    info!("Already working on {:?}", item.name);
+                } else {
+                    item.start_work();
+                    info!("Started working on {:?}", item.name);
+                }
+            });
+
+            Ok(ProgramResult {
+                should_save: true,
+                exit_status: 0,
+            })
+        }
+        SelAct::Stop => {
+            let mut stopped = 0;
+            for &id in &range {
+                manager.interact_mut(id, |item| {
+                    if item.stop_work() {
+                        info!(
+                            "Stopped working on {:?} ({} in total)",
+                            item.name,
+                            utils::time::format_duration(item.time_spent(None))
+                        );
+                        stopped += 1;
+                    }
+                });
+            }
+
+            if stopped == 0 {
+                return Err("none of the selected items were being worked on".into());
+            }
+
+            Ok(ProgramResult {
+                should_save: true,
+                exit_status: 0,
+            })
+        }
+        SelAct::Edit => {
+            let items: Vec<&Item> = range.iter().map(|&id| manager.find(id).unwrap()).collect();
+
+            // An item inside of another selected item would show up twice on the document.
+            for item in &items {
+                for item2 in &items {
+                    if item.has_child(item2) {
+                        return Err(format!(
+                            "{:?} is a child of {:?}, but both are on the selection",
+                            item2.name, item.name
+                        ));
+                    }
+                }
+            }
+
+            let mut original_ids = HashSet::new();
+            for item in &items {
+                item.traverse(&mut |i| {
+                    original_ids.insert(i.internal_id);
+                });
+            }
+
+            let selection: Vec<InternalId> =
+                items.iter().map(|i| InternalId(i.internal_id)).collect();
+            let document = outline::serialize(&items, report_cfg.spaces_per_indent);
+
+            let edited = match tmp::edit_text(&document, Some("txt")) {
+                Ok((text, 0)) => text,
+                Ok((_, code)) => return Err(format!("non-zero exit code: {}", code)),
+                Err(e) => return Err(format!("failed to edit text: {}", e)),
+            };
+
+            if edited == document {
+                info!("No changes were made");
+
+                return Ok(ProgramResult {
+                    should_save: false,
+                    exit_status: 0,
+                });
+            }
+
+            let nodes = outline::parse(&edited, report_cfg.spaces_per_indent)
+                .map_err(|e| format!("failed to parse outline: {}", e))?;
+
+            let (kept_ids, added) = outline::tally(&nodes);
+            let mut seen = HashSet::new();
+            for &id in &kept_ids {
+                if !original_ids.contains(&id) {
+                    return Err(format!("internal ID i{} is not part of the selection", id));
+                }
+
+                if !seen.insert(id) {
+                    return Err(format!("internal ID i{} appears more than once", id));
+                }
+            }
+
+            let removed = original_ids.len() - kept_ids.len();
+            eprintln!("{} item(s) will be added and {} removed.", added, removed);
+
+            if removed == 0 || confirm_with_default(true) {
+                manager.apply_outline(&selection, nodes)?;
+
+                Ok(ProgramResult {
+                    should_save: true,
+                    exit_status: 0,
+                })
+            } else {
+                Ok(ProgramResult {
+                    should_save: false,
+                    exit_status: 1,
+                })
+            }
+        }
+        SelAct::EditDescription => {
+            if range.len() != 1 {
+                return Err("The selection should have exactly one item.".into());
+            }
+
+            manager
+                .interact_mut(range[0], |i| {
+                    match tmp::edit_text(&i.description, Some("txt")) {
+                        Ok((new_description, 0)) => {
+                            i.description = new_description;
+
+                            Ok(ProgramResult {
+                                should_save: true,
+                                exit_status: 0,
+                            })
+                        }
+                        Ok((_, code)) => Err(format!("non-zero exit code: {}", code)),
+                        Err(e) => Err(format!("failed to edit text: {}", e)),
+                    }
+                })
+                .unwrap()
+        }
+        SelAct::Done(sargs) => done_selection(manager, &range, sargs.recursive == Some(true)),
+        SelAct::Undone => reopen_selection(manager, &range, false),
+        SelAct::Toggle => reopen_selection(manager, &range, true),
+        SelAct::Wait(sargs) => {
+            for &id in &range {
+                manager
+                    .change_item_state(id, |previous| match previous {
+                        ItemState::Todo | ItemState::Waiting(_) => {
+                            ItemState::Waiting(sargs.on.clone())
+                        }
+                        other => other,
+                    })
+                    .unwrap();
+            }
+
+            Ok(ProgramResult {
+                should_save: true,
+                exit_status: 0,
+            })
+        }
+        SelAct::Snooze(sargs) => {
+            let until = parse_snooze(&sargs.until)?;
+
+            for &id in &range {
+                manager.interact_mut(id, |item| item.snoozed_until = Some(until));
+            }
+
+            info!(
+                "Snoozed {} item(s) until {}",
+                range.len(),
+                until.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+            );
+
+            Ok(ProgramResult {
+                should_save: true,
+                exit_status: 0,
+            })
+        }
+        SelAct::Unsnooze => {
+            for &id in &range {
+                manager.interact_mut(id, |item| item.snoozed_until = None);
+            }
+
+            Ok(ProgramResult {
+                should_save: true,
+                exit_status: 0,
+            })
+        }
+        SelAct::Star => {
+            for &id in &range {
+                manager.interact_mut(id, |item| item.starred = true);
+            }
+
+            Ok(ProgramResult {
+                should_save: true,
+                exit_status: 0,
+            })
+        }
+        SelAct::Unstar => {
+            for &id in &range {
+                manager.interact_mut(id, |item| item.starred = false);
+            }
+
+            Ok(ProgramResult {
+                should_save: true,
+                exit_status: 0,
+            })
+        }
+        SelAct::Cancel => {
+            for &id in &range {
+                manager
+                    .change_item_state(id, |previous| match previous {
+                        ItemState::Todo | ItemState::Waiting(_) => ItemState::Cancelled,
+                        other => other,
+                    })
+                    .unwrap();
+            }
+
+            Ok(ProgramResult {
+                should_save: true,
+                exit_status: 0,
+            })
+        }
+        SelAct::ListTree => {
+            let selected: Vec<&Item> = range.iter().map(|&id| manager.find(id).unwrap()).collect();
+
+            R::report(
+                "Tree listing",
+                &mut selected.into_iter(),
+                &ReportInfo {
+                    config: report_cfg,
+                    indent: 0,
+                    filter: None,
+                    depth: ReportDepth::Tree,
+                },
+                &mut io::stdout(),
+            )
+            .expect("Failed to show report");
+
+            Ok(ProgramResult {
+                should_save: false,
+                exit_status: 0,
+            })
+        }
+        SelAct::ListBrief => {
+            let selected: Vec<&Item> = range.iter().map(|&id| manager.find(id).unwrap()).collect();
+
+            R::report(
+                "Brief listing",
+                &mut selected.into_iter(),
+                &ReportInfo {
+                    config: report_cfg,
+                    indent: 0,
+                    filter: None,
+                    depth: ReportDepth::Brief,
+                },
+                &mut io::stdout(),
+            )
+            .expect("Failed to show report");
+
+            Ok(ProgramResult {
+                should_save: false,
+                exit_status: 0,
+            })
+        }
+        SelAct::ListShallow => {
+            let selected: Vec<&Item> = range.iter().map(|&id| manager.find(id).unwrap()).collect();
+
+            R::report(
+                "Shallow listing",
+                &mut selected.into_iter(),
+                &ReportInfo {
+                    config: report_cfg,
+                    indent: 0,
+                    filter: None,
+                    depth: ReportDepth::Shallow,
+                },
+                &mut io::stdout(),
+            )
+            .expect("Failed to show report");
+
+            Ok(ProgramResult {
+                should_save: false,
+                exit_status: 0,
+            })
+        }
+        SelAct::Delete(sargs) => {
+            /// Iterates recursively of a vector of items and their children, removing any items that are on the
+            /// selection. IDs on the selection that aren't found will be ignored. This is probably not a problem
+            /// because we already made sure the selection passed here has only valid IDs, so any missing IDs are from
+            /// children of items that were already deleted on this run.
+            ///
+            /// If `orphan` is true, the children of the removed items take their places instead of being removed too.
+            /// The removed items are added to `removed`.
+            fn thing(
+                data: &mut Vec<Item>,
+                selection: &HashSet<InternalId>,
+                orphan: bool,
+                removed: &mut Vec<Item>,
+            ) {
+                let mut i = 0;
+
+                while i < data.len() {
+                    if selection.contains(&InternalId(data[i].internal_id)) {
+                        let mut item = data.remove(i);
+
+                        if orphan {
+                            // the children are checked on the next iterations, since they might be selected too
+                            data.splice(i..i, std::mem::take(&mut item.children));
+                        }
+
+                        removed.push(item);
+                    } else {
+                        thing(&mut data[i].children, selection, orphan, removed);
+                        i += 1;
+                    }
+                }
+            }
+
+            let orphan = sargs.orphan == Some(true);
+
+            let proceed = |manager: &mut ItemManager| {
+                let mut removed = Vec::new();
+                thing(
+                    &mut manager.data,
+                    &range.iter().copied().collect(),
+                    orphan,
+                    &mut removed,
+                );
+
+                // the IDs are freed so the items added on the same run (like with a batch) can take them
+                manager.release_ref_ids(&removed);
+
+                Ok(ProgramResult {
+                    should_save: true,
+                    exit_status: 0,
+                })
+            };
+
+            if !sargs.force.unwrap_or(false) {
+                let selection: Vec<&Item> =
+                    range.iter().map(|&id| manager.find(id).unwrap()).collect();
+
+                R::report(
+                    "Items to be deleted",
+                    &mut selection.into_iter(),
+                    &ReportInfo {
+                        config: report_cfg,
+                        indent: 0,
+                        filter: None,
+                        depth: if orphan {
+                            ReportDepth::Shallow
+                        } else {
+                            ReportDepth::Tree
+                        },
+                    },
+                    &mut io::stdout(),
+                )
+                .unwrap();
+
+                if orphan {
+                    eprintln!("Their children will be moved to where they are");
+                }
+
+                if confirm_with_default(true) {
+                    proceed(manager)
+                } else {
+                    Ok(ProgramResult {
+                        should_save: false,
+                        exit_status: 1,
+                    })
+                }
+            } else {
+                proceed(manager)
+            }
+        }
+        SelAct::Swap(sargs) => {
+            if range.len() != 2 {
+                return Err(format!(
+                    "the amount of arguments should be exactly two (instead of {})",
+                    range.len()
+                ));
+            }
+
+            let proceed = |manager: &mut ItemManager| match manager.swap(range[0], range[1]) {
+                Ok(()) => Ok(ProgramResult {
+                    should_save: true,
+                    exit_status: 0,
+                }),
+                Err(e) => Err(format!("item swap failed: {}", e)),
+            };
+
+            if !sargs.force.unwrap_or(false) {
+                let selection: Vec<&Item> =
+                    range.iter().map(|&id| manager.find(id).unwrap()).collect();
+
+                R::report(
+                    "Items to be swapped",
+                    &mut selection.into_iter(),
+                    &ReportInfo {
+                        config: report_cfg,
+                        indent: 0,
+                        filter: None,
+                        depth: ReportDepth::Brief,
+                    },
+                    &mut io::stdout(),
+                )
+                .unwrap();
+
+                eprintln!("Each item will keep their children.");
+                if confirm_with_default(true) {
+                    proceed(manager)
+                } else {
+                    Ok(ProgramResult {
                         should_save: false,
                         exit_status: 1,
                     })
                 }
             } else {
-                proceed(manager)
-            }
+                proceed(manager)
+            }
+        }
+        SelAct::ChangeOwnership(sargs) => {
+            let new_owner = match NewOwner::parse(&sargs.new_owner) {
+                Ok(new) => new,
+                Err(e) => return Err(format!("failed to parse new-owner argument: {}", e)),
+            };
+
+            move_selection::<R>(manager, &range, new_owner, report_cfg)
+        }
+        SelAct::MoveTo(sargs) => move_selection::<R>(
+            manager,
+            &range,
+            NewOwner::ByRef(RefId(sargs.parent)),
+            report_cfg,
+        ),
+        SelAct::MoveToRoot => move_selection::<R>(manager, &range, NewOwner::Root, report_cfg),
+        SelAct::Move(sargs) => {
+            if range.len() != 1 {
+                return Err("The selection should have exactly one item.".into());
+            }
+
+            let id = range[0];
+            let (siblings, index) = manager.locate_mut(|i| i.internal_id == id.0).unwrap();
+            let label = id_label(&siblings[index]);
+
+            let new_index = match sargs.position {
+                MovePosition::Up => index.saturating_sub(1),
+                MovePosition::Down => (index + 1).min(siblings.len() - 1),
+                MovePosition::Top => 0,
+                MovePosition::Bottom => siblings.len() - 1,
+                MovePosition::Before(SiblingArgs { sibling })
+                | MovePosition::After(SiblingArgs { sibling }) => {
+                    if siblings[index].ref_id == Some(sibling) {
+                        return Err("an item can't be moved relative to itself".into());
+                    }
+
+                    let target = siblings
+                        .iter()
+                        .position(|i| i.ref_id == Some(sibling))
+                        .ok_or_else(|| {
+                            format!(
+                                "item #{} is not a sibling of {} (use move-to to change parents)",
+                                sibling, label
+                            )
+                        })?;
+
+                    // The target shifts back once the item is taken out from before it.
+                    let target = if target > index { target - 1 } else { target };
+
+                    match sargs.position {
+                        MovePosition::Before(_) => target,
+                        _ => target + 1,
+                    }
+                }
+            };
+
+            let item = siblings.remove(index);
+            siblings.insert(new_index, item);
+
+            Ok(ProgramResult {
+                should_save: new_index != index,
+                exit_status: 0,
+            })
+        }
+        SelAct::Copy(sargs) => {
+            let destination = match sargs.to {
+                Some(ref to) => {
+                    let owner = NewOwner::parse(to)
+                        .map_err(|e| format!("failed to parse --to argument: {}", e))?;
+
+                    let exists = match owner {
+                        NewOwner::Root => true,
+                        NewOwner::ByRef(id) => manager.find(id).is_some(),
+                        NewOwner::ByInternal(id) => manager.find(id).is_some(),
+                    };
+
+                    if !exists {
+                        return Err(format!("could not find destination {:?}", to));
+                    }
+
+                    Some(owner)
+                }
+                None => None,
+            };
+
+            info!("Copying items:");
+
+            for &id in &range {
+                let mut copy = manager.find(id).unwrap().clone();
+                let label = id_label(&copy);
+                manager.refresh_ids(&mut copy)?;
+
+                let new_ref_id = copy.ref_id;
+
+                match destination {
+                    None => {
+                        let (siblings, index) =
+                            manager.locate_mut(|i| i.internal_id == id.0).unwrap();
+                        siblings.insert(index + 1, copy);
+                    }
+                    Some(NewOwner::Root) => manager.data.push(copy),
+                    Some(NewOwner::ByRef(owner)) => {
+                        manager.find_mut(owner).unwrap().children.push(copy)
+                    }
+                    Some(NewOwner::ByInternal(owner)) => {
+                        manager.find_mut(owner).unwrap().children.push(copy)
+                    }
+                }
+
+                match new_ref_id {
+                    Some(new_id) => info!("* {} => RefID: {}", label, new_id),
+                    None => info!("* {} => (no RefID)", label),
+                }
+            }
+
+            Ok(ProgramResult {
+                should_save: true,
+                exit_status: 0,
+            })
+        }
+        SelAct::SetRef(sargs) => {
+            if range.len() != 1 {
+                return Err("The selection should have exactly one item.".into());
+            }
+
+            let item = manager.find(range[0]).unwrap();
+            if item.state.is_closed() {
+                return Err(format!(
+                    "{} is closed, and closed items don't have reference IDs",
+                    id_label(item)
+                ));
+            }
+            let label = id_label(item);
+
+            manager.set_ref_id(range[0], Some(sargs.id))?;
+            info!("{} => RefID: {}", label, sargs.id);
+
+            Ok(ProgramResult {
+                should_save: true,
+                exit_status: 0,
+            })
+        }
+        SelAct::ClearRef => {
+            if let Some(&id) = range
+                .iter()
+                .find(|&&id| !manager.find(id).unwrap().state.is_closed())
+            {
+                return Err(format!(
+                    "{} is still open, so it needs its reference ID",
+                    id_label(manager.find(id).unwrap())
+                ));
+            }
+
+            let mut cleared = 0;
+            for &id in &range {
+                if manager.find(id).unwrap().ref_id.is_some() {
+                    manager.set_ref_id(id, None)?;
+                    cleared += 1;
+                }
+            }
+
+            info!("Cleared the reference IDs of {} item(s)", cleared);
+
+            Ok(ProgramResult {
+                should_save: cleared != 0,
+                exit_status: 0,
+            })
+        }
+        SelAct::Export(sargs) => {
+            let path = Path::new(&sargs.file);
+            if path.exists() {
+                return Err(format!("{:?} already exists", sargs.file));
+            }
+
+            let items: Vec<&Item> = range.iter().map(|&id| manager.find(id).unwrap()).collect();
+
+            // An item inside of another selected item would be written twice.
+            for item in &items {
+                for item2 in &items {
+                    if item.has_child(item2) {
+                        return Err(format!(
+                            "{:?} is a child of {:?}, but both are on the selection",
+                            item2.name, item.name
+                        ));
+                    }
+                }
+            }
+
+            let mut copies: Vec<Item> = items.into_iter().cloned().collect();
+            let mut standalone = ItemManager::new(Vec::new())
+                .map_err(|_| "failed to create the new items file".to_string())?;
+            standalone.adopt(&mut copies)?;
+
+            let mut count = 0;
+            for copy in &copies {
+                copy.traverse(&mut |_| count += 1);
+            }
+
+            if dry_run {
+                info!(
+                    "Dry run: {} item(s) would be exported to {}",
+                    count, sargs.file
+                );
+            } else {
+                let contents = data_serialize::export(&copies, true).map_err(|e| e.to_string())?;
+                crypt::write(path, &contents)
+                    .map_err(|e| format!("failed to write {:?}: {}", sargs.file, e))?;
+
+                info!("Exported {} item(s) to {}", count, sargs.file);
+            }
+
+            if sargs.remove == Some(true) {
+                let selected: HashSet<u32> = range.iter().map(|id| id.0).collect();
+                let removed = manager.extract_where(|item| selected.contains(&item.internal_id));
+                manager.release_ref_ids(&removed);
+
+                info!("Removed them from the items");
+            }
+
+            Ok(ProgramResult {
+                should_save: sargs.remove == Some(true),
+                exit_status: 0,
+            })
+        }
+        SelAct::Promote => {
+            // Going backwards keeps the order of promoted siblings, since each one is inserted right after the parent.
+            for &id in range.iter().rev() {
+                let label = id_label(manager.find(id).unwrap());
+                let (grandparent_children, parent_index) = manager
+                    .locate_mut(|i| i.children.iter().any(|c| c.internal_id == id.0))
+                    .ok_or_else(|| format!("item {} is already at the root", label))?;
+
+                let parent = &mut grandparent_children[parent_index];
+                let index = parent
+                    .children
+                    .iter()
+                    .position(|c| c.internal_id == id.0)
+                    .unwrap();
+                let item = parent.children.remove(index);
+
+                grandparent_children.insert(parent_index + 1, item);
+            }
+
+            Ok(ProgramResult {
+                should_save: true,
+                exit_status: 0,
+            })
+        }
+        SelAct::Demote => {
+            for &id in &range {
+                let (siblings, index) = manager.locate_mut(|i| i.internal_id == id.0).unwrap();
+
+                if index == 0 {
+                    return Err(format!(
+                        "item {} has no previous sibling to go under",
+                        id_label(&siblings[index])
+                    ));
+                }
+
+                let item = siblings.remove(index);
+                siblings[index - 1].children.push(item);
+            }
+
+            Ok(ProgramResult {
+                should_save: true,
+                exit_status: 0,
+            })
+        }
+    }
+}
+
+/// The new owner of items being moved.
+enum NewOwner {
+    Root,
+    ByInternal(InternalId),
+    ByRef(RefId),
+}
+
+impl NewOwner {
+    pub fn parse(arg: &str) -> Result<Self, String> {
+        if arg == ".ROOT" {
+            // Parse ROOT
+            Ok(Self::Root)
+        } else if let Some('i') = arg.chars().nth(0) {
+            // Parse Internal ID
+            if let Ok(num) = (&arg[1..]).parse::<u32>() {
+                Ok(Self::ByInternal(InternalId(num)))
+            } else {
+                Err(format!(
+                    "invalid number after 'i' character: {:?}",
+                    &arg[1..]
+                ))
+            }
+        } else if let Ok(num) = arg.parse::<u32>() {
+            Ok(Self::ByRef(RefId(num)))
+        } else {
+            Err(format!("invalid expression: {:?}", arg))
+        }
+    }
+}
+
+/// Marks the closed items on `range` as todo, printing their new reference IDs. If `toggle` is true, only done items
+/// are reopened, while pending ones are marked as done.
+fn reopen_selection(
+    manager: &mut ItemManager,
+    range: &[InternalId],
+    toggle: bool,
+) -> Result<ProgramResult, String> {
+    let mut reopened = Vec::new();
+
+    for &id in range {
+        let was_closed = manager.find(id).unwrap().state.is_closed();
+
+        manager.change_item_state(id, |previous| match previous {
+            ItemState::Done => ItemState::Todo,
+            ItemState::Cancelled if !toggle => ItemState::Todo,
+            ItemState::Todo | ItemState::Waiting(_) if toggle => ItemState::Done,
+            other => other,
+        })?;
+
+        let item = manager.find(id).unwrap();
+        if was_closed && !item.state.is_closed() {
+            reopened.push(item.ref_id.unwrap());
+        }
+    }
+
+    if !reopened.is_empty() {
+        info!("Reopened items:");
+        for ref_id in reopened {
+            info!("* RefID: {}", ref_id);
+        }
+    }
+
+    Ok(ProgramResult {
+        should_save: true,
+        exit_status: 0,
+    })
+}
+
+/// Marks the pending items on `range` as done, warning about the selected items that can't be marked. If `recursive`
+/// is true, the pending descendants of the items are marked as well.
+fn done_selection(
+    manager: &mut ItemManager,
+    range: &[InternalId],
+    recursive: bool,
+) -> Result<ProgramResult, String> {
+    let mut targets: Vec<InternalId> = Vec::new();
+
+    for &id in range {
+        let item = manager.find(id).unwrap(); // safe because we already made sure all IDs in the range exist.
+
+        match item.state {
+            ItemState::Todo | ItemState::Waiting(_) => targets.push(id),
+            ItemState::Done => warn!("{} is already done", id_label(item)),
+            ItemState::Cancelled => info!("{} was cancelled", id_label(item)),
+            ItemState::Note => warn!(
+                "{} is a note, so it can't be marked as done",
+                id_label(item)
+            ),
+        }
+
+        if recursive {
+            for child in &item.children {
+                child.traverse(&mut |descendant| {
+                    if matches!(descendant.state, ItemState::Todo | ItemState::Waiting(_)) {
+                        targets.push(InternalId(descendant.internal_id));
+                    }
+                });
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    targets.retain(|id| seen.insert(id.0));
+
+    for &id in &targets {
+        manager.change_item_state(id, |_| ItemState::Done).unwrap();
+    }
+
+    info!("{} item(s) marked as done", targets.len());
+
+    Ok(ProgramResult {
+        should_save: !targets.is_empty(),
+        exit_status: 0,
+    })
+}
+
+/// Moves the items on `range` (along with their children) to `new_owner`, after a confirmation.
+///
+/// Type argument `R` is the type of report that should be shown.
+fn move_selection<R: Report>(
+    manager: &mut ItemManager,
+    range: &[InternalId],
+    new_owner: NewOwner,
+    report_cfg: &ReportConfig,
+) -> Result<ProgramResult, String> {
+    let items: Vec<&Item> = range.iter().map(|&id| manager.find(id).unwrap()).collect();
+
+    R::report(
+        "Items to be moved",
+        &mut items.into_iter(),
+        &ReportInfo {
+            config: report_cfg,
+            indent: 0,
+            filter: None,
+            depth: ReportDepth::Shallow,
+        },
+        &mut io::stdout(),
+    )
+    .unwrap();
+
+    eprintln!();
+
+    let new_owner_internal_id = match new_owner {
+        NewOwner::Root => {
+            eprintln!("New owner: ROOT");
+            None
+        }
+        NewOwner::ByInternal(InternalId(id)) => {
+            if let Some(item) = manager.find(InternalId(id)) {
+                eprintln!("New owner: {:?} (I#{})", item.name, id);
+                Some(id)
+            } else {
+                return Err(format!("could not find item with InternalId = {}", id));
+            }
+        }
+        NewOwner::ByRef(RefId(id)) => {
+            if let Some(item) = manager.find(RefId(id)) {
+                eprintln!("New owner: {:?} (R#{})", item.name, id);
+                Some(item.internal_id)
+            } else {
+                return Err(format!("could not find item with RefId = {}", id));
+            }
+        }
+    };
+
+    {
+        let items: Vec<_> = range.iter().map(|&id| manager.find(id).unwrap()).collect();
+
+        // Prevent the new owner from being in the selection
+        for item in &items {
+            if Some(item.internal_id) == new_owner_internal_id {
+                return Err(format!(
+                    r#"item "{name}" ({ref}I#{internal}) is on selection and is the new owner"#,
+                    name = item.name,
+                    r#ref = match item.ref_id {
+                        Some(id) => format!("R#{}, ", id),
+                        None => String::new(),
+                    },
+                    internal = format!("{}", item.internal_id)
+                ));
+            }
+        }
+
+        // Prevent an item from being moved inside of itself
+        if let Some(owner_id) = new_owner_internal_id {
+            let owner = manager.find(InternalId(owner_id)).unwrap();
+
+            for item in &items {
+                if item.has_child(owner) {
+                    return Err(format!(
+                        "{:?} can't be moved inside of its own child {:?}",
+                        item.name, owner.name
+                    ));
+                }
+            }
+        }
+
+        // Prevent a selected item from being a child of another selected item (for now)
+        for (i, item) in items.iter().enumerate() {
+            for (i2, item2) in items.iter().enumerate() {
+                if i != i2 && item.has_child(item2) {
+                    return Err(format!(
+                        r#"parent-child conflict:
+let item A = {c_name:?} ({c_ref}I#{c_internal}), and
+    item B = {p_name:?} ({p_ref}I#{p_internal}).
+A is a child of B, but both A and B are on the selection."#,
+                        // Child
+                        c_name = item2.name,
+                        c_ref = match item2.ref_id {
+                            Some(id) => format!("R#{}, ", id),
+                            None => String::new(),
+                        },
+                        c_internal = format!("{}", item2.internal_id),
+                        // Parent
+                        p_name = item.name,
+                        p_ref = match item.ref_id {
+                            Some(id) => format!("R#{}, ", id),
+                            None => String::new(),
+                        },
+                        p_internal = format!("{}", item.internal_id),
+                    ));
+                }
+            }
+        }
+    }
+
+    eprintln!("Each item will keep its children.");
+
+    if confirm_with_default(true) {
+        let items: Vec<Item> = range
+            .iter()
+            .map(|&id| {
+                let (siblings, index) = manager.locate_mut(|i| i.internal_id == id.0).unwrap(); // safe unwrap due to range check
+                siblings.remove(index)
+            })
+            .collect();
+
+        match new_owner {
+            NewOwner::Root => manager.data.extend(items),
+            NewOwner::ByRef(id) => {
+                let owner = manager.find_mut(id).unwrap();
+                owner.children.extend(items);
+            }
+            NewOwner::ByInternal(id) => {
+                let owner = manager.find_mut(id).unwrap();
+                owner.children.extend(items);
+            }
+        }
+
+        Ok(ProgramResult {
+            should_save: true,
+            exit_status: 0,
+        })
+    } else {
+        Ok(ProgramResult {
+            should_save: false,
+            exit_status: 1,
+        })
+    }
+}
+
+/// Parses a selection range (see the [`selection`] module for its syntax) and returns the internal IDs of the selected
+/// items, checking if the selection isn't empty.
+///
+/// The IDs on the range are read as `ids`. If `state` is given, only the items on it are selected.
+fn parse_selection(
+    manager: &ItemManager,
+    range: &str,
+    ids: IdKind,
+    state: Option<&str>,
+    recursive: bool,
+) -> Result<Vec<InternalId>, String> {
+    let mut selection = Selection::parse(range)
+        .map_err(|e| format!("failed to parse range: {}", e))?
+        .with_id_kind(ids);
+
+    if let Some(state) = state {
+        selection = selection.with_state(state)?;
+    }
+
+    if recursive {
+        selection = selection.recursive();
+    }
+
+    let ids = selection.resolve(manager)?;
+
+    if ids.is_empty() {
+        return Err(format!("no items match the selection {:?}", range));
+    }
+
+    Ok(ids)
+}
+
+/// Prints every field of the item with the internal ID `id` on a block, leaving out the ones that aren't set.
+fn print_item_details(manager: &ItemManager, id: InternalId, report_cfg: &ReportConfig) {
+    fn field(label: &str, value: impl std::fmt::Display) {
+        println!("  {:<14}{}", format!("{}:", label), value);
+    }
+
+    fn moment(time: DateTime<Utc>) -> String {
+        time.with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string()
+    }
+
+    let item = manager.find(id).unwrap();
+    println!("{} ({}, i{})", item.name, id_label(item), item.internal_id);
+
+    field(
+        "State",
+        match &item.state {
+            ItemState::Waiting(Some(on)) => format!("waiting on {}", on),
+            state => state.name().to_string(),
+        },
+    );
+    field(
+        "Context",
+        item.context()
+            .map_or("(none)".into(), |ctx| format!("@{}", ctx)),
+    );
+
+    let mut ancestors = manager.ancestry(id).unwrap_or_default();
+    ancestors.pop();
+    if !ancestors.is_empty() {
+        let names: Vec<&str> = ancestors.iter().map(|item| item.name.as_str()).collect();
+        field("Ancestors", names.join(" > "));
+    }
+
+    let flags: Vec<&str> = [
+        (item.project, "project"),
+        (item.starred, "starred"),
+        (item.someday, "someday"),
+    ]
+    .iter()
+    .filter(|(set, _)| *set)
+    .map(|&(_, flag)| flag)
+    .collect();
+    if !flags.is_empty() {
+        field("Flags", flags.join(", "));
+    }
+
+    if let Some(priority) = item.priority {
+        field("Priority", priority);
+    }
+    if !item.tags.is_empty() {
+        let tags: Vec<String> = item.tags.iter().map(|tag| format!("#{}", tag)).collect();
+        field("Tags", tags.join(" "));
+    }
+
+    let today = Local::now().date_naive();
+    if let Some(due) = item.due {
+        let overdue = due < today && !item.state.is_closed();
+        field(
+            "Due",
+            if overdue {
+                report_cfg.paint(report::style::OVERDUE, &format!("{} (overdue)", due))
+            } else {
+                due.to_string()
+            },
+        );
+    }
+    if let Some(scheduled) = item.scheduled {
+        field("Scheduled", scheduled);
+    }
+    if let Some(until) = item.snoozed_until.filter(|&until| until > Utc::now()) {
+        field("Snoozed until", moment(until));
+    }
+
+    if let Some(estimate) = item.estimate {
+        field(
+            "Estimate",
+            utils::time::format_duration(chrono::Duration::minutes(estimate.into())),
+        );
+    }
+    if !item.work_log.is_empty() {
+        let spent = utils::time::format_duration(item.time_spent(None));
+
+        match item.working_since() {
+            Some(since) => field(
+                "Time spent",
+                format!("{} (working since {})", spent, moment(since)),
+            ),
+            None => field("Time spent", spent),
         }
-        SelAct::Swap(sargs) => {
-            if range.len() != 2 {
-                return Err(format!(
-                    "the amount of arguments should be exactly two (instead of {})",
-                    range.len()
-                ));
+    }
+
+    if !item.blocked_by.is_empty() {
+        let blockers: Vec<String> = item
+            .blocked_by
+            .iter()
+            .map(|&blocker| match manager.find(InternalId(blocker)) {
+                Some(blocker) => format!("{} ({})", blocker.name, id_label(blocker)),
+                None => format!("i{} (missing)", blocker),
+            })
+            .collect();
+        field("Blocked by", blockers.join(", "));
+    }
+    if !item.links.is_empty() {
+        let links: Vec<String> = item.links.iter().map(ToString::to_string).collect();
+        field("Links", links.join(", "));
+    }
+    if let Some(ref url) = item.url {
+        field("URL", url);
+    }
+    for (i, path) in item.attachments.iter().enumerate() {
+        field(if i == 0 { "Attachments" } else { "" }, path.display());
+    }
+
+    if let Some(days) = item.review_every {
+        field("Review every", format!("{} day(s)", days));
+    }
+    if let Some(reviewed_at) = item.reviewed_at {
+        field("Reviewed", moment(reviewed_at));
+    }
+    if let Some(created_at) = item.created_at {
+        field("Created", moment(created_at));
+    }
+    if let Some(modified_at) = item.modified_at {
+        field("Modified", moment(modified_at));
+    }
+    if let Some(done_at) = item.done_at {
+        field("Done", moment(done_at));
+    }
+
+    if !item.children.is_empty() {
+        let mut total = 0;
+        let mut closed = 0;
+        for child in &item.children {
+            child.traverse(&mut |descendant| {
+                total += 1;
+                if descendant.state.is_closed() {
+                    closed += 1;
+                }
+            });
+        }
+
+        field(
+            "Children",
+            format!(
+                "{} ({} descendant(s), {} of them closed)",
+                item.children.len(),
+                total,
+                closed
+            ),
+        );
+    }
+
+    if !item.description.trim().is_empty() {
+        println!("  Description:");
+        for line in item.description.trim_end().lines() {
+            println!("    {}", line);
+        }
+    }
+}
+
+/// Describes a change on an item's history, like `renamed from "a" to "b"`.
+fn describe_event(event: &ItemEvent) -> String {
+    let date = |date: &Option<chrono::NaiveDate>| match date {
+        Some(date) => date.format("%Y-%m-%d").to_string(),
+        None => String::from("nothing"),
+    };
+
+    match event {
+        ItemEvent::Created => String::from("created"),
+        ItemEvent::Renamed(from, to) => format!("renamed from {:?} to {:?}", from, to),
+        ItemEvent::StateChanged(from, to) => format!("{} -> {}", from.name(), to.name()),
+        ItemEvent::Moved => String::from("moved to another parent"),
+        ItemEvent::Snoozed(Some(until)) => format!(
+            "snoozed until {}",
+            until.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+        ),
+        ItemEvent::Snoozed(None) => String::from("unsnoozed"),
+        ItemEvent::Rescheduled(scheduled) => format!("scheduled to {}", date(scheduled)),
+        ItemEvent::DueChanged(due) => format!("due date set to {}", date(due)),
+    }
+}
+
+/// Opens `target` (a URL or a path) with `$OPENER`, or `xdg-open` if it's not set, returning whether it succeeded.
+fn open_with_opener(target: &std::ffi::OsStr) -> Result<bool, String> {
+    let opener = std::env::var("OPENER")
+        .ok()
+        .filter(|opener| !opener.is_empty())
+        .unwrap_or_else(|| String::from("xdg-open"));
+
+    match std::process::Command::new(&opener).arg(target).status() {
+        Ok(status) if status.success() => Ok(true),
+        Ok(status) => {
+            error!(
+                "{} failed to open {} ({})",
+                opener,
+                target.to_string_lossy(),
+                status
+            );
+            Ok(false)
+        }
+        Err(why) => Err(format!("failed to start opener command: {}", why)),
+    }
+}
+
+/// Finds the position of an attachment of `item`, given as its number (starting at 1) or its path.
+fn find_attachment(item: &Item, target: &str) -> Option<usize> {
+    if let Ok(number) = target.parse::<usize>() {
+        if number >= 1 && number <= item.attachments.len() {
+            return Some(number - 1);
+        }
+    }
+
+    let path = Path::new(target);
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+    item.attachments.iter().position(|a| a == &path)
+}
+
+/// Appends the items that were done since they were loaded to the completion journal of the items file on `path`.
+fn record_completions(
+    manager: &mut ItemManager,
+    path: &Path,
+    format: JournalFormat,
+) -> Result<(), String> {
+    // a plain journal would give away the names of the items
+    if Encryption::detect(path).is_some() {
+        return Err("journals aren't supported for encrypted items files".into());
+    }
+
+    let done: Vec<Item> = manager
+        .changes()
+        .into_iter()
+        .filter_map(|change| match change {
+            Change::Added(item) if item.state == ItemState::Done => Some(item),
+            Change::Modified { old, new, .. }
+                if old.state != ItemState::Done && new.state == ItemState::Done =>
+            {
+                Some(*new)
             }
+            _ => None,
+        })
+        .collect();
 
-            let proceed =
-                |manager: &mut ItemManager| match manager.swap(RefId(range[0]), RefId(range[1])) {
-                    Ok(()) => Ok(ProgramResult {
-                        should_save: true,
-                        exit_status: 0,
-                    }),
-                    Err(e) => Err(format!("item swap failed: {}", e)),
-                };
+    if done.is_empty() {
+        return Ok(());
+    }
 
-            if !sargs.force.unwrap_or(false) {
-                let selection: Vec<&Item> = range
-                    .iter()
-                    .map(|&id| manager.find(RefId(id)).unwrap())
-                    .collect();
+    let now = Utc::now();
+    let entries: Vec<journal::Entry> = done
+        .into_iter()
+        .map(|item| {
+            let mut ancestry = manager
+                .ancestry(InternalId(item.internal_id))
+                .unwrap_or_default();
+            ancestry.pop();
 
-                R::report(
-                    "Items to be swapped",
-                    &mut selection.into_iter(),
-                    &ReportInfo {
-                        config: report_cfg,
-                        indent: 0,
-                        filter: None,
-                        depth: ReportDepth::Brief,
-                    },
-                    &mut io::stdout(),
+            journal::Entry {
+                done_at: item.done_at.unwrap_or(now),
+                path: ancestry.iter().map(|i| i.name.clone()).collect(),
+                context: item.context().map(String::from),
+                name: item.name,
+            }
+        })
+        .collect();
+
+    journal::append(&journal::journal_path(path, format)?, format, &entries)
+}
+
+/// Prints `changes` like a diff: added items are prefixed by `+`, removed ones by `-` and modified ones by `~`,
+/// followed by the old and new values of the fields that changed.
+fn print_changes(changes: &[Change], report_cfg: &ReportConfig) {
+    if changes.is_empty() {
+        println!("No changes");
+        return;
+    }
+
+    for change in changes {
+        match change {
+            Change::Added(item) => println!(
+                "{}",
+                report_cfg.paint(
+                    report::style::GREEN,
+                    &format!("+ {} ({})", item.name, id_label(item))
                 )
-                .unwrap();
+            ),
+            Change::Removed(item) => println!(
+                "{}",
+                report_cfg.paint(
+                    report::style::RED,
+                    &format!("- {} ({})", item.name, id_label(item))
+                )
+            ),
+            Change::Modified { old, new, moved } => {
+                println!(
+                    "{} {} ({})",
+                    report_cfg.paint(report::style::YELLOW, "~"),
+                    new.name,
+                    id_label(old)
+                );
+                print_item_diff(old, new, report_cfg);
 
-                eprintln!("Each item will keep their children.");
-                if confirm_with_default(true) {
-                    proceed(manager)
-                } else {
-                    Ok(ProgramResult {
-                        should_save: false,
-                        exit_status: 1,
-                    })
+                if *moved {
+                    println!(
+                        "    {}",
+                        report_cfg.paint(report::style::DIM, "moved to another parent")
+                    );
                 }
-            } else {
-                proceed(manager)
             }
         }
-        SelAct::ChangeOwnership(sargs) => {
-            enum NewOwner {
-                Root,
-                ByInternal(InternalId),
-                ByRef(RefId),
-            }
-
-            impl NewOwner {
-                pub fn parse(arg: &str) -> Result<Self, String> {
-                    if arg == ".ROOT" {
-                        // Parse ROOT
-                        Ok(Self::Root)
-                    } else if let Some('i') = arg.chars().nth(0) {
-                        // Parse Internal ID
-                        if let Ok(num) = (&arg[1..]).parse::<u32>() {
-                            Ok(Self::ByInternal(InternalId(num)))
-                        } else {
-                            Err(format!(
-                                "invalid number after 'i' character: {:?}",
-                                &arg[1..]
-                            ))
-                        }
-                    } else if let Ok(num) = arg.parse::<u32>() {
-                        Ok(Self::ByRef(RefId(num)))
-                    } else {
-                        Err(format!("invalid expression: {:?}", arg))
-                    }
-                }
+    }
+}
+
+/// Prints the old (in red) and new (in green) values of the fields that differ between `old` and `new`, which are
+/// shown as JSON.
+fn print_item_diff(old: &Item, new: &Item, report_cfg: &ReportConfig) {
+    let fields = |item: &Item| match serde_json::to_value(item) {
+        Ok(serde_json::Value::Object(fields)) => fields,
+        _ => serde_json::Map::new(),
+    };
+    let (old_fields, new_fields) = (fields(old), fields(new));
+
+    let mut keys: Vec<&String> = old_fields.keys().chain(new_fields.keys()).collect();
+    keys.sort_unstable();
+    keys.dedup();
+
+    for key in keys {
+        let (before, after) = (old_fields.get(key), new_fields.get(key));
+        if before == after {
+            continue;
+        }
+
+        if let Some(before) = before {
+            let line = format!("- {}: {}", key, before);
+            println!("    {}", report_cfg.paint(report::style::RED, &line));
+        }
+        if let Some(after) = after {
+            let line = format!("+ {}: {}", key, after);
+            println!("    {}", report_cfg.paint(report::style::GREEN, &line));
+        }
+    }
+}
+
+/// Returns how an item is referred to on messages: by its reference ID or, if it has none, by its internal ID.
+fn id_label(item: &Item) -> String {
+    match item.ref_id {
+        Some(id) => format!("#{}", id),
+        None => format!("i{}", item.internal_id),
+    }
+}
+
+/// Returns the names of the item with the internal ID `id` and of its ancestors, like `Project > Phase > Task`.
+fn item_path(manager: &ItemManager, id: InternalId) -> String {
+    manager
+        .ancestry(id)
+        .unwrap_or_default()
+        .iter()
+        .map(|item| item.name.as_str())
+        .collect::<Vec<&str>>()
+        .join(" > ")
+}
+
+/// Returns the overdue items and the items due until the end of the day that `within` reaches.
+fn due_items(data: &[Item], within: chrono::Duration) -> (Vec<&Item>, Vec<&Item>) {
+    let today = Local::now().date_naive();
+    let limit = (Local::now() + within).date_naive();
+
+    let mut overdue = Vec::new();
+    let mut upcoming = Vec::new();
+    for root in data {
+        root.traverse(&mut |item| {
+            if item.state.is_closed() || item.state == ItemState::Note {
+                return;
             }
 
-            let items: Vec<&Item> = range
-                .iter()
-                .map(|&id| manager.find(RefId(id)).unwrap())
-                .collect();
+            match item.due {
+                Some(due) if due < today => overdue.push(item),
+                Some(due) if due <= limit => upcoming.push(item),
+                _ => (),
+            }
+        });
+    }
 
-            R::report(
-                "Items to be moved",
-                &mut items.into_iter(),
-                &ReportInfo {
-                    config: report_cfg,
-                    indent: 0,
-                    filter: None,
-                    depth: ReportDepth::Shallow,
-                },
-                &mut io::stdout(),
-            )
-            .unwrap();
+    (overdue, upcoming)
+}
 
-            let new_owner = match NewOwner::parse(&sargs.new_owner) {
-                Ok(new) => new,
-                Err(e) => return Err(format!("failed to parse new-owner argument: {}", e)),
-            };
+/// Prints how many of the open items on `data` are overdue and due today, if any of them are, so they aren't missed on
+/// reports that leave them out.
+fn print_due_banner(data: &[Item], report_cfg: &ReportConfig) {
+    let (overdue, today) = due_items(data, chrono::Duration::zero());
 
-            eprintln!();
+    let banner = match (overdue.len(), today.len()) {
+        (0, 0) => return,
+        (0, t) => format!("{} item(s) due today", t),
+        (o, 0) => format!("{} overdue item(s)", o),
+        (o, t) => format!("{} overdue item(s), {} due today", o, t),
+    };
 
-            let new_owner_internal_id = match new_owner {
-                NewOwner::Root => {
-                    eprintln!("New owner: ROOT");
-                    None
-                }
-                NewOwner::ByInternal(InternalId(id)) => {
-                    if let Some(item) = manager.find(InternalId(id)) {
-                        eprintln!("New owner: {:?} (I#{})", item.name, id);
-                        Some(id)
-                    } else {
-                        return Err(format!("could not find item with InternalId = {}", id));
-                    }
-                }
-                NewOwner::ByRef(RefId(id)) => {
-                    if let Some(item) = manager.find(RefId(id)) {
-                        eprintln!("New owner: {:?} (R#{})", item.name, id);
-                        Some(item.internal_id)
-                    } else {
-                        return Err(format!("could not find item with RefId = {}", id));
-                    }
-                }
-            };
+    let style = if overdue.is_empty() {
+        report::style::YELLOW
+    } else {
+        report::style::RED
+    };
 
-            {
-                let items: Vec<_> = range
-                    .iter()
-                    .map(|&id| manager.find(RefId(id)).unwrap())
-                    .collect();
+    println!("{}", report_cfg.paint(style, &format!("! {}", banner)));
+}
 
-                // Prevent the new owner from being in the selection
-                for item in &items {
-                    if Some(item.internal_id) == new_owner_internal_id {
-                        return Err(format!(
-                            r#"item "{name}" ({ref}I#{internal}) is on selection and is the new owner"#,
-                            name = item.name,
-                            r#ref = match item.ref_id {
-                                Some(id) => format!("R#{}, ", id),
-                                None => String::new(),
-                            },
-                            internal = format!("{}", item.internal_id)
-                        ));
-                    }
-                }
+/// Shows the open items under `roots` (including them) that are overdue and pass `filter` on a section of their own,
+/// from the most overdue one. Items kept out of the reports, like the ones on the someday/maybe list, are skipped along
+/// with their descendants.
+fn report_overdue<R: Report>(
+    roots: &[&Item],
+    filter: &dyn Fn(&Item) -> bool,
+    report_cfg: &ReportConfig,
+) {
+    let today = Local::now().date_naive();
 
-                // Prevent a selected item from being a child of another selected item (for now)
-                for (i, item) in items.iter().enumerate() {
-                    for (i2, item2) in items.iter().enumerate() {
-                        if i != i2 && item.has_child(item2) {
-                            return Err(format!(
-                                r#"parent-child conflict:
-let item A = {c_name:?} ({c_ref}I#{c_internal}), and
-    item B = {p_name:?} ({p_ref}I#{p_internal}).
-A is a child of B, but both A and B are on the selection."#,
-                                // Child
-                                c_name = item2.name,
-                                c_ref = match item2.ref_id {
-                                    Some(id) => format!("R#{}, ", id),
-                                    None => String::new(),
-                                },
-                                c_internal = format!("{}", item2.internal_id),
-                                // Parent
-                                p_name = item.name,
-                                p_ref = match item.ref_id {
-                                    Some(id) => format!("R#{}, ", id),
-                                    None => String::new(),
-                                },
-                                p_internal = format!("{}", item.internal_id),
-                            ));
-                        }
-                    }
-                }
+    let mut overdue: Vec<&Item> = Vec::new();
+    for root in roots {
+        root.traverse_shown(&mut |item| {
+            let open = matches!(item.state, ItemState::Todo | ItemState::Waiting(_));
+
+            if open && item.due.map_or(false, |due| due < today) && filter(item) {
+                overdue.push(item);
             }
+        });
+    }
+    overdue.sort_by_key(|item| item.due);
 
-            eprintln!("Each item will keep its children.");
+    R::section(
+        "Overdue",
+        &overdue,
+        &ReportInfo {
+            config: report_cfg,
+            indent: 0,
+            filter: None,
+            depth: ReportDepth::Shallow,
+        },
+        &mut io::stdout(),
+    )
+    .unwrap();
+}
 
-            if confirm_with_default(true) {
-                let items: Vec<Item> = range
-                    .iter()
-                    .map(|&id| manager.try_remove(RefId(id)).unwrap()) // safe unwrap due to range check
-                    .collect();
+/// Returns the items reports start from: the project on `filter` if there's one, or the root items with reference IDs
+/// otherwise.
+fn report_roots<'a>(
+    manager: &'a ItemManager,
+    filter: &FilterArgs,
+) -> Result<Vec<&'a Item>, String> {
+    match filter.project {
+        Some(ref project) => manager
+            .find_project(project)
+            .map(|item| vec![item])
+            .ok_or_else(|| format!("there's no project named {:?}", project)),
+        None => Ok(manager
+            .surface_ref_ids()
+            .iter()
+            .map(|&i| manager.find(i).unwrap())
+            .collect()),
+    }
+}
 
-                match new_owner {
-                    NewOwner::Root => manager.data.extend(items),
-                    NewOwner::ByRef(id) => {
-                        let owner = manager.find_mut(id).unwrap();
-                        owner.children.extend(items);
-                    }
-                    NewOwner::ByInternal(id) => {
-                        let owner = manager.find_mut(id).unwrap();
-                        owner.children.extend(items);
-                    }
-                }
+/// Lets the user pick some of the `candidates` with the picker (see [`Picker`]), showing them along with the names of
+/// their parents.
+///
+/// Returns the internal IDs of the chosen items.
+fn pick_items(manager: &ItemManager, candidates: &[InternalId]) -> Result<Vec<InternalId>, String> {
+    let lines: Vec<String> = candidates
+        .iter()
+        .map(|&id| {
+            let item = manager.find(id).unwrap();
+            let mut line = format!("{} ({})", item_path(manager, id), id_label(item));
 
-                Ok(ProgramResult {
-                    should_save: true,
-                    exit_status: 0,
-                })
-            } else {
-                Ok(ProgramResult {
-                    should_save: false,
-                    exit_status: 1,
-                })
+            if let Some(ctx) = item.context() {
+                line.push_str(&format!(" @{}", ctx));
             }
-        }
+
+            line
+        })
+        .collect();
+
+    match Picker::new(format!("Items ({}):", candidates.len()))
+        .multi(true)
+        .pick(&lines)
+    {
+        Ok(picked) => Ok(picked.into_iter().map(|index| candidates[index]).collect()),
+        Err(Error::UserAbort) => Err("no items were picked".into()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Loads the items stored on `path`, creating the file if it doesn't exist. Encrypted files are decrypted (see
+/// [`crypt`]).
+fn load_items(path: &Path) -> Result<Vec<Item>, String> {
+    load_items_with_source(path).map(|(items, _)| items)
+}
+
+/// Like [`load_items`], but also returns the contents of the file.
+fn load_items_with_source(path: &Path) -> Result<(Vec<Item>, String), String> {
+    let contents = crypt::read(path).map_err(|why| format!("Failed to load file: {}", why))?;
+
+    let items = data_serialize::import(validate_parsed_string(&contents))
+        .map_err(|why| format!("Failed to parse file: {}", why))?;
+
+    Ok((items, contents))
+}
+
+/// Returns the path of the archive file that goes along with the items file on `path`.
+fn archive_path(path: &Path) -> PathBuf {
+    companion_path(path, "archive")
+}
+
+/// Returns the path of the templates file that goes along with the items file on `path`.
+fn templates_path(path: &Path) -> PathBuf {
+    companion_path(path, "templates")
+}
+
+/// Returns the path of a file named after the items file on `path`, like `itmn-archive.json`. If the items file is
+/// encrypted, the companion file is encrypted the same way.
+fn companion_path(path: &Path, suffix: &str) -> PathBuf {
+    let encryption = Encryption::detect(path);
+    let plain = match encryption {
+        Some(_) => path.with_extension(""),
+        None => path.to_path_buf(),
+    };
+
+    let name = plain
+        .file_stem()
+        .map_or("itmn".into(), |n| n.to_string_lossy());
+
+    match encryption {
+        Some(encryption) => path.with_file_name(format!(
+            "{}-{}.json.{}",
+            name,
+            suffix,
+            encryption.extension()
+        )),
+        None => path.with_file_name(format!("{}-{}.json", name, suffix)),
+    }
+}
+
+/// Returns the directory where the profiles are kept, `itmn-profiles` on the data directory.
+fn profiles_dir() -> Result<PathBuf, String> {
+    dirs::data_dir()
+        .map(|dir| dir.join("itmn-profiles"))
+        .ok_or_else(|| "failed to find the data directory (is $HOME set?)".to_string())
+}
+
+/// Returns the path of the entries file of the profile `name`, like `work/work.json`. Each profile has a directory of
+/// its own, so the files that go along with the entries file (like the archive) are kept separate too, and its name is
+/// on the file so the undo snapshots and backups of different profiles don't get mixed.
+fn profile_path(name: &str) -> Result<PathBuf, String> {
+    let valid = !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'));
+
+    if valid {
+        Ok(profiles_dir()?.join(name).join(format!("{}.json", name)))
+    } else {
+        Err(format!(
+            "{:?} should only have letters, digits, -, _ and . (and not start with .)",
+            name
+        ))
     }
 }
 
+/// Returns the path of the undo snapshot for the items file on `path`, stored on the cache directory.
+fn undo_path(path: &Path) -> PathBuf {
+    cache_path(path, "undo")
+}
+
+/// Returns the path of the index of the pending items of the items file on `path`, stored on the cache directory.
+fn pending_path(path: &Path) -> PathBuf {
+    cache_path(path, "pending")
+}
+
+/// Returns the path of the daily plan for the items file on `path`, stored on the cache directory.
+fn plan_path(path: &Path) -> PathBuf {
+    cache_path(path, "today")
+}
+
+/// Returns the path of a `{name}-{hash}-{suffix}.json` file on the cache directory (or the temporary one, if it can't
+/// be found), where `name` is the stem of the items file on `path` and `hash` is a hash of its full path - so items
+/// files with the same name on different directories don't share their snapshots and plans.
+fn cache_path(path: &Path, suffix: &str) -> PathBuf {
+    let cache_dir = dirs::cache_dir().unwrap_or_else(std::env::temp_dir);
+
+    let name = path
+        .file_stem()
+        .map_or("itmn".into(), |n| n.to_string_lossy());
+
+    // the directory is canonicalized instead of the file, which might not exist yet
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let full_path = match (dir.canonicalize(), path.file_name()) {
+        (Ok(dir), Some(file_name)) => dir.join(file_name),
+        _ => path.to_path_buf(),
+    };
+
+    // FNV-1a, which (unlike the hasher of the standard library) is the same on every Rust version
+    let hash = full_path
+        .to_string_lossy()
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+
+    cache_dir
+        .join("itmn")
+        .join(format!("{}-{:016x}-{}.json", name, hash, suffix))
+}
+
 fn validate_parsed_string(string: &str) -> &str {
     for ch in string.chars() {
         if !matches!(ch, '\n' | ' ' | '\t' | '\r') {
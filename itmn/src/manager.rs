@@ -1,11 +1,20 @@
 //! Stores data structures related to managing the database.
 
-use std::collections::HashSet;
+use chrono::Utc;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
+use std::time::{Duration, SystemTime};
 
-use crate::item::{InternalId, Item, ItemState, RefId};
+use crate::crypt;
+use crate::item::{HistoryEntry, InternalId, Item, ItemEvent, ItemState, RefId};
+use crate::outline::OutlineNode;
+use crate::sync::GitSync;
 
-use utils::data::data_serialize;
+use utils::data::{data_serialize, Backups};
+use utils::error::Error;
+use utils::io::FileLock;
+use utils::{debug, error, warn};
 
 /// The utils structure of the database.
 pub struct ItemManager {
@@ -16,14 +25,115 @@ pub struct ItemManager {
     internal_ids: HashSet<u32>,
     /// A set that stores all the used reference IDs.
     ref_ids: HashSet<u32>,
+    /// The items as they were loaded, used to find out which items were modified.
+    snapshot: Snapshot,
+    /// How new reference IDs are chosen.
+    id_strategy: IdStrategy,
+    /// The highest reference ID that can be given to an item. It's only lowered by the tests, since the whole range
+    /// can't be filled there.
+    max_ref_id: u32,
+    /// Where each item is on the tree, so they can be found without going through all of it. It's built on the first
+    /// lookup and rebuilt on the ones that find it outdated, which is why it's on a cell.
+    index: RefCell<LookupIndex>,
+    /// When the items file was last modified, as it was loaded. It's only locked while being loaded and saved, so this
+    /// is used to find out whether another instance changed it in the meantime.
+    loaded_modified: Option<SystemTime>,
+    /// Whether only some of the items were loaded, like only the pending ones, in which case they can't be saved.
+    partial: bool,
+}
+
+/// How long to wait for another instance to unlock the items file.
+pub const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Returns when `file` was last modified, if it exists.
+pub fn modified_time(file: &Path) -> Option<SystemTime> {
+    std::fs::metadata(file).and_then(|m| m.modified()).ok()
+}
+
+/// The items as they were loaded.
+enum Snapshot {
+    /// Copies of the items, without their children, along with the internal IDs of their parents.
+    Copies(HashMap<u32, (Option<u32>, Item)>),
+    /// The contents of the items file, which are only parsed again (into copies) if the items are saved. This spares
+    /// the programs that only read the items of copying all of them, which is slow on large files.
+    Source(String),
+}
+
+impl Snapshot {
+    /// Copies `items` and their descendants.
+    fn copy(items: &mut [Item]) -> HashMap<u32, (Option<u32>, Item)> {
+        let mut copies = HashMap::new();
+        for_each_bare(items, None, &mut |item, parent| {
+            copies.insert(item.internal_id, (parent, item.clone()));
+        });
+
+        copies
+    }
+
+    /// Returns the copies of the items, parsing the source into them first if needed.
+    fn copies(&mut self) -> &HashMap<u32, (Option<u32>, Item)> {
+        if let Self::Source(source) = self {
+            // the source was already parsed when loaded, so this only fails if it's empty - which means no items
+            let mut items: Vec<Item> = data_serialize::import(source).unwrap_or_default();
+            *self = Self::Copies(Self::copy(&mut items));
+        }
+
+        match self {
+            Self::Copies(copies) => copies,
+            Self::Source(_) => unreachable!(),
+        }
+    }
+}
+
+/// The ways new reference IDs can be chosen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IdStrategy {
+    /// The lowest ID that isn't in use, which might be the one of a recently deleted item.
+    Lowest,
+    /// A random ID from 0 to `max` that isn't in use, falling back to the lowest one when all of them are.
+    Random { max: u32 },
+}
+
+impl IdStrategy {
+    /// Parses a strategy, either `lowest` or `random`, optionally followed by the highest ID, like `random:999`.
+    pub fn parse(string: &str) -> Result<Self, String> {
+        match string.trim() {
+            "lowest" => Ok(Self::Lowest),
+            "random" => Ok(Self::Random { max: 99 }),
+            other => match other.strip_prefix("random:").map(|max| max.parse()) {
+                Some(Ok(max)) => Ok(Self::Random { max }),
+                _ => Err(format!(
+                    "unknown ID strategy {:?} (expected lowest, random or random:<max>)",
+                    string
+                )),
+            },
+        }
+    }
+}
+
+/// Calls `f` on each item of `items` and their descendants, along with the internal ID of their parents. The children
+/// of each item are taken out of it during the call.
+fn for_each_bare<F>(items: &mut [Item], parent: Option<u32>, f: &mut F)
+where
+    F: FnMut(&mut Item, Option<u32>),
+{
+    for item in items {
+        let mut children = std::mem::take(&mut item.children);
+        f(item, parent);
+        for_each_bare(&mut children, Some(item.internal_id), f);
+        item.children = children;
+    }
 }
 
 /// A collection of errors that can happen during the ItemManager creation.
+#[derive(Debug)]
 pub enum ManagerError {
     /// At least two of the items have a repeated reference ID.
     RepeatedRefID(RefId),
     /// At least two of the items have a repeated internal ID.
     RepeatedInternalID(InternalId),
+    /// Some pending items don't have a reference ID, and there are no free ones left to give them.
+    NoFreeRefID,
 }
 
 /// A trait to help on searching through a database with different types of queries.
@@ -68,86 +178,128 @@ impl Searchable<RefId> for ItemManager {
     type Data = Item;
 
     fn find(&self, query: RefId) -> Option<&Item> {
-        fn search(items: &Vec<Item>, query: RefId) -> Option<&Item> {
-            for item in items {
-                if item.ref_id == Some(query.0) {
-                    return Some(item);
-                }
+        self.lookup(IndexKey::Ref(query.0))
+    }
 
-                let find_result = search(&item.children, query);
-                if find_result.is_some() {
-                    return find_result;
-                }
-            }
+    fn find_mut(&mut self, query: RefId) -> Option<&mut Item> {
+        self.lookup_mut(IndexKey::Ref(query.0))
+    }
+}
 
-            None
-        }
+impl Searchable<InternalId> for ItemManager {
+    type Data = Item;
 
-        search(&self.data, query)
+    fn find(&self, query: InternalId) -> Option<&Item> {
+        self.lookup(IndexKey::Internal(query.0))
     }
 
-    fn find_mut(&mut self, query: RefId) -> Option<&mut Item> {
-        fn search(items: &mut Vec<Item>, query: RefId) -> Option<&mut Item> {
-            for item in items {
-                if item.ref_id == Some(query.0) {
-                    return Some(item);
-                }
+    fn find_mut(&mut self, query: InternalId) -> Option<&mut Item> {
+        self.lookup_mut(IndexKey::Internal(query.0))
+    }
+}
 
-                let find_result = search(&mut item.children, query);
-                if find_result.is_some() {
-                    return find_result;
-                }
-            }
+/// An ID that can be looked up on the [`LookupIndex`].
+#[derive(Clone, Copy)]
+enum IndexKey {
+    Ref(u32),
+    Internal(u32),
+}
 
-            None
+impl IndexKey {
+    /// Checks if `item` is the one with this ID.
+    fn matches(self, item: &Item) -> bool {
+        match self {
+            Self::Ref(id) => item.ref_id == Some(id),
+            Self::Internal(id) => item.internal_id == id,
         }
-
-        search(&mut self.data, query)
     }
 }
 
-impl Searchable<InternalId> for ItemManager {
-    type Data = Item;
+/// Maps the IDs of the items to their positions on the tree, which are the indices of each of their ancestors among
+/// their siblings, followed by the index of the item itself.
+///
+/// Since the tree can be changed directly, the index isn't always up to date: each position is checked when it's used,
+/// and the whole index is rebuilt when one of them turns out to be wrong.
+#[derive(Default)]
+struct LookupIndex {
+    ref_ids: HashMap<u32, Vec<usize>>,
+    internal_ids: HashMap<u32, Vec<usize>>,
+}
 
-    fn find(&self, query: InternalId) -> Option<&Item> {
-        fn search(items: &Vec<Item>, query: InternalId) -> Option<&Item> {
-            for item in items {
-                if item.internal_id == query.0 {
-                    return Some(item);
-                }
+impl LookupIndex {
+    fn build(data: &[Item]) -> Self {
+        fn travel(items: &[Item], path: &mut Vec<usize>, index: &mut LookupIndex) {
+            for (i, item) in items.iter().enumerate() {
+                path.push(i);
 
-                let find_result = search(&item.children, query);
-                if find_result.is_some() {
-                    return find_result;
+                if let Some(id) = item.ref_id {
+                    index.ref_ids.insert(id, path.clone());
                 }
+                index.internal_ids.insert(item.internal_id, path.clone());
+
+                travel(&item.children, path, index);
+                path.pop();
             }
+        }
 
-            None
+        let mut index = Self::default();
+        travel(data, &mut Vec::new(), &mut index);
+
+        index
+    }
+
+    fn get(&self, key: IndexKey) -> Option<&[usize]> {
+        match key {
+            IndexKey::Ref(id) => self.ref_ids.get(&id),
+            IndexKey::Internal(id) => self.internal_ids.get(&id),
         }
+        .map(|path| path.as_slice())
+    }
 
-        search(&self.data, query)
+    /// Adds an item to the index, on `path`.
+    fn insert(&mut self, item: &Item, path: Vec<usize>) {
+        if let Some(id) = item.ref_id {
+            self.ref_ids.insert(id, path.clone());
+        }
+        self.internal_ids.insert(item.internal_id, path);
     }
+}
 
-    fn find_mut(&mut self, query: InternalId) -> Option<&mut Item> {
-        fn search(items: &mut Vec<Item>, query: InternalId) -> Option<&mut Item> {
-            for item in items {
-                if item.internal_id == query.0 {
-                    return Some(item);
-                }
+/// Returns the item on `path` (see [`LookupIndex`]), if there's one.
+fn item_at<'a>(items: &'a [Item], path: &[usize]) -> Option<&'a Item> {
+    let (&first, rest) = path.split_first()?;
+    let item = items.get(first)?;
 
-                let find_result = search(&mut item.children, query);
-                if find_result.is_some() {
-                    return find_result;
-                }
-            }
+    if rest.is_empty() {
+        Some(item)
+    } else {
+        item_at(&item.children, rest)
+    }
+}
 
-            None
-        }
+/// Returns a mutable reference to the item on `path` (see [`LookupIndex`]), if there's one.
+fn item_at_mut<'a>(items: &'a mut [Item], path: &[usize]) -> Option<&'a mut Item> {
+    let (&first, rest) = path.split_first()?;
+    let item = items.get_mut(first)?;
 
-        search(&mut self.data, query)
+    if rest.is_empty() {
+        Some(item)
+    } else {
+        item_at_mut(&mut item.children, rest)
     }
 }
 
+/// A change made to an item since the items were loaded. The copies of the item don't have its children.
+pub enum Change {
+    Added(Item),
+    Removed(Item),
+    Modified {
+        old: Box<Item>,
+        new: Box<Item>,
+        moved: bool,
+    },
+}
+
 /// The result returned by a program.
 pub struct ProgramResult {
     pub should_save: bool,
@@ -159,6 +311,27 @@ impl ItemManager {
     ///
     /// [`ManagerError`]: ManagerError
     pub fn new(mut data: Vec<Item>) -> Result<Self, ManagerError> {
+        let snapshot = Snapshot::Copies(Snapshot::copy(&mut data));
+
+        Self::with_snapshot(data, snapshot)
+    }
+
+    /// Like [`ItemManager::new`], but for items parsed from `source`, the contents of the items file. The items are
+    /// only copied (to find out which of them were modified) if they're saved, so loading them is faster.
+    pub fn with_source(data: Vec<Item>, source: String) -> Result<Self, ManagerError> {
+        Self::with_snapshot(data, Snapshot::Source(source))
+    }
+
+    /// Like [`ItemManager::new`], but for only some of the items of the file, like the pending ones (see the
+    /// `pending` module). The items can't be saved, since that would remove the other ones from the file.
+    pub fn partial(data: Vec<Item>) -> Result<Self, ManagerError> {
+        let mut manager = Self::with_snapshot(data, Snapshot::Source(String::new()))?;
+        manager.partial = true;
+
+        Ok(manager)
+    }
+
+    fn with_snapshot(mut data: Vec<Item>, snapshot: Snapshot) -> Result<Self, ManagerError> {
         let mut ref_set: HashSet<u32> = HashSet::new();
         let mut in_set: HashSet<u32> = HashSet::new();
 
@@ -202,15 +375,11 @@ impl ItemManager {
 
         // With the now filled IDs set, find free reference IDs for pending/note items that don't have IDs.
         for item in data.iter_mut() {
-            match item.state {
-                ItemState::Done => (),
-                ItemState::Todo | ItemState::Note => {
-                    if item.ref_id.is_none() {
-                        let id = utils::misc::find_lowest_free_value(&ref_set);
-                        item.ref_id = Some(id);
-                        ref_set.insert(id);
-                    }
-                }
+            if !item.state.is_closed() && item.ref_id.is_none() {
+                let id = utils::misc::find_lowest_free_value(&ref_set)
+                    .ok_or(ManagerError::NoFreeRefID)?;
+                item.ref_id = Some(id);
+                ref_set.insert(id);
             }
         }
 
@@ -218,30 +387,405 @@ impl ItemManager {
             ref_ids: ref_set,
             internal_ids: in_set,
             data: data,
+            snapshot,
+            id_strategy: IdStrategy::Lowest,
+            max_ref_id: u32::MAX,
+            index: RefCell::new(LookupIndex::default()),
+            loaded_modified: None,
+            partial: false,
         })
     }
 
+    /// Remembers when the items file was last modified, as `modified` (see [`modified_time`]), so saving them fails
+    /// instead of overwriting the changes if another instance modifies it before that.
+    pub fn set_loaded_modified(&mut self, modified: Option<SystemTime>) {
+        self.loaded_modified = modified;
+    }
+
+    /// Finds the item with `key` through the index, rebuilding it if it's outdated.
+    fn lookup(&self, key: IndexKey) -> Option<&Item> {
+        let indexed = self
+            .index
+            .borrow()
+            .get(key)
+            .and_then(|path| item_at(&self.data, path))
+            .filter(|item| key.matches(item));
+
+        if indexed.is_some() {
+            return indexed;
+        }
+
+        let index = LookupIndex::build(&self.data);
+        let found = index.get(key).and_then(|path| item_at(&self.data, path));
+        *self.index.borrow_mut() = index;
+
+        found
+    }
+
+    /// Finds the item with `key` through the index, rebuilding it if it's outdated.
+    fn lookup_mut(&mut self, key: IndexKey) -> Option<&mut Item> {
+        let data = &mut self.data;
+        let index = self.index.get_mut();
+
+        let up_to_date = index
+            .get(key)
+            .and_then(|path| item_at(data, path))
+            .map_or(false, |item| key.matches(item));
+
+        if !up_to_date {
+            *index = LookupIndex::build(data);
+        }
+
+        let path = index.get(key)?;
+        item_at_mut(data, path)
+    }
+
+    /// Updates the modification time of the items that changed since they were loaded, including the ones that were
+    /// moved to another parent. Changes to reference IDs or to the order of the children don't count.
+    ///
+    /// The notable changes (see [`ItemEvent`]) are also appended to the history of the items, along with the creation
+    /// of the ones that weren't there.
+    pub fn touch_modified(&mut self) {
+        let now = Utc::now();
+        let snapshot = self.snapshot.copies();
+
+        for_each_bare(&mut self.data, None, &mut |item, parent| {
+            let (old_parent, old) = match snapshot.get(&item.internal_id) {
+                Some(entry) => entry,
+                None => {
+                    if item.history.is_empty() {
+                        item.history.push(HistoryEntry {
+                            at: item.created_at.unwrap_or(now),
+                            event: ItemEvent::Created,
+                        });
+                    }
+
+                    return;
+                }
+            };
+
+            let (ref_id, modified_at) = (item.ref_id, item.modified_at);
+            item.ref_id = old.ref_id;
+            item.modified_at = old.modified_at;
+
+            let changed = *old_parent != parent || item != old;
+
+            item.ref_id = ref_id;
+            item.modified_at = if changed { Some(now) } else { modified_at };
+
+            if !changed {
+                return;
+            }
+
+            let mut events = Vec::new();
+            if item.name != old.name {
+                events.push(ItemEvent::Renamed(old.name.clone(), item.name.clone()));
+            }
+            if item.state != old.state {
+                events.push(ItemEvent::StateChanged(
+                    old.state.clone(),
+                    item.state.clone(),
+                ));
+            }
+            if *old_parent != parent {
+                events.push(ItemEvent::Moved);
+            }
+            if item.snoozed_until != old.snoozed_until {
+                events.push(ItemEvent::Snoozed(item.snoozed_until));
+            }
+            if item.scheduled != old.scheduled {
+                events.push(ItemEvent::Rescheduled(item.scheduled));
+            }
+            if item.due != old.due {
+                events.push(ItemEvent::DueChanged(item.due));
+            }
+
+            item.history.extend(
+                events
+                    .into_iter()
+                    .map(|event| HistoryEntry { at: now, event }),
+            );
+        });
+    }
+
+    /// Returns the changes made to the items since they were loaded, following the order of the tree and with the
+    /// removed items at the end. Changes to the order of the children don't count.
+    pub fn changes(&mut self) -> Vec<Change> {
+        let snapshot = self.snapshot.copies();
+        let mut changes = Vec::new();
+        let mut present = HashSet::new();
+
+        for_each_bare(&mut self.data, None, &mut |item, parent| {
+            present.insert(item.internal_id);
+
+            match snapshot.get(&item.internal_id) {
+                None => changes.push(Change::Added(item.clone())),
+                Some((old_parent, old)) => {
+                    let moved = *old_parent != parent;
+                    if moved || item != old {
+                        changes.push(Change::Modified {
+                            old: Box::new(old.clone()),
+                            new: Box::new(item.clone()),
+                            moved,
+                        });
+                    }
+                }
+            }
+        });
+
+        let mut removed: Vec<&Item> = snapshot
+            .iter()
+            .filter(|(id, _)| !present.contains(id))
+            .map(|(_, (_, item))| item)
+            .collect();
+        removed.sort_by_key(|item| item.internal_id);
+        changes.extend(removed.into_iter().cloned().map(Change::Removed));
+
+        changes
+    }
+
     /// Starts a program of function signature F, which takes a mutable reference of the manager as an argument and
     /// returns a ProgramResult struct.
-    pub fn start_program_with_file<F>(&mut self, file: &Path, program: F) -> i32
+    ///
+    /// If `undo_file` is specified, the previous contents of `file` are copied to it before saving, and the same goes for
+    /// `backups`. If `git` is specified, the changes are committed after saving. The file is locked while it's saved.
+    /// `on_saved` is only called once the items were saved, so it can record what changed or write the files that go
+    /// along with them; if it fails, the exit status is 1.
+    ///
+    /// Nothing is saved if the file was modified since it was loaded (see [`ItemManager::set_loaded_modified`]).
+    pub fn start_program_with_file<F, G>(
+        &mut self,
+        file: &Path,
+        undo_file: Option<&Path>,
+        backups: Option<&Backups>,
+        git: Option<&GitSync>,
+        program: F,
+        on_saved: G,
+    ) -> i32
     where
         F: FnOnce(&mut ItemManager) -> ProgramResult,
+        G: FnOnce(&mut ItemManager) -> Result<(), String>,
     {
-        let result = program(self);
+        let mut result = program(self);
 
         if result.should_save {
-            if let Err(e) = data_serialize::save_to_file(&self.data, file, true) {
-                eprintln!("Error: failed to save to file: {}", e);
+            if self.partial {
+                error!("only some of the items were loaded, so they can't be saved");
                 return 1;
             }
+
+            let _lock = match FileLock::acquire(file, LOCK_TIMEOUT) {
+                Ok(lock) => lock,
+                Err(e) => {
+                    error!("failed to lock file: {}", e);
+                    return 1;
+                }
+            };
+
+            if self.loaded_modified.is_some() && modified_time(file) != self.loaded_modified {
+                error!(
+                    "{} was changed by another instance while this one was running, so the changes weren't saved; \
+                     run the command again",
+                    file.display()
+                );
+                return 1;
+            }
+
+            self.touch_modified();
+
+            // encrypted files aren't created when loaded, so there might be nothing to copy yet
+            if let Some(undo_file) = undo_file.filter(|_| file.exists()) {
+                if let Err(e) = utils::io::touch_and_open(undo_file)
+                    .and_then(|_| std::fs::copy(file, undo_file).map_err(|e| e.to_string()))
+                {
+                    warn!("failed to save undo snapshot: {}", e);
+                }
+            }
+
+            if let Some(backups) = backups {
+                if let Err(e) = backups.save(file) {
+                    warn!("failed to back up the items file: {}", e);
+                }
+            }
+
+            let saved = data_serialize::export(&self.data, true)
+                .map_err(|e| e.to_string())
+                .and_then(|contents| crypt::write(file, &contents));
+
+            if let Err(e) = saved {
+                error!("failed to save to file: {}", e);
+                return 1;
+            }
+            debug!("Saved the items to {}", file.display());
+
+            if let Err(e) = on_saved(self) {
+                error!("{}", e);
+                result.exit_status = 1;
+            }
+
+            if let Some(git) = git {
+                if let Err(e) = git.commit("Update items") {
+                    warn!("failed to commit the changes: {}", e);
+                }
+            }
         }
 
         result.exit_status
     }
 
+    /// Changes how the reference IDs of new items are chosen.
+    pub fn set_id_strategy(&mut self, strategy: IdStrategy) {
+        self.id_strategy = strategy;
+    }
+
+    /// Reserves a reference ID that isn't in use, chosen according to the ID strategy, and returns it.
+    ///
+    /// Since the IDs of closed and deleted items are released, this only fails if every possible ID is taken by a
+    /// pending item.
+    pub fn allocate_ref_id(&mut self) -> Result<u32, Error> {
+        let id = match self.id_strategy {
+            IdStrategy::Lowest => None,
+            IdStrategy::Random { max } => {
+                utils::misc::find_random_free_value(&self.ref_ids, 0..=max.min(self.max_ref_id))
+            }
+        }
+        .or_else(|| (0..=self.max_ref_id).find(|id| !self.ref_ids.contains(id)))
+        .ok_or_else(|| Error::Conflict("there are no free reference IDs left".into()))?;
+        self.ref_ids.insert(id);
+
+        Ok(id)
+    }
+
+    /// Reserves an internal ID greater than every other one in use and returns it.
+    ///
+    /// Once the highest possible ID is taken, the IDs of items that were deleted (or pruned, or archived) are reused,
+    /// starting from the lowest one, so this only fails if every possible ID is in use.
+    pub fn allocate_internal_id(&mut self) -> Result<u32, Error> {
+        let id = utils::misc::find_highest_free_value(&self.internal_ids)
+            .or_else(|| utils::misc::find_lowest_free_value(&self.internal_ids))
+            .ok_or_else(|| Error::Conflict("there are no free internal IDs left".into()))?;
+        self.internal_ids.insert(id);
+
+        Ok(id)
+    }
+
+    /// Reserves the IDs of a new item: an internal ID and, if `with_ref_id` is set, a reference ID. Either both of them
+    /// are reserved or, if that fails, none of them is.
+    fn allocate_ids(&mut self, with_ref_id: bool) -> Result<(Option<u32>, u32), Error> {
+        let ref_id = if with_ref_id {
+            Some(self.allocate_ref_id()?)
+        } else {
+            None
+        };
+
+        match self.allocate_internal_id() {
+            Ok(internal_id) => Ok((ref_id, internal_id)),
+            Err(e) => {
+                if let Some(id) = ref_id {
+                    self.ref_ids.remove(&id);
+                }
+
+                Err(e)
+            }
+        }
+    }
+
+    /// Constructs an item with freshly allocated IDs, without adding it to the database.
+    ///
+    /// Closed items don't receive a reference ID.
+    pub fn create_item(
+        &mut self,
+        name: &str,
+        context: &str,
+        state: ItemState,
+        description: String,
+        children: Vec<Item>,
+    ) -> Result<Item, Error> {
+        let (ref_id, internal_id) = self.allocate_ids(!state.is_closed())?;
+
+        Ok(Item::new(
+            ref_id,
+            internal_id,
+            name,
+            context,
+            state,
+            description,
+            children,
+        ))
+    }
+
+    /// Gives new IDs to an item and all of its children, so a copy of them can be added to the database. The copies
+    /// count as created right now.
+    pub fn refresh_ids(&mut self, item: &mut Item) -> Result<(), Error> {
+        item.internal_id = self.allocate_internal_id()?;
+        item.created_at = Some(Utc::now());
+        item.modified_at = item.created_at;
+        item.history.clear();
+        item.ref_id = if item.state.is_closed() {
+            None
+        } else {
+            Some(self.allocate_ref_id()?)
+        };
+
+        for child in item.children.iter_mut() {
+            self.refresh_ids(child)?;
+        }
+
+        Ok(())
+    }
+
+    /// Gives new IDs to `items` and their children, keeping everything else, so they can be moved into this database
+    /// from another one. Blockers that are among the items are changed to their new IDs, while the other ones are
+    /// dropped, since their IDs wouldn't refer to the same items anymore.
+    pub fn adopt(&mut self, items: &mut [Item]) -> Result<(), Error> {
+        let mut new_ids = HashMap::new();
+        let mut result = Ok(());
+
+        for item in items.iter_mut() {
+            item.traverse_mut(&mut |i| {
+                if result.is_err() {
+                    return;
+                }
+
+                let id = match self.allocate_internal_id() {
+                    Ok(id) => id,
+                    Err(e) => return result = Err(e),
+                };
+                new_ids.insert(i.internal_id, id);
+
+                i.internal_id = id;
+                i.ref_id = if i.state.is_closed() {
+                    None
+                } else {
+                    match self.allocate_ref_id() {
+                        Ok(id) => Some(id),
+                        Err(e) => return result = Err(e),
+                    }
+                };
+            });
+        }
+        result?;
+
+        for item in items.iter_mut() {
+            item.traverse_mut(&mut |i| {
+                i.blocked_by = i
+                    .blocked_by
+                    .iter()
+                    .filter_map(|id| new_ids.get(id).copied())
+                    .collect();
+            });
+        }
+
+        Ok(())
+    }
+
     /// Constructs and adds an item to the root of the database.
     ///
-    /// Returns the item's RefId.
+    /// Returns the item's RefId, or an error if there are no free IDs left for it (see [`allocate_ref_id`] and
+    /// [`allocate_internal_id`]).
+    ///
+    /// [`allocate_ref_id`]: ItemManager::allocate_ref_id
+    /// [`allocate_internal_id`]: ItemManager::allocate_internal_id
     pub fn add_item_on_root(
         &mut self,
         name: &str,
@@ -249,15 +793,11 @@ impl ItemManager {
         state: ItemState,
         description: String,
         children: Vec<Item>,
-    ) -> RefId {
-        // Might crash with an overflow but seriously, who is gonna have 4,294,967,296 items in a lifetime?
-        let free_ref_id = utils::misc::find_lowest_free_value(&self.ref_ids);
-        self.ref_ids.insert(free_ref_id);
-
-        let free_internal_id = utils::misc::find_highest_free_value(&self.internal_ids);
-        self.internal_ids.insert(free_internal_id);
+    ) -> Result<RefId, Error> {
+        let (free_ref_id, free_internal_id) = self.allocate_ids(true)?;
+        let free_ref_id = free_ref_id.unwrap();
 
-        self.data.push(Item::new(
+        let item = Item::new(
             Some(free_ref_id),
             free_internal_id,
             name,
@@ -265,9 +805,11 @@ impl ItemManager {
             state,
             description,
             children,
-        ));
+        );
+        self.index.get_mut().insert(&item, vec![self.data.len()]);
+        self.data.push(item);
 
-        RefId(free_ref_id)
+        Ok(RefId(free_ref_id))
     }
 
     pub fn add_child<Q>(
@@ -278,31 +820,41 @@ impl ItemManager {
         state: ItemState,
         description: String,
         children: Vec<Item>,
-    ) -> Result<RefId, ()>
+    ) -> Result<RefId, Error>
     where
         Self: Searchable<Q, Data = Item>,
     {
-        let free_ref_id = utils::misc::find_lowest_free_value(self.ref_ids());
-        self.ref_ids.insert(free_ref_id);
-
-        let free_internal_id = utils::misc::find_highest_free_value(self.internal_ids());
-        self.internal_ids.insert(free_internal_id.into());
-
-        if let Some(i) = self.find_mut(query) {
-            i.children.push(Item::new(
-                Some(free_ref_id),
-                free_internal_id,
-                name,
-                context,
-                state,
-                description,
-                children,
-            ));
+        // the parent is found before the IDs are reserved, so they aren't taken if it doesn't exist
+        let parent = self
+            .find(query)
+            .ok_or_else(|| Error::NotFound("could not find the parent item".into()))?
+            .internal_id;
 
-            Ok(RefId(free_ref_id))
-        } else {
-            Err(())
+        let (free_ref_id, free_internal_id) = self.allocate_ids(true)?;
+        let free_ref_id = free_ref_id.unwrap();
+
+        let i = Searchable::<InternalId>::find_mut(self, InternalId(parent)).unwrap();
+        let position = i.children.len();
+
+        i.children.push(Item::new(
+            Some(free_ref_id),
+            free_internal_id,
+            name,
+            context,
+            state,
+            description,
+            children,
+        ));
+
+        // the parent was just found, so its position is up to date
+        let index = self.index.get_mut();
+        if let Some(mut path) = index.get(IndexKey::Internal(parent)).map(<[usize]>::to_vec) {
+            path.push(position);
+            let child = item_at(&self.data, &path).unwrap();
+            index.insert(child, path);
         }
+
+        Ok(RefId(free_ref_id))
     }
 
     pub fn surface_ref_ids(&self) -> Vec<RefId> {
@@ -337,6 +889,296 @@ impl ItemManager {
         search(&mut self.data, ref_id)
     }
 
+    /// Finds the first item that matches `predicate`, returning the list that contains it (either the root or the
+    /// children of its parent) and its index on the list.
+    pub fn locate_mut<F>(&mut self, predicate: F) -> Option<(&mut Vec<Item>, usize)>
+    where
+        F: Fn(&Item) -> bool,
+    {
+        fn search<'a, F>(
+            items: &'a mut Vec<Item>,
+            predicate: &F,
+        ) -> Option<(&'a mut Vec<Item>, usize)>
+        where
+            F: Fn(&Item) -> bool,
+        {
+            if let Some(index) = items.iter().position(|item| predicate(item)) {
+                return Some((items, index));
+            }
+
+            for item in items.iter_mut() {
+                if let Some(found) = search(&mut item.children, predicate) {
+                    return Some(found);
+                }
+            }
+
+            None
+        }
+
+        search(&mut self.data, &predicate)
+    }
+
+    /// Returns the item with the internal ID `id` along with its ancestors, starting from the root one.
+    pub fn ancestry(&self, id: InternalId) -> Option<Vec<&Item>> {
+        fn search<'a>(items: &'a [Item], id: u32, chain: &mut Vec<&'a Item>) -> bool {
+            for item in items {
+                chain.push(item);
+
+                if item.internal_id == id || search(&item.children, id, chain) {
+                    return true;
+                }
+
+                chain.pop();
+            }
+
+            false
+        }
+
+        let mut chain = Vec::new();
+        if search(&self.data, id.0, &mut chain) {
+            Some(chain)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the root items marked as projects.
+    pub fn projects(&self) -> impl Iterator<Item = &Item> {
+        self.data.iter().filter(|item| item.project)
+    }
+
+    /// Finds the project named `query` (ignoring case), or the one with `query` as its reference ID.
+    pub fn find_project(&self, query: &str) -> Option<&Item> {
+        let query = query.trim();
+
+        self.projects().find(|item| {
+            item.name.eq_ignore_ascii_case(query)
+                || (item.ref_id.is_some() && query.parse::<u32>().ok() == item.ref_id)
+        })
+    }
+
+    /// Whether `item` has a blocker that isn't closed yet. Blockers that don't exist anymore are ignored.
+    pub fn is_blocked(&self, item: &Item) -> bool {
+        item.blocked_by.iter().any(|&id| {
+            self.find(InternalId(id))
+                .map_or(false, |blocker| !blocker.state.is_closed())
+        })
+    }
+
+    /// Whether `item` is blocked by `other`, either directly or through a chain of blockers.
+    pub fn depends_on(&self, item: InternalId, other: InternalId) -> bool {
+        let mut visited = HashSet::new();
+        let mut pending = vec![item.0];
+
+        while let Some(id) = pending.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+
+            if let Some(current) = self.find(InternalId(id)) {
+                for &blocker in &current.blocked_by {
+                    if blocker == other.0 {
+                        return true;
+                    }
+
+                    pending.push(blocker);
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Returns every item that matches `predicate`, in tree order.
+    ///
+    /// Children of a matched item aren't tested.
+    pub fn find_where<F>(&self, predicate: F) -> Vec<&Item>
+    where
+        F: Fn(&Item) -> bool,
+    {
+        fn search<'a, F>(items: &'a [Item], predicate: &F, found: &mut Vec<&'a Item>)
+        where
+            F: Fn(&Item) -> bool,
+        {
+            for item in items {
+                if predicate(item) {
+                    found.push(item);
+                } else {
+                    search(&item.children, predicate, found);
+                }
+            }
+        }
+
+        let mut found = Vec::new();
+        search(&self.data, &predicate, &mut found);
+
+        found
+    }
+
+    /// Removes every item that matches `predicate` (along with its children) and returns them, in tree order.
+    ///
+    /// Children of a removed item aren't tested.
+    pub fn extract_where<F>(&mut self, predicate: F) -> Vec<Item>
+    where
+        F: Fn(&Item) -> bool,
+    {
+        fn search<F>(items: &mut Vec<Item>, predicate: &F, extracted: &mut Vec<Item>)
+        where
+            F: Fn(&Item) -> bool,
+        {
+            for mut item in std::mem::take(items) {
+                if predicate(&item) {
+                    extracted.push(item);
+                } else {
+                    search(&mut item.children, predicate, extracted);
+                    items.push(item);
+                }
+            }
+        }
+
+        let mut extracted = Vec::new();
+        search(&mut self.data, &predicate, &mut extracted);
+
+        extracted
+    }
+
+    /// Changes the context of every item whose context is `old` (after normalization) to `new`, returning how many
+    /// items were changed.
+    pub fn rename_context(&mut self, old: &str, new: &str) -> usize {
+        let old = Item::validate_context(old);
+        let mut changed = 0;
+
+        for root in self.data.iter_mut() {
+            root.traverse_mut(&mut |item| {
+                if old.is_some() && item.context().and_then(Item::validate_context) == old {
+                    item.set_context(new);
+                    changed += 1;
+                }
+            });
+        }
+
+        changed
+    }
+
+    /// Marks the reference IDs used by `items` and their children as free, so they can be used by new items.
+    pub fn release_ref_ids(&mut self, items: &[Item]) {
+        for item in items {
+            item.traverse(&mut |i| {
+                if let Some(id) = i.ref_id {
+                    self.ref_ids.remove(&id);
+                }
+            });
+        }
+    }
+
+    /// Merges the item `removed` into `kept` (see [`Item::absorb`]), taking the name of the removed one if
+    /// `keep_removed_name` is set. The items blocked by the removed item are blocked by the merged one afterwards.
+    pub fn merge(
+        &mut self,
+        kept: RefId,
+        removed: RefId,
+        keep_removed_name: bool,
+    ) -> Result<(), Error> {
+        let (kept_item, removed_item) = match (self.find(kept), self.find(removed)) {
+            (Some(k), Some(r)) => (k, r),
+            _ => return Err(Error::NotFound("could not find the items to merge".into())),
+        };
+
+        if kept == removed || removed_item.has_child(kept_item) {
+            return Err(Error::Conflict(
+                "an item can't be merged with itself or into one of its descendants".into(),
+            ));
+        }
+
+        let mut removed = self.try_remove(removed).unwrap();
+        // the children keep their IDs, since they're only moved
+        let children = std::mem::take(&mut removed.children);
+        self.release_ref_ids(std::slice::from_ref(&removed));
+        removed.children = children;
+
+        let removed_id = removed.internal_id;
+        let item = self.find_mut(kept).unwrap();
+        let kept_id = item.internal_id;
+
+        if keep_removed_name {
+            item.name = std::mem::take(&mut removed.name);
+        }
+        item.absorb(removed);
+
+        // the merged item isn't blocked by itself, and the ones blocked by the removed item are blocked by it now
+        for root in self.data.iter_mut() {
+            root.traverse_mut(&mut |i| {
+                for blocker in i.blocked_by.iter_mut() {
+                    if *blocker == removed_id {
+                        *blocker = kept_id;
+                    }
+                }
+
+                let own_id = i.internal_id;
+                let mut seen = HashSet::new();
+                i.blocked_by
+                    .retain(|&blocker| blocker != own_id && seen.insert(blocker));
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Reassigns the reference IDs so that they're contiguous and follow the order of the tree, starting from zero.
+    ///
+    /// Returns the old and new IDs of the items whose IDs changed.
+    pub fn renumber_ref_ids(&mut self) -> Vec<(u32, u32)> {
+        let mut changes = Vec::new();
+        let mut next = 0;
+
+        for root in self.data.iter_mut() {
+            root.traverse_mut(&mut |item| {
+                if let Some(old) = item.ref_id {
+                    if old != next {
+                        changes.push((old, next));
+                    }
+
+                    item.ref_id = Some(next);
+                    next += 1;
+                }
+            });
+        }
+
+        self.ref_ids = (0..next).collect();
+
+        changes
+    }
+
+    /// Changes the reference ID of the item with the internal ID `id` to `new`, or removes it if `new` is `None`. Fails
+    /// if another item already has `new`.
+    pub fn set_ref_id(&mut self, id: InternalId, new: Option<u32>) -> Result<(), Error> {
+        let old = self
+            .find(id)
+            .ok_or_else(|| {
+                Error::NotFound(format!("could not find item with InternalId = {}", id.0))
+            })?
+            .ref_id;
+
+        if let Some(new) = new {
+            if old != Some(new) && self.ref_ids.contains(&new) {
+                return Err(Error::Conflict(format!(
+                    "the reference ID {} is already in use",
+                    new
+                )));
+            }
+        }
+
+        if let Some(old) = old {
+            self.ref_ids.remove(&old);
+        }
+        if let Some(new) = new {
+            self.ref_ids.insert(new);
+        }
+        self.find_mut(id).unwrap().ref_id = new;
+
+        Ok(())
+    }
+
     pub fn first_invalid_ref_id<'a, I>(&self, ids: I) -> Option<RefId>
     where
         I: Iterator<Item = &'a u32>,
@@ -351,7 +1193,7 @@ impl ItemManager {
         None
     }
 
-    pub fn swap<T, E>(&mut self, query_1: T, query_2: E) -> Result<(), String>
+    pub fn swap<T, E>(&mut self, query_1: T, query_2: E) -> Result<(), Error>
     where
         Self: Searchable<T, Data = Item> + Searchable<E, Data = Item>,
     {
@@ -362,13 +1204,13 @@ impl ItemManager {
             // try to get first item
             let first: *mut Item = match self.find_mut(query_1) {
                 Some(m) => m,
-                None => return Err(format!("first query could not be found")),
+                None => return Err(Error::NotFound(format!("first query could not be found"))),
             };
 
             // try to get first item
             let second: *mut Item = match self.find_mut(query_2) {
                 Some(m) => m,
-                None => return Err(format!("second query could not be found")),
+                None => return Err(Error::NotFound(format!("second query could not be found"))),
             };
 
             // check if swap is needed and do the thing
@@ -376,37 +1218,323 @@ impl ItemManager {
                 std::ptr::swap(first, second);
                 Ok(())
             } else {
-                Err(format!("first and second queries are the same item"))
+                Err(Error::Conflict(format!(
+                    "first and second queries are the same item"
+                )))
             }
         }
     }
 
-    pub fn change_item_state<Q, F>(&mut self, id: Q, mapper: F) -> Result<(), ()>
+    /// Changes the state of an item to the one returned by `mapper`. Items that get closed lose their reference ID, and
+    /// items that get reopened receive a new one.
+    ///
+    /// Fails if the item can't be found or if there's no free reference ID for it, in which case it's left unchanged.
+    pub fn change_item_state<Q, F>(&mut self, id: Q, mapper: F) -> Result<(), Error>
     where
         Self: Searchable<Q, Data = Item>,
         F: FnOnce(ItemState) -> ItemState,
     {
-        let item = self.find_mut(id).ok_or(())?;
-        let new_state = mapper(item.state);
+        let item = self
+            .find(id)
+            .ok_or_else(|| Error::NotFound("could not find the item".into()))?;
+        let new_state = mapper(item.state.clone());
+        let internal_id = item.internal_id;
+        let old_ref_id = item.ref_id;
+
+        let ref_id = if new_state.is_closed() {
+            // closed items don't keep their IDs, so they can be taken by new items
+            if let Some(id) = old_ref_id {
+                self.ref_ids.remove(&id);
+            }
+
+            None
+        } else {
+            match old_ref_id {
+                Some(id) => Some(id),
+                None => Some(self.allocate_ref_id()?),
+            }
+        };
+
+        let item =
+            <Self as Searchable<InternalId>>::find_mut(self, InternalId(internal_id)).unwrap();
+        item.ref_id = ref_id;
+        item.set_state(new_state);
+
+        Ok(())
+    }
+}
+
+impl ItemManager {
+    /// Replaces the items on `selection` (and their children) with the contents of an edited outline.
+    ///
+    /// Nodes with an internal ID take the place of the original items, keeping their description and other fields not
+    /// represented on the outline. Top-level nodes that don't refer to a selected item are placed as siblings after the
+    /// previous selected one, and selected items missing from the top level are removed from their original position.
+    ///
+    /// Fails if there are no free IDs left for the new items, in which case nothing is changed.
+    pub fn apply_outline(
+        &mut self,
+        selection: &[InternalId],
+        nodes: Vec<OutlineNode>,
+    ) -> Result<(), Error> {
+        fn flatten(mut item: Item, pool: &mut HashMap<u32, Item>) {
+            for child in std::mem::take(&mut item.children) {
+                flatten(child, pool);
+            }
+
+            pool.insert(item.internal_id, item);
+        }
+
+        fn build(
+            manager: &mut ItemManager,
+            node: OutlineNode,
+            pool: &mut HashMap<u32, Item>,
+        ) -> Result<Item, Error> {
+            let mut children = Vec::with_capacity(node.children.len());
+            for child in node.children {
+                children.push(build(manager, child, pool)?);
+            }
 
-        if new_state == ItemState::Done {
-            item.ref_id = None;
+            match node.internal_id.and_then(|id| pool.remove(&id)) {
+                Some(mut item) => {
+                    item.set_name(&node.name);
+                    item.set_context(&node.context);
+
+                    if node.state.is_closed() {
+                        item.ref_id = None;
+                    } else if item.ref_id.is_none() {
+                        item.ref_id = Some(manager.allocate_ref_id()?);
+                    }
+
+                    // The outline doesn't show what an item is waiting on, so keep it if it's still waiting.
+                    if !matches!(
+                        (&item.state, &node.state),
+                        (ItemState::Waiting(_), ItemState::Waiting(None))
+                    ) {
+                        item.set_state(node.state);
+                    }
+
+                    item.children = children;
+
+                    Ok(item)
+                }
+                None => manager.create_item(
+                    &node.name,
+                    &node.context,
+                    node.state,
+                    String::new(),
+                    children,
+                ),
+            }
+        }
+
+        fn rebuild(items: &mut Vec<Item>, plan: &mut HashMap<u32, Vec<Item>>) {
+            for mut item in std::mem::take(items) {
+                if let Some(replacement) = plan.remove(&item.internal_id) {
+                    items.extend(replacement);
+                } else {
+                    rebuild(&mut item.children, plan);
+                    items.push(item);
+                }
+            }
         }
 
-        item.state = new_state;
+        let mut pool = HashMap::new();
+        for &id in selection {
+            if let Some(item) = self.find(id) {
+                flatten(item.clone(), &mut pool);
+            }
+        }
+
+        let selected: HashSet<u32> = selection.iter().map(|id| id.0).collect();
+        let mut plan: HashMap<u32, Vec<Item>> =
+            selected.iter().map(|&id| (id, Vec::new())).collect();
+        let mut leading = Vec::new();
+        let mut first_head = None;
+        let mut current_head = None;
+
+        for node in nodes {
+            let head = node.internal_id.filter(|id| selected.contains(id));
+            let item = build(self, node, &mut pool)?;
+
+            if head.is_some() {
+                current_head = head;
+                first_head = first_head.or(head);
+            }
+
+            match current_head {
+                Some(id) => plan.get_mut(&id).unwrap().push(item),
+                None => leading.push(item),
+            }
+        }
+
+        // Items that come before any selected item go right before the first one that was kept, or in the place of
+        // the first selected item if none of them were.
+        if let Some(target) = first_head.or_else(|| selection.first().map(|id| id.0)) {
+            let entry = plan.get_mut(&target).unwrap();
+            leading.append(entry);
+            *entry = leading;
+        }
+
+        rebuild(&mut self.data, &mut plan);
 
         Ok(())
     }
 }
 
 impl ItemManager {
+    #[allow(dead_code)]
     #[inline(always)]
     pub fn internal_ids(&self) -> &HashSet<u32> {
         &self.internal_ids
     }
 
+    #[allow(dead_code)]
     #[inline(always)]
     pub fn ref_ids(&self) -> &HashSet<u32> {
         &self.ref_ids
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn item(ref_id: Option<u32>, internal_id: u32) -> Item {
+        Item::new(
+            ref_id,
+            internal_id,
+            "item",
+            "",
+            ItemState::Todo,
+            String::new(),
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn internal_ids_are_reused_once_the_highest_is_taken() {
+        let mut manager =
+            ItemManager::new(vec![item(Some(0), 0), item(Some(1), u32::MAX - 1)]).unwrap();
+
+        assert_eq!(manager.allocate_internal_id().unwrap(), u32::MAX);
+        assert_eq!(manager.allocate_internal_id().unwrap(), 1);
+        assert_eq!(manager.allocate_internal_id().unwrap(), 2);
+    }
+
+    #[test]
+    fn ref_ids_fill_the_gaps_first() {
+        let mut manager = ItemManager::new(vec![item(Some(0), 0), item(Some(2), 1)]).unwrap();
+
+        assert_eq!(manager.allocate_ref_id().unwrap(), 1);
+        assert_eq!(manager.allocate_ref_id().unwrap(), 3);
+    }
+
+    #[test]
+    fn ref_ids_run_out_once_all_of_them_are_taken() {
+        let mut manager = ItemManager::new(vec![item(Some(0), 0), item(Some(2), 1)]).unwrap();
+        manager.max_ref_id = 2;
+
+        assert_eq!(manager.allocate_ref_id().unwrap(), 1);
+        assert!(matches!(manager.allocate_ref_id(), Err(Error::Conflict(_))));
+
+        manager.set_id_strategy(IdStrategy::Random { max: 99 });
+        assert!(matches!(manager.allocate_ref_id(), Err(Error::Conflict(_))));
+    }
+
+    #[test]
+    fn the_ref_ids_of_closed_items_are_reused() {
+        let mut manager = ItemManager::new(vec![item(Some(0), 0), item(Some(1), 1)]).unwrap();
+
+        manager
+            .change_item_state(InternalId(0), |_| ItemState::Done)
+            .unwrap();
+        assert_eq!(manager.data[0].ref_id, None);
+        assert_eq!(manager.allocate_ref_id().unwrap(), 0);
+
+        // reopening an item gives it a new ID, since the old one was taken
+        manager
+            .change_item_state(InternalId(0), |_| ItemState::Todo)
+            .unwrap();
+        assert_eq!(manager.data[0].ref_id, Some(2));
+    }
+
+    #[test]
+    fn missing_items_and_taken_ids_are_told_apart() {
+        let mut manager = ItemManager::new(vec![item(Some(0), 0), item(Some(1), 1)]).unwrap();
+
+        assert!(matches!(
+            manager.set_ref_id(InternalId(5), Some(2)),
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(
+            manager.set_ref_id(InternalId(0), Some(1)),
+            Err(Error::Conflict(_))
+        ));
+
+        // the IDs of a child whose parent doesn't exist aren't kept
+        assert!(matches!(
+            manager.add_child(
+                InternalId(5),
+                "child",
+                "",
+                ItemState::Todo,
+                String::new(),
+                Vec::new()
+            ),
+            Err(Error::NotFound(_))
+        ));
+        assert_eq!(manager.ref_ids.len(), 2);
+        assert_eq!(manager.internal_ids.len(), 2);
+    }
+
+    #[test]
+    fn merged_items_keep_the_fields_of_both() {
+        let mut first = item(Some(0), 0);
+        first.tags = vec!["home".into(), "urgent".into()];
+        first.blocked_by = vec![3];
+
+        let mut second = item(Some(1), 1);
+        second.tags = vec!["urgent".into(), "errands".into()];
+        second.blocked_by = vec![0, 4];
+        second.set_context("town");
+        second.due = NaiveDate::from_ymd_opt(2020, 12, 31);
+        second.url = Some("https://example.com".into());
+        second.starred = true;
+        second.children.push(item(Some(2), 2));
+
+        let mut blocked = item(Some(5), 5);
+        blocked.blocked_by = vec![1, 0];
+
+        let mut manager = ItemManager::new(vec![
+            first,
+            second,
+            item(Some(3), 3),
+            item(Some(4), 4),
+            blocked,
+        ])
+        .unwrap();
+        manager.merge(RefId(0), RefId(1), true).unwrap();
+
+        assert!(manager.find(RefId(1)).is_none());
+        let merged = manager.find(RefId(0)).unwrap();
+        assert_eq!(merged.tags, ["home", "urgent", "errands"]);
+        assert_eq!(merged.blocked_by, [3, 4]);
+        assert_eq!(merged.context(), Some("town"));
+        assert_eq!(merged.due, NaiveDate::from_ymd_opt(2020, 12, 31));
+        assert_eq!(merged.url.as_deref(), Some("https://example.com"));
+        assert!(merged.starred);
+        assert_eq!(merged.children[0].ref_id, Some(2));
+        assert_eq!(manager.find(RefId(5)).unwrap().blocked_by, [0]);
+
+        assert!(matches!(
+            manager.merge(RefId(2), RefId(0), false),
+            Err(Error::Conflict(_))
+        ));
+        assert!(matches!(
+            manager.merge(RefId(0), RefId(1), false),
+            Err(Error::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,197 @@
+//! Stores the man page generator, which renders the clap definitions of the program as troff.
+//!
+//! There's one page for the program (`itmn.1`) and one for each of its subcommands, named after the path to them (like
+//! `itmn-sel-ref-id-done.1`). Hidden subcommands and arguments are left out.
+
+use clap::{App, AppSettings, Arg, ArgSettings};
+
+/// The grammar of selection ranges, shown on the pages of the subcommands that take one (see the `selection` module).
+const RANGE_GRAMMAR: &str = r#"range     = term ("," term)*
+term      = "!"? atom
+atom      = ID                      one item, by reference ID
+          | ID ".." ID | ID "-" ID  every item between the two IDs, inclusive
+          | ID ".." | ".." ID       every item from/until an ID
+          | ID "/*"                 an item and all of its descendants
+          | "all"                   every item with a reference ID
+          | "state:" STATE          every item on that state
+          | "ctx:" CONTEXT          every item on that context (.none for none)"#;
+
+/// A generated man page.
+pub struct Page {
+    /// The file name of the page, like `itmn-add.1`.
+    pub file_name: String,
+    pub contents: String,
+}
+
+/// Renders the pages of `app` and all of its visible subcommands, using `description` as the summary of the program.
+pub fn render_all(app: &App, description: &str) -> Vec<Page> {
+    let mut pages = Vec::new();
+    render_tree(app, &[], description, &mut pages);
+    pages
+}
+
+fn render_tree(app: &App, parents: &[&str], description: &str, pages: &mut Vec<Page>) {
+    let mut path = parents.to_vec();
+    path.push(app.get_name());
+
+    pages.push(Page {
+        file_name: format!("{}.1", path.join("-")),
+        contents: render(app, &path, description),
+    });
+
+    for sub in visible_subcommands(app) {
+        render_tree(sub, &path, description, pages);
+    }
+}
+
+fn visible_subcommands<'a, 'help>(app: &'a App<'help>) -> impl Iterator<Item = &'a App<'help>> {
+    app.get_subcommands()
+        .filter(|sub| !sub.is_set(AppSettings::Hidden))
+}
+
+fn visible_arguments<'a, 'help>(app: &'a App<'help>) -> impl Iterator<Item = &'a Arg<'help>> {
+    app.get_arguments()
+        .filter(|arg| !arg.is_set(ArgSettings::Hidden))
+}
+
+/// Renders the page of `app`, which is reached through the commands on `path`.
+fn render(app: &App, path: &[&str], description: &str) -> String {
+    let title = path.join("-");
+    let mut out = String::new();
+
+    out.push_str(&format!(
+        ".TH \"{}\" \"1\" \"\" \"itmn {}\" \"itmn manual\"\n",
+        escape(&title.to_uppercase()),
+        env!("CARGO_PKG_VERSION")
+    ));
+
+    out.push_str(".SH NAME\n");
+    let about = match app.get_about() {
+        Some(about) if path.len() > 1 => about,
+        _ => description,
+    };
+    out.push_str(&format!("{} \\- {}\n", escape(&title), escape(about)));
+
+    // the indexes of positional arguments are only assigned once the app is built, so go by the lack of flags instead
+    let (positionals, options): (Vec<&Arg>, Vec<&Arg>) = visible_arguments(app)
+        .partition(|arg| arg.get_short().is_none() && arg.get_long().is_none());
+
+    out.push_str(".SH SYNOPSIS\n");
+    out.push_str(&format!("\\fB{}\\fR", escape(&path.join(" "))));
+    if !options.is_empty() {
+        out.push_str(" [\\fIoptions\\fR]");
+    }
+    for arg in &positionals {
+        if arg.is_set(ArgSettings::Required) {
+            out.push_str(&format!(" \\fI{}\\fR", escape(arg.get_name())));
+        } else {
+            out.push_str(&format!(" [\\fI{}\\fR]", escape(arg.get_name())));
+        }
+    }
+    if app.has_subcommands() {
+        out.push_str(" [\\fIsubcommand\\fR]");
+    }
+    out.push('\n');
+
+    let aliases: Vec<&str> = app.get_visible_aliases().collect();
+    if !aliases.is_empty() {
+        out.push_str(".SH ALIASES\n");
+        out.push_str(&format!("{}\n", escape(&aliases.join(", "))));
+    }
+
+    if !positionals.is_empty() {
+        out.push_str(".SH ARGUMENTS\n");
+        for arg in &positionals {
+            out.push_str(&format!(".TP\n\\fI{}\\fR\n", escape(arg.get_name())));
+            push_about(&mut out, arg);
+        }
+    }
+
+    if !options.is_empty() {
+        out.push_str(".SH OPTIONS\n");
+        for arg in &options {
+            let mut names = Vec::new();
+            if let Some(short) = arg.get_short() {
+                names.push(format!("\\fB\\-{}\\fR", escape(&short.to_string())));
+            }
+            if let Some(long) = arg.get_long() {
+                names.push(format!("\\fB\\-\\-{}\\fR", escape(long)));
+            }
+
+            out.push_str(&format!(".TP\n{}", names.join(", ")));
+            if arg.is_set(ArgSettings::TakesValue) {
+                out.push_str(&format!(" \\fI{}\\fR", escape(arg.get_name())));
+            }
+            out.push('\n');
+            push_about(&mut out, arg);
+        }
+    }
+
+    if app.has_subcommands() {
+        out.push_str(".SH SUBCOMMANDS\n");
+        for sub in visible_subcommands(app) {
+            out.push_str(&format!(".TP\n\\fB{}\\fR\n", escape(sub.get_name())));
+            if let Some(about) = sub.get_about() {
+                out.push_str(&format!("{}\n", escape(about)));
+            }
+        }
+    }
+
+    if positionals.iter().any(|arg| arg.get_name() == "range") {
+        out.push_str(".SH RANGES\n");
+        out.push_str("A range is a comma\\-separated list of terms:\n");
+        out.push_str(".PP\n.nf\n.RS 4\n");
+        for line in RANGE_GRAMMAR.lines() {
+            out.push_str(&format!("{}\n", escape(line)));
+        }
+        out.push_str(".RE\n.fi\n.PP\n");
+        out.push_str(concat!(
+            "Terms prefixed by \\fB!\\fR remove items from the selection instead of adding them; if a range only has ",
+            "those, they're removed from \\fBall\\fR. STATE is todo, done, note, waiting or cancelled.\n",
+            ".PP\n",
+            "Examples: \\fB1\\-20,!7\\fR, \\fB5..\\fR, \\fB12/*,!13\\fR and \\fBstate:todo,!ctx:work\\fR.\n",
+        ));
+
+        if app.has_subcommands() {
+            out.push_str(concat!(
+                ".PP\n",
+                "The whole range can be prefixed by \\fIprofile\\fB:\\fR to select items from another profile, ",
+                "like \\fBwork:3,5\\fR.\n",
+            ));
+        }
+    }
+
+    if path.len() > 1 {
+        out.push_str(".SH SEE ALSO\n");
+        out.push_str(&format!(
+            "\\fB{}\\fR(1)\n",
+            escape(&path[..path.len() - 1].join("-"))
+        ));
+    }
+
+    out
+}
+
+fn push_about(out: &mut String, arg: &Arg) {
+    if let Some(about) = arg.get_about() {
+        out.push_str(&format!("{}\n", escape(about)));
+    }
+    if let Some(values) = arg.get_possible_values() {
+        out.push_str(&format!(
+            "Possible values: {}.\n",
+            escape(&values.join(", "))
+        ));
+    }
+}
+
+/// Escapes the characters that troff would otherwise interpret.
+fn escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+
+    // lines starting with these would be read as requests
+    if escaped.starts_with('.') || escaped.starts_with('\'') {
+        format!("\\&{}", escaped)
+    } else {
+        escaped
+    }
+}
@@ -0,0 +1,406 @@
+//! Stores the plain-text outline format used to edit items on an external editor.
+//!
+//! Each item takes one line, indented according to its depth:
+//!
+//! ```text
+//! o Buy groceries @errands [i12]
+//!   - Check the fridge first [i13]
+//!   o Milk
+//! ```
+//!
+//! The first character is the state (the same symbols used on reports), followed by the name, an optional
+//! context and the internal ID of the item between brackets. Lines without an internal ID are new items. Empty lines
+//! and lines starting with `#` are ignored.
+//!
+//! Words of the name that start with `@` are written as `\@`, so they aren't taken as the context; a word that already
+//! starts with backslashes before the `@` gets one more.
+//!
+//! There's also a plain variant, meant for quickly capturing new items, where lines only have the name and the optional
+//! context, and the indentation can have any width (see [`parse_plain`]).
+
+use crate::item::{Item, ItemState};
+
+/// A node parsed from an outline document.
+#[derive(Debug)]
+pub struct OutlineNode {
+    /// The internal ID of the item this node refers to, if it's not a new item.
+    pub internal_id: Option<u32>,
+    pub state: ItemState,
+    pub name: String,
+    /// The context, or an empty string if there's none.
+    pub context: String,
+    pub children: Vec<OutlineNode>,
+}
+
+/// Renders `items` (and all their children) into an outline document.
+pub fn serialize(items: &[&Item], spaces_per_indent: usize) -> String {
+    fn write_item(item: &Item, indent: usize, spaces_per_indent: usize, out: &mut String) {
+        out.push_str(&" ".repeat(indent * spaces_per_indent));
+        out.push(item.state.symbol());
+        out.push(' ');
+        out.push_str(&escape_name(&item.name));
+
+        if let Some(ctx) = item.context() {
+            out.push_str(&format!(" @{}", ctx));
+        }
+
+        out.push_str(&format!(" [i{}]\n", item.internal_id));
+
+        for child in &item.children {
+            write_item(child, indent + 1, spaces_per_indent, out);
+        }
+    }
+
+    let mut out = String::from(concat!(
+        "# Edit names, states (o/x/-/w/c), contexts and nesting; reorder and delete lines as needed.\n",
+        "# Lines without an [iN] tag will be added as new items.\n",
+    ));
+
+    for item in items {
+        write_item(item, 0, spaces_per_indent, &mut out);
+    }
+
+    out
+}
+
+/// Parses an outline document, returning the top-level nodes on success.
+pub fn parse(text: &str, spaces_per_indent: usize) -> Result<Vec<OutlineNode>, String> {
+    // Each element of the stack is the children list of the node at that depth.
+    let mut stack: Vec<Vec<OutlineNode>> = vec![Vec::new()];
+
+    for (lineno, line) in text.lines().enumerate().map(|(i, l)| (i + 1, l)) {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let indent_width: usize = line[..line.len() - trimmed.len()]
+            .chars()
+            .map(|c| if c == '\t' { spaces_per_indent } else { 1 })
+            .sum();
+
+        if indent_width % spaces_per_indent.max(1) != 0 {
+            return Err(format!(
+                "line {}: indentation is not a multiple of {} spaces",
+                lineno, spaces_per_indent
+            ));
+        }
+
+        let depth = indent_width / spaces_per_indent.max(1);
+        if depth >= stack.len() {
+            return Err(format!(
+                "line {}: item is indented more than one level past its parent",
+                lineno
+            ));
+        }
+
+        let node = parse_line(trimmed).map_err(|e| format!("line {}: {}", lineno, e))?;
+
+        // Close every node deeper than the current one.
+        while stack.len() > depth + 1 {
+            let children = stack.pop().unwrap();
+            stack.last_mut().unwrap().last_mut().unwrap().children = children;
+        }
+
+        stack.last_mut().unwrap().push(node);
+        stack.push(Vec::new());
+    }
+
+    while stack.len() > 1 {
+        let children = stack.pop().unwrap();
+        stack.last_mut().unwrap().last_mut().unwrap().children = children;
+    }
+
+    Ok(stack.pop().unwrap())
+}
+
+/// Parses a plain list of new items, returning the top-level nodes on success.
+///
+/// Each line is an item, optionally followed by a context. An item is a child of the closest item above it that is
+/// less indented, so any indentation width works as long as siblings line up; tabs count as four spaces. All of the
+/// nodes are on the todo state, and empty lines are ignored.
+pub fn parse_plain(text: &str) -> Result<Vec<OutlineNode>, String> {
+    let mut stack: Vec<Vec<OutlineNode>> = vec![Vec::new()];
+    // The indentation width of the last node on each depth.
+    let mut widths: Vec<usize> = Vec::new();
+
+    for (lineno, line) in text.lines().enumerate().map(|(i, l)| (i + 1, l)) {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        let indent_width: usize = line[..line.len() - line.trim_start().len()]
+            .chars()
+            .map(|c| if c == '\t' { 4 } else { 1 })
+            .sum();
+
+        while widths.last().map_or(false, |&w| indent_width < w) {
+            widths.pop();
+        }
+
+        if widths.last() != Some(&indent_width) {
+            widths.push(indent_width);
+        }
+
+        let depth = widths.len() - 1;
+
+        let (name, context) = split_context(trimmed);
+        if name.is_empty() {
+            return Err(format!("line {}: item has an empty name", lineno));
+        }
+
+        while stack.len() > depth + 1 {
+            let children = stack.pop().unwrap();
+            stack.last_mut().unwrap().last_mut().unwrap().children = children;
+        }
+
+        stack.last_mut().unwrap().push(OutlineNode {
+            internal_id: None,
+            state: ItemState::Todo,
+            name,
+            context,
+            children: Vec::new(),
+        });
+        stack.push(Vec::new());
+    }
+
+    while stack.len() > 1 {
+        let children = stack.pop().unwrap();
+        stack.last_mut().unwrap().last_mut().unwrap().children = children;
+    }
+
+    Ok(stack.pop().unwrap())
+}
+
+/// Splits a trailing ` @context` from `text`, returning the rest of it (unescaped, see [`escape_name`]) and the context
+/// (or an empty string). A lone `@context` is only a context, so the rest is empty.
+fn split_context(text: &str) -> (String, String) {
+    if let Some(context) = text.strip_prefix('@').filter(|c| !c.contains(' ')) {
+        return (String::new(), context.to_string());
+    }
+
+    if let Some(start) = text.rfind(" @") {
+        if !text[start + 2..].contains(' ') {
+            return (
+                unescape_name(text[..start].trim_end()),
+                text[start + 2..].to_string(),
+            );
+        }
+    }
+
+    (unescape_name(text), String::new())
+}
+
+/// Whether `word` is an `@` preceded by any amount of backslashes, which is escaped on names.
+fn is_escapable(word: &str) -> bool {
+    word.trim_start_matches('\\').starts_with('@')
+}
+
+/// Escapes the words of `name` that start with `@` (or with backslashes followed by one) with a backslash, so they
+/// aren't parsed as a context.
+fn escape_name(name: &str) -> String {
+    name.split(' ')
+        .map(|word| {
+            if is_escapable(word) {
+                format!("\\{}", word)
+            } else {
+                word.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Reverts [`escape_name`].
+fn unescape_name(name: &str) -> String {
+    name.split(' ')
+        .map(|word| match word.strip_prefix('\\') {
+            Some(unescaped) if is_escapable(unescaped) => unescaped,
+            _ => word,
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parses a single (already unindented) line of the outline.
+fn parse_line(line: &str) -> Result<OutlineNode, String> {
+    let mut chars = line.chars();
+
+    let state = match chars.next() {
+        Some('o') => ItemState::Todo,
+        Some('x') => ItemState::Done,
+        Some('-') => ItemState::Note,
+        Some('w') => ItemState::Waiting(None),
+        Some('c') => ItemState::Cancelled,
+        Some(c) => return Err(format!("unknown state character {:?}", c)),
+        None => unreachable!("empty lines are skipped"),
+    };
+
+    let mut rest = chars.as_str().trim();
+
+    let mut internal_id = None;
+    if rest.ends_with(']') {
+        if let Some(start) = rest.rfind("[i") {
+            let number = &rest[start + 2..rest.len() - 1];
+            internal_id = Some(
+                number
+                    .parse::<u32>()
+                    .map_err(|_| format!("invalid internal ID tag: {:?}", &rest[start..]))?,
+            );
+            rest = rest[..start].trim_end();
+        }
+    }
+
+    let (rest, context) = split_context(rest);
+
+    if rest.is_empty() {
+        return Err("item has an empty name".into());
+    }
+
+    Ok(OutlineNode {
+        internal_id,
+        state,
+        name: rest,
+        context,
+        children: Vec::new(),
+    })
+}
+
+/// Returns the internal IDs referenced on the outline and the amount of new items in it.
+pub fn tally(nodes: &[OutlineNode]) -> (Vec<u32>, usize) {
+    fn walk(nodes: &[OutlineNode], ids: &mut Vec<u32>, new: &mut usize) {
+        for node in nodes {
+            match node.internal_id {
+                Some(id) => ids.push(id),
+                None => *new += 1,
+            }
+
+            walk(&node.children, ids, new);
+        }
+    }
+
+    let mut ids = Vec::new();
+    let mut new = 0;
+    walk(nodes, &mut ids, &mut new);
+
+    (ids, new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outlines() {
+        let text = "# a comment\no Buy groceries @errands [i12]\n  - Check the fridge first [i13]\n\n  x Milk\nw Call back\nc Old plan\n";
+        let nodes = parse(text, 2).unwrap();
+
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(nodes[0].internal_id, Some(12));
+        assert_eq!(nodes[0].state, ItemState::Todo);
+        assert_eq!(nodes[0].name, "Buy groceries");
+        assert_eq!(nodes[0].context, "errands");
+        assert_eq!(nodes[0].children.len(), 2);
+        assert_eq!(nodes[0].children[0].internal_id, Some(13));
+        assert_eq!(nodes[0].children[0].state, ItemState::Note);
+        assert_eq!(nodes[0].children[1].internal_id, None);
+        assert_eq!(nodes[0].children[1].state, ItemState::Done);
+        assert_eq!(nodes[1].state, ItemState::Waiting(None));
+        assert_eq!(nodes[1].context, "");
+        assert_eq!(nodes[2].state, ItemState::Cancelled);
+        assert_eq!(tally(&nodes), (vec![12, 13], 3));
+
+        // tabs count as a whole level
+        assert_eq!(parse("o a\n\to b\n", 2).unwrap()[0].children.len(), 1);
+
+        assert!(parse("? a\n", 2)
+            .unwrap_err()
+            .contains("unknown state character"));
+        assert!(parse("o a [ix]\n", 2)
+            .unwrap_err()
+            .contains("invalid internal ID tag"));
+        assert!(parse("o a [i]\n", 2)
+            .unwrap_err()
+            .contains("invalid internal ID tag"));
+        assert!(parse("o [i1]\n", 2).unwrap_err().contains("empty name"));
+        assert!(parse("o a\n o b\n", 2)
+            .unwrap_err()
+            .contains("not a multiple"));
+        assert!(parse("o a\n    o b\n", 2)
+            .unwrap_err()
+            .contains("more than one level"));
+    }
+
+    #[test]
+    fn names_starting_with_at_signs() {
+        let items = vec![
+            Item::new(
+                None,
+                1,
+                "email bob @home",
+                "",
+                ItemState::Todo,
+                String::new(),
+                Vec::new(),
+            ),
+            Item::new(
+                None,
+                2,
+                "@home",
+                "work",
+                ItemState::Todo,
+                String::new(),
+                Vec::new(),
+            ),
+            Item::new(
+                None,
+                3,
+                "a \\@b \\c @d",
+                "",
+                ItemState::Todo,
+                String::new(),
+                Vec::new(),
+            ),
+        ];
+        let text = serialize(&items.iter().collect::<Vec<_>>(), 2);
+
+        assert!(text.contains("o email bob \\@home [i1]\n"));
+        assert!(text.contains("o \\@home @work [i2]\n"));
+        assert!(text.contains("o a \\\\@b \\c \\@d [i3]\n"));
+
+        let nodes = parse(&text, 2).unwrap();
+        for (node, item) in nodes.iter().zip(&items) {
+            assert_eq!(node.name, item.name);
+            assert_eq!(node.context, item.context().unwrap_or_default());
+        }
+
+        let nodes = parse_plain("Write to \\@support @work\n").unwrap();
+        assert_eq!(nodes[0].name, "Write to @support");
+        assert_eq!(nodes[0].context, "work");
+    }
+
+    #[test]
+    fn plain_outlines() {
+        let text = "Trip @travel\n   Book the hotel\n   Pack\n\tSocks\n\nCall mom\n";
+        let nodes = parse_plain(text).unwrap();
+
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].name, "Trip");
+        assert_eq!(nodes[0].context, "travel");
+        assert_eq!(nodes[0].state, ItemState::Todo);
+        assert_eq!(nodes[0].children.len(), 2);
+        assert_eq!(nodes[0].children[1].name, "Pack");
+        assert_eq!(nodes[0].children[1].children[0].name, "Socks");
+        assert_eq!(nodes[1].name, "Call mom");
+        assert_eq!(tally(&nodes), (vec![], 5));
+
+        assert!(parse_plain("a\n  @work\n")
+            .unwrap_err()
+            .contains("line 2: item has an empty name"));
+        // the same goes for the outlines of existing items
+        assert!(parse("o @work [i1]\n", 2)
+            .unwrap_err()
+            .contains("empty name"));
+    }
+}
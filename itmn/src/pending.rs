@@ -0,0 +1,83 @@
+//! Keeps an index of the pending items of the items file on the cache directory, so reports that only show pending
+//! items, like `itmn next`, don't have to parse the closed ones - which are most of the file once it's been used for a
+//! while.
+//!
+//! Only the top-level items without anything pending on them are left out, so the pending items keep all of their
+//! descendants (which are counted on their progress) and the ones under closed items are still found.
+//!
+//! The index is tied to the size and the modification time of the items file, and is made again when it's loaded
+//! after they change. Encrypted files shouldn't be indexed, since the index would keep their items in plain text.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::item::Item;
+use utils::debug;
+
+/// The pending items of an items file, along with what's needed to tell whether they're up to date.
+#[derive(Deserialize, Serialize)]
+struct PendingIndex {
+    len: u64,
+    modified: SystemTime,
+    /// The top-level items with pending items on them (including themselves).
+    items: Vec<Item>,
+}
+
+/// Loads the pending items of the items file on `file` from the index on `index_path`, or with `load_all` if the
+/// index is missing or outdated, in which case it's made again.
+pub fn load<F>(file: &Path, index_path: &Path, load_all: F) -> Result<Vec<Item>, String>
+where
+    F: FnOnce() -> Result<Vec<Item>, String>,
+{
+    let stamp = std::fs::metadata(file)
+        .and_then(|m| Ok((m.len(), m.modified()?)))
+        .ok();
+
+    let index: Option<PendingIndex> = std::fs::read_to_string(index_path)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok());
+
+    match (index, stamp) {
+        (Some(index), Some(stamp)) if (index.len, index.modified) == stamp => {
+            debug!("Loaded the pending items from {}", index_path.display());
+            return Ok(index.items);
+        }
+        _ => (),
+    }
+
+    let mut items = load_all()?;
+    items.retain(has_pending);
+
+    // the items file might have been created by `load_all`, so it's only stamped now
+    let stamp = std::fs::metadata(file).and_then(|m| Ok((m.len(), m.modified()?)));
+    if let Ok((len, modified)) = stamp {
+        let index = PendingIndex {
+            len,
+            modified,
+            items,
+        };
+
+        if let Err(e) = save(&index, index_path) {
+            debug!("Failed to save the pending items index: {}", e);
+        }
+
+        return Ok(index.items);
+    }
+
+    Ok(items)
+}
+
+fn save(index: &PendingIndex, path: &Path) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+
+    let contents = serde_json::to_string(index).map_err(|e| e.to_string())?;
+    utils::io::write_atomic(path, contents.as_bytes()).map_err(|e| e.to_string())
+}
+
+/// Whether `item` or any of its descendants isn't closed.
+fn has_pending(item: &Item) -> bool {
+    !item.state.is_closed() || item.children.iter().any(has_pending)
+}
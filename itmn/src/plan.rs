@@ -0,0 +1,56 @@
+//! Stores the daily plan, a short list of items picked with `itmn plan` to be done on the day, which `itmn today` shows.
+//!
+//! The plan is kept on the cache directory (see `plan_path`), since it's only useful for a day and, unlike the items,
+//! doesn't need to be synced or backed up. It only stores the internal IDs of the items.
+
+use chrono::{Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// The items planned for a day.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Plan {
+    pub date: NaiveDate,
+    /// The internal IDs of the items, in the order they were picked.
+    pub items: Vec<u32>,
+}
+
+impl Plan {
+    /// Creates an empty plan for today.
+    pub fn today() -> Self {
+        Self {
+            date: Local::now().date_naive(),
+            items: Vec::new(),
+        }
+    }
+
+    /// Whether the plan was made for today.
+    pub fn is_current(&self) -> bool {
+        self.date == Local::now().date_naive()
+    }
+
+    /// Loads the plan stored on `path`, if there's one.
+    pub fn load(path: &Path) -> Result<Option<Self>, String> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let contents =
+            std::fs::read_to_string(path).map_err(|e| format!("failed to read plan: {}", e))?;
+
+        serde_json::from_str(&contents)
+            .map(Some)
+            .map_err(|e| format!("failed to parse plan: {}", e))
+    }
+
+    /// Saves the plan to `path`, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("failed to create {:?}: {}", dir, e))?;
+        }
+
+        let contents = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, contents).map_err(|e| format!("failed to save plan: {}", e))
+    }
+}
@@ -0,0 +1,105 @@
+//! Stores the parser for the quick-add syntax, which lets the fields of a new item be written along with its name:
+//!
+//! ```text
+//! buy milk @errands #grocery !h ^friday
+//! ```
+//!
+//! Each whitespace-separated word of the name is checked for one of these prefixes:
+//!
+//! * `@` sets the context;
+//! * `#` adds a tag (words made only of digits, like `#12`, are kept on the name);
+//! * `!` followed by `h`, `m` or `l` sets the priority to `A`, `B` or `C`, respectively;
+//! * `^` sets the due date, written like on `--due` (`2020-12-31`, `today`, `tomorrow` or a weekday).
+//!
+//! The other words are kept on the name, in the same order.
+
+use chrono::NaiveDate;
+
+use crate::cli::parse_date;
+
+/// The fields extracted from a name written on the quick-add syntax.
+#[derive(Debug, Default)]
+pub struct QuickAdd {
+    /// The name, without the words that were parsed into fields.
+    pub name: String,
+    pub context: Option<String>,
+    pub tags: Vec<String>,
+    pub priority: Option<char>,
+    pub due: Option<NaiveDate>,
+}
+
+/// Parses `text` according to the syntax on the module documentation.
+pub fn parse(text: &str) -> Result<QuickAdd, String> {
+    let mut parsed = QuickAdd::default();
+    let mut name_words: Vec<&str> = Vec::new();
+
+    for word in text.split_whitespace() {
+        if let Some(context) = word.strip_prefix('@').filter(|c| !c.is_empty()) {
+            if parsed.context.is_some() {
+                return Err(format!("more than one context was given on {:?}", text));
+            }
+
+            parsed.context = Some(context.to_string());
+        } else if let Some(tag) = word
+            .strip_prefix('#')
+            .filter(|t| !t.is_empty() && !t.chars().all(|c| c.is_ascii_digit()))
+        {
+            if !parsed.tags.iter().any(|t| t == tag) {
+                parsed.tags.push(tag.to_string());
+            }
+        } else if let Some(priority) = word.strip_prefix('!').and_then(parse_priority) {
+            parsed.priority = Some(priority);
+        } else if let Some(due) = word.strip_prefix('^').filter(|d| !d.is_empty()) {
+            parsed.due = parse_date(due)?;
+        } else {
+            name_words.push(word);
+        }
+    }
+
+    if name_words.is_empty() {
+        return Err(format!(
+            "the item {:?} has no name left after parsing",
+            text
+        ));
+    }
+
+    parsed.name = name_words.join(" ");
+
+    Ok(parsed)
+}
+
+fn parse_priority(text: &str) -> Option<char> {
+    match text.to_lowercase().as_str() {
+        "h" => Some('A'),
+        "m" => Some('B'),
+        "l" => Some('C'),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quick_add() {
+        let parsed = parse("buy milk @errands #grocery #12 !h ^2020-12-31 #grocery !x").unwrap();
+
+        assert_eq!(parsed.name, "buy milk #12 !x");
+        assert_eq!(parsed.context.as_deref(), Some("errands"));
+        assert_eq!(parsed.tags, ["grocery"]);
+        assert_eq!(parsed.priority, Some('A'));
+        assert_eq!(parsed.due, NaiveDate::from_ymd_opt(2020, 12, 31));
+
+        // lone prefixes are kept on the name
+        let parsed = parse("@ # ! ^").unwrap();
+        assert_eq!(parsed.name, "@ # ! ^");
+        assert_eq!(parsed.context, None);
+
+        assert!(parse("a @home @work")
+            .unwrap_err()
+            .contains("more than one context"));
+        assert!(parse("@work #tag !l").unwrap_err().contains("no name left"));
+        assert!(parse("a ^someday").is_err());
+    }
+}
@@ -1,10 +1,11 @@
 //! Stores data structures related to displaying the database on a terminal.
 
-// TODO: add a way to recursively sort items, like what was done with filters.
-
 use crate::item::{Item, ItemState};
+use chrono::{DateTime, Utc};
 use utils::cowstr::CowStr;
 
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::io;
 use std::io::Write;
 
@@ -36,9 +37,224 @@ pub enum ReportDepth {
 pub struct ReportConfig {
     /// The amount of spaces used per indent.
     pub spaces_per_indent: usize,
+    /// If the output should be colored with ANSI escape codes.
+    pub color: bool,
+    /// The width of the terminal, used to wrap long lines. `None` means lines shouldn't be wrapped.
+    pub width: Option<usize>,
+    /// The keys the items (and their children) are sorted by, in order. Empty means the items keep their order.
+    pub sort: Vec<SortKey>,
+    /// Which items are counted for the progress shown next to parent items, if it should be shown at all.
+    pub progress: Option<Progress>,
+    /// How many days ahead open items are marked with `DUE`. Overdue items are always marked.
+    pub due_soon: u32,
+    /// The characters that link the items to their parents on trees.
+    pub tree: TreeStyle,
+    /// Whether the ID of each item goes right before its name, instead of after it.
+    pub id_before_name: bool,
+    /// How the descriptions of the items are shown under them.
+    pub notes: Notes,
+    /// The contexts that items without one inherit from their ancestors, by internal ID (see [`inherited_contexts`]).
+    /// Empty if contexts aren't inherited.
+    pub inherited_contexts: HashMap<u32, String>,
+}
+
+/// Finds the context each item without one inherits from its nearest ancestor with one, returning them by the
+/// internal IDs of the items.
+pub fn inherited_contexts(items: &[Item]) -> HashMap<u32, String> {
+    fn walk(items: &[Item], inherited: Option<&str>, out: &mut HashMap<u32, String>) {
+        for item in items {
+            let context = match item.context() {
+                Some(context) => Some(context),
+                None => {
+                    if let Some(inherited) = inherited {
+                        out.insert(item.internal_id, inherited.to_string());
+                    }
+
+                    inherited
+                }
+            };
+
+            walk(&item.children, context, out);
+        }
+    }
+
+    let mut out = HashMap::new();
+    walk(items, None, &mut out);
+
+    out
+}
+
+/// How the descriptions (notes) of the items are shown under them on reports.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Notes {
+    /// A short excerpt of the first line.
+    Excerpt,
+    /// Up to this many of the first lines, in full.
+    Lines(usize),
+    Hidden,
+}
+
+/// The characters used to link the items to their parents on trees.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TreeStyle {
+    /// Only indentation.
+    Spaces,
+    /// Lines made of `|`, `` ` `` and `-`.
+    Ascii,
+    /// Lines made of box-drawing characters.
+    Unicode,
+}
+
+impl TreeStyle {
+    /// Parses a style, which can be `spaces`, `ascii` or `unicode`.
+    pub fn parse(string: &str) -> Result<Self, String> {
+        match string.trim() {
+            "spaces" => Ok(Self::Spaces),
+            "ascii" => Ok(Self::Ascii),
+            "unicode" => Ok(Self::Unicode),
+            other => Err(format!(
+                "unknown tree style {:?} (expected spaces, ascii or unicode)",
+                other
+            )),
+        }
+    }
+
+    /// Returns the glyph that links an item to its parent and the one that goes below it (until its last child), both
+    /// `width` columns wide. `last` tells if the item is the last of its siblings, in which case nothing needs to go
+    /// below it.
+    fn glyphs(self, width: usize, last: bool) -> (String, String) {
+        let (branch, fill, line) = match (self, last) {
+            (Self::Spaces, _) => (' ', ' ', ' '),
+            (Self::Ascii, false) => ('|', '-', '|'),
+            (Self::Ascii, true) => ('`', '-', ' '),
+            (Self::Unicode, false) => ('├', '─', '│'),
+            (Self::Unicode, true) => ('└', '─', ' '),
+        };
+
+        if width == 0 {
+            return (String::new(), String::new());
+        }
+
+        let mut connector = branch.to_string();
+        connector.extend(std::iter::repeat(fill).take(width.saturating_sub(2)));
+        if width >= 2 {
+            connector.push(' ');
+        }
+
+        let mut continuation = line.to_string();
+        continuation.extend(std::iter::repeat(' ').take(width - 1));
+
+        (connector, continuation)
+    }
+}
+
+/// The items that are counted on the progress of a parent item.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Progress {
+    /// All of the descendants.
+    Descendants,
+    /// Only the direct children.
+    Children,
+}
+
+/// Something that items can be sorted by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortKey {
+    /// From `A` to `Z`, with items without a priority at the end.
+    Priority,
+    /// From the earliest due date, with items without one at the end.
+    Due,
+    /// Alphabetically, ignoring case.
+    Name,
+    /// From the oldest item, with the ones created before creation times were tracked first, by their internal IDs.
+    Created,
+    /// Starred items first.
+    Starred,
+    /// The order the items were put on, which is kept when the other keys are equal anyway.
+    Manual,
+}
+
+impl SortKey {
+    /// Parses a comma-separated list of keys, like `priority,due`.
+    pub fn parse_list(string: &str) -> Result<Vec<Self>, String> {
+        string
+            .split(',')
+            .map(|key| match key.trim() {
+                "priority" => Ok(Self::Priority),
+                "due" => Ok(Self::Due),
+                "name" => Ok(Self::Name),
+                "created" => Ok(Self::Created),
+                "starred" => Ok(Self::Starred),
+                "manual" => Ok(Self::Manual),
+                other => Err(format!(
+                    "unknown sort key {:?} (expected priority, due, name, created, starred or manual)",
+                    other
+                )),
+            })
+            .collect()
+    }
+
+    fn compare(self, a: &Item, b: &Item) -> Ordering {
+        /// Compares options so that `None` comes last.
+        fn none_last<T: Ord>(a: Option<T>, b: Option<T>) -> Ordering {
+            match (a, b) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        }
+
+        match self {
+            Self::Priority => none_last(a.priority, b.priority),
+            Self::Due => none_last(a.due, b.due),
+            Self::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            Self::Created => (a.created_at, a.internal_id).cmp(&(b.created_at, b.internal_id)),
+            Self::Starred => b.starred.cmp(&a.starred),
+            Self::Manual => Ordering::Equal,
+        }
+    }
+}
+
+/// Collects the items, sorting them by `keys`. The sort is stable, so equal items keep their order.
+fn sorted<'a>(items: &mut dyn Iterator<Item = &'a Item>, keys: &[SortKey]) -> Vec<&'a Item> {
+    let mut items: Vec<&Item> = items.collect();
+
+    if !keys.is_empty() {
+        items.sort_by(|a, b| {
+            keys.iter()
+                .map(|key| key.compare(a, b))
+                .find(|&ordering| ordering != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+
+    items
+}
+
+/// The ANSI escape codes used on colored reports.
+pub mod style {
+    pub const DIM: &str = "2";
+    pub const OVERDUE: &str = "1;31";
+    pub const RED: &str = "31";
+    pub const GREEN: &str = "32";
+    pub const YELLOW: &str = "33";
+    pub const BLUE: &str = "34";
+    pub const MAGENTA: &str = "35";
+    pub const CYAN: &str = "36";
+    pub const GRAY: &str = "90";
 }
 
 impl ReportConfig {
+    /// Wraps `text` on the specified style, if colors are enabled.
+    pub fn paint(&self, style: &str, text: &str) -> String {
+        if self.color && !text.is_empty() {
+            format!("\x1b[{}m{}\x1b[0m", style, text)
+        } else {
+            text.to_string()
+        }
+    }
+
     pub fn get_indent_spaces(&self, indent: usize) -> String {
         std::iter::repeat(' ')
             .take(self.spaces_per_indent * indent)
@@ -56,7 +272,90 @@ pub struct ReportInfo<'a> {
     pub filter: Option<&'a dyn Fn(&Item) -> bool>,
     /// The depth that the item displaying must go through.
     pub depth: ReportDepth,
-    // pub sort: SortOption,
+}
+
+/// Filters items by their contexts.
+pub struct ContextFilter<'a> {
+    /// The contexts an item should have to be shown, if any is specified. `None` stands for having no context.
+    include: Vec<Option<String>>,
+    /// The contexts an item shouldn't have to be shown.
+    exclude: Vec<Option<String>>,
+    /// The contexts inherited by the items without one.
+    inherited: &'a HashMap<u32, String>,
+}
+
+impl<'a> ContextFilter<'a> {
+    /// Creates a filter from command-line arguments. Arguments starting with `!` are exclusions, and `.none` stands for
+    /// the absence of a context. Items without a context are filtered by the one they inherit on `inherited`, if any.
+    pub fn new(args: &[String], inherited: &'a HashMap<u32, String>) -> Self {
+        let mut include = Vec::new();
+        let mut exclude = Vec::new();
+
+        for arg in args {
+            if let Some(negated) = arg.strip_prefix('!') {
+                exclude.push(Item::validate_context(negated));
+            } else {
+                include.push(Item::validate_context(arg));
+            }
+        }
+
+        Self {
+            include,
+            exclude,
+            inherited,
+        }
+    }
+
+    /// Returns the normalized context of the item, which might be an inherited one.
+    fn context_of(&self, item: &Item) -> Option<String> {
+        item.context()
+            .or_else(|| self.inherited.get(&item.internal_id).map(String::as_str))
+            .and_then(Item::validate_context)
+    }
+
+    /// Checks if the item's own context passes the filter.
+    pub fn matches(&self, item: &Item) -> bool {
+        let context = self.context_of(item);
+
+        !self.exclude.contains(&context)
+            && (self.include.is_empty() || self.include.contains(&context))
+    }
+
+    /// Checks if the item should be shown on a tree, which is also the case when one of its descendants passes the
+    /// filter - otherwise that descendant wouldn't be reachable.
+    pub fn allows(&self, item: &Item) -> bool {
+        let context = self.context_of(item);
+
+        !self.exclude.contains(&context)
+            && (self.include.is_empty()
+                || self.include.contains(&context)
+                || item.children.iter().any(|child| self.allows(child)))
+    }
+}
+
+/// Filters items by when they were created and modified.
+#[derive(Default)]
+pub struct DateFilter {
+    pub modified_since: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+}
+
+impl DateFilter {
+    /// Checks if the item's own times pass the filter. Items without a creation time count as created before any
+    /// moment, while items without a modification time don't count as modified since any.
+    pub fn matches(&self, item: &Item) -> bool {
+        self.modified_since.map_or(true, |since| {
+            item.modified_at.map_or(false, |modified| modified >= since)
+        }) && self.created_before.map_or(true, |before| {
+            item.created_at.map_or(true, |created| created < before)
+        })
+    }
+
+    /// Checks if the item should be shown on a tree, which is also the case when one of its descendants passes the
+    /// filter.
+    pub fn allows(&self, item: &Item) -> bool {
+        self.matches(item) || item.children.iter().any(|child| self.allows(child))
+    }
 }
 
 pub trait Report {
@@ -82,114 +381,165 @@ pub trait Report {
 
         Self::display_all(items, info, out)
     }
+
+    /// Shows `items` on a section of their own, meant to be above a report, like the overdue items. Nothing is shown
+    /// if there are no items.
+    fn section(
+        label: &str,
+        items: &[&Item],
+        info: &ReportInfo,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        writeln!(out, "{} | {} item(s)", label, items.len())?;
+        Self::display_all(&mut items.iter().copied(), info, out)?;
+        writeln!(out)
+    }
 }
 
 pub struct BasicReport;
-impl Report for BasicReport {
-    fn display(item: &Item, info: &ReportInfo, out: &mut dyn Write) -> io::Result<()> {
-        let proceed = |out: &mut dyn Write| -> io::Result<()> {
-            writeln!(
-                out,
-                "{indent}{state} {text} {context}{id_repr}{flags}",
-                indent = info.config.get_indent_spaces(info.indent),
-                state = match item.state {
-                    ItemState::Todo => "o",
-                    ItemState::Done => "x",
-                    ItemState::Note => "-",
-                },
-                context = match item.context() {
-                    Some(ctx) => format!("@{} ", ctx),
-                    None => String::new(),
-                },
-                text = item.name,
-                id_repr = match item.ref_id {
-                    Some(id) => format!("#{:>02}", id),
-                    None => format!("i{:>02}", item.internal_id),
-                },
-                flags = "",
-                // flags = match item.description.is_empty() {
-                //     true => "",
-                //     false => " (D)",
-                // },
-            )?;
-
-            if !item.description.trim().is_empty() {
-                const MAX_SIZE: usize = 35;
-                let trimmed_desc = item.description.trim();
-
-                let maxpoint = if let Some((i, _)) = trimmed_desc
-                    .chars()
-                    .enumerate()
-                    .take(MAX_SIZE)
-                    .find(|(_, c)| matches!(c, '\n'))
-                {
-                    i
-                } else {
-                    trimmed_desc.len().min(MAX_SIZE + 1)
-                };
+impl BasicReport {
+    /// Shows an item on a tree. `indent` goes before each of its lines, followed by `connector` on the first one (the
+    /// glyph that links it to its parent) and by `continuation` on the others (the one that links the siblings after
+    /// it, if any).
+    fn display_linked(
+        item: &Item,
+        info: &ReportInfo,
+        indent: &str,
+        connector: &str,
+        continuation: &str,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        if !info.filter.map_or(true, |filter| filter(item)) {
+            return Ok(());
+        }
 
+        let body = format!("{}{}", indent, continuation);
+
+        writeln!(
+            out,
+            "{}",
+            wrap(
+                &item_line(item, info.config),
+                &format!("{}{}", indent, connector),
+                &format!("{}  ", body),
+                info.config.width,
+            ),
+        )?;
+
+        if let Notes::Lines(count) = info.config.notes {
+            for line in item.description.trim().lines().take(count) {
                 writeln!(
                     out,
-                    "{indent}  :: {description}{trail}",
-                    indent = info.config.get_indent_spaces(info.indent),
-                    description = &trimmed_desc[..maxpoint].trim(),
-                    trail = if trimmed_desc.len() != maxpoint {
-                        "..."
-                    } else {
-                        ""
-                    }
-                )
-                .unwrap();
+                    "{}  {}",
+                    body,
+                    info.config
+                        .paint(style::DIM, &format!(":: {}", line.trim_end()))
+                )?;
             }
+        } else if info.config.notes == Notes::Excerpt && !item.description.trim().is_empty() {
+            const MAX_SIZE: usize = 35;
+            let trimmed_desc = item.description.trim();
+
+            let maxpoint = if let Some((i, _)) = trimmed_desc
+                .chars()
+                .enumerate()
+                .take(MAX_SIZE)
+                .find(|(_, c)| matches!(c, '\n'))
+            {
+                i
+            } else {
+                trimmed_desc.len().min(MAX_SIZE + 1)
+            };
 
-            match info.depth {
-                ReportDepth::Shallow => (),
-                ReportDepth::Brief => {
-                    let mut info = info.clone();
-                    info.indent += 1;
-                    info.depth = ReportDepth::Shallow;
-
-                    if item.children.len() > 0 {
-                        Self::display(&item.children[0], &info, out)?;
-
-                        if item.children.len() > 1 {
-                            writeln!(
-                                out,
-                                "{}  {} more...",
-                                info.config.get_indent_spaces(info.indent),
-                                item.children.len() - 1
-                            )?;
+            writeln!(
+                out,
+                "{indent}  {description}",
+                indent = body,
+                description = info.config.paint(
+                    style::DIM,
+                    &format!(
+                        ":: {}{}",
+                        &trimmed_desc[..maxpoint].trim(),
+                        if trimmed_desc.len() != maxpoint {
+                            "..."
+                        } else {
+                            ""
                         }
-                    }
-                }
-                ReportDepth::Tree => {
-                    let mut info = info.clone();
-                    info.indent += 1;
+                    )
+                ),
+            )
+            .unwrap();
+        }
 
-                    Self::display_all(&mut item.children.iter(), &info, out)?;
+        let (tree, width) = (info.config.tree, info.config.spaces_per_indent);
+
+        match info.depth {
+            ReportDepth::Shallow => (),
+            ReportDepth::Brief => {
+                let mut info = info.clone();
+                info.indent += 1;
+                info.depth = ReportDepth::Shallow;
+
+                if item.children.len() > 0 {
+                    let first = sorted(&mut item.children.iter(), &info.config.sort)[0];
+                    let last = item.children.len() == 1;
+                    let (connector, continuation) = tree.glyphs(width, last);
+                    Self::display_linked(first, &info, &body, &connector, &continuation, out)?;
+
+                    if !last {
+                        writeln!(
+                            out,
+                            "{}{}  {} more...",
+                            body,
+                            tree.glyphs(width, true).0,
+                            item.children.len() - 1
+                        )?;
+                    }
                 }
             }
-
-            Ok(())
-        };
-
-        if let Some(filter) = info.filter {
-            if filter(item) {
-                proceed(out)?;
+            ReportDepth::Tree => {
+                let mut info = info.clone();
+                info.indent += 1;
+
+                // the children that are filtered out are skipped beforehand, so it's known which one is the last
+                let children: Vec<&Item> = sorted(&mut item.children.iter(), &info.config.sort)
+                    .into_iter()
+                    .filter(|child| info.filter.map_or(true, |filter| filter(child)))
+                    .collect();
+
+                for (i, child) in children.iter().enumerate() {
+                    let (connector, continuation) = tree.glyphs(width, i + 1 == children.len());
+                    Self::display_linked(child, &info, &body, &connector, &continuation, out)?;
+                }
             }
-        } else {
-            proceed(out)?;
         }
 
         Ok(())
     }
+}
+
+impl Report for BasicReport {
+    fn display(item: &Item, info: &ReportInfo, out: &mut dyn Write) -> io::Result<()> {
+        Self::display_linked(
+            item,
+            info,
+            &info.config.get_indent_spaces(info.indent),
+            "",
+            "",
+            out,
+        )
+    }
 
     fn display_all(
         items: &mut dyn Iterator<Item = &Item>,
         info: &ReportInfo,
         out: &mut dyn Write,
     ) -> io::Result<()> {
-        for item in items {
+        for item in sorted(items, &info.config.sort) {
             Self::display(item, info, out)?;
         }
 
@@ -197,28 +547,285 @@ impl Report for BasicReport {
     }
 }
 
+/// Returns the line that represents an item on the reports, without indentation.
+fn item_line(item: &Item, config: &ReportConfig) -> String {
+    let due = match item.due {
+        Some(due) if !item.state.is_closed() && item.state != ItemState::Note => {
+            let today = chrono::Local::now().date_naive();
+
+            if due < today {
+                format!("{} ", config.paint(style::OVERDUE, "DUE"))
+            } else if due <= today + chrono::Duration::days(config.due_soon as i64) {
+                format!("{} ", config.paint(style::YELLOW, "DUE"))
+            } else {
+                String::new()
+            }
+        }
+        _ => String::new(),
+    };
+
+    let state = config.paint(
+        match item.state {
+            ItemState::Todo => style::YELLOW,
+            ItemState::Done => style::GREEN,
+            ItemState::Note => style::BLUE,
+            ItemState::Waiting(_) => style::MAGENTA,
+            ItemState::Cancelled => style::RED,
+        },
+        &item.state.symbol().to_string(),
+    );
+
+    // inherited contexts are dimmed, so they can be told apart from the ones set on the item
+    let context = match (
+        item.context(),
+        config.inherited_contexts.get(&item.internal_id),
+    ) {
+        (Some(ctx), _) => format!("{} ", config.paint(style::CYAN, &format!("@{}", ctx))),
+        (None, Some(ctx)) => format!("{} ", config.paint(style::DIM, &format!("@{}", ctx))),
+        (None, None) => String::new(),
+    };
+
+    let text = if item.state.is_closed() {
+        config.paint(style::DIM, &item.name)
+    } else {
+        item.name.clone()
+    };
+
+    let progress = match config.progress {
+        Some(progress) => match item.progress(progress == Progress::Descendants) {
+            (_, 0) => String::new(),
+            (done, total) => format!("[{}/{}] ", done, total),
+        },
+        None => String::new(),
+    };
+
+    let id_repr = config.paint(
+        style::GRAY,
+        &match item.ref_id {
+            Some(id) => format!("#{:>02}", id),
+            None => format!("i{:>02}", item.internal_id),
+        },
+    );
+
+    let main = if config.id_before_name {
+        format!("{} {} {}{}", id_repr, text, progress, context)
+            .trim_end()
+            .to_string()
+    } else {
+        format!("{} {}{}{}", text, progress, context, id_repr)
+    };
+
+    format!("{}{} {}{}", due, state, main, flags(item, config))
+}
+
+/// Wraps an item line so it fits into `width` columns, prepending `indent` to the first line and `hanging` to the
+/// others, which should align them to the item name, right after the state symbol.
+///
+/// Words longer than a whole line aren't broken.
+fn wrap(line: &str, indent: &str, hanging: &str, width: Option<usize>) -> String {
+    /// Returns how many columns the text takes, ignoring ANSI escape codes.
+    fn visible_len(text: &str) -> usize {
+        let mut len = 0;
+        let mut escaping = false;
+
+        for c in text.chars() {
+            match (escaping, c) {
+                (false, '\x1b') => escaping = true,
+                (false, _) => len += 1,
+                (true, 'm') => escaping = false,
+                (true, _) => (),
+            }
+        }
+
+        len
+    }
+
+    let width = match width {
+        Some(width) if visible_len(indent) + visible_len(line) > width => width,
+        _ => return format!("{}{}", indent, line),
+    };
+
+    let mut wrapped = indent.to_string();
+    let mut column = visible_len(indent);
+    let mut line_start = true;
+
+    for word in line.split(' ') {
+        let len = visible_len(word);
+
+        if !line_start && column + 1 + len > width {
+            wrapped.push('\n');
+            wrapped.push_str(hanging);
+            column = visible_len(hanging);
+            line_start = true;
+        }
+
+        if !line_start {
+            wrapped.push(' ');
+            column += 1;
+        }
+
+        wrapped.push_str(word);
+        column += len;
+        line_start = false;
+    }
+
+    wrapped
+}
+
+/// Returns the extra information shown after an item's ID.
+fn flags(item: &Item, config: &ReportConfig) -> String {
+    let mut flags = String::new();
+
+    if item.starred {
+        flags.push(' ');
+        flags.push_str(&config.paint(style::YELLOW, "(starred)"));
+    }
+
+    if item.project {
+        flags.push_str(" (project)");
+    }
+
+    if item.url.is_some() {
+        flags.push_str(" (url)");
+    }
+
+    if !item.attachments.is_empty() {
+        flags.push_str(&format!(" ({} attachment(s))", item.attachments.len()));
+    }
+
+    if let ItemState::Waiting(Some(on)) = &item.state {
+        flags.push_str(&format!(" (waiting on {})", on));
+    }
+
+    if let Some(due) = item.due {
+        let text = format!("(due {})", due);
+        let today = chrono::Local::now().date_naive();
+
+        flags.push(' ');
+        flags.push_str(&if item.state.is_closed() {
+            text
+        } else if due < today {
+            config.paint(style::OVERDUE, &text)
+        } else if due == today {
+            config.paint(style::YELLOW, &text)
+        } else {
+            text
+        });
+    }
+
+    if let Some(scheduled) = item.scheduled {
+        flags.push_str(&format!(" (scheduled {})", scheduled));
+    }
+
+    if let Some(days) = item.review_every {
+        flags.push_str(&format!(" (review every {}d)", days));
+    }
+
+    if let Some(until) = item.snoozed_until.filter(|_| item.is_snoozed()) {
+        flags.push_str(&format!(
+            " (snoozed until {})",
+            until.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")
+        ));
+    }
+
+    if item.someday {
+        flags.push_str(" (someday)");
+    }
+
+    if let Some(priority) = item.priority {
+        flags.push_str(&format!(" (priority {})", priority));
+    }
+
+    if !item.tags.is_empty() {
+        flags.push_str(&format!(" (tags {})", item.tags.join(", ")));
+    }
+
+    if !item.links.is_empty() {
+        let links: Vec<String> = item.links.iter().map(|link| link.to_string()).collect();
+        flags.push(' ');
+        flags.push_str(&config.paint(style::BLUE, &format!("(links {})", links.join(", "))));
+    }
+
+    if let Some(minutes) = item.estimate {
+        flags.push_str(&format!(
+            " (~{})",
+            utils::time::format_duration(chrono::Duration::minutes(minutes as i64))
+        ));
+    }
+
+    flags
+}
+
+/// A report that outputs the items (and their children) as JSON, for scripting. Labels and depths are ignored, but
+/// filters still apply to the children.
+pub struct JsonReport;
+impl JsonReport {
+    fn to_json(item: &Item, info: &ReportInfo) -> serde_json::Value {
+        let mut value = serde_json::to_value(item).expect("failed to serialize item");
+
+        value["children"] = serde_json::Value::Array(
+            sorted(&mut item.children.iter(), &info.config.sort)
+                .into_iter()
+                .filter(|child| info.filter.map_or(true, |filter| filter(child)))
+                .map(|child| Self::to_json(child, info))
+                .collect(),
+        );
+
+        value
+    }
+}
+
+impl Report for JsonReport {
+    fn display(item: &Item, info: &ReportInfo, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "{}",
+            serde_json::to_string_pretty(&Self::to_json(item, info))?
+        )
+    }
+
+    fn display_all(
+        items: &mut dyn Iterator<Item = &Item>,
+        info: &ReportInfo,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        let values: Vec<serde_json::Value> = sorted(items, &info.config.sort)
+            .into_iter()
+            .filter(|item| info.filter.map_or(true, |filter| filter(item)))
+            .map(|item| Self::to_json(item, info))
+            .collect();
+
+        writeln!(out, "{}", serde_json::to_string_pretty(&values)?)
+    }
+
+    fn report(
+        _label: &str,
+        items: &mut dyn Iterator<Item = &Item>,
+        info: &ReportInfo,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        Self::display_all(items, info, out)
+    }
+
+    /// Sections are left out, so the output is only the JSON of the report itself.
+    fn section(
+        _label: &str,
+        _items: &[&Item],
+        _info: &ReportInfo,
+        _out: &mut dyn Write,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 pub struct FlatReport;
 impl Report for FlatReport {
     fn display(item: &Item, info: &ReportInfo, out: &mut dyn Write) -> io::Result<()> {
         let proceed = |out: &mut dyn Write| -> io::Result<()> {
             writeln!(
                 out,
-                "{state} {text} {context}{id_repr}{flags}",
-                state = match item.state {
-                    ItemState::Todo => "o",
-                    ItemState::Done => "x",
-                    ItemState::Note => "-",
-                },
-                context = match item.context() {
-                    Some(ctx) => format!("@{} ", ctx),
-                    None => String::new(),
-                },
-                text = item.name,
-                id_repr = match item.ref_id {
-                    Some(id) => format!("#{:>02}", id),
-                    None => format!("i{:>02}", item.internal_id),
-                },
-                flags = "",
+                "{}",
+                wrap(&item_line(item, info.config), "", "  ", info.config.width)
             )?;
 
             let mut info = info.clone();
@@ -244,7 +851,7 @@ impl Report for FlatReport {
         info: &ReportInfo,
         out: &mut dyn Write,
     ) -> io::Result<()> {
-        for item in items {
+        for item in sorted(items, &info.config.sort) {
             Self::display(item, info, out)?;
         }
 
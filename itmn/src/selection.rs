@@ -0,0 +1,428 @@
+//! Stores the parser for selection ranges, used to pick which items a command should act on.
+//!
+//! A range is a comma-separated list of terms:
+//!
+//! ```text
+//! range     = term ("," term)*
+//! term      = "!"? atom
+//! atom      = ID                      one item, by reference ID
+//!           | ID ".." ID | ID "-" ID  every item between the two IDs, inclusive
+//!           | ID ".." | ".." ID       every item from/until an ID
+//!           | ID "/*"                 an item and all of its descendants
+//!           | "all"                   every item with a reference ID
+//!           | "state:" STATE          every item on that state (todo, done, note, waiting or cancelled)
+//!           | "ctx:" CONTEXT          every item on that context (.none for items without one), including closed ones
+//! ```
+//!
+//! Terms prefixed by `!` remove items from the selection instead of adding them; if a range only has those, they're
+//! removed from `all`. For example, `1-20,!7`, `5..`, `12/*,!13`, `state:todo,!ctx:work` and `!ctx:.none`.
+//!
+//! IDs are reference IDs by default, but they can be read as internal IDs instead (see [`IdKind`]), in which case `all`
+//! includes closed items as well.
+//!
+//! Single IDs, closed ranges and subtrees must only refer to existing items, while the other terms silently skip what
+//! doesn't exist. Since closed items don't have reference IDs, `state:` and `ctx:` are the only ways to select them -
+//! and, like `all`, subtrees only include the descendants that have IDs of the used kind.
+
+use std::collections::HashSet;
+
+use crate::item::{InternalId, Item, RefId};
+use crate::manager::{ItemManager, Searchable};
+
+/// A term of a selection range.
+#[derive(Debug)]
+enum Atom {
+    Id(u32),
+    /// A range with optional start and end, both inclusive.
+    Range(Option<u32>, Option<u32>),
+    /// An item along with its descendants.
+    Subtree(u32),
+    All,
+    State(String),
+    /// A normalized context, or `None` for the lack of one.
+    Context(Option<String>),
+}
+
+/// Which kind of ID the IDs on a range refer to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IdKind {
+    Ref,
+    Internal,
+}
+
+impl IdKind {
+    /// Returns the ID of this kind that `item` has, if any.
+    fn of(self, item: &Item) -> Option<u32> {
+        match self {
+            Self::Ref => item.ref_id,
+            Self::Internal => Some(item.internal_id),
+        }
+    }
+}
+
+/// A parsed selection range.
+#[derive(Debug)]
+pub struct Selection {
+    include: Vec<Atom>,
+    exclude: Vec<Atom>,
+    ids: IdKind,
+    /// A state that all of the selected items should be on.
+    state: Option<String>,
+    /// Whether the descendants of the selected items are selected as well.
+    recursive: bool,
+}
+
+const STATE_NAMES: [&str; 5] = ["todo", "done", "note", "waiting", "cancelled"];
+
+impl Selection {
+    /// Parses a range, according to the grammar on the module documentation.
+    pub fn parse(range: &str) -> Result<Self, String> {
+        let mut include = Vec::new();
+        let mut exclude = Vec::new();
+
+        for term in range.split(',').map(str::trim) {
+            if term.is_empty() {
+                return Err(format!("empty term on range {:?}", range));
+            }
+
+            match term.strip_prefix('!') {
+                Some(negated) => exclude.push(parse_atom(negated.trim())?),
+                None => include.push(parse_atom(term)?),
+            }
+        }
+
+        Ok(Self {
+            include,
+            exclude,
+            ids: IdKind::Ref,
+            state: None,
+            recursive: false,
+        })
+    }
+
+    /// Makes the IDs on the range refer to `ids`.
+    pub fn with_id_kind(self, ids: IdKind) -> Self {
+        Self { ids, ..self }
+    }
+
+    /// Restricts the selection to the items on `state`.
+    pub fn with_state(self, state: &str) -> Result<Self, String> {
+        Ok(Self {
+            state: Some(parse_state(state)?),
+            ..self
+        })
+    }
+
+    /// Makes the selection include the descendants of the selected items, as if all of them were subtrees.
+    pub fn recursive(self) -> Self {
+        Self {
+            recursive: true,
+            ..self
+        }
+    }
+
+    /// Returns the internal IDs of the selected items, in the order they were specified and without duplicates.
+    pub fn resolve(&self, manager: &ItemManager) -> Result<Vec<InternalId>, String> {
+        let mut available: Vec<&Item> = Vec::new();
+        for root in &manager.data {
+            root.traverse(&mut |item| {
+                let on_state = match self.state {
+                    Some(ref state) => item.state.name() == state,
+                    None => true,
+                };
+
+                if on_state {
+                    available.push(item);
+                }
+            });
+        }
+
+        // items with IDs of the used kind go first, ordered by them; the others keep the order of the tree
+        let ids = self.ids;
+        available.sort_by_key(|item| (ids.of(item).is_none(), ids.of(item)));
+
+        let mut excluded: HashSet<u32> = HashSet::new();
+        for atom in &self.exclude {
+            excluded.extend(matching(atom, &available, ids));
+        }
+
+        let mut selected: Vec<InternalId> = Vec::new();
+        let mut seen: HashSet<u32> = HashSet::new();
+
+        let all = [Atom::All];
+        let include: &[Atom] = if self.include.is_empty() {
+            &all
+        } else {
+            &self.include
+        };
+
+        for atom in include {
+            // explicitly mentioned IDs should exist
+            let explicit = match *atom {
+                Atom::Id(id) | Atom::Subtree(id) => Some((id, id)),
+                Atom::Range(Some(start), Some(end)) => Some((start, end)),
+                _ => None,
+            };
+
+            if let Some((start, end)) = explicit {
+                let exists = |id| match ids {
+                    IdKind::Ref => manager.find(RefId(id)).is_some(),
+                    IdKind::Internal => manager.find(InternalId(id)).is_some(),
+                };
+
+                if let Some(missing) = (start..=end).find(|&id| !exists(id)) {
+                    return Err(format!(
+                        "there's at least one invalid ID ({}{}) on the selection",
+                        if ids == IdKind::Ref { "#" } else { "i" },
+                        missing
+                    ));
+                }
+            }
+
+            for id in matching(atom, &available, ids) {
+                if !excluded.contains(&id) && seen.insert(id) {
+                    selected.push(InternalId(id));
+                }
+            }
+        }
+
+        if self.recursive {
+            for i in 0..selected.len() {
+                let item = match available
+                    .iter()
+                    .find(|item| item.internal_id == selected[i].0)
+                {
+                    Some(item) => item,
+                    None => continue,
+                };
+
+                for id in descendants(item, &available, ids) {
+                    if !excluded.contains(&id) && seen.insert(id) {
+                        selected.push(InternalId(id));
+                    }
+                }
+            }
+        }
+
+        Ok(selected)
+    }
+}
+
+/// Returns the internal IDs of the descendants of `item` that are available and have IDs of the used kind, in the
+/// order of the tree.
+fn descendants(item: &Item, available: &[&Item], ids: IdKind) -> Vec<u32> {
+    let available: HashSet<u32> = available
+        .iter()
+        .filter(|item| ids.of(item).is_some())
+        .map(|item| item.internal_id)
+        .collect();
+
+    let mut found = Vec::new();
+    for child in &item.children {
+        child.traverse(&mut |descendant| {
+            if available.contains(&descendant.internal_id) {
+                found.push(descendant.internal_id);
+            }
+        });
+    }
+
+    found
+}
+
+/// Returns the internal IDs of the items that match `atom`.
+fn matching(atom: &Atom, available: &[&Item], ids: IdKind) -> Vec<u32> {
+    if let Atom::Subtree(id) = *atom {
+        return available
+            .iter()
+            .filter(|item| ids.of(item) == Some(id))
+            .flat_map(|item| {
+                std::iter::once(item.internal_id).chain(descendants(item, available, ids))
+            })
+            .collect();
+    }
+
+    let in_range = |item: &&&Item, start: Option<u32>, end: Option<u32>| match ids.of(item) {
+        Some(id) => start.map_or(true, |s| id >= s) && end.map_or(true, |e| id <= e),
+        None => false,
+    };
+
+    available
+        .iter()
+        .filter(|item| match atom {
+            Atom::Id(id) => in_range(item, Some(*id), Some(*id)),
+            Atom::Range(start, end) => in_range(item, *start, *end),
+            Atom::Subtree(_) => unreachable!(),
+            Atom::All => ids.of(item).is_some(),
+            Atom::State(state) => item.state.name() == state,
+            Atom::Context(context) => &item.context().and_then(Item::validate_context) == context,
+        })
+        .map(|item| item.internal_id)
+        .collect()
+}
+
+/// Parses the name of a state (see [`ItemState::name`]), ignoring case.
+///
+/// [`ItemState::name`]: crate::item::ItemState::name
+pub fn parse_state(state: &str) -> Result<String, String> {
+    let state = state.trim().to_lowercase();
+
+    if STATE_NAMES.contains(&state.as_str()) {
+        Ok(state)
+    } else {
+        Err(format!(
+            "unknown state {:?} (expected one of: {})",
+            state,
+            STATE_NAMES.join(", ")
+        ))
+    }
+}
+
+fn parse_atom(term: &str) -> Result<Atom, String> {
+    fn parse_id(text: &str, term: &str) -> Result<u32, String> {
+        text.trim()
+            .parse()
+            .map_err(|_| format!("invalid ID {:?} on {:?}", text, term))
+    }
+
+    if term == "all" {
+        return Ok(Atom::All);
+    }
+
+    if let Some(state) = term.strip_prefix("state:") {
+        return Ok(Atom::State(parse_state(state)?));
+    }
+
+    if let Some(context) = term
+        .strip_prefix("ctx:")
+        .or_else(|| term.strip_prefix("context:"))
+    {
+        return Ok(Atom::Context(Item::validate_context(context)));
+    }
+
+    if term.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(Atom::Id(parse_id(term, term)?));
+    }
+
+    if let Some(id) = term.strip_suffix("/*") {
+        return Ok(Atom::Subtree(parse_id(id, term)?));
+    }
+
+    let bounds = if let Some(index) = term.find("..") {
+        Some((&term[..index], &term[index + 2..]))
+    } else if let Some(index) = term.find('-') {
+        Some((&term[..index], &term[index + 1..]))
+    } else {
+        None
+    };
+
+    match bounds {
+        Some((start, end)) => {
+            let start = match start.trim() {
+                "" => None,
+                start => Some(parse_id(start, term)?),
+            };
+            let end = match end.trim() {
+                "" => None,
+                end => Some(parse_id(end, term)?),
+            };
+
+            match (start, end) {
+                (None, None) => Err(format!("the range {:?} has no bounds", term)),
+                (Some(start), Some(end)) if end < start => Err(format!(
+                    "the range {:?} ends ({}) before it starts ({})",
+                    term, end, start
+                )),
+                _ => Ok(Atom::Range(start, end)),
+            }
+        }
+        None => Err(format!(
+            "could not parse {:?} - expected an ID, a range like 3..7, a subtree like 12/*, all, state:<state> or ctx:<context>",
+            term
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::ItemState;
+
+    fn manager() -> ItemManager {
+        let items = (1..=5)
+            .map(|id| {
+                let context = if id <= 2 { "work" } else { "" };
+                Item::new(
+                    Some(id),
+                    id,
+                    "item",
+                    context,
+                    ItemState::Todo,
+                    String::new(),
+                    Vec::new(),
+                )
+            })
+            .collect();
+
+        ItemManager::new(items).unwrap_or_else(|_| panic!("the test items should be valid"))
+    }
+
+    #[test]
+    fn ranges() {
+        let selection =
+            Selection::parse("3, 1..20,5-7,4..,..9,12/*,all,state:Todo,ctx:work,!ctx:.none,!7")
+                .unwrap();
+
+        assert!(matches!(
+            selection.include[..],
+            [
+                Atom::Id(3),
+                Atom::Range(Some(1), Some(20)),
+                Atom::Range(Some(5), Some(7)),
+                Atom::Range(Some(4), None),
+                Atom::Range(None, Some(9)),
+                Atom::Subtree(12),
+                Atom::All,
+                Atom::State(_),
+                Atom::Context(Some(_)),
+            ]
+        ));
+        assert!(matches!(selection.include[7], Atom::State(ref s) if s == "todo"));
+        assert!(matches!(
+            selection.exclude[..],
+            [Atom::Context(None), Atom::Id(7)]
+        ));
+
+        let error = |range| Selection::parse(range).unwrap_err();
+        assert!(error("1,,2").contains("empty term"));
+        assert!(error("99999999999").contains("invalid ID"));
+        assert!(error("1..x").contains("invalid ID"));
+        assert!(error("x/*").contains("invalid ID"));
+        assert!(error("..").contains("has no bounds"));
+        assert!(error("7-3").contains("ends (3) before it starts (7)"));
+        assert!(error("state:open").contains("unknown state"));
+        assert!(error("someday").contains("could not parse"));
+    }
+
+    #[test]
+    fn exclusions() {
+        let manager = manager();
+        // the internal IDs of the test items are the same as their reference IDs
+        let resolve = |range| {
+            Selection::parse(range)
+                .unwrap()
+                .resolve(&manager)
+                .map(|ids| ids.into_iter().map(|id| id.0).collect::<Vec<u32>>())
+        };
+
+        assert_eq!(resolve("1-5,!3"), Ok(vec![1, 2, 4, 5]));
+        assert_eq!(resolve("all,!2..4"), Ok(vec![1, 5]));
+        assert_eq!(resolve("3,1,!1"), Ok(vec![3]));
+        assert_eq!(resolve("2,!2"), Ok(vec![]));
+        // a range with only exclusions removes them from all of the items
+        assert_eq!(resolve("!ctx:work"), Ok(vec![3, 4, 5]));
+        assert_eq!(resolve("!ctx:.none,!1"), Ok(vec![2]));
+        // excluded IDs don't need to exist, unlike the included ones
+        assert_eq!(resolve("4..,!9"), Ok(vec![4, 5]));
+        assert!(resolve("1-6,!6").is_err());
+    }
+}
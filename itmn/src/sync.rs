@@ -0,0 +1,146 @@
+//! Stores the support for keeping the items file on a git repository, which allows it to be synced between machines
+//! and gives it a history of its own.
+//!
+//! The repository is the one the items file is in, and only the items file and its companion files (like the archive)
+//! are committed, so it can be shared with other files.
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// The files kept on a git repository.
+pub struct GitSync {
+    dir: PathBuf,
+    files: Vec<PathBuf>,
+}
+
+impl GitSync {
+    /// Creates a sync for `files`, on the repository that contains the first one. All of them should be on the same
+    /// directory.
+    pub fn new(files: Vec<PathBuf>) -> Self {
+        let dir = files
+            .first()
+            .and_then(|file| file.parent())
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."))
+            .to_path_buf();
+
+        Self { dir, files }
+    }
+
+    /// The directory of the repository.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Commits the changes made to the files with `message`, returning whether there was anything to commit.
+    pub fn commit(&self, message: &str) -> Result<bool, String> {
+        let inside = self
+            .git(&["rev-parse", "--is-inside-work-tree"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map_err(|e| format!("failed to start git: {}", e))?;
+
+        if !inside.success() {
+            return Err(format!(
+                "{} isn't inside of a git repository (run git init there first)",
+                self.dir.display()
+            ));
+        }
+
+        // git runs on the directory of the files, so their names are enough
+        let files: Vec<&OsStr> = self
+            .files
+            .iter()
+            .filter(|file| file.exists())
+            .filter_map(|file| file.file_name())
+            .collect();
+
+        if files.is_empty() {
+            return Ok(false);
+        }
+
+        self.run(self.git(&["add", "--"]).args(&files))?;
+
+        let unchanged = self
+            .git(&["diff", "--cached", "--quiet", "--"])
+            .args(&files)
+            .status()
+            .map_err(|e| format!("failed to start git: {}", e))?
+            .success();
+
+        if unchanged {
+            return Ok(false);
+        }
+
+        self.run(
+            self.git(&["commit", "--quiet", "-m", message, "--"])
+                .args(&files),
+        )?;
+
+        Ok(true)
+    }
+
+    /// Returns the name of the first remote of the repository, if it has any.
+    pub fn remote(&self) -> Result<Option<String>, String> {
+        let output = self
+            .git(&["remote"])
+            .output()
+            .map_err(|e| format!("failed to start git: {}", e))?;
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .next()
+            .map(|remote| remote.trim().to_string()))
+    }
+
+    /// Pulls the changes from the remote, rebasing the local ones on top of them, and then pushes the result to it.
+    ///
+    /// If the current branch doesn't track a remote one yet, nothing is pulled and it's pushed to `remote`, which
+    /// becomes its upstream.
+    pub fn pull_and_push(&self, remote: &str) -> Result<(), String> {
+        let has_upstream = self
+            .git(&["rev-parse", "--abbrev-ref", "@{upstream}"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map_err(|e| format!("failed to start git: {}", e))?
+            .success();
+
+        if !has_upstream {
+            return self.run(&mut self.git(&["push", "--quiet", "--set-upstream", remote, "HEAD"]));
+        }
+
+        self.run(&mut self.git(&["pull", "--quiet", "--rebase"]))
+            .map_err(|_| {
+                format!(
+                    "git pull failed; solve the conflicts on {} and run sync again",
+                    self.dir.display()
+                )
+            })?;
+
+        self.run(&mut self.git(&["push", "--quiet"]))
+    }
+
+    /// Creates a git command that runs on the repository.
+    fn git(&self, args: &[&str]) -> Command {
+        let mut command = Command::new("git");
+        command.arg("-C").arg(&self.dir).args(args);
+
+        command
+    }
+
+    /// Runs a git command, failing if it doesn't succeed.
+    fn run(&self, command: &mut Command) -> Result<(), String> {
+        let status = command
+            .status()
+            .map_err(|e| format!("failed to start git: {}", e))?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("git failed with {}", status))
+        }
+    }
+}
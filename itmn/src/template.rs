@@ -0,0 +1,59 @@
+//! Stores the data structure for templates, which are reusable subtrees kept on their own file.
+
+use serde::{Deserialize, Serialize};
+
+use crate::item::{Item, ItemState};
+
+/// A named subtree that can be copied into the items.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Template {
+    pub name: String,
+    pub items: Vec<Item>,
+}
+
+impl Template {
+    /// Creates a template from copies of `items`.
+    ///
+    /// Everything that only makes sense for the original items is cleared: closed items become todo again, and IDs,
+    /// dates, snoozes, stars, blockers, work logs and histories are dropped.
+    pub fn new(name: &str, items: &[&Item]) -> Self {
+        let mut items: Vec<Item> = items.iter().map(|&item| item.clone()).collect();
+
+        for root in items.iter_mut() {
+            root.traverse_mut(&mut |item| {
+                if item.state.is_closed() {
+                    item.set_state(ItemState::Todo);
+                }
+
+                item.ref_id = None;
+                item.internal_id = 0;
+                item.blocked_by.clear();
+                item.work_log.clear();
+                item.due = None;
+                item.scheduled = None;
+                item.reviewed_at = None;
+                item.created_at = None;
+                item.modified_at = None;
+                item.history.clear();
+                item.snoozed_until = None;
+                item.starred = false;
+                item.someday = false;
+            });
+        }
+
+        Self {
+            name: name.to_string(),
+            items,
+        }
+    }
+
+    /// How many items the template has, counting the children.
+    pub fn item_count(&self) -> usize {
+        let mut count = 0;
+        for root in &self.items {
+            root.traverse(&mut |_| count += 1);
+        }
+
+        count
+    }
+}
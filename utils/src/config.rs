@@ -0,0 +1,293 @@
+//! Loads the configuration file shared by the programs, `rust-scripts/config.toml` on the configuration directory (see
+//! the `dirs` module), like `~/.config/rust-scripts/config.toml` on Linux. It has a section for each program:
+//!
+//! ```toml
+//! # shared by all programs
+//! opener = "firefox"
+//!
+//! [itmn]
+//! file = "~/notes/items.json"
+//! color = "never"
+//! due-soon = 5
+//!
+//! [bkmk]
+//! file = "~/notes/bookmarks.json"
+//! picker = "rofi"
+//! ```
+//!
+//! Each key of a section stands for the environment variable of its program named after both, in uppercase and with
+//! dashes turned into underscores (like `ITMN_DUE_SOON` above), so every setting that can be given by one can be put
+//! on the file too. The exceptions are the keys that are shared by all programs, like `opener` (`$OPENER`), `picker`
+//! (`$PICKER`, see the `picker` module) and `no-color` (`$NO_COLOR`), which can also be put at the top of the file -
+//! the value on a section is only used by its program, and takes precedence over the one at the top.
+//!
+//! Settings are taken from, in order of precedence:
+//!
+//! 1. command-line flags;
+//! 2. environment variables;
+//! 3. the configuration file;
+//! 4. the defaults of the program.
+//!
+//! Strings starting with `~/` are taken as paths on the home directory, and arrays are joined with commas, like the
+//! lists given to the flags (so `sort = ["due", "priority"]` is the same as `--sort due,priority`).
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use toml::Value;
+
+use crate::dirs;
+
+/// The keys that stand for environment variables shared by all programs, instead of for ones of their own.
+const SHARED_KEYS: &[&str] = &["opener", "picker", "no-color"];
+
+/// The settings on the configuration file, already converted to strings.
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    /// The settings at the top of the file, before any section.
+    shared: BTreeMap<String, String>,
+    sections: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Config {
+    /// Returns the path of the configuration file, if the configuration directory is known.
+    pub fn path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("rust-scripts").join("config.toml"))
+    }
+
+    /// Loads the configuration file, which is empty if it doesn't exist.
+    pub fn load() -> Result<Self, String> {
+        match Self::path() {
+            Some(path) if path.exists() => {
+                let contents = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+
+                Self::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+            }
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// Parses the contents of a configuration file.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        // parsed as a value instead of a table, since only that checks for keys set more than once
+        let table = match contents.parse::<Value>().map_err(|e| e.to_string())? {
+            Value::Table(table) => table,
+            _ => unreachable!("a document is always a table"),
+        };
+        let mut config = Self::default();
+
+        for (key, value) in table {
+            match value {
+                Value::Table(section) => {
+                    let settings = section
+                        .into_iter()
+                        .map(|(k, v)| {
+                            let value =
+                                to_setting(&v).map_err(|e| format!("{}.{}: {}", key, k, e))?;
+                            Ok((k, value))
+                        })
+                        .collect::<Result<_, String>>()?;
+
+                    config.sections.insert(key, settings);
+                }
+                value => {
+                    let value = to_setting(&value).map_err(|e| format!("{}: {}", key, e))?;
+                    config.shared.insert(key, value);
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Returns the value of `key` for `program`, taken from its section or, for the shared keys, from the top of the
+    /// file.
+    pub fn get(&self, program: &str, key: &str) -> Option<&str> {
+        let own = self.sections.get(program).and_then(|s| s.get(key));
+
+        match own {
+            Some(value) => Some(value),
+            None if SHARED_KEYS.contains(&key) => self.shared.get(key).map(|v| v.as_str()),
+            None => None,
+        }
+    }
+
+    /// Returns the environment variables that the settings for `program` stand for, along with their values.
+    pub fn variables(&self, program: &str) -> BTreeMap<String, String> {
+        let mut variables = BTreeMap::new();
+
+        for (key, value) in &self.shared {
+            if SHARED_KEYS.contains(&key.as_str()) {
+                variables.insert(variable_name(None, key), value.clone());
+            }
+        }
+
+        for (key, value) in self.sections.get(program).into_iter().flatten() {
+            let prefix = if SHARED_KEYS.contains(&key.as_str()) {
+                None
+            } else {
+                Some(program)
+            };
+
+            variables.insert(variable_name(prefix, key), value.clone());
+        }
+
+        variables
+    }
+
+    /// Sets the environment variables that the settings for `program` stand for, unless they're already set - so
+    /// environment variables take precedence over the file. This should be called before anything reads them.
+    pub fn apply_env(&self, program: &str) {
+        for (name, value) in self.variables(program) {
+            if std::env::var_os(&name).is_none() {
+                std::env::set_var(name, value);
+            }
+        }
+    }
+}
+
+/// Loads the configuration file and applies the settings for `program` to the environment (see `Config::apply_env`).
+pub fn load_into_env(program: &str) -> Result<(), String> {
+    Config::load().map(|config| config.apply_env(program))
+}
+
+fn variable_name(program: Option<&str>, key: &str) -> String {
+    let name = match program {
+        Some(program) => format!("{}_{}", program, key),
+        None => key.to_string(),
+    };
+
+    name.to_uppercase().replace('-', "_")
+}
+
+/// Turns the value of a setting into the string its environment variable is set to. Arrays are joined with commas, like
+/// the lists given on the command line, and strings starting with `~/` are taken as paths on the home directory.
+fn to_setting(value: &Value) -> Result<String, String> {
+    Ok(match value {
+        Value::String(string) => match (string.strip_prefix("~/"), dirs::home_dir()) {
+            (Some(path), Some(home)) => home.join(path).to_string_lossy().into_owned(),
+            _ => string.clone(),
+        },
+        Value::Integer(integer) => integer.to_string(),
+        Value::Float(float) => float.to_string(),
+        Value::Boolean(boolean) => boolean.to_string(),
+        Value::Datetime(datetime) => datetime.to_string(),
+        Value::Array(values) => values
+            .iter()
+            .map(|value| match value {
+                Value::Array(_) | Value::Table(_) => {
+                    Err("arrays can't have arrays or tables".to_string())
+                }
+                value => to_setting(value),
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .join(","),
+        Value::Table(_) => {
+            return Err("tables are only supported as the sections of the programs".into())
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = r#"
+        # shared by all programs
+        opener = "firefox"
+        editor = "vim" # not a shared key, so it's ignored
+
+        [itmn]
+        file = '/tmp/items.json'
+        due-soon = 5
+        color = "never"  # a comment
+
+        [bkmk]
+        opener = "qutebrowser"
+        backups = 1_0
+        dry = false
+    "#;
+
+    #[test]
+    fn parse() {
+        let config = Config::parse(EXAMPLE).unwrap();
+
+        assert_eq!(config.get("itmn", "file"), Some("/tmp/items.json"));
+        assert_eq!(config.get("itmn", "due-soon"), Some("5"));
+        assert_eq!(config.get("itmn", "opener"), Some("firefox"));
+        assert_eq!(config.get("bkmk", "opener"), Some("qutebrowser"));
+        assert_eq!(config.get("bkmk", "backups"), Some("10"));
+        assert_eq!(config.get("bkmk", "file"), None);
+        assert_eq!(config.get("bkmk", "editor"), None);
+    }
+
+    #[test]
+    fn variables() {
+        let config = Config::parse(EXAMPLE).unwrap();
+        let variables = config.variables("itmn");
+
+        assert_eq!(
+            variables.get("ITMN_DUE_SOON").map(|v| v.as_str()),
+            Some("5")
+        );
+        assert_eq!(
+            variables.get("ITMN_COLOR").map(|v| v.as_str()),
+            Some("never")
+        );
+        assert_eq!(variables.get("OPENER").map(|v| v.as_str()), Some("firefox"));
+        assert_eq!(variables.get("EDITOR"), None);
+
+        let variables = config.variables("bkmk");
+        assert_eq!(
+            variables.get("OPENER").map(|v| v.as_str()),
+            Some("qutebrowser")
+        );
+        assert_eq!(variables.get("BKMK_DRY").map(|v| v.as_str()), Some("false"));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Config::parse("[itmn").is_err());
+        assert!(Config::parse("[itmn.sub]").is_err());
+        assert!(Config::parse("file").is_err());
+        assert!(Config::parse("file = \"unterminated").is_err());
+        assert!(Config::parse("file = bare").is_err());
+        assert!(Config::parse("file = 'a' 'b'").is_err());
+        assert!(Config::parse("a = 1\na = 2").is_err());
+        assert!(Config::parse("[itmn]\nsort = [[1], [2]]").is_err());
+    }
+
+    #[test]
+    fn toml() {
+        let config = Config::parse(
+            r#"
+            [itmn]
+            sort = ["due", "priority"]
+            "due-soon" = 3
+            description = """
+one
+two"""
+
+            [bkmk]
+            opener = { command = "firefox" }
+            "#,
+        );
+        assert!(config.is_err());
+
+        let config = Config::parse(
+            r#"
+            [itmn]
+            sort = ["due", "priority"]
+            "due-soon" = 3
+            description = """
+one
+two"""
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.get("itmn", "sort"), Some("due,priority"));
+        assert_eq!(config.get("itmn", "due-soon"), Some("3"));
+        assert_eq!(config.get("itmn", "description"), Some("one\ntwo"));
+    }
+}
@@ -1,5 +1,7 @@
+use chrono::{Duration, Local, NaiveDateTime};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 use crate::aliases::JsonError;
 
@@ -71,6 +73,7 @@ pub mod data_serialize {
     pub enum SaveToFileError {
         Saving(std::io::Error),
         Exporting(serde_json::Error),
+        BackingUp(String),
     }
 
     impl std::fmt::Display for SaveToFileError {
@@ -78,6 +81,7 @@ pub mod data_serialize {
             match self {
                 Self::Saving(e) => write!(fmt, "Error while saving: {}", e),
                 Self::Exporting(e) => write!(fmt, "Error while exporting: {}", e),
+                Self::BackingUp(e) => write!(fmt, "Error while backing up: {}", e),
             }
         }
     }
@@ -112,7 +116,8 @@ pub mod data_serialize {
         T: Deserialize<'a> + Serialize,
     {
         let export_string = export(data, prettified).map_err(|e| SaveToFileError::Exporting(e))?;
-        std::fs::write(file, &export_string).map_err(|e| SaveToFileError::Saving(e))?;
+        crate::io::write_atomic(file, export_string.as_bytes())
+            .map_err(|e| SaveToFileError::Saving(e))?;
 
         Ok(())
     }
@@ -141,6 +146,21 @@ where
     ) -> Result<(), data_serialize::SaveToFileError> {
         data_serialize::save_to_file(self.data(), file, prettified)
     }
+
+    /// Back up the file with `backups` and then export the data to json and save it to the file. Nothing is saved if
+    /// the backup fails.
+    fn save_to_file_with_backups(
+        &'a self,
+        file: &'a Path,
+        prettified: bool,
+        backups: &Backups,
+    ) -> Result<(), data_serialize::SaveToFileError> {
+        backups
+            .save(file)
+            .map_err(data_serialize::SaveToFileError::BackingUp)?;
+
+        self.save_to_file(file, prettified)
+    }
 }
 
 impl<'a, M> JsonSerializer<'a> for M
@@ -149,3 +169,171 @@ where
     <M as Manager>::Data: Deserialize<'a> + Serialize,
 {
 }
+
+/// The format of the moment a backup was taken, on its name.
+const BACKUP_TIMESTAMP: &str = "%Y-%m-%dT%H-%M-%S%.3f";
+
+/// Which backups are kept once a new one is taken.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Retention {
+    /// The last N backups of each file.
+    Last(usize),
+    /// The backups taken on the last N days.
+    Days(u32),
+}
+
+impl Retention {
+    /// Parses a retention like `10` (the last 10 backups) or `30d` (the backups of the last 30 days). Zero of either
+    /// means that no backups are taken.
+    pub fn parse(string: &str) -> Result<Self, String> {
+        let string = string.trim();
+
+        let parsed = match string.strip_suffix('d') {
+            Some(days) => days.parse().map(Self::Days),
+            None => string.parse().map(Self::Last),
+        };
+
+        parsed.map_err(|_| {
+            format!(
+                "invalid retention {:?} (expected a count like 10 or an amount of days like 30d)",
+                string
+            )
+        })
+    }
+
+    fn is_disabled(self) -> bool {
+        matches!(self, Self::Last(0) | Self::Days(0))
+    }
+}
+
+/// Timestamped copies of data files, taken before they're overwritten so a bad change can always be reverted.
+///
+/// Backups are named after the file they copy and the moment they were taken, like `items.json@2020-12-31T23-59-59.999`,
+/// so sorting their names also sorts them chronologically.
+pub struct Backups {
+    dir: PathBuf,
+    retention: Retention,
+}
+
+impl Backups {
+    /// Creates a backup configuration that keeps the backups on `dir` according to `retention`.
+    pub fn new(dir: PathBuf, retention: Retention) -> Self {
+        Self { dir, retention }
+    }
+
+    /// Creates a backup configuration that keeps the backups on a `backups` directory next to `file`.
+    pub fn next_to(file: &Path, retention: Retention) -> Self {
+        Self::new(file.with_file_name("backups"), retention)
+    }
+
+    /// Copies `file` into the backups directory and then removes its backups that aren't retained anymore. Files that
+    /// don't exist yet or are empty aren't backed up.
+    pub fn save(&self, file: &Path) -> Result<(), String> {
+        if self.retention.is_disabled() || fs::metadata(file).map_or(true, |m| m.len() == 0) {
+            return Ok(());
+        }
+
+        let prefix = format!(
+            "{}@",
+            file.file_name()
+                .map_or("data".into(), |n| n.to_string_lossy())
+        );
+
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("failed to create {}: {}", self.dir.display(), e))?;
+
+        let now = Local::now().naive_local();
+        let backup = self
+            .dir
+            .join(format!("{}{}", prefix, now.format(BACKUP_TIMESTAMP)));
+        fs::copy(file, &backup)
+            .map_err(|e| format!("failed to copy to {}: {}", backup.display(), e))?;
+
+        let mut existing: Vec<(PathBuf, String)> = fs::read_dir(&self.dir)
+            .map_err(|e| format!("failed to read {}: {}", self.dir.display(), e))?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                name.strip_prefix(&prefix)
+                    .map(|timestamp| (entry.path(), timestamp.to_string()))
+            })
+            .collect();
+        existing.sort();
+
+        let expired: Vec<&PathBuf> = match self.retention {
+            Retention::Last(keep) => {
+                let excess = existing.len().saturating_sub(keep);
+                existing[..excess].iter().map(|(path, _)| path).collect()
+            }
+            Retention::Days(days) => match now.checked_sub_signed(Duration::days(days.into())) {
+                // backups whose moment can't be read are left alone, since they might not be backups at all
+                Some(oldest) => existing
+                    .iter()
+                    .filter(|(_, timestamp)| {
+                        matches!(
+                            NaiveDateTime::parse_from_str(timestamp, BACKUP_TIMESTAMP),
+                            Ok(taken) if taken < oldest
+                        )
+                    })
+                    .map(|(path, _)| path)
+                    .collect(),
+                // so many days ago that there's no date for it, so every backup is kept
+                None => Vec::new(),
+            },
+        };
+
+        for old in expired {
+            fs::remove_file(old)
+                .map_err(|e| format!("failed to remove {}: {}", old.display(), e))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retention() {
+        assert_eq!(Retention::parse("10"), Ok(Retention::Last(10)));
+        assert_eq!(Retention::parse(" 30d "), Ok(Retention::Days(30)));
+        assert!(Retention::parse("30 days").is_err());
+        assert!(Retention::parse("").is_err());
+    }
+
+    #[test]
+    fn backups_are_rotated() {
+        let dir = std::env::temp_dir().join(format!("utils-backups-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("data.json");
+        let backups = Backups::next_to(&file, Retention::Last(2));
+
+        // there's nothing to back up yet
+        backups.save(&file).unwrap();
+        assert!(!dir.join("backups").exists());
+
+        for contents in &["1", "2", "3"] {
+            fs::write(&file, contents).unwrap();
+            backups.save(&file).unwrap();
+            // the backups are told apart by their milliseconds
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        let mut kept: Vec<String> = fs::read_dir(dir.join("backups"))
+            .unwrap()
+            .map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect();
+        kept.sort();
+        assert_eq!(kept, vec!["2", "3"]);
+
+        // a retention too long for a date keeps everything
+        Backups::next_to(&file, Retention::Days(u32::MAX))
+            .save(&file)
+            .unwrap();
+        assert_eq!(fs::read_dir(dir.join("backups")).unwrap().count(), 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
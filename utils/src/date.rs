@@ -0,0 +1,243 @@
+//! Parses dates written by people, relative to the current day.
+//!
+//! The accepted expressions are (ignoring case):
+//!
+//! - `YYYY-MM-DD`, like `2024-07-01`;
+//! - `today`, `tomorrow` and `yesterday`;
+//! - a weekday, like `friday` or `fri`, which is the next one counting today; `next fri` doesn't count today and
+//!   `last fri` is the previous one;
+//! - `next week`, `next month` and `next year`;
+//! - an amount of time from today, like `in 3 weeks`, `in a month` or `in 10d`, or before today, like `2 days ago`;
+//! - a day of a month, like `jul 1`, `1 july` or `jul 1 2025`, which is the next one counting today when the year is
+//!   missing.
+//!
+//! Months are added on the calendar, so `in 1 month` from January 31st is the last day of February.
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use std::convert::TryFrom;
+
+const EXPECTED: &str =
+    "expected a date like 2024-07-01, today, fri, next week, in 3 weeks or jul 1";
+
+/// Parses `string` as a date, with relative expressions counting from `today`.
+pub fn parse_date(string: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+    let string = string.trim().to_lowercase();
+    let words: Vec<&str> = string.split_whitespace().collect();
+
+    match words.as_slice() {
+        [] => Err("empty date".into()),
+        ["today"] => Ok(today),
+        ["tomorrow"] => Ok(today + Duration::days(1)),
+        ["yesterday"] => Ok(today - Duration::days(1)),
+        ["next", "week"] => Ok(today + Duration::weeks(1)),
+        ["next", "month"] => add_months(today, 1),
+        ["next", "year"] => add_months(today, 12),
+        ["next", weekday] => Ok(next_weekday(
+            today + Duration::days(1),
+            parse_weekday(weekday)?,
+        )),
+        ["last", weekday] => {
+            let weekday = parse_weekday(weekday)?;
+            Ok(next_weekday(today - Duration::weeks(1), weekday))
+        }
+        ["in", amount @ ..] => offset(today, amount, 1, &string),
+        [amount @ .., "ago"] => offset(today, amount, -1, &string),
+        [word] => {
+            if let Ok(weekday) = parse_weekday(word) {
+                return Ok(next_weekday(today, weekday));
+            }
+
+            if word.contains('-') {
+                return NaiveDate::parse_from_str(word, "%Y-%m-%d")
+                    .map_err(|_| format!("invalid date {:?} (expected YYYY-MM-DD)", word));
+            }
+
+            Err(format!("could not parse date {:?} ({})", string, EXPECTED))
+        }
+        _ => day_of_month(today, &words)
+            .ok_or_else(|| format!("could not parse date {:?} ({})", string, EXPECTED))?,
+    }
+}
+
+/// Returns the first day on `weekday` starting from `from`, which counts.
+fn next_weekday(from: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let days_ahead =
+        (7 + weekday.num_days_from_monday() - from.weekday().num_days_from_monday()) % 7;
+
+    from + Duration::days(days_ahead as i64)
+}
+
+fn parse_weekday(word: &str) -> Result<Weekday, String> {
+    word.parse().map_err(|_| {
+        format!(
+            "unknown weekday {:?} (expected one like fri or friday)",
+            word
+        )
+    })
+}
+
+/// Parses an amount of time like `3 weeks`, `a month` or `10d` (given as `words`) and moves `today` by it, forwards if
+/// `sign` is positive and backwards otherwise.
+fn offset(today: NaiveDate, words: &[&str], sign: i32, string: &str) -> Result<NaiveDate, String> {
+    let (amount, unit) = match words {
+        [amount, unit] => (*amount, *unit),
+        // like 10d, with the number glued to the unit
+        [word] => word.split_at(
+            word.find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(word.len()),
+        ),
+        _ => return Err(format!("could not parse date {:?} ({})", string, EXPECTED)),
+    };
+
+    let amount: i32 = match amount {
+        "a" | "an" | "one" => 1,
+        number => number
+            .parse()
+            .map_err(|_| format!("invalid amount {:?} in date {:?}", number, string))?,
+    };
+
+    let out_of_range = || format!("the date {:?} is out of range", string);
+    let amount = amount.checked_mul(sign).ok_or_else(out_of_range)?;
+
+    match unit {
+        "d" | "day" | "days" => today
+            .checked_add_signed(Duration::days(amount.into()))
+            .ok_or_else(out_of_range),
+        "w" | "week" | "weeks" => today
+            .checked_add_signed(Duration::weeks(amount.into()))
+            .ok_or_else(out_of_range),
+        "m" | "month" | "months" => add_months(today, amount),
+        "y" | "year" | "years" => {
+            add_months(today, amount.checked_mul(12).ok_or_else(out_of_range)?)
+        }
+        unit => Err(format!(
+            "unknown unit {:?} in date {:?} (expected days, weeks, months or years)",
+            unit, string
+        )),
+    }
+}
+
+/// Adds `months` to `date` on the calendar, moving to the last day of the month if it doesn't have as many days.
+fn add_months(date: NaiveDate, months: i32) -> Result<NaiveDate, String> {
+    let out_of_range = || {
+        format!(
+            "the date is out of range ({} month(s) from {})",
+            months, date
+        )
+    };
+
+    // counted on i64, so far away dates are out of range instead of overflowing
+    let index = i64::from(date.year()) * 12 + i64::from(date.month0()) + i64::from(months);
+    let year = i32::try_from(index.div_euclid(12)).map_err(|_| out_of_range())?;
+    let month = index.rem_euclid(12) as u32 + 1;
+
+    (1..=date.day())
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+        .ok_or_else(out_of_range)
+}
+
+/// Parses a day of a month like `jul 1`, `1 july` or `jul 1 2025`. Without a year, it's the next one counting `today`.
+///
+/// Returns `None` if `words` doesn't look like one, or the error if it's not a valid date.
+fn day_of_month(today: NaiveDate, words: &[&str]) -> Option<Result<NaiveDate, String>> {
+    let (month, day, year) = match words {
+        [first, second] | [first, second, _] => match (parse_month(first), parse_month(second)) {
+            (Some(month), None) => (month, second.parse::<u32>().ok()?, words.get(2)),
+            (None, Some(month)) => (month, first.parse::<u32>().ok()?, words.get(2)),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    let invalid = || format!("invalid date: there's no day {} on month {}", day, month);
+
+    Some(match year {
+        Some(year) => year
+            .parse()
+            .ok()
+            .and_then(|year| NaiveDate::from_ymd_opt(year, month, day))
+            .ok_or_else(invalid),
+        None => match NaiveDate::from_ymd_opt(today.year(), month, day) {
+            Some(date) if date >= today => Ok(date),
+            _ => NaiveDate::from_ymd_opt(today.year() + 1, month, day).ok_or_else(invalid),
+        },
+    })
+}
+
+/// Parses the name of a month, in full or by its first three letters, into its number.
+fn parse_month(word: &str) -> Option<u32> {
+    const MONTHS: [&str; 12] = [
+        "january",
+        "february",
+        "march",
+        "april",
+        "may",
+        "june",
+        "july",
+        "august",
+        "september",
+        "october",
+        "november",
+        "december",
+    ];
+
+    MONTHS
+        .iter()
+        .position(|month| word.len() >= 3 && month.starts_with(word))
+        .map(|index| index as u32 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn relative_dates() {
+        // a wednesday
+        let today = date(2024, 1, 31);
+
+        assert_eq!(parse_date("today", today), Ok(today));
+        assert_eq!(parse_date(" Tomorrow ", today), Ok(date(2024, 2, 1)));
+        assert_eq!(parse_date("wed", today), Ok(today));
+        assert_eq!(parse_date("next wednesday", today), Ok(date(2024, 2, 7)));
+        assert_eq!(parse_date("last fri", today), Ok(date(2024, 1, 26)));
+        assert_eq!(parse_date("fri", today), Ok(date(2024, 2, 2)));
+        assert_eq!(parse_date("in 3 weeks", today), Ok(date(2024, 2, 21)));
+        assert_eq!(parse_date("in 10d", today), Ok(date(2024, 2, 10)));
+        assert_eq!(parse_date("in a month", today), Ok(date(2024, 2, 29)));
+        assert_eq!(parse_date("next year", today), Ok(date(2025, 1, 31)));
+        assert_eq!(parse_date("2 days ago", today), Ok(date(2024, 1, 29)));
+    }
+
+    #[test]
+    fn absolute_dates() {
+        let today = date(2024, 7, 2);
+
+        assert_eq!(parse_date("2024-07-01", today), Ok(date(2024, 7, 1)));
+        assert_eq!(parse_date("jul 2", today), Ok(today));
+        assert_eq!(parse_date("1 july", today), Ok(date(2025, 7, 1)));
+        assert_eq!(parse_date("Jul 1 2024", today), Ok(date(2024, 7, 1)));
+    }
+
+    #[test]
+    fn invalid_dates() {
+        let today = date(2024, 7, 2);
+
+        assert!(parse_date("", today).is_err());
+        assert!(parse_date("frday", today).is_err());
+        assert!(parse_date("2024-02-30", today).is_err());
+        assert!(parse_date("feb 30", today).is_err());
+        assert!(parse_date("in 3 fortnights", today).is_err());
+        assert!(parse_date("in some weeks", today).is_err());
+        assert!(parse_date("in 999999999 days", today).is_err());
+        assert!(parse_date("in 999999999 weeks", today).is_err());
+        assert!(parse_date("in 999999999 years", today).is_err());
+        assert!(parse_date("2147483647 months ago", today).is_err());
+        assert!(parse_date("in -2147483648 days", today).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+//! Finds the directories where the programs keep their files, following the conventions of each platform:
+//!
+//! | Directory | Linux (and other Unix systems)       | macOS                           | Windows         |
+//! |-----------|--------------------------------------|---------------------------------|-----------------|
+//! | data      | `$XDG_DATA_HOME` or `~/.local/share` | `~/Library/Application Support` | `%APPDATA%`     |
+//! | config    | `$XDG_CONFIG_HOME` or `~/.config`    | `~/Library/Application Support` | `%APPDATA%`     |
+//! | cache     | `$XDG_CACHE_HOME` or `~/.cache`      | `~/Library/Caches`              | `%LOCALAPPDATA%` |
+//!
+//! Each function returns `None` if the directory can't be found, like when the home directory isn't known.
+
+use std::path::PathBuf;
+
+/// Returns the home directory of the user, `$HOME` (or `%USERPROFILE%` on Windows).
+pub fn home_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        env_dir("USERPROFILE")
+    } else {
+        env_dir("HOME")
+    }
+}
+
+/// Returns the directory where programs keep their data.
+pub fn data_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        env_dir("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        Some(home_dir()?.join("Library/Application Support"))
+    } else {
+        env_dir("XDG_DATA_HOME").or_else(|| Some(home_dir()?.join(".local/share")))
+    }
+}
+
+/// Returns the directory where programs keep their configuration files.
+pub fn config_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        env_dir("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        Some(home_dir()?.join("Library/Application Support"))
+    } else {
+        env_dir("XDG_CONFIG_HOME").or_else(|| Some(home_dir()?.join(".config")))
+    }
+}
+
+/// Returns the directory where programs keep files that can be lost without harm.
+pub fn cache_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        env_dir("LOCALAPPDATA")
+    } else if cfg!(target_os = "macos") {
+        Some(home_dir()?.join("Library/Caches"))
+    } else {
+        env_dir("XDG_CACHE_HOME").or_else(|| Some(home_dir()?.join(".cache")))
+    }
+}
+
+/// Returns the directory on the environment variable `name`, if it's set to an absolute path. Relative ones are ignored,
+/// as the XDG specification asks for.
+fn env_dir(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+}
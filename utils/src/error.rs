@@ -1,4 +1,5 @@
-use std::fmt::Display;
+use std::fmt::{self, Display};
+use std::io;
 use std::process::Termination;
 
 #[derive(Clone, Copy)]
@@ -58,11 +59,98 @@ impl Termination for ExitCode {
     }
 }
 
+/// The kinds of failures of the programs, so callers can tell them apart instead of only showing them.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file, or another I/O operation, failed.
+    Io(io::Error),
+    /// Some data, like the contents of a file, couldn't be understood.
+    Parse(String),
+    /// A request over the network failed.
+    Network(String),
+    /// The operation doesn't fit the existing data, like when an ID is already in use.
+    Conflict(String),
+    /// Something the operation refers to, like an item, doesn't exist.
+    NotFound(String),
+    /// The user cancelled the operation.
+    UserAbort,
+    /// An external program failed or couldn't be run.
+    External(String),
+}
+
+impl Error {
+    /// The exit code a program should end with because of this error. They follow `sysexits.h` where there's a code
+    /// for the kind of failure:
+    ///
+    /// | Kind      | Code |
+    /// |-----------|------|
+    /// | Conflict  | 1    |
+    /// | NotFound  | 1    |
+    /// | Parse     | 65   |
+    /// | Network   | 69   |
+    /// | External  | 70   |
+    /// | Io        | 74   |
+    /// | UserAbort | 130  |
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::new(match self {
+            Self::Conflict(_) | Self::NotFound(_) => 1,
+            Self::Parse(_) => 65,
+            Self::Network(_) => 69,
+            Self::External(_) => 70,
+            Self::Io(_) => 74,
+            Self::UserAbort => 130,
+        })
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(fmt, "{}", e),
+            Self::Parse(e)
+            | Self::Network(e)
+            | Self::Conflict(e)
+            | Self::NotFound(e)
+            | Self::External(e) => write!(fmt, "{}", e),
+            Self::UserAbort => write!(fmt, "cancelled by the user"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Parse(error.to_string())
+    }
+}
+
+/// Lets the functions that still fail with a message use `?` on the ones that fail with an [`Error`].
+impl From<Error> for String {
+    fn from(error: Error) -> Self {
+        error.to_string()
+    }
+}
+
 pub enum CliError {
     /// Indicates an error that doesn't show anything on the screen.
     Silent,
     /// Indicates an error that shows something on the screen.
     Display(Box<dyn Display + 'static>),
+    /// Indicates an error that shows something on the screen and exits with the code of its kind.
+    Error(Error),
+}
+
+impl From<Error> for CliError {
+    fn from(error: Error) -> Self {
+        Self::Error(error)
+    }
 }
 
 impl CliError {
@@ -126,10 +214,15 @@ impl<T> CliResult<T> {
             Ok(_) => ExitCode::SUCCESS,
             Err(CliError::Silent) => ExitCode::FAILURE,
             Err(CliError::Display(ref why)) => {
-                eprintln!("Error: {}", why);
+                crate::error!("{}", why);
 
                 ExitCode::FAILURE
             }
+            Err(CliError::Error(ref error)) => {
+                crate::error!("{}", error);
+
+                error.exit_code()
+            }
         }
     }
 }
@@ -1,6 +1,8 @@
-use std::fs::{create_dir_all, File, OpenOptions};
-use std::io::{self, Read, Write};
-use std::path::Path;
+use std::fs::{self, create_dir_all, File, OpenOptions};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
 
 pub fn touch_and_open(path: &Path) -> Result<File, String> {
     if path.exists() {
@@ -56,6 +58,240 @@ pub fn touch_read(path: &Path) -> Result<String, String> {
     }
 }
 
+/// Writes `contents` to the file on `path` atomically: they're written to a temporary file on the same directory,
+/// synced to the disk and then renamed over `path`, so a crash or a full disk can't leave the file half-written.
+///
+/// If the file already exists, its permissions are kept.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let temp_path = temp_path_for(path);
+
+    let result = (|| {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(contents)?;
+
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+
+        file.sync_all()?;
+        fs::rename(&temp_path, path)?;
+
+        // sync the directory too, so the rename itself survives a crash
+        if let Some(dir) = path.parent() {
+            let dir = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            };
+
+            if let Ok(dir) = File::open(dir) {
+                dir.sync_all().ok();
+            }
+        }
+
+        Ok(())
+    })();
+
+    if result.is_err() {
+        fs::remove_file(&temp_path).ok();
+    }
+
+    result
+}
+
+/// Returns the path of the temporary file used by `write_atomic` for `path`, a hidden file next to it like
+/// `.items.json.1234.tmp`.
+fn temp_path_for(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map_or("file".into(), |n| n.to_string_lossy());
+
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+/// How long to wait between attempts to acquire a `FileLock`.
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An advisory lock that keeps two processes from changing the same data file at once, which would make the changes
+/// of the one that saved first be lost. It's released when dropped.
+///
+/// The lock is taken on a file next to the data file, like `data.json.lock`, which holds the PID of its owner. It's
+/// locked with `flock` where that's available, so a crashed process never leaves it held. Otherwise (like on some
+/// network filesystems), the lock file is created exclusively and removed once released, and lock files left behind by
+/// processes that aren't running anymore are taken over.
+pub struct FileLock {
+    path: PathBuf,
+    /// The lock file, kept open while it's locked with `flock`, or `None` if the fallback is being used.
+    file: Option<File>,
+}
+
+impl FileLock {
+    /// Acquires the lock for the data file on `file`, waiting up to `timeout` for another process to release it.
+    pub fn acquire(file: &Path, timeout: Duration) -> Result<Self, String> {
+        let path = Self::lock_path(file);
+        let start = Instant::now();
+
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            create_dir_all(parent)
+                .map_err(|e| format!("failed to create {}: {}", parent.display(), e))?;
+        }
+
+        let mut lock_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+
+        loop {
+            match try_flock(&lock_file) {
+                Ok(true) => {
+                    write_pid(&mut lock_file)
+                        .map_err(|e| format!("failed to write to {}: {}", path.display(), e))?;
+
+                    return Ok(Self {
+                        path,
+                        file: Some(lock_file),
+                    });
+                }
+                Ok(false) if start.elapsed() >= timeout => {
+                    return Err(format!(
+                        "{} is being used by {}",
+                        file.display(),
+                        describe_owner(lock_owner(&path))
+                    ));
+                }
+                Ok(false) => thread::sleep(LOCK_RETRY_INTERVAL),
+                Err(e) => {
+                    crate::debug!(
+                        "Can't use flock on {} ({}), creating it exclusively instead",
+                        path.display(),
+                        e
+                    );
+
+                    // the file was only created to be locked with flock, so it shouldn't look like a held lock
+                    drop(lock_file);
+                    if matches!(fs::metadata(&path), Ok(m) if m.len() == 0) {
+                        fs::remove_file(&path).ok();
+                    }
+
+                    return Self::acquire_fallback(file, path, start, timeout);
+                }
+            }
+        }
+    }
+
+    /// Acquires the lock by creating the lock file exclusively, for where `flock` can't be used.
+    fn acquire_fallback(
+        file: &Path,
+        path: PathBuf,
+        start: Instant,
+        timeout: Duration,
+    ) -> Result<Self, String> {
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut lock_file) => {
+                    write_pid(&mut lock_file)
+                        .map_err(|e| format!("failed to write to {}: {}", path.display(), e))?;
+
+                    return Ok(Self { path, file: None });
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => (),
+                Err(e) => return Err(format!("failed to create {}: {}", path.display(), e)),
+            }
+
+            let owner = lock_owner(&path);
+
+            match owner {
+                Some(pid) if !is_running(pid) => {
+                    crate::warn!("taking over the lock left behind by process {}", pid);
+                    fs::remove_file(&path).ok();
+                    continue;
+                }
+                _ if start.elapsed() >= timeout => {
+                    return Err(format!(
+                        "{} is being used by {} (if that's not the case, remove {})",
+                        file.display(),
+                        describe_owner(owner),
+                        path.display()
+                    ));
+                }
+                _ => thread::sleep(LOCK_RETRY_INTERVAL),
+            }
+        }
+    }
+
+    /// Returns the path of the lock file for the data file on `file`.
+    pub fn lock_path(file: &Path) -> PathBuf {
+        let name = file
+            .file_name()
+            .map_or("data".into(), |n| n.to_string_lossy());
+
+        file.with_file_name(format!("{}.lock", name))
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // a file locked with flock is released by closing it, and removing it could let another process lock a file
+        // that's not the one on the path anymore
+        if self.file.is_none() {
+            fs::remove_file(&self.path).ok();
+        }
+    }
+}
+
+/// Tries to lock `file` with `flock` without blocking. Returns whether it was locked, or an error if `flock` isn't
+/// supported there.
+#[cfg(unix)]
+fn try_flock(file: &File) -> io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+
+    let error = io::Error::last_os_error();
+    if error.raw_os_error() == Some(libc::EWOULDBLOCK) {
+        Ok(false)
+    } else {
+        Err(error)
+    }
+}
+
+#[cfg(not(unix))]
+fn try_flock(_file: &File) -> io::Result<bool> {
+    Err(io::Error::new(ErrorKind::Other, "flock isn't supported"))
+}
+
+/// Replaces the contents of the lock file with the PID of this process.
+fn write_pid(file: &mut File) -> io::Result<()> {
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    write!(file, "{}", std::process::id())
+}
+
+/// Reads the PID of the owner of the lock file on `path`, if it has a valid one.
+fn lock_owner(path: &Path) -> Option<u32> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| contents.trim().parse().ok())
+}
+
+fn describe_owner(owner: Option<u32>) -> String {
+    owner.map_or(String::from("another process"), |pid| {
+        format!("process {}", pid)
+    })
+}
+
+/// Checks if a process is running. Where that can't be known (without `/proc`), processes are assumed to be running.
+fn is_running(pid: u32) -> bool {
+    let proc = Path::new("/proc");
+
+    !proc.is_dir() || proc.join(pid.to_string()).exists()
+}
+
 pub fn read_line(prompt: &str) -> Result<String, io::Error> {
     eprint!("{}", prompt);
     io::stdout().flush().unwrap();
@@ -65,3 +301,38 @@ pub fn read_line(prompt: &str) -> Result<String, io::Error> {
 
     Ok(buffer.trim().into())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_atomic_replaces_the_file() {
+        let dir = std::env::temp_dir().join(format!("utils-io-test-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("data.json");
+
+        write_atomic(&path, b"[1, 2, 3]").unwrap();
+        write_atomic(&path, b"[]").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[]");
+        // the temporary file doesn't stay behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn file_lock_is_exclusive() {
+        let dir = std::env::temp_dir().join(format!("utils-lock-test-{}", std::process::id()));
+        let path = dir.join("data.json");
+
+        let lock = FileLock::acquire(&path, Duration::from_secs(0)).unwrap();
+        assert!(FileLock::acquire(&path, Duration::from_millis(200)).is_err());
+
+        drop(lock);
+        assert!(FileLock::acquire(&path, Duration::from_secs(0)).is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,9 +2,15 @@
 #![feature(try_trait)]
 
 pub mod aliases;
+pub mod config;
 pub mod cowstr;
 pub mod data;
+pub mod date;
+pub mod dirs;
 pub mod error;
 pub mod io;
+pub mod log;
 pub mod misc;
+pub mod picker;
+pub mod time;
 pub mod tmp;
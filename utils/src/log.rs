@@ -0,0 +1,144 @@
+//! A tiny leveled logger for the messages the programs show on stderr, so they can be silenced (with `-q`) or made
+//! more detailed (with `-v`) the same way everywhere.
+//!
+//! Messages are logged with the [`error!`], [`warn!`], [`info!`] and [`debug!`] macros. Errors and warnings are
+//! prefixed by `Error: ` and `Warning: `, which are colored unless that's disabled or `$NO_COLOR` is set, and every
+//! message can be prefixed by the time it was logged.
+//!
+//! Prompts and other output that's part of an interaction shouldn't go through the logger, since they're needed even
+//! when the program is quiet.
+
+use chrono::Local;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// How important a message is. Only the messages at the current level or above it are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    /// Returns the level for the number of `-v` and `-q` flags given: `Info` for none of them, `Debug` for at least one
+    /// `-v`, `Warn` for one `-q` and `Error` for more.
+    pub fn from_verbosity(verbose: i32, quiet: i32) -> Self {
+        match verbose - quiet {
+            v if v >= 1 => Self::Debug,
+            0 => Self::Info,
+            -1 => Self::Warn,
+            _ => Self::Error,
+        }
+    }
+
+    fn from_u8(level: u8) -> Self {
+        match level {
+            0 => Self::Debug,
+            1 => Self::Info,
+            2 => Self::Warn,
+            _ => Self::Error,
+        }
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+static TIMESTAMPS: AtomicBool = AtomicBool::new(false);
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+/// Sets up the logger, which should be done once the command-line flags are known. Before that, messages are logged
+/// at the `Info` level, without timestamps or colors.
+pub fn init(level: Level, timestamps: bool, color: bool) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+    TIMESTAMPS.store(timestamps, Ordering::Relaxed);
+    COLOR.store(
+        color && !matches!(std::env::var_os("NO_COLOR"), Some(v) if !v.is_empty()),
+        Ordering::Relaxed,
+    );
+}
+
+/// Returns whether messages at `level` are shown.
+pub fn enabled(level: Level) -> bool {
+    level >= Level::from_u8(LEVEL.load(Ordering::Relaxed))
+}
+
+/// Shows a message at `level`, if it's enabled. The macros should be used instead of this.
+pub fn log(level: Level, args: fmt::Arguments) {
+    if !enabled(level) {
+        return;
+    }
+
+    let color = COLOR.load(Ordering::Relaxed);
+    let mut line = String::new();
+
+    if TIMESTAMPS.load(Ordering::Relaxed) {
+        line.push_str(&format!("[{}] ", Local::now().format("%Y-%m-%d %H:%M:%S")));
+    }
+
+    let prefix = match level {
+        Level::Error => Some(("Error:", "\x1b[1;31m")),
+        Level::Warn => Some(("Warning:", "\x1b[33m")),
+        Level::Info | Level::Debug => None,
+    };
+
+    match prefix {
+        Some((prefix, style)) if color => line.push_str(&format!("{}{}\x1b[0m ", style, prefix)),
+        Some((prefix, _)) => line.push_str(&format!("{} ", prefix)),
+        None => (),
+    }
+
+    if level == Level::Debug && color {
+        line.push_str(&format!("\x1b[2m{}\x1b[0m", args));
+    } else {
+        line.push_str(&args.to_string());
+    }
+
+    eprintln!("{}", line);
+}
+
+/// Logs an error, which is always shown.
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Error, format_args!($($arg)*))
+    };
+}
+
+/// Logs a warning, which is hidden by `-qq`.
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Warn, format_args!($($arg)*))
+    };
+}
+
+/// Logs an informational message, like the result of a command, which is hidden by `-q`.
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Info, format_args!($($arg)*))
+    };
+}
+
+/// Logs a debugging message, which is only shown with `-v`.
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Debug, format_args!($($arg)*))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verbosity() {
+        assert_eq!(Level::from_verbosity(0, 0), Level::Info);
+        assert_eq!(Level::from_verbosity(2, 0), Level::Debug);
+        assert_eq!(Level::from_verbosity(0, 1), Level::Warn);
+        assert_eq!(Level::from_verbosity(0, 3), Level::Error);
+        assert_eq!(Level::from_verbosity(1, 1), Level::Info);
+    }
+}
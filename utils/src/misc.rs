@@ -1,80 +1,42 @@
+use rand::distributions::Uniform;
+use rand::Rng;
 use regex::Regex;
 use std::cmp::Eq;
 use std::collections::HashSet;
 use std::hash::Hash;
-use std::io::{Read, Write};
-use std::process::{Command, Stdio};
+use std::ops::RangeInclusive;
 
-use crate::error::CliError;
+/// Finds the first free value in the set, or returns `None` if every value is taken.
+pub fn find_lowest_free_value(set: &HashSet<u32>) -> Option<u32> {
+    (0..=u32::MAX).find(|value| !set.contains(value))
+}
 
-/// Runs the `fzagnostic` command with data from the arguments.
-///
-/// Returns Ok with the choice if everything went successfully.
+/// Picks a random value from `range` that isn't in the set, or returns `None` if all of them are taken.
 ///
-/// Returns Err with the error if the error was not intended.
-/// Returns Err with an empty string if fzagnostic was cancelled manually. (Ctrl-C, ESC etc.)
-pub fn fzagnostic<'a, C, S>(prompt: &str, choices: C, height: u32) -> Result<String, CliError>
-where
-    C: IntoIterator<Item = S>,
-    S: AsRef<str>,
-{
-    match Command::new("fzagnostic")
-        .args(&["-h", &format!("{}", height), "-p", prompt])
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .spawn()
-    {
-        Ok(mut child) => {
-            let stdin = child.stdin.as_mut().unwrap();
-
-            for line in choices.into_iter() {
-                write!(stdin, "{}\n", line.as_ref()).map_err(|why| {
-                    CliError::from_display(format!(
-                        "fzagnostic: failed to write to process stdin: {}",
-                        why
-                    ))
-                })?;
-            }
+/// Values are drawn from the range a few times, so a large range isn't gone through; if all of them are taken, which
+/// is likely only when most of the range is, the first free value on the range is used instead.
+pub fn find_random_free_value(set: &HashSet<u32>, range: RangeInclusive<u32>) -> Option<u32> {
+    const ATTEMPTS: usize = 32;
 
-            if child.wait().unwrap().success() {
-                let mut choice = String::new();
-                match child.stdout.as_mut().unwrap().read_to_string(&mut choice) {
-                    Ok(_) => Ok(choice),
-                    Err(why) => Err(CliError::from_display(format!(
-                        "fzagnostic: failed to get process stdout: {}",
-                        why
-                    ))),
-                }
-            } else {
-                Err(CliError::Silent)
-            }
-        }
-        Err(why) => Err(CliError::from_display(format!(
-            "fzagnostic: failed to run command: {}",
-            why
-        ))),
+    if range.is_empty() {
+        return None;
     }
-}
 
-/// Finds the first free value in the set.
-pub fn find_lowest_free_value(set: &HashSet<u32>) -> u32 {
-    let mut free_value = 0;
-    loop {
-        if !set.contains(&free_value) {
-            break free_value;
-        }
-        free_value += 1;
-    }
-}
+    let distribution = Uniform::new_inclusive(*range.start(), *range.end());
+    let mut rng = rand::thread_rng();
 
-/// Finds the first free value that is bigger than the highest used value in the set.
-pub fn find_highest_free_value(set: &HashSet<u32>) -> u32 {
-    let free_value = set.iter().fold(0, |x, &y| x.max(y));
+    (0..ATTEMPTS)
+        .map(|_| rng.sample(distribution))
+        .find(|value| !set.contains(value))
+        .or_else(|| range.clone().find(|value| !set.contains(value)))
+}
 
-    if set.contains(&free_value) {
-        free_value + 1
-    } else {
-        free_value
+/// Finds the first free value that is bigger than the highest used value in the set, or returns `None` if the highest
+/// value is taken.
+pub fn find_highest_free_value(set: &HashSet<u32>) -> Option<u32> {
+    match set.iter().max() {
+        Some(&highest) => highest.checked_add(1),
+        None => Some(0),
     }
 }
 
@@ -163,4 +125,33 @@ mod tests {
             Ok(vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 4, 5])
         );
     }
+
+    #[test]
+    fn random_free_value() {
+        let set: HashSet<u32> = [0, 1, 3].iter().copied().collect();
+
+        for _ in 0..20 {
+            let value = find_random_free_value(&set, 0..=4).unwrap();
+            assert!(value == 2 || value == 4);
+        }
+
+        assert_eq!(find_random_free_value(&set, 0..=1), None);
+        assert_eq!(find_random_free_value(&set, 3..=3), None);
+        assert!(find_random_free_value(&set, 0..=u32::MAX).is_some());
+    }
+
+    #[test]
+    fn lowest_and_highest_free_values() {
+        let set: HashSet<u32> = [0, 1, 3].iter().copied().collect();
+        assert_eq!(find_lowest_free_value(&set), Some(2));
+        assert_eq!(find_highest_free_value(&set), Some(4));
+        assert_eq!(find_highest_free_value(&HashSet::new()), Some(0));
+
+        let near_limit: HashSet<u32> = [0, u32::MAX - 1].iter().copied().collect();
+        assert_eq!(find_highest_free_value(&near_limit), Some(u32::MAX));
+
+        let at_limit: HashSet<u32> = [0, 1, u32::MAX].iter().copied().collect();
+        assert_eq!(find_highest_free_value(&at_limit), None);
+        assert_eq!(find_lowest_free_value(&at_limit), Some(2));
+    }
 }
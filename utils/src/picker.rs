@@ -0,0 +1,265 @@
+//! Lets the user pick among some choices with an external fuzzy finder or menu, like `fzf` or `rofi`.
+//!
+//! The program is chosen with `$PICKER` (which can be set by the `picker` key of the configuration file), and is
+//! `fzagnostic` by default. It can be the name of one of the supported [`Backend`]s or a command template, like
+//! `fzf --reverse --prompt {prompt}`, where `{prompt}` and `{height}` are replaced by the prompt and the number of lines
+//! to show. Templates are split on whitespace before the placeholders are replaced, so a prompt with spaces is still a
+//! single argument.
+//!
+//! The choices are written to the standard input of the program, one per line, and the lines it prints are matched
+//! back to them, so callers get the positions of the picked choices instead of having to parse the lines.
+
+use std::collections::HashSet;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::error::Error;
+
+/// A program that shows the choices and prints the picked ones.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Backend {
+    Fzagnostic,
+    Fzf,
+    Skim,
+    Fzy,
+    Rofi,
+    Dmenu,
+    /// A command template, with `{prompt}` and `{height}` placeholders.
+    Custom(String),
+}
+
+impl Backend {
+    /// Parses the name of a backend, taking anything else as a command template.
+    pub fn parse(string: &str) -> Self {
+        match string.trim() {
+            "fzagnostic" => Self::Fzagnostic,
+            "fzf" => Self::Fzf,
+            "skim" | "sk" => Self::Skim,
+            "fzy" => Self::Fzy,
+            "rofi" => Self::Rofi,
+            "dmenu" => Self::Dmenu,
+            template => Self::Custom(template.to_string()),
+        }
+    }
+
+    /// Returns the backend on `$PICKER`, or `Fzagnostic` if it isn't set.
+    pub fn from_env() -> Self {
+        match std::env::var("PICKER") {
+            Ok(picker) if !picker.trim().is_empty() => Self::parse(&picker),
+            _ => Self::Fzagnostic,
+        }
+    }
+
+    /// Returns the command template of the backend, with the flag for picking many choices if `multi` is set and the
+    /// backend supports it.
+    fn template(&self, multi: bool) -> String {
+        let (template, multi_flag) = match self {
+            Self::Fzagnostic => ("fzagnostic -h {height} -p {prompt}", None),
+            Self::Fzf => ("fzf --height {height} --prompt {prompt}", Some("--multi")),
+            Self::Skim => ("sk --height {height} --prompt {prompt}", Some("--multi")),
+            Self::Fzy => ("fzy --lines {height} --prompt {prompt}", None),
+            Self::Rofi => (
+                "rofi -dmenu -i -l {height} -p {prompt}",
+                Some("-multi-select"),
+            ),
+            Self::Dmenu => ("dmenu -i -l {height} -p {prompt}", None),
+            Self::Custom(template) => return template.clone(),
+        };
+
+        match multi_flag {
+            Some(flag) if multi => format!("{} {}", template, flag),
+            _ => template.to_string(),
+        }
+    }
+}
+
+/// Shows choices to the user with a [`Backend`].
+#[derive(Debug, Clone)]
+pub struct Picker {
+    backend: Backend,
+    prompt: String,
+    height: u32,
+    multi: bool,
+}
+
+impl Picker {
+    /// Creates a picker showing `prompt`, with the backend on `$PICKER`, 30 lines of height and only one choice to
+    /// pick.
+    pub fn new<S: Into<String>>(prompt: S) -> Self {
+        Self {
+            backend: Backend::from_env(),
+            prompt: prompt.into(),
+            height: 30,
+            multi: false,
+        }
+    }
+
+    /// Sets the backend, instead of the one on `$PICKER`.
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Sets how many lines of choices are shown, on the backends that support it.
+    pub fn height(mut self, height: u32) -> Self {
+        self.height = height;
+        self
+    }
+
+    /// Sets whether many choices can be picked at once, on the backends that support it.
+    pub fn multi(mut self, multi: bool) -> Self {
+        self.multi = multi;
+        self
+    }
+
+    /// Returns the program and arguments to run.
+    fn command(&self) -> Result<(String, Vec<String>), Error> {
+        let height = self.height.to_string();
+        let template = self.backend.template(self.multi);
+        let mut words = template.split_whitespace().map(|word| {
+            word.replace("{prompt}", &self.prompt)
+                .replace("{height}", &height)
+        });
+
+        match words.next() {
+            Some(program) => Ok((program, words.collect())),
+            None => Err(Error::External("the picker command is empty".into())),
+        }
+    }
+
+    /// Lets the user pick among `choices`, returning the positions of the picked ones in the order they were printed.
+    ///
+    /// Fails with `Error::UserAbort` if the picker was cancelled (like with Ctrl-C or ESC) or nothing was picked, and
+    /// with `Error::External` if it failed otherwise.
+    pub fn pick<S: AsRef<str>>(&self, choices: &[S]) -> Result<Vec<usize>, Error> {
+        let lines: Vec<String> = choices.iter().map(|c| single_line(c.as_ref())).collect();
+        let (program, args) = self.command()?;
+
+        let mut child = Command::new(&program)
+            .args(&args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|why| Error::External(format!("failed to run {}: {}", program, why)))?;
+
+        {
+            let stdin = child.stdin.as_mut().unwrap();
+
+            for line in &lines {
+                if let Err(why) = writeln!(stdin, "{}", line) {
+                    // the picker might quit before reading every line, like when it's cancelled early
+                    if why.kind() == std::io::ErrorKind::BrokenPipe {
+                        break;
+                    }
+
+                    return Err(Error::Io(why));
+                }
+            }
+        }
+
+        let output = child.wait_with_output()?;
+
+        // the backends exit with 1 when cancelled or when nothing matches, and with 130 (or by the signal itself) when
+        // interrupted - anything else means they failed, like with an invalid flag
+        match output.status.code() {
+            Some(0) => (),
+            Some(1) | Some(130) | None => return Err(Error::UserAbort),
+            Some(code) => {
+                return Err(Error::External(format!(
+                    "{} failed with exit code {}",
+                    program, code
+                )))
+            }
+        }
+
+        let picked =
+            match_output(&lines, &String::from_utf8_lossy(&output.stdout)).map_err(|line| {
+                Error::External(format!("unexpected choice from {}: {:?}", program, line))
+            })?;
+
+        if picked.is_empty() {
+            return Err(Error::UserAbort);
+        }
+
+        Ok(picked)
+    }
+
+    /// Like `pick`, but returns only the first picked choice.
+    pub fn pick_one<S: AsRef<str>>(&self, choices: &[S]) -> Result<usize, Error> {
+        self.pick(choices).map(|picked| picked[0])
+    }
+}
+
+/// Turns a choice into a single line, so it can be matched back to the line the picker prints.
+fn single_line(choice: &str) -> String {
+    choice.replace(['\n', '\r'], " ")
+}
+
+/// Finds the positions of the lines printed by the picker on `lines`, with repeated lines matching the next ones that
+/// weren't picked yet. Empty lines are ignored.
+///
+/// Returns Err with the line that doesn't match any of them.
+fn match_output<'a>(lines: &[String], output: &'a str) -> Result<Vec<usize>, &'a str> {
+    let mut picked = Vec::new();
+    let mut taken = HashSet::new();
+
+    for line in output.lines().filter(|line| !line.is_empty()) {
+        let position = (0..lines.len())
+            .find(|&i| lines[i] == line && !taken.contains(&i))
+            .ok_or(line)?;
+
+        taken.insert(position);
+        picked.push(position);
+    }
+
+    Ok(picked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands() {
+        let picker = Picker::new("Items (2):")
+            .backend(Backend::parse("fzf"))
+            .multi(true);
+        let (program, args) = picker.command().unwrap();
+
+        assert_eq!(program, "fzf");
+        assert_eq!(
+            args,
+            ["--height", "30", "--prompt", "Items (2):", "--multi"]
+        );
+
+        let picker = Picker::new("Action:")
+            .backend(Backend::parse("my-menu --lines={height} {prompt}"))
+            .height(5);
+        let (program, args) = picker.command().unwrap();
+
+        assert_eq!(program, "my-menu");
+        assert_eq!(args, ["--lines=5", "Action:"]);
+
+        assert_eq!(Backend::parse("sk"), Backend::Skim);
+        assert!(Picker::new("")
+            .backend(Backend::parse(" "))
+            .command()
+            .is_err());
+    }
+
+    #[test]
+    fn output_matching() {
+        let lines: Vec<String> = ["a", "b", "a", "multi\nline"]
+            .iter()
+            .map(|c| single_line(c))
+            .collect();
+
+        assert_eq!(match_output(&lines, "b\n"), Ok(vec![1]));
+        assert_eq!(
+            match_output(&lines, "a\na\n\nmulti line\n"),
+            Ok(vec![0, 2, 3])
+        );
+        assert_eq!(match_output(&lines, "c\n"), Err("c"));
+        assert_eq!(match_output(&lines, "a\na\na\n"), Err("a"));
+    }
+}
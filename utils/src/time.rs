@@ -0,0 +1,58 @@
+use chrono::Duration;
+use regex::Regex;
+
+/// Parses a duration string like `90min`, `12h`, `3d`, `1w`, `2m` (months, as 30 days) or `1y` (as 365 days).
+pub fn parse_duration(string: &str) -> Result<Duration, String> {
+    let regex = Regex::new(r"^(\d+)\s*([a-z]+)$").unwrap();
+    let string = string.trim().to_lowercase();
+
+    let captures = regex
+        .captures(&string)
+        .ok_or_else(|| format!("Could not parse duration {:?}", string))?;
+
+    let amount: i64 = captures[1]
+        .parse()
+        .map_err(|_| format!("Number too big in duration {:?}", string))?;
+
+    match &captures[2] {
+        "min" => Ok(Duration::minutes(amount)),
+        "h" => Ok(Duration::hours(amount)),
+        "d" => Ok(Duration::days(amount)),
+        "w" => Ok(Duration::weeks(amount)),
+        "m" => Ok(Duration::days(amount * 30)),
+        "y" => Ok(Duration::days(amount * 365)),
+        unit => Err(format!(
+            "Unknown unit {:?} in duration {:?} (expected min, h, d, w, m or y)",
+            unit, string
+        )),
+    }
+}
+
+/// Formats a duration in hours and minutes, like `3h 05m` or `12m`.
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes().max(0);
+
+    if minutes >= 60 {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duration() {
+        assert_eq!(parse_duration("1w"), Ok(Duration::days(7)));
+        assert_eq!(parse_duration(" 90min "), Ok(Duration::minutes(90)));
+        assert!(parse_duration("3 fortnights").is_err());
+    }
+
+    #[test]
+    fn duration_format() {
+        assert_eq!(format_duration(Duration::minutes(185)), "3h 05m");
+        assert_eq!(format_duration(Duration::minutes(12)), "12m");
+    }
+}